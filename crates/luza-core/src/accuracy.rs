@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::games::azul;
+
+// Mean expected score lost per move at which accuracy drops to about 37%
+const ACCURACY_SCALE: f64 = 2.0;
+
+// Expected score lost against the engine over a player's moves
+#[derive(Clone, Debug, Default)]
+pub struct Losses {
+    pub moves: usize,
+    pub total: f32,
+}
+
+impl Losses {
+    pub fn add(&mut self, loss: f32) {
+        self.moves += 1;
+        self.total += loss.max(0.0);
+    }
+
+    // Hundredths of an expected point, like centipawns in chess
+    pub fn centipoints(&self) -> i64 {
+        (self.total * 100.0).round() as i64
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.moves == 0 {
+            0.0
+        } else {
+            self.total as f64 / self.moves as f64
+        }
+    }
+
+    // Score from 100 for never losing expected points, falling off
+    // exponentially with the mean loss per move
+    pub fn accuracy(&self) -> f64 {
+        100.0 * (-self.mean() / ACCURACY_SCALE).exp()
+    }
+}
+
+// Sum losses by whoever made the moves
+pub fn by_player<K: Ord>(losses: impl IntoIterator<Item = (K, f32)>) -> BTreeMap<K, Losses> {
+    let mut totals: BTreeMap<K, Losses> = BTreeMap::new();
+    for (player, loss) in losses {
+        totals.entry(player).or_default().add(loss);
+    }
+    totals
+}
+
+// Losses of the moves made quicker than the median think time against the
+// rest
+#[derive(Clone, Debug)]
+pub struct ThinkTimeSplit {
+    pub median: Duration,
+    pub fast: Losses,
+    pub slow: Losses,
+}
+
+impl ThinkTimeSplit {
+    // How many times the expected score a slow move loses is lost by a fast
+    // one, if slow moves lose any
+    pub fn ratio(&self) -> Option<f64> {
+        (self.slow.mean() > 0.0).then(|| self.fast.mean() / self.slow.mean())
+    }
+}
+
+// Split moves by how long they were thought over. There is no split when all
+// moves took as long.
+pub fn by_think_time(moves: impl IntoIterator<Item = (Duration, f32)>) -> Option<ThinkTimeSplit> {
+    let moves: Vec<(Duration, f32)> = moves.into_iter().collect();
+    let mut times: Vec<Duration> = moves.iter().map(|(time, _)| *time).collect();
+    times.sort();
+    let median = *times.get(times.len() / 2)?;
+
+    let mut fast = Losses::default();
+    let mut slow = Losses::default();
+    for (time, loss) in moves {
+        if time < median { fast.add(loss) } else { slow.add(loss) }
+    }
+    (fast.moves > 0).then_some(ThinkTimeSplit { median, fast, slow })
+}
+
+// Engine evaluation of a logged move against the teacher's best one
+pub struct MoveEval {
+    pub state: azul::State,
+    pub action: azul::Action,
+    pub best_action: azul::Action,
+    pub expected_score: f32,
+    pub best_expected_score: f32,
+}
+
+impl MoveEval {
+    pub fn loss(&self) -> f32 {
+        (self.best_expected_score - self.expected_score).max(0.0)
+    }
+}
+
+// Evaluate a move against the teacher's best one with the given seed
+pub fn evaluate_move(rules: &azul::AzulRules, state: azul::State, player_idx: usize, action: azul::Action, seed: u64) -> MoveEval {
+    azul::with_seed(seed, || {
+        let best_action = azul::play_mcts(rules, &state, player_idx);
+        let expected_score = azul::mcts_q_fn(rules, &state, player_idx, action).0;
+        let best_expected_score = if best_action == action {
+            expected_score
+        } else {
+            azul::mcts_q_fn(rules, &state, player_idx, best_action).0
+        };
+        MoveEval { state, action, best_action, expected_score, best_expected_score }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accuracy() {
+        let totals = by_player([(0, 0.0), (1, 1.5), (1, -0.5), (0, 0.0), (1, 2.5)]);
+
+        assert_eq!(totals[&0].accuracy(), 100.0);
+        assert_eq!(totals[&1].centipoints(), 400);
+        assert!((totals[&1].mean() - 4.0 / 3.0).abs() < 1e-9);
+        assert!(totals[&1].accuracy() < totals[&0].accuracy());
+    }
+
+    #[test]
+    fn test_by_think_time() {
+        let secs = Duration::from_secs;
+        let split = by_think_time([(secs(1), 3.0), (secs(9), 1.0), (secs(2), 3.0), (secs(6), 0.0), (secs(5), 1.0)]).unwrap();
+
+        assert_eq!(split.median, secs(5));
+        assert_eq!((split.fast.moves, split.slow.moves), (2, 3));
+        assert!((split.ratio().unwrap() - 4.5).abs() < 1e-9);
+
+        assert!(by_think_time([(secs(3), 1.0), (secs(3), 0.0)]).is_none());
+        assert!(by_think_time([]).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use rand::Rng;
+
+pub mod azul;
+pub mod clock;
+
+// Games that can be played, by the name commands take
+pub const GAMES: [&str; 1] = ["azul"];
+
+pub fn check_game(name: &str) -> Result<()> {
+    if GAMES.contains(&name) {
+        Ok(())
+    } else {
+        Err(anyhow!("Unknown game '{}', available games: {}", name, GAMES.join(", ")))
+    }
+}
+
+pub trait Validate {
+    fn validate(&self) -> Result<()>;
+}
+
+// A named slice of `size` values in a representation, groups are the part
+// of the state the feature comes from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FeatureDescriptor {
+    pub name: String,
+    pub size: usize,
+    pub group: String,
+    // Largest value expected, used for normalizing. Larger values are
+    // clipped.
+    pub max: f64,
+}
+
+impl FeatureDescriptor {
+    pub fn new(name: &str, size: usize, group: &str, max: f64) -> Self {
+        Self { name: name.to_string(), size, group: group.to_string(), max }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RepresentOptions {
+    // Scale every feature to [0, 1] by its maximum
+    pub normalize: bool,
+    // Player whose board comes first, followed by the others in turn order
+    pub perspective: usize,
+}
+
+// Scale features to [0, 1] using the maxima in their schema
+pub fn normalize(features: &mut [f64], schema: &[FeatureDescriptor]) {
+    let mut start = 0;
+    for feature in schema {
+        for value in &mut features[start..start + feature.size] {
+            *value = (*value / feature.max).clamp(0.0, 1.0);
+        }
+        start += feature.size;
+    }
+}
+
+pub trait Representable {
+    fn represent(&self) -> Vec<f64>;
+
+    // Features of `represent` in the same order. This takes the value since
+    // sizes can depend on it, like on the number of players.
+    fn schema(&self) -> Vec<FeatureDescriptor>;
+
+    // Representation with options applied. Perspective only matters for
+    // values that have players.
+    fn represent_with(&self, options: &RepresentOptions) -> Vec<f64> {
+        let mut features = self.represent();
+        if options.normalize {
+            normalize(&mut features, &self.schema());
+        }
+        features
+    }
+}
+
+// A game state for sequential games with n player. This is played in many
+// rounds where players take plys in sequence.
+pub trait GameState {
+    fn new(n_players: usize) -> Self;
+
+    // Tell if a round is over. A round might be over but the game might not be.
+    // Whether the game is over depends on the rules, see `Rules::is_game_over`.
+    fn is_round_over(&self) -> bool;
+}
+
+// Where rules report what a change did. Searches apply far more moves than
+// anyone looks at, so they pass `()` which drops the events without
+// allocating.
+pub trait EventSink<E> {
+    fn push(&mut self, event: E);
+}
+
+impl<E> EventSink<E> for Vec<E> {
+    fn push(&mut self, event: E) {
+        Vec::push(self, event);
+    }
+}
+
+impl<E> EventSink<E> for () {
+    fn push(&mut self, _event: E) {}
+}
+
+// Everything that changes a game state. States are plain data so the same
+// state can be played under different rules values, like rule variants.
+pub trait Rules {
+    type State;
+    type Action;
+    type Event;
+
+    // Fresh game before the first deal
+    fn new_state(&self, n_players: usize) -> Self::State;
+
+    // Deal for the next round
+    fn refill(&self, state: &mut Self::State, rng: &mut impl Rng);
+
+    fn list_valid_actions(&self, state: &Self::State, player_idx: usize) -> Vec<Self::Action>;
+
+    // Apply an action from `list_valid_actions`, reporting what it did to
+    // `events`
+    fn take_action_into(&self, state: &mut Self::State, player_idx: usize, action: Self::Action, events: &mut impl EventSink<Self::Event>);
+
+    // Score the player's board as if the round ended now
+    fn score_round_into(&self, state: &mut Self::State, player_idx: usize, events: &mut impl EventSink<Self::Event>);
+
+    // Score all players once the round's actions are over
+    fn end_round_into(&self, state: &mut Self::State, events: &mut impl EventSink<Self::Event>);
+
+    fn is_round_over(&self, state: &Self::State) -> bool;
+
+    fn is_game_over(&self, state: &Self::State) -> bool;
+
+    // Same as the `_into` methods, returning the events
+    fn take_action(&self, state: &mut Self::State, player_idx: usize, action: Self::Action) -> Vec<Self::Event> {
+        let mut events = Vec::new();
+        self.take_action_into(state, player_idx, action, &mut events);
+        events
+    }
+
+    fn score_round(&self, state: &mut Self::State, player_idx: usize) -> Vec<Self::Event> {
+        let mut events = Vec::new();
+        self.score_round_into(state, player_idx, &mut events);
+        events
+    }
+
+    fn end_round(&self, state: &mut Self::State) -> Vec<Self::Event> {
+        let mut events = Vec::new();
+        self.end_round_into(state, &mut events);
+        events
+    }
+}
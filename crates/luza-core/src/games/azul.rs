@@ -0,0 +1,2846 @@
+use super::{EventSink, FeatureDescriptor, RepresentOptions, Representable, Rules, Validate, GameState};
+use super::clock::{Clock, TimeControl};
+use std::{cell::RefCell, collections::{HashMap, HashSet}, vec};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use rand::{distr::{weighted::WeightedIndex, Distribution}, rngs::StdRng, seq::IndexedRandom, seq::IteratorRandom, Rng, RngCore, SeedableRng};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub enum Tile {
+    Black, Blue, Red, White, Yellow,
+}
+
+pub const COLORS: [Tile; 5] = [Tile::Black, Tile::Blue, Tile::Red, Tile::White, Tile::Yellow];
+pub const WALL_COLORS: [[Tile; 5]; 5] = [
+    [Tile::Blue, Tile::Yellow, Tile::Red, Tile::Black, Tile::White],
+    [Tile::White, Tile::Blue, Tile::Yellow, Tile::Red, Tile::Black],
+    [Tile::Black, Tile::White, Tile::Blue, Tile::Yellow, Tile::Red],
+    [Tile::Red, Tile::Black, Tile::White, Tile::Blue, Tile::Yellow],
+    [Tile::Yellow, Tile::Red, Tile::Black, Tile::White, Tile::Blue],
+];
+
+pub type FactoryDisplayState = HashMap<Tile, usize>;
+
+// Serde for tile count maps. Maps are written with every color in `COLORS`
+// order so serialized states are stable and can be diffed. On reading, colors
+// can come in any order and missing ones count as zero, which keeps logs from
+// older versions readable.
+mod tile_counts {
+    use super::{Tile, COLORS};
+    use serde::ser::{SerializeMap, SerializeSeq};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    struct Ordered<'a>(&'a HashMap<Tile, usize>);
+
+    impl Serialize for Ordered<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(COLORS.len()))?;
+            for color in COLORS {
+                map.serialize_entry(&color, &self.0.get(&color).copied().unwrap_or(0))?;
+            }
+            map.end()
+        }
+    }
+
+    fn complete(counts: HashMap<Tile, usize>) -> HashMap<Tile, usize> {
+        COLORS.iter().map(|color| (*color, counts.get(color).copied().unwrap_or(0))).collect()
+    }
+
+    pub fn serialize<S: Serializer>(counts: &HashMap<Tile, usize>, serializer: S) -> Result<S::Ok, S::Error> {
+        Ordered(counts).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<Tile, usize>, D::Error> {
+        Ok(complete(HashMap::deserialize(deserializer)?))
+    }
+
+    // Same for a list of maps, like the factory displays
+    pub mod list {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(list: &[HashMap<Tile, usize>], serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(list.len()))?;
+            for counts in list {
+                seq.serialize_element(&Ordered(counts))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<HashMap<Tile, usize>>, D::Error> {
+            let list: Vec<HashMap<Tile, usize>> = Vec::deserialize(deserializer)?;
+            Ok(list.into_iter().map(complete).collect())
+        }
+    }
+}
+
+// Rules of the game that house rules can change. The standard rules are the
+// default and a partial rules file only overrides the fields it has.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AzulConfig {
+    // A game has `factories_per_player * n_players + extra_factories` displays
+    pub factories_per_player: usize,
+    pub extra_factories: usize,
+    pub tiles_per_factory: usize,
+    // Penalty for each slot of the floor line, this also sets its size
+    pub floor_penalties: Vec<usize>,
+    // Bonus points for completing a row, a column, and all tiles of a color
+    pub row_bonus: i32,
+    pub column_bonus: i32,
+    pub color_bonus: i32,
+    // Tiles of each color in the bag at the start of the game. Colors left
+    // out have no tiles.
+    #[serde(with = "tile_counts")]
+    pub tiles_per_color: HashMap<Tile, usize>,
+    // Games stop after this many rounds even if nobody completed a row, so
+    // rule variants that never end can't hang simulations and rollouts
+    pub max_rounds: usize,
+    // Points added to the score of each seat when the game ends, like komi,
+    // to make up for the first player advantage. Seats past the end get none.
+    pub handicaps: Vec<i32>,
+}
+
+impl Default for AzulConfig {
+    fn default() -> Self {
+        Self {
+            factories_per_player: 2,
+            extra_factories: 1,
+            tiles_per_factory: 4,
+            floor_penalties: vec![1, 1, 2, 2, 2, 3, 3],
+            row_bonus: 2,
+            column_bonus: 7,
+            color_bonus: 10,
+            tiles_per_color: COLORS.iter().map(|&color| (color, 20)).collect(),
+            max_rounds: 20,
+            handicaps: Vec::new(),
+        }
+    }
+}
+
+impl AzulConfig {
+    // Read a TOML rules file
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn n_factories(&self, n_players: usize) -> usize {
+        self.factories_per_player * n_players + self.extra_factories
+    }
+
+    pub fn handicap(&self, seat: usize) -> i32 {
+        self.handicaps.get(seat).copied().unwrap_or(0)
+    }
+
+    // Handicaps for reports like "P0 +0, P1 +3", None without any
+    pub fn handicap_summary(&self) -> Option<String> {
+        if self.handicaps.iter().all(|&h| h == 0) {
+            return None;
+        }
+        Some(self.handicaps.iter().enumerate().map(|(i, h)| format!("P{} {:+}", i, h)).collect::<Vec<_>>().join(", "))
+    }
+}
+
+impl Validate for AzulConfig {
+    fn validate(&self) -> Result<()> {
+        if self.n_factories(2) == 0 {
+            return Err(anyhow!("Rules need at least one factory display"));
+        }
+        if self.tiles_per_factory == 0 {
+            return Err(anyhow!("Rules need at least one tile per factory display"));
+        }
+        if self.tiles_per_color.values().all(|&count| count == 0) {
+            return Err(anyhow!("Rules need tiles of at least one color"));
+        }
+        if self.max_rounds == 0 {
+            return Err(anyhow!("Rules need to allow at least one round"));
+        }
+
+        Ok(())
+    }
+}
+
+static CONFIG: LazyLock<RwLock<Arc<AzulConfig>>> = LazyLock::new(|| RwLock::new(Arc::new(AzulConfig::default())));
+
+// Set the rules for games played from now on. Games already running can see
+// a mix of old and new rules so only change them between games.
+pub fn set_config(config: AzulConfig) {
+    *CONFIG.write().unwrap() = Arc::new(config);
+}
+
+// Rules in use, standard unless `set_config` was called
+pub fn config() -> Arc<AzulConfig> {
+    CONFIG.read().unwrap().clone()
+}
+
+thread_local! {
+    static SEEDED_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+// Run `f` with all tile draws and strategy choices on this thread following
+// `seed`, so a game played inside can be played again exactly. Strategies
+// with a time budget or external engines can still differ between runs.
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let previous = SEEDED_RNG.replace(Some(StdRng::seed_from_u64(seed)));
+    let result = f();
+    SEEDED_RNG.set(previous);
+    result
+}
+
+// Source of randomness for the game and strategies, seeded inside `with_seed`
+pub fn game_rng() -> StdRng {
+    SEEDED_RNG.with_borrow_mut(|seeded| match seeded {
+        Some(rng) => StdRng::seed_from_u64(rng.next_u64()),
+        None => StdRng::from_rng(&mut rand::rng()),
+    })
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CenterState {
+    #[serde(with = "tile_counts")]
+    pub tiles: HashMap<Tile, usize>,
+    pub starting_marker: bool,
+}
+
+#[derive(Clone, Debug, Hash, serde::Serialize, serde::Deserialize)]
+pub struct PlayerState {
+    pub score: i32,
+    pub wall: [[bool; 5]; 5],
+    pub pattern_lines: [(Option<Tile>, usize); 5],
+    pub floor_line: usize,
+    pub starting_marker: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    #[serde(with = "tile_counts::list")]
+    pub factory_displays: Vec<FactoryDisplayState>,
+    pub center: CenterState,
+    pub players: Vec<PlayerState>,
+    pub rounds: usize,
+    // Discarded tiles waiting to go back in the bag
+    #[serde(with = "tile_counts", default = "build_empty_display")]
+    pub lid: HashMap<Tile, usize>,
+    // Tiles left in the bag in `COLORS` order, kept by refills since nothing
+    // else moves tiles in or out of the bag. States built by hand, or saved
+    // before this was kept, have None and their bag is counted from the table
+    // (see `bag_tiles`). Anything changing the lid or the table outside the
+    // rules has to reset this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bag: Option<[usize; 5]>,
+}
+
+// Action that tells which tile stash is picked by a player. Displays are
+// ordered by number with the center last.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ActionDisplay {
+    FactoryDisplay(usize),
+    Center
+}
+
+#[derive(Clone, Debug, Copy, serde::Serialize, serde::Deserialize, Eq, PartialEq, Hash)]
+pub struct Action {
+    pub action_display_choice: ActionDisplay,
+    pub color_choice: Tile,
+    pub pattern_line_choice: Option<usize>,
+}
+
+// Canonical order of actions: by display, then color, then pattern line with
+// the floor last
+impl Ord for Action {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |a: &Action| (a.action_display_choice, a.color_choice, a.pattern_line_choice.unwrap_or(usize::MAX));
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for Action {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Everything that changes in the game as a result of an action or the end of a
+// round. Consumers can follow these instead of diffing states.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GameEvent {
+    // Tiles of a color taken by a player, with the rest of a factory display
+    // moving to the center
+    TilesTaken { player: usize, source: ActionDisplay, color: Tile, count: usize, to_center: usize },
+    MarkerTaken { player: usize },
+    // Tiles put on a pattern line, or on the floor line for None, and how many
+    // tiles that line has after this
+    TilesStaged { player: usize, line: Option<usize>, color: Tile, count: usize, filled: usize },
+    // Tiles that didn't fit in the pattern line and went to the floor
+    Overflowed { player: usize, count: usize },
+    WallTiled { player: usize, row: usize, column: usize, color: Tile, points: i32 },
+    // Round points for a player and their score after that
+    Scored { player: usize, tiling: i32, penalty: i32, score: i32 },
+    RoundEnded { round: usize },
+    // Handicap points of a seat added at the end of the game
+    Handicapped { player: usize, points: i32, score: i32 },
+    // No winner for a draw that the tie-break didn't settle
+    GameEnded { winner: Option<usize>, scores: Vec<i32> },
+}
+
+fn source_name(source: ActionDisplay) -> String {
+    match source {
+        ActionDisplay::FactoryDisplay(i) => format!("D{}", i),
+        ActionDisplay::Center => "Center".to_string(),
+    }
+}
+
+impl std::fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TilesTaken { player, source, color, count, to_center } => {
+                write!(f, "P{} takes {} {:?} from {}", player, count, color, source_name(*source))?;
+                if *to_center > 0 {
+                    write!(f, ", {} tiles go to the center", to_center)?;
+                }
+                Ok(())
+            },
+            Self::MarkerTaken { player } => write!(f, "P{} takes the starting marker", player),
+            Self::TilesStaged { player, line: Some(i), color, count, filled } => {
+                write!(f, "P{} puts {} {:?} on pattern line {} ({}/{})", player, count, color, i, filled, i + 1)
+            },
+            Self::TilesStaged { player, line: None, color, count, .. } => {
+                write!(f, "P{} puts {} {:?} on the floor", player, count, color)
+            },
+            Self::Overflowed { player, count } => write!(f, "P{} overflows {} tiles to the floor", player, count),
+            Self::WallTiled { player, row, column, color, points } => {
+                write!(f, "P{} tiles {:?} at row {}, column {} (+{})", player, color, row, column, points)
+            },
+            Self::Scored { player, tiling, penalty, score } => {
+                write!(f, "P{} gets +{} for tiling and -{} for the floor, now at {}", player, tiling, penalty, score)
+            },
+            Self::RoundEnded { round } => write!(f, "Round {} is over", round),
+            Self::Handicapped { player, points, score } => write!(f, "P{} gets a handicap of {:+}, now at {}", player, points, score),
+            Self::GameEnded { winner: Some(winner), scores } => write!(f, "Game over, P{} wins with {}", winner, scores[*winner]),
+            Self::GameEnded { winner: None, scores } => {
+                write!(f, "Game over, a draw at {}", scores.iter().max().copied().unwrap_or(0))
+            },
+        }
+    }
+}
+
+// Bump when the features of `State::represent` change
+pub const SCHEMA_VERSION: usize = 2;
+
+// Normalizing bounds for features without a limit in the rules. The highest
+// possible score in standard rules is 240.
+const MAX_SCORE: f64 = 240.0;
+const MAX_ROUNDS: f64 = 20.0;
+const MAX_DISPLAYS: f64 = 9.0;
+
+fn color_name(color: Tile) -> String {
+    format!("{:?}", color).to_lowercase()
+}
+
+// Schema with names put under the prefix, like `players.0.score`
+fn prefixed(prefix: &str, schema: Vec<FeatureDescriptor>) -> Vec<FeatureDescriptor> {
+    schema.into_iter().map(|f| FeatureDescriptor { name: format!("{}.{}", prefix, f.name), ..f }).collect()
+}
+
+// Schema of a color one-hot and count for every color
+fn tile_counts_schema(group: &str, max_count: usize) -> Vec<FeatureDescriptor> {
+    COLORS
+        .iter()
+        .flat_map(|&color| [
+            FeatureDescriptor::new(&format!("{}.color", color_name(color)), 5, group, 1.0),
+            FeatureDescriptor::new(&format!("{}.count", color_name(color)), 1, group, max_count as f64),
+        ])
+        .collect()
+}
+
+// Most tiles of any one color in the game
+fn max_tiles_per_color() -> usize {
+    config().tiles_per_color.values().copied().max().unwrap_or(0)
+}
+
+impl<T: Representable> Representable for Vec<T> {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = Vec::new();
+        for item in self {
+            vec.extend(item.represent());
+        }
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        self.iter().enumerate().flat_map(|(i, item)| prefixed(&i.to_string(), item.schema())).collect()
+    }
+}
+
+impl Representable for Tile {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = vec![0.0; 5];
+        let idx = COLORS.iter().position(|c| c == self).unwrap();
+        vec[idx] = 1.0;
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        vec![FeatureDescriptor::new("color", 5, "tile", 1.0)]
+    }
+}
+
+impl Representable for FactoryDisplayState {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = Vec::with_capacity(5 * (5 + 1));
+
+        for color in COLORS {
+            vec.extend(color.represent().iter());
+            vec.push(self[&color] as f64)
+        }
+
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        tile_counts_schema("display", config().tiles_per_factory)
+    }
+}
+
+impl Representable for CenterState {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = Vec::with_capacity(5 * (5 + 1) + 1);
+
+        for color in COLORS {
+            vec.extend(color.represent().iter());
+            vec.push(self.tiles[&color] as f64);
+        }
+
+        vec.push(self.starting_marker as i32 as f64);
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        let mut schema = tile_counts_schema("center", max_tiles_per_color());
+        schema.push(FeatureDescriptor::new("starting_marker", 1, "center", 1.0));
+        schema
+    }
+}
+
+impl Representable for PlayerState {
+    fn represent(&self) -> Vec<f64> {
+        // The size is based on flat representation of all items in the state
+        let mut vec = Vec::with_capacity(1 + (5 * 5) + 5 * (5 + 1) + 1 + 1);
+
+        vec.push(self.score as f64);
+        for i in 0..5 {
+            for j in 0..5 {
+                vec.push(self.wall[i][j] as usize as f64);
+            }
+        }
+
+        for i in 0..5 {
+            match self.pattern_lines[i] {
+                (None, count) => {
+                    vec.extend([0.0; 5].iter());
+                    vec.push(count as f64);
+                },
+                (Some(tile), count) => {
+                    vec.extend(tile.represent().iter());
+                    vec.push(count as f64);
+                },
+            }
+        }
+
+        vec.push(self.floor_line as f64);
+        vec.push(self.starting_marker as i32 as f64);
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        let mut schema = vec![
+            FeatureDescriptor::new("score", 1, "player", MAX_SCORE),
+            FeatureDescriptor::new("wall", 25, "player", 1.0),
+        ];
+        for i in 0..5 {
+            schema.push(FeatureDescriptor::new(&format!("pattern_lines.{}.color", i), 5, "player", 1.0));
+            schema.push(FeatureDescriptor::new(&format!("pattern_lines.{}.count", i), 1, "player", (i + 1) as f64));
+        }
+        schema.push(FeatureDescriptor::new("floor_line", 1, "player", config().floor_penalties.len() as f64));
+        schema.push(FeatureDescriptor::new("starting_marker", 1, "player", 1.0));
+        schema
+    }
+}
+
+impl Representable for State {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = Vec::new();
+
+        vec.extend(self.factory_displays.represent());
+        vec.extend(self.center.represent());
+        vec.extend(self.players.represent());
+        vec.push(self.rounds as f64);
+
+        // Tiles left to draw in later rounds. States from before the first
+        // deal don't keep the bag yet so it is counted under the current rules.
+        let bag = self.bag.unwrap_or_else(|| {
+            let bag = AzulRules::current().bag_tiles(self);
+            COLORS.map(|color| bag[&color])
+        });
+        vec.extend(bag.map(|count| count as f64));
+        vec.extend(COLORS.map(|color| self.lid[&color] as f64));
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        let mut schema = prefixed("displays", self.factory_displays.schema());
+        schema.extend(prefixed("center", self.center.schema()));
+        schema.extend(prefixed("players", self.players.schema()));
+        schema.push(FeatureDescriptor::new("rounds", 1, "game", MAX_ROUNDS));
+        schema.push(FeatureDescriptor::new("bag", 5, "tiles", max_tiles_per_color() as f64));
+        schema.push(FeatureDescriptor::new("lid", 5, "tiles", max_tiles_per_color() as f64));
+        schema
+    }
+
+    // Players are rotated so the perspective player's board comes first
+    fn represent_with(&self, options: &RepresentOptions) -> Vec<f64> {
+        let mut state = self.clone();
+        state.players.rotate_left(options.perspective % self.players.len());
+
+        let mut features = state.represent();
+        if options.normalize {
+            super::normalize(&mut features, &state.schema());
+        }
+        features
+    }
+}
+
+impl Representable for ActionDisplay {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec: Vec<f64> = Vec::with_capacity(1);
+        // Note that this will make the representation very tied to the number
+        // of players
+        match self {
+            Self::FactoryDisplay(i) => vec.push(*i as f64),
+            Self::Center => vec.push(-1.0)
+        }
+
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        vec![FeatureDescriptor::new("display", 1, "action", MAX_DISPLAYS)]
+    }
+}
+
+impl Representable for Action {
+    fn represent(&self) -> Vec<f64> {
+        let mut vec = Vec::new();
+
+        vec.extend(self.action_display_choice.represent());
+        vec.extend(self.color_choice.represent());
+        match self.pattern_line_choice {
+            None => vec.push(5.0),
+            Some(line_idx) => vec.push(line_idx as f64),
+        }
+
+        vec
+    }
+
+    fn schema(&self) -> Vec<FeatureDescriptor> {
+        vec![
+            FeatureDescriptor::new("display", 1, "action", MAX_DISPLAYS),
+            FeatureDescriptor::new("color", 5, "action", 1.0),
+            FeatureDescriptor::new("pattern_line", 1, "action", 5.0),
+        ]
+    }
+}
+
+impl CenterState {
+    fn new() -> Self {
+        Self {
+            tiles: HashMap::from([
+                (Tile::Black, 0),
+                (Tile::Blue, 0),
+                (Tile::Red, 0),
+                (Tile::White, 0),
+                (Tile::Yellow, 0),
+            ]),
+            starting_marker: true,
+        }
+    }
+
+    fn has_no_tiles(&self) -> bool {
+        self.tiles.values().sum::<usize>() == 0
+    }
+}
+
+impl PlayerState {
+    fn new() -> Self {
+        Self {
+            score: 0,
+            wall: [[false; 5]; 5],
+            pattern_lines: [(None, 0); 5],
+            floor_line: 0,
+            starting_marker: false,
+        }
+    }
+
+    // Change the score, which by the rules never goes below zero, not even
+    // while taking penalties. Returns the change actually made.
+    pub fn add_points(&mut self, points: i32) -> i32 {
+        let before = self.score;
+        self.score = (self.score + points).max(0);
+        self.score - before
+    }
+
+    // Tell if a player has completed at last one row. If this happen, the game gets over after the current round.
+    fn has_completed_row(&self) -> bool {
+        for i in 0..5 {
+            if self.wall[i].iter().all(|&x| x) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn completed_rows(&self) -> usize {
+        self.wall.iter().filter(|row| row.iter().all(|&x| x)).count()
+    }
+
+    pub fn wall_progress(&self) -> WallProgress {
+        let wall = &self.wall;
+        WallProgress {
+            rows: std::array::from_fn(|r| (0..5).filter(|&c| wall[r][c]).count()),
+            columns: std::array::from_fn(|c| (0..5).filter(|&r| wall[r][c]).count()),
+            colors: WALL_COLORS[0].map(|color| {
+                (0..5).filter(|&r| (0..5).any(|c| wall[r][c] && WALL_COLORS[r][c] == color)).count()
+            }),
+        }
+    }
+}
+
+// Tiles on the wall in each row, column, and color, the colors in the order of
+// the first wall row. A full row ends the game after the round while full
+// columns and colors score bonuses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WallProgress {
+    pub rows: [usize; 5],
+    pub columns: [usize; 5],
+    pub colors: [usize; 5],
+}
+
+fn build_empty_display() -> HashMap<Tile, usize> {
+    HashMap::from([
+        (Tile::Black, 0),
+        (Tile::Blue, 0),
+        (Tile::Red, 0),
+        (Tile::White, 0),
+        (Tile::Yellow, 0),
+    ])
+}
+
+// The state under the rules set with `set_config`
+impl GameState for State {
+    // Create new game with empty displays
+    fn new(n_players: usize) -> Self {
+        AzulRules::current().new_state(n_players)
+    }
+
+    fn is_round_over(&self) -> bool {
+        round_over(self)
+    }
+}
+
+// Azul played under a rules configuration. Everything that changes a `State`
+// goes through here so different rules can be used side by side.
+#[derive(Clone, Debug)]
+pub struct AzulRules {
+    pub config: Arc<AzulConfig>,
+}
+
+impl AzulRules {
+    pub fn new(config: AzulConfig) -> Self {
+        Self { config: Arc::new(config) }
+    }
+
+    // Rules set with `set_config`, standard by default
+    pub fn current() -> Self {
+        Self { config: config() }
+    }
+
+    // Tiles still in the bag. These are all the tiles of the game that are
+    // not on the walls, pattern lines, displays, center, or in the lid, so
+    // they can be counted by anyone watching the table.
+    pub fn bag_tiles(&self, state: &State) -> HashMap<Tile, usize> {
+        if let Some(bag) = state.bag {
+            return COLORS.into_iter().zip(bag).collect();
+        }
+
+        let mut bag = self.config.tiles_per_color.clone();
+        let mut take = |color: Tile, count: usize| {
+            if let Some(n) = bag.get_mut(&color) {
+                *n = n.saturating_sub(count);
+            }
+        };
+
+        for player in &state.players {
+            for (row, colors) in WALL_COLORS.iter().enumerate() {
+                for (col, &color) in colors.iter().enumerate() {
+                    if player.wall[row][col] {
+                        take(color, 1);
+                    }
+                }
+            }
+            for &(color, count) in &player.pattern_lines {
+                if let Some(color) = color {
+                    take(color, count);
+                }
+            }
+        }
+
+        for counts in state.factory_displays.iter().chain([&state.center.tiles, &state.lid]) {
+            for (&color, &count) in counts {
+                take(color, count);
+            }
+        }
+
+        bag
+    }
+
+    // Tiles left to draw for later rounds, in the bag and the lid. This is
+    // checked after every move of a rollout so it only counts the table when
+    // the bag isn't kept in the state.
+    pub fn tiles_to_draw(&self, state: &State) -> usize {
+        let bag: usize = match state.bag {
+            Some(bag) => bag.iter().sum(),
+            None => self.bag_tiles(state).values().sum(),
+        };
+        bag + state.lid.values().sum::<usize>()
+    }
+
+    // Whether the table has run out of tiles. Later rounds couldn't put
+    // anything on the displays, so the game ends after the current round even
+    // if nobody completed a row. The physical game only says to refill from
+    // the lid and keep playing with partly filled displays, which never ends
+    // once both are empty.
+    pub fn out_of_tiles(&self, state: &State) -> bool {
+        self.tiles_to_draw(state) == 0
+    }
+
+    // Whether the game was stopped by the round cap instead of ending by the
+    // rules
+    pub fn hit_round_cap(&self, state: &State) -> bool {
+        state.rounds >= self.config.max_rounds
+            && !state.players.iter().any(|p| p.has_completed_row())
+            && self.tiles_to_draw(state) > 0
+    }
+
+    // Points lost for the given number of tiles on the floor line
+    pub fn floor_penalty(&self, floor_line: usize) -> i32 {
+        self.config.floor_penalties.iter().take(floor_line).sum::<usize>() as i32
+    }
+
+    // Put tiles in the pattern and floor lines
+    fn stage_tiles(&self, state: &mut State, player_idx: usize, line: Option<usize>, color: Tile, count: usize, events: &mut impl EventSink<GameEvent>) {
+        let floor_size = self.config.floor_penalties.len();
+        let player = &mut state.players[player_idx];
+
+        match line {
+            None => {
+                player.floor_line = std::cmp::min(player.floor_line + count, floor_size);
+                events.push(GameEvent::TilesStaged { player: player_idx, line, color, count, filled: player.floor_line });
+                discard(&mut state.lid, color, count);
+            },
+            Some(idx) => {
+                let line_size = idx + 1;
+                let space = line_size - player.pattern_lines[idx].1;
+                let staged = std::cmp::min(space, count);
+
+                player.pattern_lines[idx] = (Some(color), player.pattern_lines[idx].1 + staged);
+                events.push(GameEvent::TilesStaged { player: player_idx, line, color, count: staged, filled: player.pattern_lines[idx].1 });
+
+                // Penalize for the leftovers
+                if count > staged {
+                    player.floor_line = std::cmp::min(player.floor_line + count - staged, floor_size);
+                    events.push(GameEvent::Overflowed { player: player_idx, count: count - staged });
+                    discard(&mut state.lid, color, count - staged);
+                }
+            }
+        }
+    }
+
+    // Immediate score gain of the action, see `calculate_reward`
+    pub fn reward(&self, state: &State, player_idx: usize, action: Action) -> i32 {
+        let mut state_clone_a = state.clone();
+        let mut state_clone_b = state.clone();
+
+        // This is needed since if this is not the first ply of the player in
+        // given round, they already might have more score than what's noted in
+        // state at the moment.
+        self.score_round_into(&mut state_clone_a, player_idx, &mut ());
+
+        self.take_action_into(&mut state_clone_b, player_idx, action, &mut ());
+        self.score_round_into(&mut state_clone_b, player_idx, &mut ());
+
+        // Calculate what gain will we have just from this action
+        state_clone_b.players[player_idx].score - state_clone_a.players[player_idx].score
+    }
+
+    fn score_placement(&self, wall: &[[bool; 5]; 5], row_idx: usize, color: Tile) -> i32 {
+        let mut score: i32 = 0;
+
+        let col_idx = WALL_COLORS[row_idx].iter().position(|&x| x == color).unwrap();
+        let col = (0..5).map(|i| wall[row_idx][i]).collect::<Vec<bool>>();
+
+        // Basic adjacency checks
+        let col_continuous = count_continuous(&col.clone().try_into().expect("Failed to convert column in a bool array"), col_idx);
+        let row_continuous = count_continuous(&wall[row_idx], row_idx);
+        score += std::cmp::max((col_continuous + row_continuous) as i32, 1);
+
+        // Check if col gets completed
+        let col_completed: bool = col
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| if i == row_idx { true } else { x });
+
+        if col_completed {
+            score += self.config.column_bonus
+        }
+
+        // Check if row gets completed
+        let row_completed: bool = wall[row_idx]
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| if i == col_idx { true } else { x });
+
+        if row_completed {
+            score += self.config.row_bonus
+        }
+
+        // Check if color gets completed
+        let mut color_coverage: usize = 0;
+
+        for i in 0..5 {
+            for j in 0..5 {
+                if i == row_idx && j == col_idx {
+                    color_coverage += 1;
+                } else {
+                    if WALL_COLORS[i][j] == color && wall[i][j] {
+                        color_coverage += 1;
+                    }
+                }
+            }
+        }
+
+        if color_coverage == 5 {
+            score += self.config.color_bonus
+        }
+
+        score
+    }
+}
+
+impl Rules for AzulRules {
+    type State = State;
+    type Action = Action;
+    type Event = GameEvent;
+
+    fn new_state(&self, n_players: usize) -> State {
+        let n_displays = self.config.n_factories(n_players);
+        let mut factory_displays: Vec<FactoryDisplayState> = Vec::with_capacity(n_displays);
+        for _i in 0..n_displays {
+            factory_displays.push(build_empty_display());
+        }
+
+        State {
+            factory_displays,
+            center: CenterState::new(),
+            players: vec![PlayerState::new(); n_players],
+            rounds: 0,
+            lid: build_empty_display(),
+            bag: None,
+        }
+    }
+
+    // Refill by drawing tiles from the bag with the given generator, resetting
+    // the center. When the bag runs out the lid is poured back in, and when
+    // both are empty the remaining displays stay short.
+    fn refill(&self, state: &mut State, rng: &mut impl Rng) {
+        let tiles_per_factory = self.config.tiles_per_factory;
+        state.center = CenterState::new();
+        let mut bag = self.bag_tiles(state);
+
+        for display in &mut state.factory_displays {
+            for _i in 0..tiles_per_factory {
+                if bag.values().sum::<usize>() == 0 {
+                    for (color, count) in state.lid.iter_mut() {
+                        *bag.get_mut(color).unwrap() += *count;
+                        *count = 0;
+                    }
+                }
+                let Ok(weights) = WeightedIndex::new(COLORS.map(|color| bag[&color])) else {
+                    state.bag = Some([0; 5]);
+                    return;
+                };
+
+                let tile = COLORS[weights.sample(rng)];
+                *bag.get_mut(&tile).unwrap() -= 1;
+                if let Some(count) = display.get_mut(&tile) {
+                    *count += 1;
+                }
+            }
+        }
+        state.bag = Some(COLORS.map(|color| bag[&color]));
+    }
+
+    // Actions come in the canonical `Action` order: displays in ascending
+    // order with the center last, colors in `COLORS` order, and pattern lines
+    // in ascending order with the floor last.
+    fn list_valid_actions(&self, state: &State, player_idx: usize) -> Vec<Action> {
+        let mut actions: Vec<Action> = Vec::new();
+
+        for display_idx in 0..state.factory_displays.len() {
+            if state.factory_displays[display_idx].is_empty() {
+                continue;
+            }
+
+            for color in COLORS {
+                if state.factory_displays[display_idx][&color] > 0 {
+                    for line in list_valid_lines(state, player_idx, color) {
+                        actions.push(Action {
+                            action_display_choice: ActionDisplay::FactoryDisplay(display_idx),
+                            color_choice: color,
+                            pattern_line_choice: line,
+                        })
+                    }
+                }
+            }
+        }
+
+        if !state.center.has_no_tiles() {
+            for color in COLORS {
+                if state.center.tiles[&color] > 0 {
+                    for line in list_valid_lines(state, player_idx, color) {
+                        actions.push(Action {
+                            action_display_choice: ActionDisplay::Center,
+                            color_choice: color,
+                            pattern_line_choice: line,
+                        })
+                    }
+                }
+            }
+        }
+
+        actions
+    }
+
+    // Assume that the action is valid and won't cause any issue. The action
+    // generator has to ensure this.
+    fn take_action_into(&self, state: &mut State, player_idx: usize, action: Action, events: &mut impl EventSink<GameEvent>) {
+        let source = action.action_display_choice;
+        let to_center = match source {
+            ActionDisplay::FactoryDisplay(i) => display_counts(&state.factory_displays[i]).iter().sum::<usize>() - state.factory_displays[i][&action.color_choice],
+            ActionDisplay::Center => 0,
+        };
+        let tiles = take_out_tiles(state, source, action.color_choice);
+        events.push(GameEvent::TilesTaken { player: player_idx, source, color: action.color_choice, count: tiles.len(), to_center });
+
+        // In case the action involves picking from center, take the starting
+        // marker if not already taken
+        if let ActionDisplay::Center = source {
+            if state.center.starting_marker {
+                state.players[player_idx].starting_marker = true;
+                state.center.starting_marker = false;
+                events.push(GameEvent::MarkerTaken { player: player_idx });
+            }
+        }
+
+        self.stage_tiles(state, player_idx, action.pattern_line_choice, action.color_choice, tiles.len(), events);
+    }
+
+    // Tile the wall and score. You can also call this in between a round but
+    // remember to do this on a copy of the state.
+    fn score_round_into(&self, state: &mut State, player_idx: usize, events: &mut impl EventSink<GameEvent>) {
+        let mut tiling_points = 0;
+        for (i, wall_colors) in WALL_COLORS.iter().enumerate() {
+            let line_size = i + 1;
+            if state.players[player_idx].pattern_lines[i].1 == line_size {
+                let color = state.players[player_idx].pattern_lines[i].0.unwrap();
+                let points = self.score_placement(&state.players[player_idx].wall, i, color);
+                tiling_points += points;
+                let col_idx = wall_colors.iter().position(|&x| x == color).unwrap();
+                state.players[player_idx].wall[i][col_idx] = true;
+                state.players[player_idx].pattern_lines[i] = (None, 0);
+                // One tile goes on the wall and the rest of the line to the lid
+                discard(&mut state.lid, color, line_size - 1);
+                events.push(GameEvent::WallTiled { player: player_idx, row: i, column: col_idx, color, points });
+            }
+        }
+        state.players[player_idx].add_points(tiling_points);
+
+        // Take penalties, if any
+        let penalties = self.floor_penalty(state.players[player_idx].floor_line);
+        state.players[player_idx].add_points(-penalties);
+
+        state.players[player_idx].floor_line = 0;
+        events.push(GameEvent::Scored {
+            player: player_idx,
+            tiling: tiling_points,
+            penalty: penalties,
+            score: state.players[player_idx].score,
+        });
+    }
+
+    // Score all players once the round's tiles are taken and tell if the
+    // game is over
+    fn end_round_into(&self, state: &mut State, events: &mut impl EventSink<GameEvent>) {
+        state.rounds += 1;
+        for i in 0..state.players.len() {
+            self.score_round_into(state, i, events);
+        }
+        events.push(GameEvent::RoundEnded { round: state.rounds });
+
+        if self.is_game_over(state) {
+            for (i, player) in state.players.iter_mut().enumerate() {
+                let points = self.config.handicap(i);
+                if points != 0 {
+                    let points = player.add_points(points);
+                    events.push(GameEvent::Handicapped { player: i, points, score: player.score });
+                }
+            }
+            events.push(GameEvent::GameEnded {
+                winner: winner(state),
+                scores: state.players.iter().map(|p| p.score).collect(),
+            });
+        }
+    }
+
+    fn is_round_over(&self, state: &State) -> bool {
+        round_over(state)
+    }
+
+    // The game also ends when the table runs out of tiles, see `out_of_tiles`
+    fn is_game_over(&self, state: &State) -> bool {
+        self.is_round_over(state) && (
+            state.players.iter().any(|p| p.has_completed_row()) ||
+            self.out_of_tiles(state) ||
+            state.rounds >= self.config.max_rounds
+        )
+    }
+}
+
+fn display_counts(display: &FactoryDisplayState) -> [usize; 5] {
+    COLORS.map(|color| display.get(&color).copied().unwrap_or(0))
+}
+
+// Feed the state to a hasher. Factory displays are interchangeable so they
+// are hashed sorted by contents with empty ones left out, and tile maps are
+// hashed in a fixed color order. States that only differ in the order of
+// displays hash the same.
+pub fn hash_state<H: Hasher>(state: &State, hasher: &mut H) {
+    let mut displays: Vec<[usize; 5]> = state.factory_displays
+        .iter()
+        .map(display_counts)
+        .filter(|counts| counts.iter().any(|&c| c > 0))
+        .collect();
+    displays.sort_unstable();
+    displays.hash(hasher);
+
+    display_counts(&state.center.tiles).hash(hasher);
+    state.center.starting_marker.hash(hasher);
+
+    state.players.hash(hasher);
+    state.rounds.hash(hasher);
+    display_counts(&state.lid).hash(hasher);
+}
+
+// Same position and move with the factory displays reordered so display `i`
+// becomes display `permutation[i]`. Display order doesn't change the game so
+// this is a free training sample.
+pub fn permute_displays(state: &State, action: Action, permutation: &[usize]) -> (State, Action) {
+    let mut permuted = state.clone();
+    for (i, display) in state.factory_displays.iter().enumerate() {
+        permuted.factory_displays[permutation[i]] = display.clone();
+    }
+
+    let action_display_choice = match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) => ActionDisplay::FactoryDisplay(permutation[i]),
+        ActionDisplay::Center => ActionDisplay::Center,
+    };
+
+    (permuted, Action { action_display_choice, ..action })
+}
+
+// Feed the action to a hasher in a way that matches `hash_state`. A display is
+// identified by its contents instead of its index.
+pub fn hash_action<H: Hasher>(state: &State, action: Action, hasher: &mut H) {
+    match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) => Some(display_counts(&state.factory_displays[i])),
+        ActionDisplay::Center => None,
+    }.hash(hasher);
+    action.color_choice.hash(hasher);
+    action.pattern_line_choice.hash(hasher);
+}
+
+impl Validate for State {
+    fn validate(&self) -> Result<()> {
+        let n_players = self.players.len();
+        if !(2..=4).contains(&n_players) {
+            return Err(anyhow!("Number of players ({}) outside the bound [2, 4]", n_players));
+        }
+
+        Ok(())
+    }
+}
+
+fn has_no_tiles(display: FactoryDisplayState) -> bool {
+    display.values().sum::<usize>() == 0
+}
+
+// Rounds end when all tiles are taken whatever the rules
+fn round_over(state: &State) -> bool {
+    state.center.has_no_tiles() && state.factory_displays
+        .iter()
+        .all(|d| has_no_tiles(d.clone()))
+}
+
+// Mutate the game state and take out given color tiles based on the action
+fn take_out_tiles(state: &mut State, action: ActionDisplay, color: Tile) -> Vec<Tile> {
+    let count = match action {
+        ActionDisplay::Center => {
+            let count = state.center.tiles[&color];
+            if let Some(v) = state.center.tiles.get_mut(&color) {
+                *v = 0
+            }
+            count
+        },
+        ActionDisplay::FactoryDisplay(i) => {
+            let mut count: usize = 0;
+            for c in COLORS {
+                let c_count = state.factory_displays[i][&c];
+                if color == c {
+                    count = c_count;
+                } else {
+                    if let Some(v) = state.center.tiles.get_mut(&c) {
+                        *v += c_count;
+                    }
+                }
+                if let Some(v) = state.factory_displays[i].get_mut(&c) {
+                    *v = 0
+                }
+            }
+
+            count
+        },
+    };
+
+    vec![color; count]
+}
+
+fn wall_row_has_color(wall: &[[bool; 5]; 5], row_idx: usize, color: Tile) -> bool {
+    let row_colors = WALL_COLORS[row_idx];
+    let wall_row = wall[row_idx];
+
+    let idx = row_colors.iter().position(|&c| c == color).unwrap();
+    wall_row[idx]
+}
+
+fn find_empty_lines(state: &State, color: Tile, player_idx: usize) -> Vec<usize> {
+    // A line is not available if it has a color other than given, if it has no
+    // space, or its wall row has the same color filled.
+    let mut empty_line_ids: Vec<usize> = Vec::new();
+
+    for i in 0..5 {
+        let line_size = i + 1;
+        let line = state.players[player_idx].pattern_lines[i];
+        let wall = state.players[player_idx].wall;
+
+        match line.0 {
+            None => { if !wall_row_has_color(&wall, i, color) { empty_line_ids.push(i) } },
+            Some(tile) => { if tile == color && line_size > line.1 { empty_line_ids.push(i) } }
+        }
+    }
+
+    empty_line_ids
+}
+
+// Floor tiles end up in the lid after scoring. They are put there right away
+// since nothing reads the lid before the next refill.
+fn discard(lid: &mut HashMap<Tile, usize>, color: Tile, count: usize) {
+    if let Some(n) = lid.get_mut(&color) {
+        *n += count;
+    }
+}
+
+// List all valid lines that can be considered for given color and player. None
+// means choosing floor line.
+fn list_valid_lines(state: &State, player_idx: usize, color: Tile) -> Vec<Option<usize>> {
+    let empty_lines = find_empty_lines(state, color, player_idx);
+    let mut lines: Vec<Option<usize>> = empty_lines.iter().map(|&i| Some(i)).collect();
+    lines.push(None);
+    lines
+}
+
+// Valid actions in canonical order for anything that refers to actions by
+// their position in the list, like policy outputs and replays by index. Their
+// meaning relies on this order staying the same across versions.
+pub fn actions_canonical(rules: &AzulRules, state: &State, player_idx: usize) -> Vec<Action> {
+    let actions = rules.list_valid_actions(state, player_idx);
+    debug_assert!(actions.is_sorted());
+    actions
+}
+
+// Canonical action space covers up to 9 factory displays and the center, all
+// colors, and the 5 pattern lines plus the floor
+const N_SOURCES: usize = 10;
+const N_LINES: usize = 6;
+pub const N_ACTIONS: usize = N_SOURCES * 5 * N_LINES;
+
+// Position of the action in the canonical action space, None for displays
+// beyond the ninth
+pub fn action_index(action: &Action) -> Option<usize> {
+    let source = match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) if i < N_SOURCES - 1 => i,
+        ActionDisplay::FactoryDisplay(_) => return None,
+        ActionDisplay::Center => N_SOURCES - 1,
+    };
+    let color = COLORS.iter().position(|&c| c == action.color_choice).unwrap();
+    let line = action.pattern_line_choice.unwrap_or(N_LINES - 1);
+
+    Some((source * 5 + color) * N_LINES + line)
+}
+
+fn tiles_taken(state: &State, action: Action) -> usize {
+    let tiles = match action.action_display_choice {
+        ActionDisplay::Center => &state.center.tiles,
+        ActionDisplay::FactoryDisplay(i) => &state.factory_displays[i],
+    };
+    tiles.get(&action.color_choice).copied().unwrap_or(0)
+}
+
+// Move to play without asking when there is no real choice: the only valid
+// action, or the first one when every action puts the same number of tiles on
+// the floor
+pub fn forced_action(rules: &AzulRules, state: &State, player_idx: usize) -> Option<Action> {
+    let actions = rules.list_valid_actions(state, player_idx);
+    let first = *actions.first()?;
+    let all_floor = actions.iter().all(|a| a.pattern_line_choice.is_none() && tiles_taken(state, *a) == tiles_taken(state, first));
+    (actions.len() == 1 || all_floor).then_some(first)
+}
+
+fn count_continuous(array: &[bool; 5], anchor: usize) -> usize {
+    let mut count = 0;
+    let mut curr: i32;
+
+    for i in 1..5 {
+        curr = anchor as i32 - i;
+        if curr < 0 {
+            break;
+        }
+        if array[curr as usize] {
+            count += 1;
+        } else {
+            break;
+        }
+    }
+
+    for i in 1..5 {
+        curr = anchor as i32 + i;
+        if curr > 4 {
+            break;
+        }
+        if array[curr as usize] {
+            count += 1;
+        } else {
+            break;
+        }
+    }
+
+    count
+}
+
+// Tell if one of the players has starting marker
+pub fn first_player(state: &State) -> Option<usize> {
+    for i in 0..state.players.len() {
+        if state.players[i].starting_marker {
+            return Some(i)
+        }
+    }
+
+    None
+}
+
+// Players with the highest score, ties broken by the most completed rows.
+// Players still tied after that share the victory.
+pub fn winners(state: &State) -> Vec<usize> {
+    let best = state.players.iter().map(|p| (p.score, p.completed_rows())).max().unwrap();
+    (0..state.players.len())
+        .filter(|&i| (state.players[i].score, state.players[i].completed_rows()) == best)
+        .collect()
+}
+
+// The single winner, None for a draw
+pub fn winner(state: &State) -> Option<usize> {
+    match winners(state)[..] {
+        [i] => Some(i),
+        _ => None,
+    }
+}
+
+pub fn winner_name(winner: Option<usize>) -> String {
+    winner.map_or("a draw".to_string(), |i| format!("P{}", i))
+}
+
+// Best immediate reward available to the next player after the given action is
+// taken. A high value means the action gifts a big move to the opponent.
+pub fn best_reply_gain(rules: &AzulRules, state: &State, player_idx: usize, action: Action) -> i32 {
+    let mut next_state = state.clone();
+    rules.take_action_into(&mut next_state, player_idx, action, &mut ());
+
+    if next_state.is_round_over() {
+        return 0;
+    }
+
+    let next_player_idx = (player_idx + 1) % state.players.len();
+    rules.list_valid_actions(&next_state, next_player_idx)
+        .into_iter()
+        .map(|a| rules.reward(&next_state, next_player_idx, a))
+        .max()
+        .unwrap_or(0)
+}
+
+// Anything that can pick an action for a player in a state under the rules
+// of the game. The last argument is the player's remaining time when the game is played with clocks.
+pub type PlayerFn<'a> = &'a dyn Fn(&AzulRules, &State, usize, Option<Duration>) -> Action;
+
+// Play a full game from a fresh state with one play function per seat and
+// return the final state with all rounds scored. With a time control, the game
+// stops as soon as a player's flag falls and that player is returned too.
+pub fn play_game(rules: &AzulRules, players: &[PlayerFn], time_control: Option<TimeControl>) -> (State, Option<usize>) {
+    let n_players = players.len();
+    let mut state = rules.new_state(n_players);
+    let mut clock = time_control.map(|tc| Clock::new(tc, n_players));
+
+    loop {
+        let mut current_player = match first_player(&state) {
+            Some(one) => {
+                state.players[one].starting_marker = false;
+                one
+            },
+            None => 0,
+        };
+
+        rules.refill(&mut state, &mut game_rng());
+
+        while !rules.is_round_over(&state) {
+            let remaining = clock.as_ref().map(|c| c.remaining(current_player, current_player));
+            let action = players[current_player](rules, &state, current_player, remaining);
+
+            if let Some(clock) = clock.as_mut() {
+                if !clock.switch(current_player) {
+                    return (state, Some(current_player));
+                }
+            }
+
+            rules.take_action_into(&mut state, current_player, action, &mut ());
+            current_player += 1;
+            current_player %= n_players;
+        }
+        rules.end_round_into(&mut state, &mut ());
+
+        if rules.is_game_over(&state) {
+            break;
+        }
+    }
+
+    (state, None)
+}
+
+// Play out the rest of the current round on a copy of the state with all
+// players using the given policy, starting with `player_idx`. Walls are tiled
+// and scored in the returned state so it shows the projected round end.
+pub fn play_out_round(rules: &AzulRules, state: &State, player_idx: usize, policy: fn(&AzulRules, &State, usize) -> Action) -> State {
+    let mut future_state = state.clone();
+    let mut current_player = player_idx;
+
+    while !rules.is_round_over(&future_state) {
+        let action = policy(rules, &future_state, current_player);
+        rules.take_action_into(&mut future_state, current_player, action, &mut ());
+        current_player += 1;
+        current_player %= future_state.players.len();
+    }
+
+    for i in 0..future_state.players.len() {
+        rules.score_round_into(&mut future_state, i, &mut ());
+    }
+
+    future_state
+}
+
+// Likely continuation after the action, up to `depth` plies within the round.
+// Every reply is the choice of a short search of the position it is played in,
+// the same search the replies of `search_tree` come from.
+pub fn principal_variation(rules: &AzulRules, state: &State, player_idx: usize, action: Action, depth: usize) -> Vec<(usize, Action)> {
+    let reply_config = MctsConfig { n_games: REPLY_GAMES, ..MctsConfig::default() };
+    let mut future_state = state.clone();
+    let mut current_player = player_idx;
+    let mut variation = vec![(player_idx, action)];
+    rules.take_action_into(&mut future_state, player_idx, action, &mut ());
+
+    while variation.len() < depth && !rules.is_round_over(&future_state) {
+        current_player = (current_player + 1) % future_state.players.len();
+        let Some(reply) = mcts_evaluate(rules, &future_state, current_player, &reply_config).first().map(|s| s.action) else {
+            break;
+        };
+        rules.take_action_into(&mut future_state, current_player, reply, &mut ());
+        variation.push((current_player, reply));
+    }
+
+    variation
+}
+
+// Count tiles of given color left on the table, in displays and the center
+fn tiles_on_table(state: &State, color: Tile) -> usize {
+    state.factory_displays.iter().map(|d| d[&color]).sum::<usize>() + state.center.tiles[&color]
+}
+
+// Tiles of each color left on the table, in `COLORS` order
+pub fn colors_on_table(state: &State) -> [(Tile, usize); 5] {
+    COLORS.map(|color| (color, tiles_on_table(state, color)))
+}
+
+// Colors the player is collecting this round with the number of tiles still
+// missing, from pattern lines that are started but not full. Lines are
+// started only with colors missing from their wall row so all of these can
+// still be placed.
+pub fn colors_needed(state: &State, player_idx: usize) -> Vec<(Tile, usize)> {
+    let mut needed: Vec<(Tile, usize)> = Vec::new();
+
+    for (line_idx, &(tile, count)) in state.players[player_idx].pattern_lines.iter().enumerate() {
+        let Some(color) = tile else {
+            continue;
+        };
+        if count == line_idx + 1 {
+            continue;
+        }
+        match needed.iter_mut().find(|(c, _)| *c == color) {
+            Some((_, missing)) => *missing += line_idx + 1 - count,
+            None => needed.push((color, line_idx + 1 - count)),
+        }
+    }
+
+    needed
+}
+
+// Why a started pattern line might not get finished this round
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DenialRisk {
+    // Fewer tiles of the color are left than the line is missing
+    Short,
+    // The other players moving before the player's next turn can take every
+    // source of the color
+    Contested,
+}
+
+// Started pattern lines of the player that could be denied this round, with
+// the line index, its color, and why
+pub fn denial_risks(state: &State, player_idx: usize) -> Vec<(usize, Tile, DenialRisk)> {
+    let n_others = state.players.len() - 1;
+    let mut risks = Vec::new();
+
+    for (line_idx, &(tile, count)) in state.players[player_idx].pattern_lines.iter().enumerate() {
+        let Some(color) = tile else {
+            continue;
+        };
+        if count == line_idx + 1 {
+            continue;
+        }
+
+        let n_sources = state.factory_displays.iter().filter(|d| d[&color] > 0).count()
+            + (state.center.tiles[&color] > 0) as usize;
+        if tiles_on_table(state, color) < line_idx + 1 - count {
+            risks.push((line_idx, color, DenialRisk::Short));
+        } else if n_sources <= n_others {
+            risks.push((line_idx, color, DenialRisk::Contested));
+        }
+    }
+
+    risks
+}
+
+// Short text form of an action, like "D2 Blue 3" or "C Red F", with F for
+// dumping the tiles on the floor line
+pub fn action_notation(action: Action) -> String {
+    let source = match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) => format!("D{}", i),
+        ActionDisplay::Center => "C".to_string(),
+    };
+
+    let line = match action.pattern_line_choice {
+        Some(i) => i.to_string(),
+        None => "F".to_string(),
+    };
+
+    format!("{} {:?} {}", source, action.color_choice, line)
+}
+
+pub fn parse_action_notation(notation: &str) -> Result<Action> {
+    let parts: Vec<&str> = notation.split_whitespace().collect();
+    if parts.len() != 3 {
+        return Err(anyhow!("Action '{}' is not in '<source> <color> <line>' form", notation));
+    }
+
+    let action_display_choice = match parts[0].to_uppercase().as_str() {
+        "C" => ActionDisplay::Center,
+        source => match source.strip_prefix('D').and_then(|i| i.parse().ok()) {
+            Some(i) => ActionDisplay::FactoryDisplay(i),
+            None => return Err(anyhow!("Unknown source '{}'", parts[0])),
+        },
+    };
+
+    let color_choice = *COLORS
+        .iter()
+        .find(|c| format!("{:?}", c).eq_ignore_ascii_case(parts[1]))
+        .ok_or_else(|| anyhow!("Unknown color '{}'", parts[1]))?;
+
+    let pattern_line_choice = match parts[2] {
+        "F" | "f" => None,
+        line => Some(line.parse().map_err(|_| anyhow!("Unknown pattern line '{}'", line))?),
+    };
+
+    Ok(Action { action_display_choice, color_choice, pattern_line_choice })
+}
+
+// Describe what the action does in plain words, like "Takes 3 Blue from D2;
+// completes pattern line 3; 2 tiles overflow to floor (-2)". This simulates the
+// action on a copy of the state and explains its events.
+pub fn explain_action(rules: &AzulRules, state: &State, player_idx: usize, action: Action) -> String {
+    let mut next_state = state.clone();
+    let events = rules.take_action(&mut next_state, player_idx, action);
+
+    let color = action.color_choice;
+    let penalty = rules.floor_penalty(next_state.players[player_idx].floor_line) - rules.floor_penalty(state.players[player_idx].floor_line);
+
+    let mut parts = Vec::new();
+    for event in &events {
+        match event {
+            GameEvent::TilesTaken { source, count, .. } => {
+                parts.push(format!("Takes {} {:?} from {}", count, color, source_name(*source)));
+            },
+            GameEvent::MarkerTaken { .. } => parts.push("takes the starting marker".to_string()),
+            GameEvent::TilesStaged { line: Some(i), filled, .. } => {
+                if *filled == i + 1 {
+                    parts.push(format!("completes pattern line {}", i));
+                } else {
+                    parts.push(format!("fills pattern line {} ({}/{})", i, filled, i + 1));
+                }
+            },
+            GameEvent::TilesStaged { line: None, count, .. } => {
+                parts.push(format!("dumps {} tiles to floor (-{})", count, penalty));
+            },
+            GameEvent::Overflowed { count, .. } => {
+                parts.push(format!("{} tiles overflow to floor (-{})", count, penalty));
+            },
+            _ => {},
+        }
+    }
+
+    // Other players who were collecting this color and now can't get more of
+    // it this round
+    if tiles_on_table(&next_state, color) == 0 {
+        for (i, player) in next_state.players.iter().enumerate() {
+            if i == player_idx {
+                continue;
+            }
+
+            let needs_color = player.pattern_lines
+                .iter()
+                .enumerate()
+                .any(|(line_idx, &(tile, count))| tile == Some(color) && count < line_idx + 1);
+
+            if needs_color {
+                parts.push(format!("denies P{} the last {:?}", i, color));
+            }
+        }
+    }
+
+    parts.join("; ")
+}
+
+// Choose a random action from the list of valid actions available to the
+// player
+pub fn play_random(rules: &AzulRules, state: &State, player_idx: usize) -> Action {
+    let mut rng = game_rng();
+    *rules.list_valid_actions(state, player_idx).choose(&mut rng).unwrap()
+}
+
+// See all possible actions and choose the one that has highest immediate reward
+// for the player
+pub fn play_greedy(rules: &AzulRules, state: &State, player_idx: usize) -> Action {
+    rules.list_valid_actions(state, player_idx).into_iter().max_by_key(|a| rules.reward(state, player_idx, *a)).unwrap()
+}
+
+fn max_n_action_score(rules: &AzulRules, state: &State, action: Action, player_idx: usize, current_depth: usize) -> (State, Vec<i32>) {
+    let mut state_clone = state.clone();
+    rules.take_action_into(&mut state_clone, player_idx, action, &mut ());
+
+    // Base case
+    if current_depth == 0 || rules.is_game_over(&state_clone) {
+        return (state_clone.clone(), (0..state_clone.players.len()).map(|pi| {
+            rules.score_round_into(&mut state_clone, pi, &mut ());
+            state_clone.players[pi].score
+        }).collect());
+    }
+
+    if rules.is_round_over(&state_clone) {
+        state_clone.rounds += 1;
+        for i in 0..state_clone.players.len() {
+            rules.score_round_into(&mut state_clone, i, &mut ());
+        }
+        rules.refill(&mut state_clone, &mut game_rng());
+    }
+
+    let next_player_idx = (player_idx + 1) % state.players.len();
+    let next_actions = rules.list_valid_actions(&state_clone, next_player_idx);
+
+    log::debug!("Total actions at {} for {}: {:?}", current_depth, player_idx, next_actions.len());
+
+    let state_scores: Vec<(State, Vec<i32>)> = (0..next_actions.len()).map(|i| {
+        max_n_action_score(rules, &state_clone, next_actions[i], next_player_idx, current_depth - 1)
+    }).collect();
+
+    state_scores.into_iter().max_by_key(|(_state, scores)| scores[next_player_idx]).unwrap()
+}
+
+// Play using a minimax variant for multiple players. Depth is the depth of
+// plies and not rounds.
+pub fn play_max_n(rules: &AzulRules, state: &State, player_idx: usize) -> Action {
+    // This is not very efficient since we don't maintain any state across plies
+    let depth = 2;
+    let actions = rules.list_valid_actions(state, player_idx);
+    let scores: Vec<Vec<i32>> = (0..actions.len()).map(|i| {
+        max_n_action_score(rules, state, actions[i], player_idx, depth).1
+    }).collect();
+
+    // TODO: alpha-beta pruning and pre-sorting
+
+    let best_action_idx = scores.iter().enumerate().max_by_key(|(_i, ps)| ps[player_idx]).unwrap().0;
+    actions[best_action_idx]
+}
+
+fn rewards_dist(rewards: Vec<i32>) -> Vec<usize> {
+    let rewards_d: Vec<i32> = rewards.iter().map(|r| *r.max(&0)).collect();
+
+    if rewards_d.iter().all(|r| *r == 0) {
+        rewards_d.iter().map(|_r| 1).collect()
+    } else {
+        rewards_d.iter().map(|r| *r as usize).collect()
+    }
+}
+
+fn mcts_ply(rules: &AzulRules, state: &State, player_idx: usize) -> Action {
+    let mut rng = game_rng();
+
+    let actions = rules.list_valid_actions(state, player_idx);
+    let rewards: Vec<i32> = actions.iter().map(|&a| rules.reward(state, player_idx, a)).collect();
+    // Normalize rewards to return positive numbers
+    let rewards_d = rewards_dist(rewards.clone());
+
+    let epsilon = 0.05;
+    let action_idx = if rng.random_range(0.0..1.0) < epsilon {
+        (0..actions.len()).choose(&mut rng).unwrap()
+    } else {
+        let dist = WeightedIndex::new(&rewards_d).unwrap();
+        dist.sample(&mut rng)
+    };
+
+    actions[action_idx]
+}
+
+// How a rollout ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RolloutEnd {
+    Finished,
+    // A position came up again, which is scored as a draw where nobody wins
+    Repeated,
+}
+
+// Positions met in a rollout, by their hash with the player to move
+#[derive(Default)]
+struct SeenPositions(HashSet<u64>);
+
+impl SeenPositions {
+    // Remember the position and tell if it came up before
+    fn repeated(&mut self, state: &State, player_idx: usize) -> bool {
+        let mut hasher = DefaultHasher::new();
+        hash_state(state, &mut hasher);
+        player_idx.hash(&mut hasher);
+        !self.0.insert(hasher.finish())
+    }
+}
+
+// Play the game out after `player_idx` moved with every player sampling
+// actions by immediate reward. This implementation doesn't do caching so it
+// will not be super efficient nor effective. Positions are remembered so rules
+// where play can go round in circles stop the rollout instead of spinning
+// forever.
+fn rollout(rules: &AzulRules, state: &mut State, player_idx: usize, refill_rng: &mut StdRng) -> RolloutEnd {
+    let mut seen = SeenPositions::default();
+    let mut next_player_idx = player_idx;
+
+    loop {
+        if rules.is_game_over(state) {
+            return RolloutEnd::Finished;
+        }
+
+        if rules.is_round_over(state) {
+            rules.end_round_into(state, &mut ());
+            rules.refill(state, refill_rng);
+        }
+
+        next_player_idx += 1;
+        next_player_idx %= state.players.len();
+
+        if seen.repeated(state, next_player_idx) {
+            log::debug!("Rollout stopped on a repeated position in round {}", state.rounds);
+            return RolloutEnd::Repeated;
+        }
+
+        let next_action = mcts_ply(rules, state, next_player_idx);
+        rules.take_action_into(state, next_player_idx, next_action, &mut ());
+    }
+}
+
+// Rollouts done by `mcts_q_fn` for one action
+pub const Q_FN_GAMES: usize = 200;
+
+// Q function using MCTS but not using distribution sampling in the first step
+pub fn mcts_q_fn(rules: &AzulRules, state: &State, player_idx: usize, action: Action) -> (f32, f32) {
+    let n_games = Q_FN_GAMES;
+
+    let mut scores: Vec<usize> = Vec::new();
+    let mut n_wins = 0;
+
+    for _ in 0..n_games {
+        let mut future_state = state.clone();
+        rules.take_action_into(&mut future_state, player_idx, action, &mut ());
+        let end = rollout(rules, &mut future_state, player_idx, &mut game_rng());
+
+        scores.push(future_state.players[player_idx].score as usize);
+        n_wins += (end == RolloutEnd::Finished && winner(&future_state) == Some(player_idx)) as usize;
+    }
+
+    ((scores.into_iter().sum::<usize>() as f32 / n_games as f32), n_wins as f32 / n_games as f32)
+}
+
+// Estimate, using rollouts of the rest of the round, the probability that the
+// player is forced to put more tiles on the floor after taking this action.
+// Also returns the expected penalty from those later floor tiles.
+pub fn forecast_floor(rules: &AzulRules, state: &State, player_idx: usize, action: Action) -> (f32, f32) {
+    let n_games = 200;
+
+    let mut n_floored = 0;
+    let mut total_penalty = 0;
+
+    for _ in 0..n_games {
+        let mut future_state = state.clone();
+        rules.take_action_into(&mut future_state, player_idx, action, &mut ());
+        let floor_line = future_state.players[player_idx].floor_line;
+        let mut next_player_idx = player_idx;
+
+        // Only the current round matters here since floor lines are cleared
+        // when the wall is tiled
+        while !rules.is_round_over(&future_state) {
+            next_player_idx += 1;
+            next_player_idx %= future_state.players.len();
+
+            let next_action = mcts_ply(rules, &future_state, next_player_idx);
+            rules.take_action_into(&mut future_state, next_player_idx, next_action, &mut ());
+        }
+
+        let final_floor_line = future_state.players[player_idx].floor_line;
+        if final_floor_line > floor_line {
+            n_floored += 1;
+            total_penalty += rules.floor_penalty(final_floor_line) - rules.floor_penalty(floor_line);
+        }
+    }
+
+    (n_floored as f32 / n_games as f32, total_penalty as f32 / n_games as f32)
+}
+
+// Rollouts done by `marker_value` for each side of the comparison
+pub const MARKER_GAMES: usize = 100;
+
+// Play the game out after `player_idx` moved. Unlike `rollout`, the holder of
+// the starting marker opens each new round as in `play_game` since that is
+// what the marker is worth.
+fn marker_rollout(rules: &AzulRules, state: &mut State, player_idx: usize, refill_rng: &mut StdRng) {
+    let mut seen = SeenPositions::default();
+    let mut next_player_idx = player_idx;
+
+    loop {
+        if rules.is_round_over(state) {
+            rules.end_round_into(state, &mut ());
+            if rules.is_game_over(state) {
+                return;
+            }
+            next_player_idx = match first_player(state) {
+                Some(one) => {
+                    state.players[one].starting_marker = false;
+                    one
+                },
+                None => 0,
+            };
+            rules.refill(state, refill_rng);
+        } else {
+            next_player_idx += 1;
+            next_player_idx %= state.players.len();
+        }
+
+        if seen.repeated(state, next_player_idx) {
+            return;
+        }
+
+        let next_action = mcts_ply(rules, state, next_player_idx);
+        rules.take_action_into(state, next_player_idx, next_action, &mut ());
+    }
+}
+
+// Expected final score gained by taking the starting marker with this center
+// action now instead of leaving it in the center for whoever takes from there
+// next. This is the first player advantage net of whatever the rules charge
+// for the marker. None if the action doesn't take the marker.
+pub fn marker_value(rules: &AzulRules, state: &State, player_idx: usize, action: Action) -> Option<f32> {
+    if action.action_display_choice != ActionDisplay::Center || !state.center.starting_marker {
+        return None;
+    }
+
+    let mut now = state.clone();
+    rules.take_action_into(&mut now, player_idx, action, &mut ());
+
+    let mut later = state.clone();
+    later.center.starting_marker = false;
+    rules.take_action_into(&mut later, player_idx, action, &mut ());
+    later.center.starting_marker = true;
+
+    let mut total = 0;
+    for _ in 0..MARKER_GAMES {
+        // Both sides see the same refills so the difference is less noisy
+        let refill_rng = game_rng();
+        let mut now = now.clone();
+        marker_rollout(rules, &mut now, player_idx, &mut refill_rng.clone());
+        let mut later = later.clone();
+        marker_rollout(rules, &mut later, player_idx, &mut refill_rng.clone());
+        total += now.players[player_idx].score - later.players[player_idx].score;
+    }
+
+    Some(total as f32 / MARKER_GAMES as f32)
+}
+
+// Knobs for the MCTS player. Weaker configurations are used to make the engine
+// a more forgiving opponent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MctsConfig {
+    // Number of rollouts per move
+    pub n_games: usize,
+    // Probability of exploring a uniformly random first action in a rollout
+    pub epsilon: f64,
+    // Probability of intentionally playing a worse action than the best one
+    pub blunder_rate: f64,
+    // Maximum mean score loss allowed for an intentional blunder
+    pub max_blunder_loss: f64,
+    // Stop doing rollouts once this much time is spent on the move
+    pub time_budget: Option<Duration>,
+    // Progressive widening, rollouts only start from this many actions with
+    // the best immediate reward plus one more for every square number of
+    // rollouts done. 0 considers all actions from the start.
+    pub widening: usize,
+    // Number of bag draw sequences sampled once and shared by all rollouts, so
+    // actions are compared over the same refills. 0 draws afresh every
+    // rollout.
+    pub determinizations: usize,
+    // Weight of the exploration term when first actions are picked by PUCT
+    // from policy priors
+    pub c_puct: f64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            n_games: 200,
+            epsilon: 0.05,
+            blunder_rate: 0.0,
+            max_blunder_loss: 0.0,
+            time_budget: None,
+            widening: 0,
+            determinizations: 0,
+            c_puct: 1.5,
+        }
+    }
+}
+
+// Scores, wins, number of rollouts, and rollouts stopped on a repeated
+// position for each valid action
+type MctsActionLog = Vec<(Vec<usize>, usize, usize, usize)>;
+
+// Interval and callback getting the number of rollouts done and the statistics
+// so far, best first, while a search runs
+pub type SearchInfo<'a> = (Duration, &'a mut dyn FnMut(usize, &[MctsStats]));
+
+// Rollouts from the state where first actions are sampled by immediate
+// reward, or picked by PUCT with prior weights for the valid actions if given,
+// stopping early if `stop` returns true or the time budget is spent
+fn mcts_search(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, priors: Option<&[f64]>, stop: &dyn Fn() -> bool, mut info: Option<SearchInfo>) -> (Vec<Action>, MctsActionLog) {
+    let n_games = config.n_games;
+    let mut rng = game_rng();
+
+    let actions = rules.list_valid_actions(state, player_idx);
+    let rewards: Vec<i32> = actions.iter().map(|&a| rules.reward(state, player_idx, a)).collect();
+    // Normalize rewards to return positive numbers
+    let rewards_d = rewards_dist(rewards.clone());
+    let weights: Vec<f64> = rewards_d.iter().map(|&r| r as f64).collect();
+    let priors = priors.map(normalize_priors);
+
+    // Scores, wins, total expansions, repeats
+    let mut action_log: MctsActionLog = vec![(Vec::new(), 0, 0, 0); actions.len()];
+
+    // Action indices from the best immediate reward down, for widening
+    let mut by_reward: Vec<usize> = (0..actions.len()).collect();
+    by_reward.sort_by_key(|&i| std::cmp::Reverse(rewards[i]));
+
+    // Seeds for the refill draws of each determinization
+    let refill_seeds: Vec<u64> = (0..config.determinizations).map(|_| rng.random()).collect();
+
+    let mut dist: WeightedIndex<f64>;
+
+    let epsilon = config.epsilon;
+    let started = Instant::now();
+    let mut last_info = started;
+
+    for game_idx in 0..n_games {
+        if game_idx > 0 && (stop() || config.time_budget.is_some_and(|budget| started.elapsed() >= budget)) {
+            break;
+        }
+
+        if let Some((interval, report)) = info.as_mut() {
+            if game_idx > 0 && last_info.elapsed() >= *interval {
+                report(game_idx, &mcts_stats(&actions, &action_log));
+                last_info = Instant::now();
+            }
+        }
+
+        let mut future_state = state.clone();
+        let action_idx: usize;
+
+        let n_considered = if config.widening > 0 {
+            actions.len().min(config.widening + game_idx.isqrt())
+        } else {
+            actions.len()
+        };
+        let considered = &by_reward[..n_considered];
+
+        if rng.random_range(0.0..1.0) < epsilon {
+            action_idx = *considered.choose(&mut rng).unwrap();
+        } else if let Some(priors) = &priors {
+            action_idx = puct_pick(considered, priors, &action_log, game_idx, config.c_puct);
+        } else {
+            dist = WeightedIndex::new(considered.iter().map(|&i| weights[i])).unwrap();
+            action_idx = considered[dist.sample(&mut rng)];
+        }
+
+        rules.take_action_into(&mut future_state, player_idx, actions[action_idx], &mut ());
+        let mut refill_rng = match refill_seeds.len() {
+            0 => game_rng(),
+            n => StdRng::seed_from_u64(refill_seeds[game_idx % n]),
+        };
+        let end = rollout(rules, &mut future_state, player_idx, &mut refill_rng);
+
+        // One MC game is over, update the log
+        let (scores, n_wins, n_games, n_repeats) = &mut action_log[action_idx];
+        scores.push(future_state.players[player_idx].score as usize);
+        *n_wins += (end == RolloutEnd::Finished && winner(&future_state) == Some(player_idx)) as usize;
+        *n_games += 1;
+        *n_repeats += (end == RolloutEnd::Repeated) as usize;
+    }
+
+    (actions, action_log)
+}
+
+// Priors scaled to sum to 1, uniform if they are all 0
+fn normalize_priors(priors: &[f64]) -> Vec<f64> {
+    let total: f64 = priors.iter().sum();
+    if total > 0.0 {
+        priors.iter().map(|p| p / total).collect()
+    } else {
+        vec![1.0 / priors.len() as f64; priors.len()]
+    }
+}
+
+// First action by PUCT: the mean score of its rollouts as a fraction of the
+// best mean so far, plus an exploration term that grows with the prior and
+// shrinks as the action gets more rollouts. Actions without rollouts have a
+// value of 0.
+fn puct_pick(considered: &[usize], priors: &[f64], action_log: &MctsActionLog, n_rollouts: usize, c_puct: f64) -> usize {
+    let mean_score = |i: usize| {
+        let scores = &action_log[i].0;
+        if scores.is_empty() { 0.0 } else { scores.iter().sum::<usize>() as f64 / scores.len() as f64 }
+    };
+    let best_mean = considered.iter().map(|&i| mean_score(i)).fold(0.0, f64::max);
+    let sqrt_n = (n_rollouts.max(1) as f64).sqrt();
+
+    let puct = |i: usize| {
+        let value = if best_mean > 0.0 { mean_score(i) / best_mean } else { 0.0 };
+        value + c_puct * priors[i] * sqrt_n / (1 + action_log[i].2) as f64
+    };
+    *considered.iter().max_by(|&&i, &&j| puct(i).total_cmp(&puct(j))).unwrap()
+}
+
+// Rollout statistics of one explored action
+#[derive(Clone, Debug)]
+pub struct MctsStats {
+    pub action: Action,
+    pub n_games: usize,
+    pub mean_score: f32,
+    pub max_score: usize,
+    pub win_rate: f32,
+    // Rollouts stopped as draws on a repeated position
+    pub n_repeats: usize,
+}
+
+// Run the MCTS search and return statistics of all explored actions, best
+// first by the same criterion the player uses
+pub fn mcts_evaluate(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig) -> Vec<MctsStats> {
+    let (actions, action_log) = mcts_search(rules, state, player_idx, config, None, &|| false, None);
+    mcts_stats(&actions, &action_log)
+}
+
+// Same as `mcts_evaluate` but reporting the statistics as the search goes
+pub fn mcts_evaluate_with_info(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, info: SearchInfo) -> Vec<MctsStats> {
+    let (actions, action_log) = mcts_search(rules, state, player_idx, config, None, &|| false, Some(info));
+    mcts_stats(&actions, &action_log)
+}
+
+// Rollouts for the replies under each action of the search tree
+pub const REPLY_GAMES: usize = 100;
+
+// Top of the search for inspection: the best `width` actions of the player and,
+// under each, the best `width` replies of the next player from their own
+// search of the position after it. Replies are empty when the action ends the
+// round since the next refill isn't known.
+pub fn search_tree(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, width: usize) -> Vec<(MctsStats, Vec<MctsStats>)> {
+    search_replies(rules, state, player_idx, mcts_evaluate(rules, state, player_idx, config), width)
+}
+
+// Search tree from the statistics of a search already done
+pub fn search_replies(rules: &AzulRules, state: &State, player_idx: usize, stats: Vec<MctsStats>, width: usize) -> Vec<(MctsStats, Vec<MctsStats>)> {
+    let reply_config = MctsConfig { n_games: REPLY_GAMES, ..MctsConfig::default() };
+    let next_player = (player_idx + 1) % state.players.len();
+
+    stats
+        .into_iter()
+        .take(width)
+        .map(|stats| {
+            let mut next_state = state.clone();
+            rules.take_action_into(&mut next_state, player_idx, stats.action, &mut ());
+            let replies = if rules.is_round_over(&next_state) {
+                Vec::new()
+            } else {
+                mcts_evaluate(rules, &next_state, next_player, &reply_config).into_iter().take(width).collect()
+            };
+            (stats, replies)
+        })
+        .collect()
+}
+
+fn mcts_stats(actions: &[Action], action_log: &MctsActionLog) -> Vec<MctsStats> {
+    let mut stats: Vec<MctsStats> = actions
+        .iter()
+        .zip(action_log)
+        .filter(|(_action, (_scores, _n_wins, n_games, _n_repeats))| *n_games > 0)
+        .map(|(&action, (scores, n_wins, n_games, n_repeats))| MctsStats {
+            action,
+            n_games: *n_games,
+            mean_score: scores.iter().sum::<usize>() as f32 / *n_games as f32,
+            max_score: *scores.iter().max().unwrap(),
+            win_rate: *n_wins as f32 / *n_games as f32,
+            n_repeats: *n_repeats,
+        })
+        .collect();
+
+    stats.sort_by_key(|s| std::cmp::Reverse(s.max_score));
+    stats
+}
+
+// Run MCTS guided by immediate scores
+pub fn play_mcts(rules: &AzulRules, state: &State, player_idx: usize) -> Action {
+    play_mcts_with(rules, state, player_idx, &MctsConfig::default())
+}
+
+// Run MCTS with the given configuration
+pub fn play_mcts_with(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig) -> Action {
+    play_mcts_until(rules, state, player_idx, config, &|| false, None)
+}
+
+// Same as `play_mcts_with` but rollouts stop early once `stop` returns true,
+// which lets a background search be cancelled, and the search can report its
+// progress
+pub fn play_mcts_until(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, stop: &dyn Fn() -> bool, info: Option<SearchInfo>) -> Action {
+    pick_mcts_action(config, mcts_search(rules, state, player_idx, config, None, stop, info))
+}
+
+// Same as `play_mcts_until` but first actions of rollouts are picked by PUCT
+// with the prior weights, given for the actions in `list_valid_actions` order
+pub fn play_mcts_with_priors(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, priors: &[f64], stop: &dyn Fn() -> bool) -> Action {
+    pick_mcts_action(config, mcts_search(rules, state, player_idx, config, Some(priors), stop, None))
+}
+
+// Same as `play_mcts_with_priors`, or searching without priors, but the
+// action is sampled by its number of rollouts raised to 1 / temperature so
+// self-play explores. A temperature of 0 picks the best action as usual.
+pub fn play_mcts_sampled(rules: &AzulRules, state: &State, player_idx: usize, config: &MctsConfig, priors: Option<&[f64]>, temperature: f64) -> Action {
+    let (actions, action_log) = mcts_search(rules, state, player_idx, config, priors, &|| false, None);
+    if temperature <= 0.0 {
+        return pick_mcts_action(config, (actions, action_log));
+    }
+
+    // Scaled by the most rollouts first so low temperatures don't overflow
+    let most = action_log.iter().map(|(_, _, n_games, _)| *n_games).max().unwrap_or(0).max(1) as f64;
+    let weights: Vec<f64> = action_log.iter().map(|(_, _, n_games, _)| (*n_games as f64 / most).powf(1.0 / temperature)).collect();
+    actions[WeightedIndex::new(&weights).unwrap().sample(&mut game_rng())]
+}
+
+// Sample from Gamma(shape, 1) (Marsaglia and Tsang), shapes below 1 are
+// boosted from shape + 1
+fn sample_gamma(shape: f64, rng: &mut impl Rng) -> f64 {
+    if shape < 1.0 {
+        let u: f64 = rng.random_range(f64::EPSILON..1.0);
+        return sample_gamma(shape + 1.0, rng) * u.powf(1.0 / shape);
+    }
+
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        // Standard normal by Box-Muller
+        let (u1, u2): (f64, f64) = (rng.random_range(f64::EPSILON..1.0), rng.random());
+        let x = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = rng.random_range(f64::EPSILON..1.0);
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+// Mix Dirichlet(alpha) noise into root priors as `(1 - epsilon) * prior +
+// epsilon * noise` so self-play tries actions the policy rules out. Priors
+// are normalized first.
+pub fn dirichlet_noise(priors: &[f64], alpha: f64, epsilon: f64) -> Vec<f64> {
+    let mut rng = game_rng();
+    let noise: Vec<f64> = priors.iter().map(|_| sample_gamma(alpha, &mut rng)).collect();
+    let noise_total: f64 = noise.iter().sum();
+    let prior_total: f64 = priors.iter().sum();
+
+    priors
+        .iter()
+        .zip(noise)
+        .map(|(prior, noise)| {
+            let prior = if prior_total > 0.0 { prior / prior_total } else { 1.0 / priors.len() as f64 };
+            (1.0 - epsilon) * prior + epsilon * noise / noise_total
+        })
+        .collect()
+}
+
+// Action with the best rollout, or an intentional blunder
+fn pick_mcts_action(config: &MctsConfig, (actions, action_log): (Vec<Action>, MctsActionLog)) -> Action {
+    let mut rng = game_rng();
+
+    let best_action_idx = action_log
+        .iter()
+        .enumerate()
+        .filter(|(_i, (_scores, _n_wins, n_games, _n_repeats))| *n_games > 0)
+        .max_by(|(_i, (scores_i, ..)), (_j, (scores_j, ..))| {
+            let max_score_i = *scores_i.iter().max().unwrap();
+            let max_score_j = *scores_j.iter().max().unwrap();
+
+            max_score_i.cmp(&max_score_j)
+        })
+        .unwrap()
+        .0;
+
+    log::debug!("Picked {:?}", action_log[best_action_idx]);
+
+    if config.blunder_rate > 0.0 && rng.random_range(0.0..1.0) < config.blunder_rate {
+        // Pick some other explored action whose mean score is not too far from
+        // the best one so the blunder looks human and not absurd
+        let mean_score = |scores: &Vec<usize>| scores.iter().sum::<usize>() as f64 / scores.len() as f64;
+        let best_mean = mean_score(&action_log[best_action_idx].0);
+
+        let candidates: Vec<usize> = action_log
+            .iter()
+            .enumerate()
+            .filter(|(i, (scores, _n_wins, n_games, _n_repeats))| {
+                *i != best_action_idx && *n_games > 0 && best_mean - mean_score(scores) <= config.max_blunder_loss
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if let Some(&blunder_idx) = candidates.choose(&mut rng) {
+            log::debug!("Blundering with {:?}", action_log[blunder_idx]);
+            return actions[blunder_idx];
+        }
+    }
+
+    actions[best_action_idx]
+}
+
+// Partial function that always plays, returning the greedy move
+pub fn play_partial_greedy(rules: &AzulRules, state: &State, player_idx: usize) -> Option<Action> {
+    Some(play_greedy(rules, state, player_idx))
+}
+
+// Partial function that always plays, returning a random move
+pub fn play_partial_random(rules: &AzulRules, state: &State, player_idx: usize) -> Option<Action> {
+    Some(play_random(rules, state, player_idx))
+}
+
+// Heuristic that suggests an action only in the states it applies to
+pub type PartialFn = fn(&AzulRules, &State, usize) -> Option<Action>;
+
+// Named heuristics checked against the moves of simulated games
+pub const PARTIALS: [(&str, PartialFn); 2] = [
+    ("greedy", play_partial_greedy),
+    ("random", play_partial_random),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_continuous() {
+        assert_eq!(count_continuous(&[false, true, true, false, false], 0), 2);
+        assert_eq!(count_continuous(&[false, true, false, false, false], 2), 1);
+        assert_eq!(count_continuous(&[false, false, false, false, false], 2), 0);
+        assert_eq!(count_continuous(&[false, true, true, true, false], 4), 3);
+        assert_eq!(count_continuous(&[true, true, true, false, false], 3), 3);
+    }
+
+    #[test]
+    fn test_explain_action() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        state.factory_displays[2].insert(Tile::Blue, 3);
+        state.factory_displays[2].insert(Tile::Red, 1);
+        state.players[1].pattern_lines[3] = (Some(Tile::Blue), 1);
+
+        let action = Action {
+            action_display_choice: ActionDisplay::FactoryDisplay(2),
+            color_choice: Tile::Blue,
+            pattern_line_choice: Some(0),
+        };
+
+        assert_eq!(
+            explain_action(&rules, &state, 0, action),
+            "Takes 3 Blue from D2; completes pattern line 0; 2 tiles overflow to floor (-2); denies P1 the last Blue"
+        );
+    }
+
+    #[test]
+    fn test_hash_state_display_order() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |state: &State, action: Action| {
+            let mut hasher = DefaultHasher::new();
+            hash_state(state, &mut hasher);
+            hash_action(state, action, &mut hasher);
+            hasher.finish()
+        };
+
+        let mut state = State::new(2);
+        state.factory_displays[0].insert(Tile::Red, 4);
+        state.factory_displays[3].insert(Tile::Blue, 2);
+        state.factory_displays[3].insert(Tile::White, 2);
+
+        let mut swapped = state.clone();
+        swapped.factory_displays.swap(0, 3);
+        swapped.factory_displays.swap(1, 4);
+
+        let action = |i| Action {
+            action_display_choice: ActionDisplay::FactoryDisplay(i),
+            color_choice: Tile::Red,
+            pattern_line_choice: Some(3),
+        };
+
+        assert_eq!(hash(&state, action(0)), hash(&swapped, action(3)));
+        assert_ne!(hash(&state, action(0)), hash(&swapped, action(0)));
+    }
+
+    #[test]
+    fn test_state_serde() {
+        let mut state = State::new(2);
+        state.center.tiles.insert(Tile::Yellow, 2);
+        state.factory_displays[1].insert(Tile::Blue, 4);
+
+        let json = serde_json::to_string(&state.center).unwrap();
+        assert_eq!(json, r#"{"tiles":{"Black":0,"Blue":0,"Red":0,"White":0,"Yellow":2},"starting_marker":true}"#);
+
+        let read: State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(read.factory_displays, state.factory_displays);
+
+        // Older logs could have colors in any order or left out
+        let center: CenterState = serde_json::from_str(r#"{"tiles":{"Yellow":2,"Black":1},"starting_marker":false}"#).unwrap();
+        assert_eq!(center.tiles[&Tile::Black], 1);
+        assert_eq!(center.tiles[&Tile::Red], 0);
+        assert_eq!(center.tiles[&Tile::Yellow], 2);
+    }
+
+    #[test]
+    fn test_partial_config() {
+        let config: AzulConfig = serde_json::from_str(r#"{"extra_factories": 0, "floor_penalties": [1, 2, 3]}"#).unwrap();
+        assert_eq!(config.n_factories(2), 4);
+        assert_eq!(config.floor_penalties, vec![1, 2, 3]);
+        assert_eq!(config.color_bonus, AzulConfig::default().color_bonus);
+        assert!(config.validate().is_ok());
+
+        let config: AzulConfig = toml::from_str("row_bonus = 3\nhandicaps = [0, 2]\n[tiles_per_color]\nBlue = 10\nRed = 10\n").unwrap();
+        assert_eq!(config.row_bonus, 3);
+        assert_eq!(config.handicap(1), 2);
+        assert_eq!(config.tiles_per_color[&Tile::Blue], 10);
+        assert_eq!(config.tiles_per_color[&Tile::Black], 0);
+        assert_eq!(config.extra_factories, AzulConfig::default().extra_factories);
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let mut state = State::new(3);
+        state.players[0].score = 40;
+        state.players[1].score = 40;
+        state.players[2].score = 12;
+        assert_eq!(winners(&state), vec![0, 1]);
+        assert_eq!(winner(&state), None);
+
+        // More completed rows break the tie, whatever the seat
+        state.players[1].wall[2] = [true; 5];
+        assert_eq!(winner(&state), Some(1));
+        state.players[0].wall[0] = [true; 5];
+        state.players[0].wall[4] = [true; 5];
+        assert_eq!(winner(&state), Some(0));
+
+        // A higher score still beats more rows
+        state.players[2].score = 41;
+        assert_eq!(winner(&state), Some(2));
+        assert_eq!(winner_name(None), "a draw");
+    }
+
+    #[test]
+    fn test_handicaps() {
+        let rules = AzulRules::new(AzulConfig { handicaps: vec![0, 3], ..AzulConfig::default() });
+        let mut state = rules.new_state(2);
+        state.players[0].wall[0] = [true; 5];
+        state.players[0].score = 10;
+        state.players[1].score = 8;
+
+        let events = rules.end_round(&mut state);
+        assert_eq!((state.players[0].score, state.players[1].score), (10, 11));
+        assert!(events.contains(&GameEvent::Handicapped { player: 1, points: 3, score: 11 }));
+        assert!(events.contains(&GameEvent::GameEnded { winner: Some(1), scores: vec![10, 11] }));
+        assert_eq!(rules.config.handicap_summary().as_deref(), Some("P0 +0, P1 +3"));
+        assert_eq!(AzulConfig::default().handicap_summary(), None);
+    }
+
+    #[test]
+    fn test_score_clamp() {
+        let rules = AzulRules::new(AzulConfig { handicaps: vec![-5, 0], ..AzulConfig::default() });
+        let mut state = rules.new_state(2);
+
+        // Tiling counts before the penalties, which stop at zero
+        state.players[0].score = 3;
+        state.players[0].pattern_lines[0] = (Some(Tile::Blue), 1);
+        state.players[0].floor_line = 7;
+        let events = rules.score_round(&mut state, 0);
+        assert_eq!(events[1], GameEvent::Scored { player: 0, tiling: 1, penalty: 14, score: 0 });
+
+        state.players[1].pattern_lines[0] = (Some(Tile::Blue), 1);
+        state.players[1].floor_line = 1;
+        rules.score_round_into(&mut state, 1, &mut ());
+        assert_eq!(state.players[1].score, 0);
+
+        // The preview of a move loses no more than the score
+        state.players[0].score = 2;
+        state.center.tiles.insert(Tile::Red, 4);
+        let to_floor = Action { action_display_choice: ActionDisplay::Center, color_choice: Tile::Red, pattern_line_choice: None };
+        assert_eq!(rules.reward(&state, 0, to_floor), -2);
+
+        // Handicaps can't take a score below zero either
+        assert_eq!(state.players[0].add_points(-5), -2);
+        assert_eq!(state.players[0].add_points(4), 4);
+        state.players[0].score = 3;
+        state.center.tiles.insert(Tile::Red, 0);
+        state.players[1].wall[0] = [true; 5];
+        let events = rules.end_round(&mut state);
+        assert!(events.contains(&GameEvent::Handicapped { player: 0, points: -3, score: 0 }));
+    }
+
+    #[test]
+    fn test_forced_action() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        state.factory_displays[0].insert(Tile::Red, 2);
+        state.factory_displays[1].insert(Tile::Blue, 2);
+        assert_eq!(forced_action(&rules, &state, 0), None);
+
+        // Red only goes on the floor once every row has its red tile
+        for (row, colors) in WALL_COLORS.iter().enumerate() {
+            let column = colors.iter().position(|&c| c == Tile::Red).unwrap();
+            state.players[0].wall[row][column] = true;
+        }
+        state.factory_displays[1].insert(Tile::Blue, 0);
+        let floor = Action { action_display_choice: ActionDisplay::FactoryDisplay(0), color_choice: Tile::Red, pattern_line_choice: None };
+        assert_eq!(rules.list_valid_actions(&state, 0), vec![floor]);
+        assert_eq!(forced_action(&rules, &state, 0), Some(floor));
+
+        // Dumping the same number of tiles from anywhere is no choice either,
+        // a different number is
+        state.center.tiles.insert(Tile::Red, 2);
+        assert_eq!(forced_action(&rules, &state, 0), Some(rules.list_valid_actions(&state, 0)[0]));
+        state.center.tiles.insert(Tile::Red, 3);
+        assert_eq!(forced_action(&rules, &state, 0), None);
+    }
+
+    #[test]
+    fn test_rules_side_by_side() {
+        let standard = AzulRules::new(AzulConfig::default());
+        let harsh = AzulRules::new(AzulConfig { floor_penalties: vec![5; 7], extra_factories: 0, ..AzulConfig::default() });
+        assert_eq!(standard.new_state(2).factory_displays.len(), 5);
+        assert_eq!(harsh.new_state(2).factory_displays.len(), 4);
+
+        let mut state = standard.new_state(2);
+        state.players[0].score = 20;
+        state.players[0].floor_line = 2;
+        let mut harsh_state = state.clone();
+
+        standard.score_round(&mut state, 0);
+        harsh.score_round(&mut harsh_state, 0);
+        assert_eq!(state.players[0].score, 18);
+        assert_eq!(harsh_state.players[0].score, 10);
+    }
+
+    #[test]
+    fn test_take_action_events() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        state.factory_displays[1].insert(Tile::Red, 3);
+        state.factory_displays[1].insert(Tile::Blue, 1);
+
+        let action = Action {
+            action_display_choice: ActionDisplay::FactoryDisplay(1),
+            color_choice: Tile::Red,
+            pattern_line_choice: Some(1),
+        };
+
+        // Dropping the events changes the state the same way
+        let mut unrecorded = state.clone();
+        rules.take_action_into(&mut unrecorded, 0, action, &mut ());
+        rules.score_round_into(&mut unrecorded, 0, &mut ());
+
+        assert_eq!(rules.take_action(&mut state, 0, action), vec![
+            GameEvent::TilesTaken { player: 0, source: ActionDisplay::FactoryDisplay(1), color: Tile::Red, count: 3, to_center: 1 },
+            GameEvent::TilesStaged { player: 0, line: Some(1), color: Tile::Red, count: 2, filled: 2 },
+            GameEvent::Overflowed { player: 0, count: 1 },
+        ]);
+
+        let events = rules.score_round(&mut state, 0);
+        assert_eq!(events[0], GameEvent::WallTiled { player: 0, row: 1, column: 3, color: Tile::Red, points: 1 });
+        assert_eq!(events[1], GameEvent::Scored { player: 0, tiling: 1, penalty: 1, score: 0 });
+        assert_eq!(serde_json::to_string(&unrecorded).unwrap(), serde_json::to_string(&state).unwrap());
+    }
+
+    #[test]
+    fn test_out_of_tiles() {
+        let rules = AzulRules::new(AzulConfig { tiles_per_color: COLORS.iter().map(|&c| (c, 4)).collect(), ..AzulConfig::default() });
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut rand::rng());
+        assert!(rules.out_of_tiles(&state));
+        assert!(!rules.is_game_over(&state));
+
+        // Nobody has a row but there is nothing left for another round
+        for display in state.factory_displays.iter_mut() {
+            display.values_mut().for_each(|n| *n = 0);
+        }
+        assert!(rules.is_game_over(&state));
+    }
+
+    #[test]
+    fn test_stage_on_started_line() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        state.players[0].pattern_lines[3] = (Some(Tile::Black), 1);
+        state.factory_displays[0].insert(Tile::Black, 4);
+
+        let action = Action {
+            action_display_choice: ActionDisplay::FactoryDisplay(0),
+            color_choice: Tile::Black,
+            pattern_line_choice: Some(3),
+        };
+        let events = rules.take_action(&mut state, 0, action);
+        assert_eq!(state.players[0].pattern_lines[3], (Some(Tile::Black), 4));
+        assert_eq!(state.players[0].floor_line, 1);
+        assert!(events.contains(&GameEvent::TilesStaged { player: 0, line: Some(3), color: Tile::Black, count: 3, filled: 4 }));
+    }
+
+    #[test]
+    fn test_action_notation() {
+        let action = Action {
+            action_display_choice: ActionDisplay::FactoryDisplay(2),
+            color_choice: Tile::Blue,
+            pattern_line_choice: Some(3),
+        };
+
+        assert_eq!(action_notation(action), "D2 Blue 3");
+        assert_eq!(parse_action_notation("D2 Blue 3").unwrap(), action);
+        assert_eq!(parse_action_notation("c red f").unwrap().pattern_line_choice, None);
+        assert!(parse_action_notation("D2 Purple 3").is_err());
+    }
+
+    #[test]
+    fn test_seen_positions() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut game_rng());
+        let mut seen = SeenPositions::default();
+
+        assert!(!seen.repeated(&state, 0));
+        assert!(!seen.repeated(&state, 1));
+        assert!(seen.repeated(&state, 0));
+
+        // Swapping displays gives the same position
+        state.factory_displays.swap(0, 1);
+        assert!(seen.repeated(&state, 1));
+
+        let action = rules.list_valid_actions(&state, 0)[0];
+        rules.take_action(&mut state, 0, action);
+        assert!(!seen.repeated(&state, 1));
+    }
+
+    #[test]
+    fn test_wall_progress() {
+        let mut player = PlayerState::new();
+        player.wall[0] = [true; 5];
+        player.wall[1][1] = true;
+        player.wall[2][2] = true;
+
+        let progress = player.wall_progress();
+        assert_eq!(progress.rows, [5, 1, 1, 0, 0]);
+        assert_eq!(progress.columns, [1, 2, 2, 1, 1]);
+        // Blue sits on the diagonal so it has three tiles
+        assert_eq!(progress.colors, [3, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_state_analysis() {
+        let mut state = State::new(2);
+        state.factory_displays[0] = HashMap::from([(Tile::Black, 0), (Tile::Blue, 3), (Tile::Red, 1), (Tile::White, 0), (Tile::Yellow, 0)]);
+        state.center.tiles.insert(Tile::Blue, 1);
+        state.center.tiles.insert(Tile::Red, 2);
+        state.players[0].pattern_lines[2] = (Some(Tile::Red), 1);
+        state.players[0].pattern_lines[4] = (Some(Tile::Yellow), 2);
+        state.players[1].pattern_lines[1] = (Some(Tile::Blue), 1);
+        state.players[1].pattern_lines[3] = (Some(Tile::Blue), 4);
+        state.players[1].pattern_lines[4] = (Some(Tile::Blue), 1);
+
+        let on_table = colors_on_table(&state);
+        assert_eq!(on_table[1], (Tile::Blue, 4));
+        assert_eq!(on_table[2], (Tile::Red, 3));
+
+        assert_eq!(colors_needed(&state, 0), vec![(Tile::Red, 2), (Tile::Yellow, 3)]);
+        assert_eq!(colors_needed(&state, 1), vec![(Tile::Blue, 5)]);
+
+        // Red is in two places so the one other player can't take it all
+        assert_eq!(denial_risks(&state, 0), vec![(4, Tile::Yellow, DenialRisk::Short)]);
+        state.factory_displays[0].insert(Tile::Red, 0);
+        assert_eq!(denial_risks(&state, 0), vec![(2, Tile::Red, DenialRisk::Contested), (4, Tile::Yellow, DenialRisk::Short)]);
+    }
+
+    #[test]
+    fn test_marker_value() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        with_seed(5, || rules.refill(&mut state, &mut game_rng()));
+
+        let from_display = rules.list_valid_actions(&state, 0)[0];
+        assert_eq!(marker_value(&rules, &state, 0, from_display), None);
+        rules.take_action(&mut state, 0, from_display);
+
+        let from_center = rules.list_valid_actions(&state, 1)
+            .into_iter()
+            .find(|a| a.action_display_choice == ActionDisplay::Center)
+            .unwrap();
+        let value = with_seed(5, || marker_value(&rules, &state, 1, from_center)).unwrap();
+        assert!(value.is_finite());
+
+        // Nothing to weigh once the marker is gone
+        rules.take_action(&mut state, 1, from_center);
+        let action = rules.list_valid_actions(&state, 0)
+            .into_iter()
+            .find(|a| a.action_display_choice == ActionDisplay::Center);
+        if let Some(action) = action {
+            assert_eq!(marker_value(&rules, &state, 0, action), None);
+        }
+    }
+
+    #[test]
+    fn test_search_info() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        with_seed(3, || rules.refill(&mut state, &mut game_rng()));
+        let config = MctsConfig { n_games: 30, ..MctsConfig::default() };
+
+        let mut reports: Vec<(usize, usize)> = Vec::new();
+        let mut record = |rollouts: usize, stats: &[MctsStats]| {
+            reports.push((rollouts, stats.iter().map(|s| s.n_games).sum()));
+        };
+        let reported = with_seed(5, || mcts_evaluate_with_info(&rules, &state, 0, &config, (Duration::ZERO, &mut record)));
+        assert_eq!(reports.len(), 29);
+        assert!(reports.iter().all(|&(rollouts, n_games)| rollouts == n_games));
+
+        // Reporting doesn't change the search
+        let plain = with_seed(5, || mcts_evaluate(&rules, &state, 0, &config));
+        let summary = |stats: &[MctsStats]| stats.iter().map(|s| (s.action, s.n_games, s.max_score)).collect::<Vec<_>>();
+        assert_eq!(summary(&reported), summary(&plain));
+    }
+
+    #[test]
+    fn test_search_tree() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        with_seed(4, || rules.refill(&mut state, &mut game_rng()));
+        let config = MctsConfig { n_games: 40, ..MctsConfig::default() };
+
+        let tree = with_seed(6, || search_tree(&rules, &state, 0, &config, 3));
+        assert_eq!(tree.len(), 3);
+        for (stats, replies) in &tree {
+            assert!(!replies.is_empty() && replies.len() <= 3);
+            let mut next_state = state.clone();
+            rules.take_action(&mut next_state, 0, stats.action);
+            let valid = rules.list_valid_actions(&next_state, 1);
+            assert!(replies.iter().all(|reply| valid.contains(&reply.action)));
+        }
+    }
+
+    #[test]
+    fn test_exploration() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        with_seed(4, || rules.refill(&mut state, &mut game_rng()));
+        let config = MctsConfig { n_games: 30, ..MctsConfig::default() };
+
+        // No temperature plays the best action
+        let best = with_seed(2, || play_mcts_with(&rules, &state, 0, &config));
+        assert_eq!(with_seed(2, || play_mcts_sampled(&rules, &state, 0, &config, None, 0.0)), best);
+        let sampled: HashSet<Action> = (0..10).map(|seed| with_seed(seed, || play_mcts_sampled(&rules, &state, 0, &config, None, 5.0))).collect();
+        assert!(sampled.len() > 1);
+
+        let priors = vec![2.0, 1.0, 1.0, 0.0];
+        let noisy = with_seed(3, || dirichlet_noise(&priors, 0.3, 0.25));
+        assert!((noisy.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(noisy.iter().zip([0.5, 0.25, 0.25, 0.0]).all(|(n, p)| *n >= 0.75 * p && *n <= 0.75 * p + 0.25));
+        assert!(noisy[3] > 0.0);
+        assert_eq!(dirichlet_noise(&priors, 0.3, 0.0), vec![0.5, 0.25, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_puct() {
+        let rules = AzulRules::current();
+        assert_eq!(normalize_priors(&[0.0, 0.0]), vec![0.5, 0.5]);
+        assert_eq!(normalize_priors(&[3.0, 1.0]), vec![0.75, 0.25]);
+
+        // Without rollouts the prior decides, then a strong mean score wins
+        // over a prior the rollouts don't back up
+        let priors = vec![0.1, 0.9];
+        let mut action_log: MctsActionLog = vec![(Vec::new(), 0, 0, 0); 2];
+        assert_eq!(puct_pick(&[0, 1], &priors, &action_log, 0, 1.5), 1);
+        action_log[0] = (vec![30; 5], 0, 5, 0);
+        action_log[1] = (vec![5; 5], 0, 5, 0);
+        assert_eq!(puct_pick(&[0, 1], &priors, &action_log, 10, 1.5), 0);
+        assert_eq!(puct_pick(&[0], &priors, &action_log, 10, 1.5), 0);
+
+        // Searches with priors still give every rollout to some action
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut game_rng());
+        let n_actions = rules.list_valid_actions(&state, 0).len();
+        let config = MctsConfig { n_games: 50, ..MctsConfig::default() };
+        let (_, log) = mcts_search(&rules, &state, 0, &config, Some(&vec![0.0; n_actions]), &|| false, None);
+        assert_eq!(log.iter().map(|entry| entry.2).sum::<usize>(), 50);
+    }
+
+    #[test]
+    fn test_widening() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut game_rng());
+
+        // Four rollouts can only reach the two best actions plus one
+        let config = MctsConfig { n_games: 4, widening: 2, ..MctsConfig::default() };
+        let (actions, action_log) = mcts_search(&rules, &state, 0, &config, None, &|| false, None);
+        assert!(actions.len() > 3);
+        assert!(action_log.iter().filter(|(_scores, _n_wins, n_games, _n_repeats)| *n_games > 0).count() <= 3);
+    }
+
+    #[test]
+    fn test_bag() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut game_rng());
+        assert_eq!(rules.bag_tiles(&state).values().sum::<usize>(), 100 - 5 * 4);
+
+        // Tiles dumped on the floor go to the lid, not back in the bag
+        let color = *COLORS.iter().find(|c| state.factory_displays[0][c] > 0).unwrap();
+        let count = state.factory_displays[0][&color];
+        let action = Action { action_display_choice: ActionDisplay::FactoryDisplay(0), color_choice: color, pattern_line_choice: None };
+        rules.take_action(&mut state, 0, action);
+        assert_eq!(state.lid[&color], count);
+        assert_eq!(rules.bag_tiles(&state).values().sum::<usize>(), 100 - 5 * 4);
+
+        // The bag kept by refills matches the one counted from the table
+        let kept = rules.bag_tiles(&state);
+        state.bag = None;
+        assert_eq!(rules.bag_tiles(&state), kept);
+    }
+
+    #[test]
+    fn test_short_bag() {
+        // Bag runs out partway through the refill and the lid is poured back
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        for color in COLORS {
+            state.lid.insert(color, 18);
+        }
+        rules.refill(&mut state, &mut game_rng());
+        assert!(state.factory_displays.iter().all(|d| d.values().sum::<usize>() == 4));
+        assert_eq!(state.lid.values().sum::<usize>(), 0);
+        assert_eq!(rules.bag_tiles(&state).values().sum::<usize>(), 80);
+
+        // Walls full except for the Blue diagonal leave only 20 Blue tiles, so
+        // 5 of the 9 displays fill and the rest stay empty
+        let mut state = rules.new_state(4);
+        for player in &mut state.players {
+            for row in 0..5 {
+                for col in 0..5 {
+                    player.wall[row][col] = row != col;
+                }
+            }
+        }
+        rules.refill(&mut state, &mut game_rng());
+        let filled: Vec<usize> = state.factory_displays.iter().map(|d| d.values().sum()).collect();
+        assert_eq!(filled, vec![4, 4, 4, 4, 4, 0, 0, 0, 0]);
+        assert!(state.factory_displays.iter().all(|d| d[&Tile::Blue] == d.values().sum::<usize>()));
+        assert!(!rules.is_game_over(&state));
+
+        // With those tiles on pattern lines there is nothing to draw next
+        // round, which ends the game without a completed row
+        for player in &mut state.players {
+            player.pattern_lines[4] = (Some(Tile::Blue), 5);
+        }
+        state.factory_displays.iter_mut().for_each(|d| d.values_mut().for_each(|c| *c = 0));
+        assert!(state.players.iter().all(|p| !p.has_completed_row()));
+        assert!(rules.is_game_over(&state));
+    }
+
+    #[test]
+    fn test_schema() {
+        let rules = AzulRules::current();
+        for n_players in 2..=4 {
+            let mut state = rules.new_state(n_players);
+            rules.refill(&mut state, &mut game_rng());
+
+            let schema = state.schema();
+            assert_eq!(schema.iter().map(|f| f.size).sum::<usize>(), state.represent().len());
+            assert_eq!(schema[0].name, "displays.0.black.color");
+        }
+    }
+
+    #[test]
+    fn test_represent_with() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(3);
+        rules.refill(&mut state, &mut game_rng());
+        state.players[2].score = 300;
+
+        let features = state.represent_with(&RepresentOptions { normalize: true, perspective: 2 });
+        assert!(features.iter().all(|&v| (0.0..=1.0).contains(&v)));
+
+        // The perspective player's score is the first player feature
+        let score_idx = state.schema().iter().take_while(|f| f.name != "players.0.score").map(|f| f.size).sum::<usize>();
+        assert_eq!(features[score_idx], 1.0);
+    }
+
+    #[test]
+    fn test_permute_displays() {
+        let rules = AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut game_rng());
+        let action = rules.list_valid_actions(&state, 0)[0];
+
+        let (permuted, permuted_action) = permute_displays(&state, action, &[4, 3, 2, 1, 0]);
+        assert_eq!(permuted.factory_displays[4], state.factory_displays[0]);
+        assert_eq!(permuted_action.action_display_choice, ActionDisplay::FactoryDisplay(4));
+
+        let mut after = state.clone();
+        rules.take_action(&mut after, 0, action);
+        let mut permuted_after = permuted.clone();
+        rules.take_action(&mut permuted_after, 0, permuted_action);
+        assert_eq!(after.players[0].pattern_lines, permuted_after.players[0].pattern_lines);
+        assert_eq!(after.center.tiles, permuted_after.center.tiles);
+    }
+
+    #[test]
+    fn test_action_order() {
+        let action = |display, color, line| Action { action_display_choice: display, color_choice: color, pattern_line_choice: line };
+
+        let mut actions = vec![
+            action(ActionDisplay::Center, Tile::Black, Some(0)),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Black, None),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Black, Some(4)),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Blue, Some(0)),
+            action(ActionDisplay::FactoryDisplay(0), Tile::Yellow, None),
+        ];
+        actions.sort();
+
+        assert_eq!(actions, vec![
+            action(ActionDisplay::FactoryDisplay(0), Tile::Yellow, None),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Black, Some(4)),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Black, None),
+            action(ActionDisplay::FactoryDisplay(1), Tile::Blue, Some(0)),
+            action(ActionDisplay::Center, Tile::Black, Some(0)),
+        ]);
+    }
+
+    #[test]
+    fn test_actions_canonical() {
+        let rules = AzulRules::current();
+        for seed in 0..20 {
+            with_seed(seed, || {
+                let mut state = rules.new_state(3);
+                rules.refill(&mut state, &mut game_rng());
+
+                // Move some tiles to the center and partly fill lines so all
+                // kinds of sources and lines show up
+                for ply in 0..4 {
+                    let player_idx = ply % 3;
+                    let actions = actions_canonical(&rules, &state, player_idx);
+                    assert!(actions.windows(2).all(|w| w[0] < w[1]));
+                    assert!(actions.iter().all(|a| a.pattern_line_choice.is_some() || !actions.iter().any(|b| {
+                        b.action_display_choice == a.action_display_choice && b.color_choice == a.color_choice && b > a
+                    })));
+                    rules.take_action(&mut state, player_idx, actions[actions.len() / 2]);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+// Rough number of moves a player still has to make in a game, used to split
+// the remaining time into per move budgets
+const MOVES_LEFT_ESTIMATE: u32 = 20;
+
+// Chess style time control with a base time and an increment added after
+// every move. Written as `<base>+<increment>` in seconds, like `300+2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl FromStr for TimeControl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (base, increment) = s.split_once('+').unwrap_or((s, "0"));
+        let invalid = || anyhow!("Invalid time control '{}', use <base>+<increment> in seconds", s);
+        // Negative, infinite, and NaN seconds are no duration
+        let parse = |v: &str| v.trim().parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()).ok_or_else(invalid);
+
+        Ok(Self { base: parse(base)?, increment: parse(increment)? })
+    }
+}
+
+// Clocks for all players of a game. Only the player to move has a running
+// clock, `switch` stops it and starts the next one.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    remaining: Vec<Duration>,
+    increment: Duration,
+    turn_start: Instant,
+    flagged: Option<usize>,
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl, n_players: usize) -> Self {
+        Self {
+            remaining: vec![time_control.base; n_players],
+            increment: time_control.increment,
+            turn_start: Instant::now(),
+            flagged: None,
+        }
+    }
+
+    // Remaining time for the player, counting the running turn if they are the
+    // one to move
+    pub fn remaining(&self, player_idx: usize, to_move: usize) -> Duration {
+        if player_idx == to_move {
+            self.remaining[player_idx].saturating_sub(self.turn_start.elapsed())
+        } else {
+            self.remaining[player_idx]
+        }
+    }
+
+    // Charge the time since the last switch to the player who just moved.
+    // Returns false if their flag fell.
+    pub fn switch(&mut self, player_idx: usize) -> bool {
+        let elapsed = self.turn_start.elapsed();
+        self.turn_start = Instant::now();
+
+        if elapsed > self.remaining[player_idx] {
+            self.remaining[player_idx] = Duration::ZERO;
+            self.flagged.get_or_insert(player_idx);
+            return false;
+        }
+
+        self.remaining[player_idx] = self.remaining[player_idx] - elapsed + self.increment;
+        true
+    }
+
+    // Start the running turn over without charging anyone, for pauses in
+    // the game
+    pub fn restart_turn(&mut self) {
+        self.turn_start = Instant::now();
+    }
+
+    // First player who ran out of time, if any
+    pub fn flagged(&self) -> Option<usize> {
+        self.flagged
+    }
+}
+
+// Thinking time a strategy should spend on one move given its remaining time
+pub fn move_budget(remaining: Duration) -> Duration {
+    remaining / MOVES_LEFT_ESTIMATE
+}
+
+// Clock display as minutes and seconds
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_control() {
+        let time_control: TimeControl = "300+2".parse().unwrap();
+        assert_eq!(time_control.base, Duration::from_secs(300));
+        assert_eq!(time_control.increment, Duration::from_secs(2));
+        for invalid in ["-1+0", "inf+0", "300+-2", "300+NaN", "fast"] {
+            assert!(invalid.parse::<TimeControl>().is_err(), "{}", invalid);
+        }
+
+        let mut clock = Clock::new(time_control, 2);
+        assert!(clock.switch(0));
+        assert!(clock.remaining(0, 1) > Duration::from_secs(301));
+        assert_eq!(clock.flagged(), None);
+    }
+}
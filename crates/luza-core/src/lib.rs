@@ -0,0 +1,2 @@
+pub mod accuracy;
+pub mod games;
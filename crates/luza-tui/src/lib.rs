@@ -0,0 +1,1588 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use luza_core::accuracy;
+use luza_core::games::azul::{self, ActionDisplay, DenialRisk, GameEvent, Tile, WALL_COLORS};
+use luza_core::games::clock::{self, Clock};
+use luza_core::games::GameState;
+
+use ratatui::layout::{Constraint, Direction, Flex, Layout, Margin};
+use ratatui::style::{self, Modifier, Style};
+use ratatui::text::Span;
+use ratatui::widgets::{BorderType, Borders, Cell, Clear, HighlightSpacing, Row, StatefulWidget, Table, TableState, Wrap};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::{Line, Text},
+    widgets::{Block, Paragraph, Widget},
+};
+
+// Text based markers for various displays
+const TILE_M: &str = "⬛";
+const TILE_EMPTY_M: &str = "⬜";
+const STARTING_M: &str = "1";
+const FLOOR_M: &str = "⬤";
+
+// What the interactive interface needs from a game. The generic parts of the
+// screen (header, actions table, popups) are laid out here and the game only
+// draws its own pieces and describes its actions.
+pub trait GameUi: GameState + Clone {
+    type Action: Copy + Eq + Hash;
+    type Event: Clone;
+
+    fn n_players(&self) -> usize;
+    fn round(&self) -> usize;
+    fn scores(&self) -> Vec<i32>;
+
+    // Height of the shared part of the board, like the factory displays
+    fn shared_height(&self) -> u16;
+    fn render_shared(&self, area: Rect, buf: &mut Buffer);
+
+    // Height of one player's board including its border
+    fn player_height(&self) -> u16;
+    fn render_player(&self, player_idx: usize, area: Rect, buf: &mut Buffer);
+
+    // Short styled description of an action for tables
+    fn action_line(action: &Self::Action) -> Line<'static>;
+
+    // Plain words explanation of a move made in this state
+    fn format_move(&self, player_idx: usize, action: Self::Action) -> String;
+
+    // Column names and one row per player for the compact score sheet view
+    fn score_sheet_columns() -> Vec<&'static str>;
+    fn score_sheet_row(&self, player_idx: usize) -> Vec<String>;
+
+    // Lines for the State Analysis panel, seen by the player to move
+    fn state_analysis(&self, player_idx: usize) -> Vec<Line<'static>>;
+
+    // How close the player is to the conditions that end the game or score
+    // bonuses, in one line
+    fn progress_line(&self, player_idx: usize) -> Line<'static>;
+
+    // What the round end events did to one player's board, described on the
+    // state before scoring
+    fn tiling_lines(&self, events: &[Self::Event], player_idx: usize) -> Vec<Line<'static>>;
+
+    // Highlight what the events changed over the already rendered board.
+    // Progress goes from 0 to 1 over the animation and player areas are empty
+    // when boards are not shown.
+    fn render_animation(&self, events: &[Self::Event], progress: f32, shared_area: Rect, player_areas: &[Rect], buf: &mut Buffer);
+}
+
+// Round end shown one player at a time, with the pattern lines moving to the
+// wall and the floor penalties, before the scores change on the board
+#[derive(Clone)]
+pub struct TilingPhase<G: GameUi> {
+    // State after the round is scored
+    pub scored: G,
+    pub events: Vec<G::Event>,
+    // Player being shown
+    pub player: usize,
+}
+
+impl<G: GameUi> TilingPhase<G> {
+    // Move to the next player, telling if there was one
+    pub fn advance(&mut self) -> bool {
+        if self.player + 1 < self.scored.n_players() {
+            self.player += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// Events of the last move or round end being animated
+#[derive(Clone)]
+pub struct Animation<E> {
+    pub events: Vec<E>,
+    started: Instant,
+    duration: Duration,
+}
+
+impl<E> Animation<E> {
+    pub fn new(events: Vec<E>, duration: Duration) -> Self {
+        Self { events, started: Instant::now(), duration }
+    }
+
+    pub fn progress(&self) -> f32 {
+        (self.started.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.started.elapsed() >= self.duration
+    }
+}
+
+#[derive(Clone)]
+pub struct Move<A = azul::Action> {
+    pub player: usize,
+    pub action: A,
+    pub explanation: String,
+}
+
+#[derive(Clone, Copy)]
+pub struct ActionAnalysis {
+    pub score_gain: i32,
+    // Best immediate gain available to the next player after this action
+    pub gift: i32,
+    pub expected_score: Option<f32>,
+    pub win_probability: Option<f32>,
+    // Chance of being forced to take more floor tiles later this round, and
+    // the penalty expected from those
+    pub floor_probability: Option<f32>,
+    pub expected_floor_penalty: Option<f32>,
+    // Expected score from taking the starting marker now instead of leaving
+    // it, only for center actions that take it
+    pub marker_value: Option<f32>,
+}
+
+// Suggested action of a heuristic, None where it doesn't apply
+pub type HeuristicFn<G> = Arc<dyn Fn(&G, usize) -> Option<<G as GameUi>::Action> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Heuristic<G: GameUi = azul::State> {
+    pub name: String,
+    pub function: HeuristicFn<G>,
+}
+
+// One human decision with the engine evaluations needed for reviewing it
+#[derive(Clone)]
+pub struct PlyReview {
+    pub ply: usize,
+    pub player: usize,
+    // State before the move was made
+    pub state: azul::State,
+    pub action: azul::Action,
+    pub best_action: azul::Action,
+    pub expected_score: f32,
+    pub best_expected_score: f32,
+    // Time taken to pick the move
+    pub think_time: Duration,
+    // Forced move played without asking
+    pub auto_played: bool,
+}
+
+impl PlyReview {
+    // Expected score lost by not playing the engine's best action
+    pub fn loss(&self) -> f32 {
+        (self.best_expected_score - self.expected_score).max(0.0)
+    }
+}
+
+// Post game screen listing the biggest expected value losses
+#[derive(Clone)]
+pub struct ReviewApp {
+    pub reviews: Vec<PlyReview>,
+    pub reviews_state: TableState,
+    // Reviews still being searched in the background
+    pub pending: usize,
+}
+
+// Puzzle screen asking for the best move in a position
+#[derive(Clone)]
+pub struct PuzzleApp {
+    pub state: azul::State,
+    pub player: usize,
+    pub actions: Vec<azul::Action>,
+    pub actions_state: TableState,
+    pub puzzle: usize,
+    pub n_puzzles: usize,
+    pub tries_left: usize,
+    pub streak: usize,
+    pub best_streak: usize,
+    // Wrong guesses so far
+    pub tried: Vec<azul::Action>,
+    // Best move, shown once solved or out of tries
+    pub solution: Option<azul::Action>,
+    // Move played in the game the position came from, shown with the solution
+    pub played: Option<String>,
+    pub feedback: String,
+}
+
+// One searched action with its rollout statistics and the searched replies
+// of the next player under it
+#[derive(Clone)]
+pub struct SearchNode<A> {
+    pub player: usize,
+    pub action: A,
+    pub n_games: usize,
+    pub mean_score: f32,
+    pub win_rate: f32,
+    pub replies: Vec<SearchNode<A>>,
+}
+
+// Top of the search for the current position as a list where the replies
+// under each action can be expanded and collapsed
+#[derive(Clone)]
+pub struct SearchTreeView<A> {
+    pub nodes: Vec<SearchNode<A>>,
+    pub expanded: Vec<bool>,
+    pub rows_state: TableState,
+}
+
+impl<A> SearchTreeView<A> {
+    pub fn new(nodes: Vec<SearchNode<A>>) -> Self {
+        let expanded = vec![false; nodes.len()];
+        Self { nodes, expanded, rows_state: TableState::default().with_selected(Some(0)) }
+    }
+
+    // Action and reply indices of the rows shown
+    pub fn rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            rows.push((i, None));
+            if self.expanded[i] {
+                rows.extend((0..node.replies.len()).map(|j| (i, Some(j))));
+            }
+        }
+        rows
+    }
+
+    pub fn select_next(&mut self) {
+        let last = self.rows().len().saturating_sub(1);
+        let next = self.rows_state.selected().map_or(0, |i| (i + 1).min(last));
+        self.rows_state.select(Some(next));
+    }
+
+    pub fn select_previous(&mut self) {
+        let previous = self.rows_state.selected().map_or(0, |i| i.saturating_sub(1));
+        self.rows_state.select(Some(previous));
+    }
+
+    // Expand or collapse the replies of the selected row's action, keeping
+    // that action selected
+    pub fn toggle(&mut self) {
+        let rows = self.rows();
+        let Some(&(i, _)) = self.rows_state.selected().and_then(|row| rows.get(row)) else {
+            return;
+        };
+        self.expanded[i] = !self.expanded[i];
+        let row = self.rows().iter().position(|&r| r == (i, None));
+        self.rows_state.select(row);
+    }
+}
+
+// Best actions of two analysis engines for the same position, each with the
+// engine's spec, to be shown side by side
+#[derive(Clone)]
+pub struct EngineComparison<A> {
+    pub engines: [(String, Vec<SearchNode<A>>); 2],
+}
+
+impl<A: PartialEq> EngineComparison<A> {
+    pub fn same_best(&self) -> bool {
+        let [(_, a), (_, b)] = &self.engines;
+        a.first().map(|n| &n.action) == b.first().map(|n| &n.action)
+    }
+}
+
+#[derive(Clone)]
+pub struct InteractiveApp<G: GameUi = azul::State> {
+    pub state: G,
+    // Whether the game is over is up to the rules, which the caller holds
+    pub game_over: bool,
+    pub current_player: usize,
+    pub ply: usize,
+    pub ply_round: usize,
+    pub last_move: Option<Move<G::Action>>,
+    pub actions: Vec<G::Action>,
+    pub actions_state: TableState,
+    pub analyses: HashMap<G::Action, ActionAnalysis>,
+    pub show_action_details: bool,
+    pub show_heuristic_details: bool,
+    pub show_state_details: bool,
+    // Projected state at the end of the current round, not committed
+    pub round_preview: Option<G>,
+    // Round end being resolved player by player
+    pub tiling: Option<TilingPhase<G>>,
+    // Search tree inspection popup
+    pub search_tree: Option<SearchTreeView<G::Action>>,
+    // Two engines' best actions for the position, side by side
+    pub comparison: Option<EngineComparison<G::Action>>,
+    // Likely continuation of the action shown in the details popup
+    pub continuation: Vec<String>,
+    // Teacher's favorite from a search still running in the background
+    pub teacher_info: Option<String>,
+    // Label of the teacher's current difficulty level
+    pub difficulty: String,
+    pub gift_threshold: i32,
+    // Player clocks when playing with a time control
+    pub clock: Option<Clock>,
+    // Show the score sheet instead of full player boards
+    pub compact_players: bool,
+    pub animation: Option<Animation<G::Event>>,
+    pub heuristics: Vec<Heuristic<G>>,
+    // Notes written by the player, by the ply of the move they are about
+    pub notes: BTreeMap<usize, String>,
+    // Note being written for the current ply
+    pub note_input: Option<String>,
+    // Play forced moves without asking
+    pub autoplay: bool,
+    // Plies of the forced moves played without asking
+    pub auto_played: BTreeSet<usize>,
+}
+
+impl<G: GameUi> InteractiveApp<G> {
+    fn players_height(&self, compact: bool) -> u16 {
+        if compact {
+            score_sheet_height(self.state.n_players())
+        } else {
+            self.state.player_height() * player_rows(self.state.n_players())
+        }
+    }
+
+    // Whether full player boards fit in a terminal of this height
+    pub fn fits_full_boards(&self, height: u16) -> bool {
+        3 + self.state.shared_height() + self.players_height(false) + 15 + 7 + 7 <= height
+    }
+}
+
+fn tile_to_color(tile: Tile) -> style::Color {
+    match tile {
+        Tile::Black => style::Color::Black,
+        Tile::Blue => style::Color::Blue,
+        Tile::Red => style::Color::Red,
+        Tile::White => style::Color::White,
+        Tile::Yellow => style::Color::Yellow,
+    }
+}
+
+fn render_center(center: &azul::CenterState, area: Rect, buf: &mut Buffer) {
+    let mut lines = Vec::new();
+
+    let mut center_line = vec![" Center:".into()];
+    if center.starting_marker {
+        center_line.push(Span::styled(format!(" {STARTING_M}"), Style::default().fg(style::Color::Blue)));
+    }
+
+    if center.tiles.is_empty() {
+        center_line.push(Span::styled(format!(" {TILE_EMPTY_M}"), Style::default().fg(style::Color::Gray)));
+    } else {
+        for (&tile, &count) in center.tiles.iter() {
+            for _ in 0..count {
+                center_line.push(Span::styled(format!(" {TILE_M}"), Style::default().fg(tile_to_color(tile))));
+            }
+        }
+    }
+
+    lines.push(Line::from(center_line));
+
+    Text::from(lines).render(area, buf);
+}
+
+fn render_player(player: &azul::PlayerState, area: Rect, buf: &mut Buffer) {
+    Paragraph::new(format!("\n  Score: {}", player.score)).render(area, buf);
+
+    let cols = 5;
+    let mut grid_lines = Vec::new();
+    grid_lines.push(Line::from(""));
+    grid_lines.push(Line::from(""));
+    grid_lines.push(Line::from(""));
+
+    for (i, (pattern_line, wall_row)) in player.pattern_lines.iter().zip(&player.wall).enumerate() {
+        let mut row = vec![" ".into()];
+        for j in 0..cols {
+            let text = if j < (4 - i) {
+                Span::styled("   ", Style::default())
+            } else {
+                match *pattern_line {
+                    (None, _) => {
+                        Span::styled(format!(" {TILE_EMPTY_M}"), Style::default().fg(style::Color::Gray))
+                    },
+                    (Some(tile), count) => {
+                        let pos = 4 - j;
+                        if pos < count {
+                            Span::styled(format!(" {TILE_M}"), Style::default().fg(tile_to_color(tile)))
+                        } else {
+                            Span::styled(format!(" {TILE_EMPTY_M}"), Style::default().fg(style::Color::Gray))
+                        }
+                    }
+                }
+            };
+            row.push(text);
+        }
+        row.push("  ".into());
+
+        for (&placed, &color) in wall_row.iter().zip(&WALL_COLORS[i]) {
+            let text = if placed { format!("{TILE_M} ") } else { format!("{TILE_EMPTY_M} ") };
+            row.push(Span::styled(text, Style::default().fg(tile_to_color(color))));
+        }
+        grid_lines.push(Line::from(row));
+    }
+
+    grid_lines.push(Line::from(""));
+
+    let mut row = vec![Span::styled(" ", Style::default())];
+    if player.starting_marker {
+        row.push(Span::styled(format!(" {STARTING_M}"), Style::default().fg(style::Color::Red)));
+    }
+    for i in 0..azul::config().floor_penalties.len() {
+        if i < player.floor_line {
+            row.push(Span::styled(format!(" {FLOOR_M}"), Style::default().fg(style::Color::Red)));
+        } else {
+            row.push(Span::styled(format!(" {FLOOR_M}"), Style::default().fg(style::Color::Gray)));
+        }
+    }
+    grid_lines.push(Line::from(row));
+
+    Text::from(grid_lines).render(area, buf);
+}
+
+fn action_line(action: &azul::Action) -> Line<'static> {
+    let display = match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) => format!("D{}", i),
+        ActionDisplay::Center => "Center".to_string()
+    };
+
+    let row = match action.pattern_line_choice {
+        Some(i) => format!("row {}", i),
+        None => "penalty row".to_string()
+    };
+
+    Line::from(vec![
+        display.into(),
+        " ".into(),
+        Span::styled(TILE_M, Style::default().fg(tile_to_color(action.color_choice))),
+        " to ".into(),
+        row.into()
+    ])
+}
+
+fn format_gain(gain: i32) -> Span<'static> {
+    if gain == 0 {
+        Span::styled("0", Style::default().blue())
+    } else if gain.is_positive() {
+        Span::styled(format!("+{}", gain), Style::default().green().reversed())
+    } else {
+        Span::styled(gain.to_string(), Style::default().red().reversed())
+    }
+ }
+
+// Gift is the reply available to the next player, shown as a warning when it
+// crosses the threshold
+fn format_gift(gift: i32, threshold: i32) -> Span<'static> {
+    if gift > threshold {
+        Span::styled(format!("!{}", gift), Style::default().red().bold())
+    } else {
+        Span::from(gift.to_string())
+    }
+}
+
+fn format_score(score: Option<f32>) -> Span<'static> {
+    match score {
+        Some(s) => Span::from(s.to_string()),
+        None => Span::styled("NA", Style::default().gray()),
+    }
+}
+
+// Areas of each factory display and of the center
+fn display_areas(n_displays: usize, displays_area: Rect) -> (Vec<Rect>, Rect) {
+    let display_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Length(4), Constraint::Length(3)])
+        .split(displays_area);
+
+    let factory_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Length(9); n_displays])
+        .split(display_layout[0]);
+
+    (factory_layout.to_vec(), display_layout[1])
+}
+
+// Render factory displays and the center for the state
+fn render_displays(state: &azul::State, displays_area: Rect, buf: &mut Buffer) {
+    let (factory_layout, center_area) = display_areas(state.factory_displays.len(), displays_area);
+
+    for (i, fd) in state.factory_displays.iter().enumerate() {
+        let mut lines = Vec::new();
+        lines.push(Line::from(""));
+
+        let n_tiles: usize = fd.values().sum();
+        let mut tile_spans: Vec<Span> = Vec::with_capacity(4);
+
+        for (&tile, &count) in fd.iter() {
+            for _ in 0..count {
+                tile_spans.push(Span::styled(format!("{TILE_M} "), Style::default().fg(tile_to_color(tile))));
+            }
+        }
+
+        for _ in 0..(4 - n_tiles) {
+            tile_spans.push(Span::styled(format!("{TILE_EMPTY_M} "), Style::default().fg(style::Color::Gray)));
+        }
+
+        lines.push(Line::from(vec![
+            "  ".into(),
+            tile_spans[0].clone(),
+            tile_spans[1].clone(),
+        ]));
+        lines.push(Line::from(vec![
+            "  ".into(),
+            tile_spans[2].clone(),
+            tile_spans[3].clone(),
+        ]));
+        Text::from(lines).render(factory_layout[i], buf);
+
+        Block::bordered().title(format!(" D{} ", i)).render(factory_layout[i], buf);
+    }
+
+    render_center(&state.center, center_area, buf);
+}
+
+const TILE_COUNTS_WIDTH: u16 = 24;
+
+// Width of the win conditions panel next to the state analysis
+const PROGRESS_WIDTH: u16 = 57;
+
+// Tiles of each color not on the table, either still in the bag or discarded
+// to the lid, for learning to count tiles
+fn render_tile_counts(state: &azul::State, area: Rect, buf: &mut Buffer) {
+    let bag = azul::AzulRules::current().bag_tiles(state);
+
+    let lines: Vec<Line> = WALL_COLORS[0]
+        .iter()
+        .map(|tile| Line::from(vec![
+            Span::styled(format!(" {TILE_M}"), Style::default().fg(tile_to_color(*tile))),
+            format!(" {:>3} bag", bag[tile]).into(),
+            format!(" {:>3} lid", state.lid[tile]).gray(),
+        ]))
+        .collect();
+
+    Paragraph::new(lines)
+        .block(Block::bordered().title(" Unseen Tiles "))
+        .render(area, buf);
+}
+
+// Positions on a player board as drawn by the `PlayerState` widget
+fn pattern_line_area(board: Rect, line: usize) -> Rect {
+    Rect::new(board.x + 1, board.y + 3 + line as u16, 15, 1)
+}
+
+fn wall_cell_area(board: Rect, row: usize, column: usize) -> Rect {
+    Rect::new(board.x + 18 + 3 * column as u16, board.y + 3 + row as u16, 2, 1)
+}
+
+fn floor_area(board: Rect) -> Rect {
+    Rect::new(board.x + 1, board.y + 9, 2 * (azul::config().floor_penalties.len() as u16 + 1), 1)
+}
+
+impl GameUi for azul::State {
+    type Action = azul::Action;
+    type Event = GameEvent;
+
+    fn n_players(&self) -> usize {
+        self.players.len()
+    }
+
+    fn round(&self) -> usize {
+        self.rounds
+    }
+
+    fn scores(&self) -> Vec<i32> {
+        self.players.iter().map(|p| p.score).collect()
+    }
+
+    fn shared_height(&self) -> u16 {
+        7
+    }
+
+    fn render_shared(&self, area: Rect, buf: &mut Buffer) {
+        let [displays_area, tiles_area] = Layout::horizontal([Constraint::Min(0), Constraint::Length(TILE_COUNTS_WIDTH)]).areas(area);
+        render_displays(self, displays_area, buf);
+        render_tile_counts(self, tiles_area, buf);
+    }
+
+    fn player_height(&self) -> u16 {
+        12
+    }
+
+    fn render_player(&self, player_idx: usize, area: Rect, buf: &mut Buffer) {
+        render_player(&self.players[player_idx], area, buf);
+    }
+
+    fn action_line(action: &azul::Action) -> Line<'static> {
+        action_line(action)
+    }
+
+    fn format_move(&self, player_idx: usize, action: azul::Action) -> String {
+        azul::explain_action(&azul::AzulRules::current(), self, player_idx, action)
+    }
+
+    fn score_sheet_columns() -> Vec<&'static str> {
+        vec!["Score", "Rows", "Columns", "Colors", "Floor"]
+    }
+
+    fn score_sheet_row(&self, player_idx: usize) -> Vec<String> {
+        let player = &self.players[player_idx];
+        let progress = player.wall_progress();
+
+        vec![
+            player.score.to_string(),
+            wall_progress(progress.rows.into_iter()),
+            wall_progress(progress.columns.into_iter()),
+            wall_progress(progress.colors.into_iter()),
+            format!("{}{}", player.floor_line, if player.starting_marker { " + 1st" } else { "" }),
+        ]
+    }
+
+    // Tiles in each wall row, column, and color with full ones in green and
+    // those one tile short in yellow
+    fn progress_line(&self, player_idx: usize) -> Line<'static> {
+        let progress = self.players[player_idx].wall_progress();
+        let count_span = |n: usize, style: Style| match n {
+            5 => Span::styled(n.to_string(), style.fg(style::Color::Green).bold()),
+            4 => Span::styled(n.to_string(), style.fg(style::Color::Yellow)),
+            _ => Span::styled(n.to_string(), style),
+        };
+
+        let mut spans = vec!["Rows ".italic()];
+        spans.extend(progress.rows.map(|n| count_span(n, Style::default())));
+        spans.push("  Columns ".italic());
+        spans.extend(progress.columns.map(|n| count_span(n, Style::default())));
+        spans.push("  Colors ".italic());
+        for (color, n) in WALL_COLORS[0].iter().zip(progress.colors) {
+            spans.push(Span::styled(TILE_M, Style::default().fg(tile_to_color(*color))));
+            spans.push(count_span(n, Style::default()));
+        }
+        Line::from(spans)
+    }
+
+    // Wall placements with their points, then the floor penalty and the new
+    // score
+    fn tiling_lines(&self, events: &[GameEvent], player_idx: usize) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        for event in events {
+            match event {
+                GameEvent::WallTiled { player, row, column, color, points } if *player == player_idx => {
+                    lines.push(Line::from(vec![
+                        format!("  Pattern line {} ", row).into(),
+                        Span::styled(TILE_M, Style::default().fg(tile_to_color(*color))),
+                        format!(" to wall column {}: ", column).into(),
+                        format!("+{}", points).green().bold(),
+                    ]));
+                },
+                GameEvent::Scored { player, tiling, penalty, score } if *player == player_idx => {
+                    if lines.is_empty() {
+                        lines.push("  No pattern line is complete".italic().into());
+                    }
+                    lines.push(Line::from(vec![
+                        format!("  Floor line ({} tiles): ", self.players[player_idx].floor_line).into(),
+                        format!("-{}", penalty).red().bold(),
+                    ]));
+                    lines.push(Line::from(""));
+                    lines.push(Line::from(vec![
+                        "  Score: ".italic(),
+                        format!("{} ", self.players[player_idx].score).into(),
+                        format_gain(tiling - penalty),
+                        format!(" = {}", score).bold(),
+                    ]));
+                },
+                _ => {},
+            }
+        }
+        lines
+    }
+
+    // Scarcity of each color, what the other players are collecting, and which
+    // of the player's lines could be denied before their next turn
+    fn state_analysis(&self, player_idx: usize) -> Vec<Line<'static>> {
+        let tile_span = |color: Tile| Span::styled(TILE_M, Style::default().fg(tile_to_color(color)));
+
+        let mut on_table = vec!["  On table: ".italic()];
+        for (color, count) in azul::colors_on_table(self) {
+            on_table.push(tile_span(color));
+            on_table.push(format!(" {:<3}", count).into());
+        }
+        let mut lines = vec![Line::from(on_table)];
+
+        for other in (1..self.players.len()).map(|i| (player_idx + i) % self.players.len()) {
+            let mut needs = vec![format!("  P{} needs: ", other).italic()];
+            let needed = azul::colors_needed(self, other);
+            if needed.is_empty() {
+                needs.push("nothing started".gray());
+            }
+            for (color, missing) in needed {
+                needs.push(tile_span(color));
+                needs.push(format!(" {:<3}", missing).into());
+            }
+            lines.push(Line::from(needs));
+        }
+
+        let mut risks = vec![format!("  P{} at risk: ", player_idx).italic()];
+        let denial_risks = azul::denial_risks(self, player_idx);
+        if denial_risks.is_empty() {
+            risks.push("none".gray());
+        }
+        for (line_idx, color, risk) in denial_risks {
+            let reason = match risk {
+                DenialRisk::Short => "short",
+                DenialRisk::Contested => "contested",
+            };
+            risks.push(tile_span(color));
+            risks.push(format!(" line {} ({})  ", line_idx, reason).into());
+        }
+        lines.push(Line::from(risks));
+
+        lines
+    }
+
+    // Tiles are first shown leaving their source and then landing on the
+    // player's board. Floor penalties flash and round scores pop up on each
+    // board.
+    fn render_animation(&self, events: &[GameEvent], progress: f32, shared_area: Rect, player_areas: &[Rect], buf: &mut Buffer) {
+        let (display_areas, center_area) = display_areas(self.factory_displays.len(), shared_area);
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+        let flash_on = ((progress * 6.0) as usize).is_multiple_of(2);
+        let landing = progress >= 0.5;
+
+        for event in events {
+            match event {
+                GameEvent::TilesTaken { source, .. } if !landing => {
+                    let area = match source {
+                        ActionDisplay::FactoryDisplay(i) => display_areas[*i],
+                        ActionDisplay::Center => center_area,
+                    };
+                    buf.set_style(area, Style::default().fg(style::Color::Yellow).add_modifier(Modifier::BOLD));
+                },
+                GameEvent::TilesStaged { player, line, .. } if landing => {
+                    if let Some(&board) = player_areas.get(*player) {
+                        let area = match line {
+                            Some(i) => pattern_line_area(board, *i),
+                            None => floor_area(board),
+                        };
+                        buf.set_style(area, highlight);
+                    }
+                },
+                GameEvent::Overflowed { player, .. } if landing && flash_on => {
+                    if let Some(&board) = player_areas.get(*player) {
+                        buf.set_style(floor_area(board), highlight.fg(style::Color::Red));
+                    }
+                },
+                GameEvent::WallTiled { player, row, column, .. } if flash_on => {
+                    if let Some(&board) = player_areas.get(*player) {
+                        buf.set_style(wall_cell_area(board, *row, *column), highlight);
+                    }
+                },
+                GameEvent::Scored { player, tiling, penalty, .. } => {
+                    if let Some(&board) = player_areas.get(*player) {
+                        let popup = Line::from(vec![
+                            format!(" +{} ", tiling).green().reversed(),
+                            " ".into(),
+                            format!(" -{} ", penalty).red().reversed(),
+                            " ".into(),
+                        ]).right_aligned();
+                        popup.render(Rect::new(board.x + 1, board.y + 1, board.width.saturating_sub(2), 1), buf);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+// Completed lines out of tiles placed in each line, with the fill of the
+// closest incomplete one
+fn wall_progress(filled: impl Iterator<Item = usize>) -> String {
+    let filled: Vec<usize> = filled.collect();
+    let completed = filled.iter().filter(|&&n| n == 5).count();
+
+    match filled.iter().filter(|&&n| n < 5).max() {
+        Some(best) => format!("{} (next {}/5)", completed, best),
+        None => completed.to_string(),
+    }
+}
+
+// Height of the score sheet with its borders and header
+fn score_sheet_height(n_players: usize) -> u16 {
+    n_players as u16 + 3
+}
+
+// Table with one line per player, highlighting the current player
+fn render_score_sheet<G: GameUi>(state: &G, current_player: usize, area: Rect, buf: &mut Buffer) {
+    let columns = G::score_sheet_columns();
+
+    let rows: Vec<Row> = (0..state.n_players()).map(|i| {
+        let mut cells = vec![format!(" {} Player {}", if i == current_player { "→" } else { " " }, i)];
+        cells.extend(state.score_sheet_row(i));
+
+        let row = Row::new(cells);
+        if i == current_player { row.bold() } else { row }
+    }).collect();
+
+    let mut header = vec!["   Player"];
+    header.extend(&columns);
+
+    let mut widths = vec![Constraint::Length(14)];
+    widths.extend(columns.iter().map(|_| Constraint::Length(16)));
+
+    let table = Table::new(rows, widths)
+        .column_spacing(1)
+        .header(Row::new(header).style(Style::default().fg(style::Color::Blue)))
+        .block(Block::bordered().title(" Score Sheet "));
+
+    Widget::render(table, area, buf);
+}
+
+// Player boards are side by side, wrapping to two rows beyond three players
+pub fn player_rows(n_players: usize) -> u16 {
+    if n_players > 3 { 2 } else { 1 }
+}
+
+fn player_areas(n_players: usize, area: Rect) -> Vec<Rect> {
+    let n_rows = player_rows(n_players) as usize;
+    let per_row = n_players.div_ceil(n_rows);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![Constraint::Ratio(1, n_rows as u32); n_rows])
+        .split(area);
+
+    (0..n_players)
+        .map(|i| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Min(24); per_row])
+                .split(rows[i / per_row])[i % per_row]
+        })
+        .collect()
+}
+
+// Render all player boards, highlighting the current player
+fn render_players<G: GameUi>(state: &G, current_player: usize, players_area: Rect, buf: &mut Buffer) {
+    let players_layout = player_areas(state.n_players(), players_area);
+
+    for (i, &player_area) in players_layout.iter().enumerate().take(state.n_players()) {
+        let block = Block::default()
+            .title(Line::from(format!(" Player {} ", i).bold()))
+            .border_type(if current_player == i { BorderType::QuadrantOutside } else { BorderType::Plain })
+            .border_style(Style::default().fg(style::Color::Blue))
+            .borders(Borders::ALL);
+
+        state.render_player(i, player_area, buf);
+        block.render(player_area, buf);
+    }
+}
+
+impl<G: GameUi> Widget for InteractiveApp<G> {
+    fn render(mut self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),  // Header
+                Constraint::Length(self.state.shared_height()), // Shared Board
+                Constraint::Length(self.players_height(self.compact_players)), // Player States
+                Constraint::Length(15), // Actions
+                Constraint::Length(7),  // Heuristics Analysis
+                Constraint::Length(7),  // States Analysis
+            ])
+            .split(area);
+
+        let block = Block::bordered()
+            .border_set(border::THICK);
+
+        let mut header_spans = vec![
+            " ".into(),
+            if self.game_over {
+                Span::styled(" GAME OVER ", Style::default().fg(style::Color::Red)).bold().add_modifier(Modifier::SLOW_BLINK | Modifier::REVERSED)
+            } else {
+                Span::styled(" GAME RUNNING ", Style::default().fg(style::Color::Blue)).bold().add_modifier(Modifier::REVERSED)
+            },
+            format!(" Players: {}, ", self.state.n_players()).into(),
+            format!("Current Player: {}, ", self.current_player).into(),
+            format!("Round: {}, ", self.state.round()).into(),
+            format!("Ply: {}, ({} this round), ", self.ply, self.ply_round).into(),
+            format!("Teacher: {}", self.difficulty).into(),
+        ];
+
+        if let Some(clock) = &self.clock {
+            header_spans.push(", Clocks:".into());
+            for i in 0..self.state.n_players() {
+                let text = format!(" P{} {}", i, clock::format_duration(clock.remaining(i, self.current_player)));
+                let style = if clock.flagged() == Some(i) {
+                    Style::default().fg(style::Color::Red).bold()
+                } else if i == self.current_player {
+                    Style::default().bold()
+                } else {
+                    Style::default()
+                };
+                header_spans.push(Span::styled(text, style));
+            }
+        }
+
+        let header_text = Text::from(vec![Line::from(header_spans)]);
+
+        Paragraph::new(header_text)
+            .block(block)
+            .render(layout[0], buf);
+
+        self.state.render_shared(layout[1], buf);
+        if self.compact_players {
+            render_score_sheet(&self.state, self.current_player, layout[2], buf);
+        } else {
+            render_players(&self.state, self.current_player, layout[2], buf);
+        }
+
+        if let Some(animation) = &self.animation {
+            let boards = if self.compact_players { Vec::new() } else { player_areas(self.state.n_players(), layout[2]) };
+            self.state.render_animation(&animation.events, animation.progress(), layout[1], &boards, buf);
+        }
+
+        let actions_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Max(4), Constraint::Max(10)])
+            .split(layout[3]);
+
+        let mut last_move_lines = Vec::new();
+        last_move_lines.push(Line::from(""));
+
+        match self.last_move {
+            Some(mov) => {
+                let mut line = G::action_line(&mov.action);
+                line.spans.insert(0, format!("        Last Move by P{}: ", mov.player).italic());
+                last_move_lines.push(line);
+                last_move_lines.push(Line::from(format!("        {}", mov.explanation)).gray());
+                // The note takes the place of the top padding
+                if let Some(note) = self.ply.checked_sub(1).and_then(|ply| self.notes.get(&ply)) {
+                    last_move_lines.remove(0);
+                    last_move_lines.push(Line::from(vec!["        Note: ".italic(), note.clone().into()]));
+                }
+            },
+            None => {
+                last_move_lines.push("        Last Move: NA".italic().into())
+            }
+        }
+
+        Paragraph::new(last_move_lines)
+            .render(actions_layout[0], buf);
+
+        let mut rows: Vec<Row> = vec![];
+
+        for (idx, action) in self.actions.iter().enumerate() {
+            if self.analyses.contains_key(action) {
+                let analysis = self.analyses[action];
+                let row = Row::new(vec![
+                    Cell::from(format!(" {:>3}. ", idx)),
+                    Cell::from(G::action_line(action)),
+                    Cell::from(format_gain(analysis.score_gain)),
+                    Cell::from(format_gift(analysis.gift, self.gift_threshold)),
+                    Cell::from(format_score(analysis.expected_score)),
+                    Cell::from(format_score(analysis.win_probability)),
+                ]);
+
+                if analysis.gift > self.gift_threshold {
+                    rows.push(row.style(Style::default().fg(style::Color::Red)));
+                } else {
+                    rows.push(row);
+                }
+            } else {
+                rows.push(Row::new(vec![
+                    Cell::from(idx.to_string()),
+                    Cell::from(G::action_line(action)),
+                    Cell::from(format_score(None)),
+                    Cell::from(format_score(None)),
+                    Cell::from(format_score(None)),
+                    Cell::from(format_score(None)),
+                ]));
+            }
+        }
+
+        let table = Table::new(rows, [
+            Constraint::Length(6),
+            Constraint::Percentage(30),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+        ])
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_symbol(" →")
+            .row_highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .column_spacing(1)
+            .header(Row::new(vec![
+                "".into(),
+                Span::styled("Action", Style::default().italic().blue()),
+                Span::styled("Gain", Style::default().italic().blue()),
+                Span::styled("Gift", Style::default().italic().blue()),
+                Span::styled("EXP Score", Style::default().italic().blue()),
+                Span::styled("Win P", Style::default().italic().blue()),
+            ]));
+
+        StatefulWidget::render(table, actions_layout[1], buf, &mut self.actions_state);
+
+        Block::bordered()
+            .border_set(border::THICK)
+            .title(Line::from(" Actions ".bold()).centered())
+            .title_bottom(Line::from(vec![
+                " Teacher Play ".into(),
+                "<SPC> ".blue().bold(),
+                " Project Action ".into(),
+                "<p> ".blue().bold(),
+                " Details ".into(),
+                "<a> ".blue().bold(),
+                " Play Ranked ".into(),
+                "<1-5> ".blue().bold(),
+                " Random ".into(),
+                "<r> ".blue().bold(),
+                " Greedy ".into(),
+                "<g> ".blue().bold(),
+                " Preview Round ".into(),
+                "<f> ".blue().bold(),
+                " Difficulty ".into(),
+                "<d> ".blue().bold(),
+                " Score Sheet ".into(),
+                "<c> ".blue().bold(),
+                " Search Tree ".into(),
+                "<t> ".blue().bold(),
+                " Note ".into(),
+                "<;> ".blue().bold(),
+                format!(" Autoplay {} ", if self.autoplay { "On" } else { "Off" }).into(),
+                "<o> ".blue().bold(),
+                " Proceed ".into(),
+                "<RET> ".blue().bold(),
+                " Quit ".into(),
+                "<q> ".blue().bold(),
+            ]).right_aligned())
+            .render(layout[3], buf);
+
+        Block::bordered()
+            .title(" Heuristic Analysis ")
+            .title_bottom(Line::from(vec![
+                " Show more ".into(),
+                "<h> ".blue().bold(),
+            ]).right_aligned())
+            .render(layout[4], buf);
+
+        let marker_value = self.actions_state.selected()
+            .and_then(|i| self.actions.get(i))
+            .and_then(|action| self.analyses.get(action))
+            .and_then(|analysis| analysis.marker_value);
+        let mut heuristic_lines = vec![match &self.teacher_info {
+            Some(info) => Line::from(vec!["  Teacher: ".italic(), info.clone().into()]),
+            None => Line::from(""),
+        }];
+        if let Some(action) = self.actions_state.selected().and_then(|i| self.actions.get(i)) {
+            let picking: Vec<&str> = self.heuristics
+                .iter()
+                .filter(|heuristic| (heuristic.function)(&self.state, self.current_player) == Some(*action))
+                .map(|heuristic| heuristic.name.as_str())
+                .collect();
+            heuristic_lines.push(Line::from(vec![
+                "  Picked by: ".italic(),
+                if picking.is_empty() { "no heuristic".into() } else { picking.join(", ").into() },
+            ]));
+        }
+        if let Some(value) = marker_value {
+            heuristic_lines.push(Line::from(vec![
+                "  Starting Marker: ".italic(),
+                format!("{:+.2}", value).bold(),
+                " expected points for taking it now over leaving it in the center".into(),
+            ]));
+        }
+        Paragraph::new(heuristic_lines)
+            .render(layout[4].inner(Margin { horizontal: 1, vertical: 0 }), buf);
+
+        let [state_area, progress_area] = Layout::horizontal([Constraint::Min(0), Constraint::Length(PROGRESS_WIDTH)]).areas(layout[5]);
+
+        Paragraph::new(self.state.state_analysis(self.current_player))
+            .render(state_area.inner(Margin { horizontal: 1, vertical: 1 }), buf);
+
+        let progress_lines: Vec<Line> = (0..self.state.n_players()).map(|i| {
+            let mut line = self.state.progress_line(i);
+            line.spans.insert(0, format!(" {}P{} ", if i == self.current_player { "→" } else { " " }, i).into());
+            if i == self.current_player { line.bold() } else { line }
+        }).collect();
+        Paragraph::new(progress_lines)
+            .block(Block::bordered().title(" Win Conditions "))
+            .render(progress_area, buf);
+
+        Block::bordered()
+            .title(" State Analysis ")
+            .title_bottom(Line::from(vec![
+                " Show more ".into(),
+                "<s> ".blue().bold(),
+            ]).right_aligned())
+            .render(state_area, buf);
+
+        // Action analysis popup
+        if self.show_action_details {
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(" Action Details ");
+            let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            let analysis_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(4), Constraint::Length(7), Constraint::Length(2), Constraint::Min(10)])
+                .split(area);
+
+            let selected_action = self.actions[self.actions_state.selected().unwrap()];
+            let mut selected_action_line = Vec::new();
+            selected_action_line.push(Line::from(""));
+            selected_action_line.push(Line::from(""));
+
+            let mut line = G::action_line(&selected_action);
+            line.spans.insert(0, format!("  Move by P{}: ", self.current_player).italic());
+            selected_action_line.push(line);
+
+            Paragraph::new(selected_action_line)
+                .render(analysis_layout[0], buf);
+
+            let analysis = self.analyses[&selected_action];
+
+            let table = Table::new([
+                Row::new(vec!["  Immediate Gain".to_string(), analysis.score_gain.to_string()]),
+                Row::new(vec!["  Best Reply for Next Player".to_string(), analysis.gift.to_string()]),
+                Row::new(vec!["  Expected Score".to_string(), if let Some(s) = analysis.expected_score { s.to_string() } else { "NA".to_string() }]),
+                Row::new(vec!["  Win Probability".to_string(), if let Some(p) = analysis.win_probability { p.to_string() } else { "NA".to_string() }]),
+                Row::new(vec!["  Later Floor Probability".to_string(), if let Some(p) = analysis.floor_probability { p.to_string() } else { "NA".to_string() }]),
+                Row::new(vec!["  Expected Later Floor Penalty".to_string(), if let Some(p) = analysis.expected_floor_penalty { p.to_string() } else { "NA".to_string() }]),
+                Row::new(vec!["  Starting Marker Value".to_string(), if let Some(v) = analysis.marker_value { v.to_string() } else { "NA".to_string() }]),
+            ], [
+                Constraint::Percentage(80),
+                Constraint::Percentage(20),
+            ])
+                .column_spacing(1);
+
+            Widget::render(table, analysis_layout[1], buf);
+
+            Paragraph::new(format!("  Likely Continuation: {}", self.continuation.join(", ")))
+                .render(analysis_layout[2], buf);
+
+            let mut rows = vec![];
+
+            for heuristic in &self.heuristics {
+                let result = (heuristic.function)(&self.state, self.current_player);
+                let applicable = result.is_some();
+                let action_match = if let Some(heuristic_action) = result {
+                    heuristic_action == selected_action
+                } else { false };
+
+                rows.push(Row::new(vec![format!("  {}", heuristic.name), applicable.to_string(), action_match.to_string()]));
+            }
+
+            let table = Table::new(rows, [
+                Constraint::Percentage(60),
+                Constraint::Percentage(20),
+                Constraint::Percentage(20),
+            ])
+                .column_spacing(1)
+                .header(Row::new(vec!["  Heuristic", "Applicable", "Match"]));
+
+            Widget::render(table, analysis_layout[3], buf);
+
+            block.render(area, buf);
+        }
+
+        // Round end of one player, their board before and after tiling
+        if let Some(tiling) = &self.tiling {
+            let player = tiling.player;
+            let last = player + 1 == tiling.scored.n_players();
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(format!(" Wall Tiling: Player {} ({}/{}) ", player, player + 1, tiling.scored.n_players()))
+                .title_bottom(Line::from(vec![
+                    if last { " Score Round ".into() } else { " Next Player ".into() },
+                    "<RET> ".blue().bold(),
+                    " Skip ".into(),
+                    "<q> ".blue().bold(),
+                ]).right_aligned());
+            let lines = self.state.tiling_lines(&tiling.events, player);
+            let height = self.state.player_height() + lines.len() as u16 + 3;
+            let vertical = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            let [boards_area, _, lines_area] = Layout::vertical([Constraint::Length(self.state.player_height()), Constraint::Length(1), Constraint::Min(0)])
+                .areas(area.inner(Margin::new(1, 1)));
+            let [before_area, after_area] = Layout::horizontal([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).areas(boards_area);
+            for (state, board, title) in [(&self.state, before_area, " Before "), (&tiling.scored, after_area, " After ")] {
+                state.render_player(player, board, buf);
+                Block::bordered()
+                    .title(title.bold())
+                    .border_style(Style::default().fg(style::Color::Gray))
+                    .render(board, buf);
+            }
+            Paragraph::new(lines).render(lines_area, buf);
+
+            block.render(area, buf);
+        }
+
+        // Top actions and replies of the teacher's search
+        if let Some(tree) = &self.search_tree {
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(" Search Tree ")
+                .title_bottom(Line::from(vec![
+                    " Expand ".into(),
+                    "<RET> ".blue().bold(),
+                    " Close ".into(),
+                    "<t> ".blue().bold(),
+                ]).right_aligned());
+            let vertical = Layout::vertical([Constraint::Percentage(70)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(70)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            let rows = tree.rows().into_iter().map(|(i, reply)| {
+                let (node, prefix) = match reply {
+                    None if tree.nodes[i].replies.is_empty() => (&tree.nodes[i], "   "),
+                    None if tree.expanded[i] => (&tree.nodes[i], " ▾ "),
+                    None => (&tree.nodes[i], " ▸ "),
+                    Some(j) => (&tree.nodes[i].replies[j], "     └ "),
+                };
+                let mut line = G::action_line(&node.action);
+                line.spans.insert(0, format!("{}P{} ", prefix, node.player).into());
+                Row::new(vec![
+                    Cell::from(line),
+                    Cell::from(node.n_games.to_string()),
+                    Cell::from(format!("{:.1}", node.mean_score)),
+                    Cell::from(format!("{:.0}%", node.win_rate * 100.0)),
+                ])
+            });
+
+            let table = Table::new(rows, [
+                Constraint::Min(30),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(8),
+            ])
+                .highlight_spacing(HighlightSpacing::Always)
+                .highlight_symbol(" →")
+                .row_highlight_style(Style::default().add_modifier(Modifier::BOLD))
+                .column_spacing(1)
+                .header(Row::new(vec![
+                    Span::styled("   Action", Style::default().italic().blue()),
+                    Span::styled("Rollouts", Style::default().italic().blue()),
+                    Span::styled("EXP Score", Style::default().italic().blue()),
+                    Span::styled("Win P", Style::default().italic().blue()),
+                ]));
+
+            let mut rows_state = tree.rows_state.clone();
+            StatefulWidget::render(table, area.inner(Margin::new(1, 1)), buf, &mut rows_state);
+            block.render(area, buf);
+        }
+
+        // Best actions of the two compared engines, with the actions the other
+        // engine doesn't have among its best in yellow
+        if let Some(comparison) = &self.comparison {
+            let (verdict, color) = if comparison.same_best() {
+                (" Same Best Action ", style::Color::Green)
+            } else {
+                (" Best Actions Differ ", style::Color::Red)
+            };
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(" Compare Engines ")
+                .title_bottom(Line::from(vec![
+                    Span::styled(verdict, Style::default().fg(color).bold()),
+                    " Close ".into(),
+                    "<v> ".blue().bold(),
+                ]).right_aligned());
+            let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(90)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            let panels = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area.inner(Margin::new(1, 1)));
+            for (i, (spec, nodes)) in comparison.engines.iter().enumerate() {
+                let others = &comparison.engines[1 - i].1;
+                let rows = nodes.iter().enumerate().map(|(rank, node)| {
+                    let mut line = G::action_line(&node.action);
+                    line.spans.insert(0, format!(" {}. ", rank + 1).into());
+                    let row = Row::new(vec![
+                        Cell::from(line),
+                        Cell::from(node.n_games.to_string()),
+                        Cell::from(format!("{:.1}", node.mean_score)),
+                        Cell::from(format!("{:.0}%", node.win_rate * 100.0)),
+                    ]);
+                    if others.iter().any(|other| other.action == node.action) {
+                        row
+                    } else {
+                        row.style(Style::default().fg(style::Color::Yellow))
+                    }
+                });
+
+                let table = Table::new(rows, [
+                    Constraint::Min(24),
+                    Constraint::Length(9),
+                    Constraint::Length(10),
+                    Constraint::Length(6),
+                ])
+                    .column_spacing(1)
+                    .header(Row::new(vec![
+                        Span::styled("    Action", Style::default().italic().blue()),
+                        Span::styled("Rollouts", Style::default().italic().blue()),
+                        Span::styled("EXP Score", Style::default().italic().blue()),
+                        Span::styled("Win P", Style::default().italic().blue()),
+                    ]))
+                    .block(Block::bordered().title(format!(" {} ", spec)));
+                Widget::render(table, panels[i], buf);
+            }
+            block.render(area, buf);
+        }
+
+        // Note being written on the current ply
+        if let Some(note) = &self.note_input {
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(format!(" Note on Ply {} ", self.ply))
+                .title_bottom(Line::from(vec![
+                    " Save ".into(),
+                    "<RET> ".blue().bold(),
+                    " Cancel ".into(),
+                    "<ESC> ".blue().bold(),
+                ]).right_aligned());
+            let vertical = Layout::vertical([Constraint::Length(5)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            Paragraph::new(Line::from(vec![note.clone().into(), "█".slow_blink()]))
+                .wrap(Wrap { trim: false })
+                .render(area.inner(Margin::new(2, 1)), buf);
+            block.render(area, buf);
+        }
+
+        // Projected boards at the end of the round
+        if let Some(preview) = self.round_preview {
+            let block = Block::bordered()
+                .border_type(BorderType::Thick)
+                .title(" Round Preview (Projected) ")
+                .title_bottom(Line::from(vec![
+                    " Close ".into(),
+                    "<q> ".blue().bold(),
+                ]).right_aligned());
+            let vertical = Layout::vertical([Constraint::Length(self.state.player_height() * player_rows(preview.n_players()) + 4)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+            let [area] = vertical.areas(area);
+            let [area] = horizontal.areas(area);
+            Clear.render(area, buf);
+
+            let players_layout = player_areas(preview.n_players(), area.inner(Margin::new(1, 1)));
+
+            let scores = self.state.scores();
+            for (i, score) in preview.scores().into_iter().enumerate() {
+                let gain = score - scores[i];
+                let block = Block::default()
+                    .title(Line::from(vec![
+                        format!(" Player {} ", i).bold(),
+                        format_gain(gain),
+                        " ".into(),
+                    ]))
+                    .border_style(Style::default().fg(style::Color::Gray))
+                    .borders(Borders::ALL);
+
+                preview.render_player(i, players_layout[i], buf);
+                block.render(players_layout[i], buf);
+            }
+
+            block.render(area, buf);
+        }
+    }
+}
+
+impl Widget for ReviewApp {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let players_height = self.reviews.first().map_or(12, |r| r.state.player_height() * player_rows(r.state.n_players()));
+
+        // Expected score lost on quick moves against slow ones, by player
+        let mut players: Vec<usize> = self.reviews.iter().map(|r| r.player).collect();
+        players.sort();
+        players.dedup();
+        let think_lines: Vec<Line> = players
+            .into_iter()
+            .filter_map(|player| {
+                let moves = self.reviews.iter().filter(|r| r.player == player && !r.auto_played).map(|r| (r.think_time, r.loss()));
+                let split = accuracy::by_think_time(moves)?;
+                let comparison = match split.ratio() {
+                    Some(ratio) => format!("lose {:.1}x the EV of slower ones", ratio),
+                    None => format!("lose {:.2} EV on average, slower ones none", split.fast.mean()),
+                };
+                Some(Line::from(format!(" P{} moves under {:.1}s {}", player, split.median.as_secs_f32(), comparison)))
+            })
+            .collect();
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3 + think_lines.len() as u16),  // Header
+                Constraint::Length(7),  // Displays
+                Constraint::Length(players_height), // Player States
+                Constraint::Min(8),     // Reviews
+            ])
+            .split(area);
+
+        let total_loss: f32 = self.reviews.iter().map(|r| r.loss()).sum();
+        let mut header = vec![
+            " ".into(),
+            Span::styled(" MISTAKE REVIEW ", Style::default().fg(style::Color::Blue)).bold().add_modifier(Modifier::REVERSED),
+            format!(" Reviewed plies: {}, ", self.reviews.len()).into(),
+        ];
+        if self.pending > 0 {
+            header.push(format!("{} still reviewing, ", self.pending).into());
+        }
+        header.push(format!("Total expected score lost: {:.2}", total_loss).into());
+        for (player, losses) in accuracy::by_player(self.reviews.iter().map(|r| (r.player, r.loss()))) {
+            header.push(format!(", P{} accuracy: {:.1} ({} centipoints lost)", player, losses.accuracy(), losses.centipoints()).into());
+        }
+        let mut header_lines = vec![Line::from(header)];
+        header_lines.extend(think_lines);
+        let header_text = Text::from(header_lines);
+
+        Paragraph::new(header_text)
+            .block(Block::bordered().border_set(border::THICK))
+            .render(layout[0], buf);
+
+        if let Some(review) = self.reviews_state.selected().and_then(|i| self.reviews.get(i)) {
+            review.state.render_shared(layout[1], buf);
+            render_players(&review.state, review.player, layout[2], buf);
+        }
+
+        let rows: Vec<Row> = self.reviews.iter().map(|review| {
+            Row::new(vec![
+                Cell::from(format!(" {:>3} ", review.ply)),
+                Cell::from(format!("P{}", review.player)),
+                Cell::from(action_line(&review.action)),
+                Cell::from(action_line(&review.best_action)),
+                Cell::from(format_score(Some(review.expected_score))),
+                Cell::from(format_score(Some(review.best_expected_score))),
+                Cell::from(Span::styled(format!("{:.2}", review.loss()), Style::default().red())),
+                Cell::from(if review.auto_played { "auto".to_string() } else { format!("{:.1}s", review.think_time.as_secs_f32()) }),
+            ])
+        }).collect();
+
+        let table = Table::new(rows, [
+            Constraint::Length(6),
+            Constraint::Length(6),
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(8),
+        ])
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_symbol(" →")
+            .row_highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .column_spacing(1)
+            .header(Row::new(vec![
+                Span::styled(" Ply", Style::default().italic().blue()),
+                Span::styled("Player", Style::default().italic().blue()),
+                Span::styled("Played", Style::default().italic().blue()),
+                Span::styled("Best", Style::default().italic().blue()),
+                Span::styled("EXP Score", Style::default().italic().blue()),
+                Span::styled("Best EXP", Style::default().italic().blue()),
+                Span::styled("Loss", Style::default().italic().blue()),
+                Span::styled("Think", Style::default().italic().blue()),
+            ]));
+
+        let block = Block::bordered()
+            .border_set(border::THICK)
+            .title(Line::from(" Biggest Losses ".bold()).centered())
+            .title_bottom(Line::from(vec![
+                " Navigate ".into(),
+                "<UP/DOWN> ".blue().bold(),
+                " Quit ".into(),
+                "<q> ".blue().bold(),
+            ]).right_aligned());
+
+        let table_area = block.inner(layout[3]);
+        block.render(layout[3], buf);
+        StatefulWidget::render(table, table_area, buf, &mut self.reviews_state);
+    }
+}
+
+impl Widget for PuzzleApp {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(4),  // Header
+                Constraint::Length(7),  // Displays
+                Constraint::Length(self.state.player_height() * player_rows(self.state.n_players())), // Player States
+                Constraint::Min(8),     // Actions
+            ])
+            .split(area);
+
+        let mut feedback = vec![" ".into(), Span::from(self.feedback.clone())];
+        if let (Some(_), Some(played)) = (self.solution, &self.played) {
+            feedback.push(Span::styled(format!(" (played in the game: {})", played), Style::default().gray()));
+        }
+        let header_text = Text::from(vec![
+            Line::from(vec![
+                " ".into(),
+                Span::styled(" PUZZLE ", Style::default().fg(style::Color::Blue)).bold().add_modifier(Modifier::REVERSED),
+                format!(" {}/{}, P{} to move, ", self.puzzle + 1, self.n_puzzles, self.player).into(),
+                format!("Tries left: {}, ", self.tries_left).into(),
+                format!("Streak: {} (best {})", self.streak, self.best_streak).into(),
+            ]),
+            Line::from(feedback),
+        ]);
+
+        Paragraph::new(header_text)
+            .block(Block::bordered().border_set(border::THICK))
+            .render(layout[0], buf);
+
+        self.state.render_shared(layout[1], buf);
+        render_players(&self.state, self.player, layout[2], buf);
+
+        let rules = azul::AzulRules::current();
+        let rows: Vec<Row> = self.actions.iter().map(|action| {
+            let style = if self.solution == Some(*action) {
+                Style::default().green()
+            } else if self.tried.contains(action) {
+                Style::default().red()
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                Cell::from(action_line(action)),
+                Cell::from(azul::explain_action(&rules, &self.state, self.player, *action)),
+            ]).style(style)
+        }).collect();
+
+        let table = Table::new(rows, [Constraint::Percentage(25), Constraint::Percentage(75)])
+            .highlight_spacing(HighlightSpacing::Always)
+            .highlight_symbol(" →")
+            .row_highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .column_spacing(1)
+            .header(Row::new(vec![
+                Span::styled("Action", Style::default().italic().blue()),
+                Span::styled("Effect", Style::default().italic().blue()),
+            ]));
+
+        let block = Block::bordered()
+            .border_set(border::THICK)
+            .title(Line::from(" Actions ".bold()).centered())
+            .title_bottom(Line::from(vec![
+                " Navigate ".into(),
+                "<UP/DOWN> ".blue().bold(),
+                " Play ".into(),
+                "<ENTER> ".blue().bold(),
+                " Next ".into(),
+                "<n> ".blue().bold(),
+                " Quit ".into(),
+                "<q> ".blue().bold(),
+            ]).right_aligned());
+
+        let table_area = block.inner(layout[3]);
+        block.render(layout[3], buf);
+        StatefulWidget::render(table, table_area, buf, &mut self.actions_state);
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+use crate::logreader;
+use crate::output::{self, Format, PlayerAccuracy};
+use crate::par::prelude::*;
+use crate::playlog::{LogRecord, Ply};
+use crate::tournament;
+
+pub use luza_core::accuracy::{by_player, evaluate_move, MoveEval};
+
+// A logged move with the strategy that made it and the state it was made in
+pub struct LoggedMove<'a> {
+    pub strategy: &'a str,
+    pub state: &'a str,
+    pub ply: &'a Ply,
+}
+
+impl LoggedMove<'_> {
+    pub fn evaluate(&self, rules: &azul::AzulRules) -> Result<MoveEval> {
+        let state: azul::State = serde_json::from_str(self.state)?;
+        let action: azul::Action = serde_json::from_str(&self.ply.action)?;
+        let player_idx = self.ply.player_id;
+
+        // Seeded by the position so the analysis comes out the same again
+        let seed = tournament::match_seed(0, self.ply.game_id, self.ply.ply_id, player_idx);
+        Ok(evaluate_move(rules, state, player_idx, action, seed))
+    }
+}
+
+// Moves of the logged games (or only `game_id`) and the number of games
+pub fn logged_moves(play_log: &[LogRecord], game_id: Option<usize>) -> Result<(Vec<LoggedMove<'_>>, usize)> {
+    let mut games: BTreeMap<usize, Vec<&LogRecord>> = BTreeMap::new();
+    for record in play_log {
+        if let Some(id) = record.game_id().filter(|&id| game_id.is_none_or(|g| g == id)) {
+            games.entry(id).or_default().push(record);
+        }
+    }
+    if games.is_empty() {
+        return Err(anyhow!("No games to analyze in the log"));
+    }
+
+    let mut moves = Vec::new();
+    for records in games.values() {
+        let Some(LogRecord::GameStart { players, .. }) = records.first() else {
+            continue;
+        };
+        for (before, record) in records.iter().zip(records.iter().skip(1)) {
+            if let (LogRecord::Ply(ply), Some(state)) = (record, before.state()) {
+                moves.push(LoggedMove { strategy: &players[ply.player_id], state, ply });
+            }
+        }
+    }
+    Ok((moves, games.len()))
+}
+
+// Evaluate every move of the logged games (or only `game_id`) against the
+// engine and report the expected score lost and accuracy of every strategy
+pub fn run(log_file: &PathBuf, game_id: Option<usize>, format: Format) -> Result<()> {
+    let play_log = logreader::read(log_file)?;
+    let (moves, n_games) = logged_moves(&play_log, game_id)?;
+    let rules = azul::AzulRules::current();
+    output::progress(format, &format!("Analyzing {} moves from {} games", moves.len(), n_games));
+
+    let losses = moves
+        .par_iter()
+        .map(|m| Ok((m.strategy, m.evaluate(&rules)?.loss())))
+        .collect::<Result<Vec<(&str, f32)>>>()?;
+
+    let rows: Vec<PlayerAccuracy> = by_player(losses)
+        .into_iter()
+        .map(|(strategy, losses)| PlayerAccuracy {
+            player: strategy.to_string(),
+            moves: losses.moves,
+            centipoints_lost: losses.centipoints(),
+            mean_loss: losses.mean(),
+            accuracy: losses.accuracy(),
+        })
+        .collect();
+
+    if format != Format::Text {
+        return output::write_rows(&rows, format);
+    }
+    for row in rows {
+        println!(
+            "{}: accuracy {:.1}, {} centipoints lost over {} moves ({:.2} per move)",
+            row.player, row.accuracy, row.centipoints_lost, row.moves, row.mean_loss
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use crate::games::azul::{self, AzulConfig};
+use crate::profile;
+
+// Interactive game in progress, written after every ply so a crashed or
+// closed terminal doesn't lose the game
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Autosave {
+    pub rules: AzulConfig,
+    pub state: azul::State,
+    pub current_player: usize,
+    pub ply: usize,
+    pub ply_round: usize,
+    // Moves played so far as (player, action)
+    pub history: Vec<(usize, azul::Action)>,
+    // Milliseconds spent choosing each move in history, for moves picked by
+    // hand
+    #[serde(default)]
+    pub think_times: Vec<Option<u64>>,
+    // State each move in history was played from. Saves made before these
+    // were kept have none.
+    #[serde(default)]
+    pub positions: Vec<azul::State>,
+    // Mistakes made by the profile owner, by kind
+    pub mistakes: HashMap<String, usize>,
+    // Notes written by the player, by the ply of the move they are about
+    #[serde(default)]
+    pub notes: BTreeMap<usize, String>,
+    // Plies of forced moves that were played without asking
+    #[serde(default)]
+    pub auto_played: BTreeSet<usize>,
+}
+
+fn autosave_path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("autosave.json"))
+}
+
+// Load the autosaved game if there is one
+pub fn load() -> Result<Option<Autosave>> {
+    let path = autosave_path()?;
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_reader(File::open(path)?)?))
+}
+
+impl Autosave {
+    // Write to a temporary file first so a crash while saving doesn't
+    // corrupt the last good save
+    pub fn save(&self) -> Result<()> {
+        let path = autosave_path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+}
+
+// Keep the autosave of a finished game in `games` in the data directory,
+// named by the time it ended, and return where it went
+pub fn archive() -> Result<PathBuf> {
+    let path = autosave_path()?;
+    let dir = profile::data_dir()?.join("games");
+    fs::create_dir_all(&dir)?;
+
+    let ended = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let archived = dir.join(format!("{}.json", ended));
+    fs::rename(path, &archived)?;
+    Ok(archived)
+}
+
+// Print the moves of a saved game with the think times and notes kept with
+// them
+pub fn replay(file: &Path) -> Result<()> {
+    let save: Autosave = serde_json::from_reader(File::open(file)?)?;
+    for (ply, (player, action)) in save.history.iter().enumerate() {
+        let think_time = match save.think_times.get(ply).copied().flatten() {
+            Some(ms) => format!(" ({:.1}s)", ms as f64 / 1000.0),
+            None if save.auto_played.contains(&ply) => " (auto)".to_string(),
+            None => String::new(),
+        };
+        println!("{:>3}. P{}: {}{}", ply, player, azul::action_notation(*action), think_time);
+        if let Some(note) = save.notes.get(&ply) {
+            println!("     ; {}", note);
+        }
+    }
+
+    let scores: Vec<i32> = save.state.players.iter().map(|p| p.score).collect();
+    println!("Scores: {:?}", scores);
+    Ok(())
+}
+
+// Remove the autosave once its game is over
+pub fn clear() -> Result<()> {
+    let path = autosave_path()?;
+
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "arrow")]
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "arrow")]
+use arrow::array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array};
+#[cfg(feature = "arrow")]
+use parquet::arrow::ArrowWriter;
+
+use crate::accuracy;
+use crate::games::azul;
+use crate::logreader;
+use crate::output::{self, Format, LogSummary};
+use crate::par::prelude::*;
+use crate::playlog::LogRecord;
+
+// Logs already summarized in a directory, so nightly runs only analyze the new
+// ones. A log is summarized again if its size or modification time changed.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+    pub size: u64,
+    // Seconds since the Unix epoch
+    pub modified: u64,
+    pub rows: Vec<LogSummary>,
+}
+
+impl Manifest {
+    pub fn load(file: &Path) -> Result<Self> {
+        if !file.exists() {
+            return Ok(Manifest::default());
+        }
+        Ok(serde_json::from_reader(File::open(file)?)?)
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(file)?), self)?;
+        Ok(())
+    }
+}
+
+// Size and modification time of a file, to tell when it changed
+fn file_stamp(file: &Path) -> Result<(u64, u64)> {
+    let metadata = fs::metadata(file)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+    Ok((metadata.len(), modified))
+}
+
+// Simulation logs in the directory, in name order
+pub fn list_logs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|file| file.extension().is_some_and(|ext| ext == "jsonl"));
+    files.sort();
+    Ok(files)
+}
+
+// File format of the combined summary
+#[derive(Debug, PartialEq)]
+enum OutFormat {
+    #[cfg(feature = "arrow")]
+    Parquet,
+    Rows(Format),
+}
+
+// Format of the combined summary, from the extension of the output file
+fn out_format(out: &Path) -> Result<OutFormat> {
+    match out.extension().and_then(|ext| ext.to_str()) {
+        #[cfg(feature = "arrow")]
+        Some("parquet") => Ok(OutFormat::Parquet),
+        #[cfg(not(feature = "arrow"))]
+        Some("parquet") => Err(anyhow!("Parquet summaries need luza built with the arrow feature")),
+        Some("csv") => output::check_format(Format::Csv).map(|_| OutFormat::Rows(Format::Csv)),
+        Some("json") => Ok(OutFormat::Rows(Format::Json)),
+        _ => Err(anyhow!("Summary file {} should end with .parquet, .csv, or .json", out.display())),
+    }
+}
+
+// Summary rows as one record batch with a column per field of LogSummary
+#[cfg(feature = "arrow")]
+fn summary_batch(rows: &[LogSummary]) -> Result<RecordBatch> {
+    let uints = |field: fn(&LogSummary) -> u64| Arc::new(UInt64Array::from_iter_values(rows.iter().map(field))) as ArrayRef;
+    let floats = |field: fn(&LogSummary) -> f64| Arc::new(Float64Array::from_iter_values(rows.iter().map(field))) as ArrayRef;
+    let strings = |field: fn(&LogSummary) -> &str| Arc::new(StringArray::from_iter_values(rows.iter().map(field))) as ArrayRef;
+
+    Ok(RecordBatch::try_from_iter([
+        ("file", strings(|r| &r.file)),
+        ("timestamp", uints(|r| r.timestamp)),
+        ("strategy", strings(|r| &r.strategy)),
+        ("games", uints(|r| r.games as u64)),
+        ("wins", uints(|r| r.wins as u64)),
+        ("mean_score", floats(|r| r.mean_score)),
+        ("moves", uints(|r| r.moves as u64)),
+        ("centipoints_lost", Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.centipoints_lost))) as ArrayRef),
+        ("accuracy", floats(|r| r.accuracy)),
+    ])?)
+}
+
+#[cfg(feature = "arrow")]
+fn write_parquet(rows: &[LogSummary], out: &Path) -> Result<()> {
+    let batch = summary_batch(rows)?;
+    let mut writer = ArrowWriter::try_new(File::create(out)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+// Games, wins, scores, and engine accuracy of every strategy in one log. Logs
+// made before run headers were written take the time of the file.
+pub fn summarize(file: &Path, modified: u64) -> Result<Vec<LogSummary>> {
+    let play_log = logreader::read(&file.to_path_buf())?;
+    let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let timestamp = match play_log.first() {
+        Some(LogRecord::Run(header)) => header.timestamp,
+        _ => modified,
+    };
+
+    // Games, wins, and total final score by strategy
+    let mut results: BTreeMap<&str, (usize, usize, i64)> = BTreeMap::new();
+    let mut seats: BTreeMap<usize, &[String]> = BTreeMap::new();
+    for record in &play_log {
+        match record {
+            LogRecord::GameStart { game_id, players, .. } => {
+                seats.insert(*game_id, players);
+            },
+            LogRecord::GameEnd { game_id, winner, final_scores, .. } => {
+                let Some(players) = seats.get(game_id) else {
+                    continue;
+                };
+                for (seat, strategy) in players.iter().enumerate() {
+                    let entry = results.entry(strategy).or_default();
+                    entry.0 += 1;
+                    entry.1 += (*winner == Some(seat)) as usize;
+                    entry.2 += final_scores.get(seat).copied().unwrap_or(0) as i64;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    let (moves, _n_games) = accuracy::logged_moves(&play_log, None)?;
+    let rules = azul::AzulRules::current();
+    let losses = moves
+        .par_iter()
+        .map(|m| Ok((m.strategy, m.evaluate(&rules)?.loss())))
+        .collect::<Result<Vec<(&str, f32)>>>()?;
+    let losses = accuracy::by_player(losses);
+
+    let mut strategies: Vec<&str> = results.keys().chain(losses.keys()).copied().collect();
+    strategies.sort();
+    strategies.dedup();
+
+    Ok(strategies
+        .into_iter()
+        .map(|strategy| {
+            let (games, wins, total_score) = results.get(strategy).copied().unwrap_or_default();
+            let losses = losses.get(strategy).cloned().unwrap_or_default();
+            LogSummary {
+                file: name.clone(),
+                timestamp,
+                strategy: strategy.to_string(),
+                games,
+                wins,
+                mean_score: if games == 0 { 0.0 } else { total_score as f64 / games as f64 },
+                moves: losses.moves,
+                centipoints_lost: losses.centipoints(),
+                accuracy: losses.accuracy(),
+            }
+        })
+        .collect())
+}
+
+// Summarize every log in the directory in parallel, reusing the summaries of
+// logs that didn't change since the manifest was written, and write all of
+// them to one Parquet, CSV, or JSON file ordered by time
+pub fn run(dir: &Path, out: &Path, manifest_file: Option<&Path>) -> Result<()> {
+    let format = out_format(out)?;
+    let manifest_file = manifest_file.map(Path::to_path_buf).unwrap_or_else(|| dir.join(".luza-analyze-all.json"));
+    let manifest = Manifest::load(&manifest_file)?;
+
+    let files = list_logs(dir)?;
+    if files.is_empty() {
+        return Err(anyhow!("No .jsonl logs in {}", dir.display()));
+    }
+
+    let stamped = files
+        .iter()
+        .map(|file| {
+            let name = file.file_name().unwrap().to_string_lossy().to_string();
+            Ok((file, name, file_stamp(file)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (cached, pending): (Vec<_>, Vec<_>) = stamped.into_iter().partition(|(_, name, (size, modified))| {
+        manifest.files.get(name).is_some_and(|entry| entry.size == *size && entry.modified == *modified)
+    });
+    println!("{} logs, {} already analyzed", cached.len() + pending.len(), cached.len());
+
+    let analyzed = pending
+        .par_iter()
+        .map(|(file, name, (size, modified))| {
+            let rows = summarize(file, *modified).map_err(|err| anyhow!("{}: {}", file.display(), err))?;
+            println!("Analyzed {}", name);
+            Ok((name.clone(), ManifestEntry { size: *size, modified: *modified, rows }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Logs no longer in the directory are dropped
+    let mut files = BTreeMap::new();
+    for (_, name, _) in cached {
+        files.insert(name.clone(), manifest.files[&name].clone());
+    }
+    files.extend(analyzed);
+    let manifest = Manifest { files };
+    manifest.save(&manifest_file)?;
+
+    let mut rows: Vec<LogSummary> = manifest.files.values().flat_map(|entry| entry.rows.clone()).collect();
+    rows.sort_by(|a, b| (a.timestamp, &a.file, &a.strategy).cmp(&(b.timestamp, &b.file, &b.strategy)));
+    match format {
+        #[cfg(feature = "arrow")]
+        OutFormat::Parquet => write_parquet(&rows, out)?,
+        OutFormat::Rows(format) => output::write_rows_to(&mut BufWriter::new(File::create(out)?), &rows, format)?,
+    }
+    println!("Wrote {} rows to {}", rows.len(), out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlog;
+    #[cfg(feature = "arrow")]
+    use arrow::array::AsArray;
+    #[cfg(feature = "arrow")]
+    use arrow::datatypes::{Float64Type, UInt64Type};
+    #[cfg(feature = "arrow")]
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_out_format() {
+        #[cfg(feature = "arrow")]
+        assert_eq!(out_format(Path::new("summary.parquet")).unwrap(), OutFormat::Parquet);
+        #[cfg(feature = "csv")]
+        assert_eq!(out_format(Path::new("summary.csv")).unwrap(), OutFormat::Rows(Format::Csv));
+        assert_eq!(out_format(Path::new("summary.json")).unwrap(), OutFormat::Rows(Format::Json));
+        assert!(out_format(Path::new("summary")).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "arrow")]
+    fn test_write_parquet() {
+        let dir = std::env::temp_dir().join(format!("luza-parquet-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let row = |strategy: &str, wins, accuracy| LogSummary {
+            file: "night.jsonl".to_string(),
+            timestamp: 1700000000,
+            strategy: strategy.to_string(),
+            games: 4,
+            wins,
+            mean_score: 31.5,
+            moves: 40,
+            centipoints_lost: 250,
+            accuracy,
+        };
+        let rows = vec![row("greedy", 3, 92.5), row("random", 1, 40.0)];
+
+        let out = dir.join("summary.parquet");
+        write_parquet(&rows, &out).unwrap();
+        let batches: Vec<RecordBatch> = ParquetRecordBatchReaderBuilder::try_new(File::open(&out).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0], summary_batch(&rows).unwrap());
+
+        let batch = &batches[0];
+        let strategies: Vec<&str> = batch.column_by_name("strategy").unwrap().as_string::<i32>().iter().flatten().collect();
+        assert_eq!(strategies, ["greedy", "random"]);
+        assert_eq!(batch.column_by_name("wins").unwrap().as_primitive::<UInt64Type>().values(), &[3, 1]);
+        assert_eq!(batch.column_by_name("accuracy").unwrap().as_primitive::<Float64Type>().values(), &[92.5, 40.0]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_skips_analyzed() {
+        let dir = std::env::temp_dir().join(format!("luza-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let records = [
+            LogRecord::GameStart { game_id: 0, seed: 0, players: vec!["greedy".to_string(), "random".to_string()], state: String::new() },
+            LogRecord::GameEnd { game_id: 0, winner: Some(0), final_scores: vec![30, 12], end: playlog::GameEnd::Finished },
+        ];
+        playlog::write(&records, &dir.join("night.jsonl")).unwrap();
+
+        let out = dir.join("summary.json");
+        run(&dir, &out, None).unwrap();
+        let rows: Vec<LogSummary> = serde_json::from_reader(File::open(&out).unwrap()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].strategy.as_str(), rows[0].wins, rows[0].mean_score), ("greedy", 1, 30.0));
+
+        // Unchanged logs come from the manifest without being read again
+        let manifest_file = dir.join(".luza-analyze-all.json");
+        let mut manifest = Manifest::load(&manifest_file).unwrap();
+        manifest.files.get_mut("night.jsonl").unwrap().rows[0].wins = 7;
+        manifest.save(&manifest_file).unwrap();
+        run(&dir, &out, None).unwrap();
+        let rows: Vec<LogSummary> = serde_json::from_reader(File::open(&out).unwrap()).unwrap();
+        assert_eq!(rows[0].wins, 7);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::{azul, Rules};
+use crate::observed;
+use crate::treedump::{self, SearchTree};
+
+// Rollouts are capped by the time budget so the count only has to be large
+const MAX_ROLLOUTS: usize = 1_000_000;
+// Plies shown in principal variations
+pub const PV_DEPTH: usize = 4;
+// Searches a long running engine keeps, the oldest are dropped beyond this
+const MAX_CACHED_SEARCHES: usize = 10_000;
+
+#[derive(serde::Serialize)]
+pub struct Evaluation {
+    action: azul::Action,
+    notation: String,
+    n_games: usize,
+    mean_score: f32,
+    win_rate: f32,
+    // Rollouts stopped as draws on a repeated position
+    n_repeats: usize,
+    // Likely continuation starting with this action, as `P<i> <notation>`
+    pv: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct BestMove {
+    action: azul::Action,
+    notation: String,
+    pv: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top: Option<Vec<Evaluation>>,
+    // Answered from an earlier search of the position
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cached: bool,
+}
+
+// Current favorite of a running search, printed as an `info` line
+#[derive(serde::Serialize)]
+pub struct SearchInfo {
+    elapsed_ms: u64,
+    rollouts: usize,
+    action: azul::Action,
+    notation: String,
+    n_games: usize,
+    mean_score: f32,
+    win_rate: f32,
+}
+
+impl SearchInfo {
+    pub fn new(elapsed: Duration, rollouts: usize, best: &azul::MctsStats) -> Self {
+        Self {
+            elapsed_ms: elapsed.as_millis() as u64,
+            rollouts,
+            action: best.action,
+            notation: azul::action_notation(best.action),
+            n_games: best.n_games,
+            mean_score: best.mean_score,
+            win_rate: best.win_rate,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct InfoLine {
+    info: SearchInfo,
+}
+
+pub fn format_variation(variation: &[(usize, azul::Action)]) -> Vec<String> {
+    variation.iter().map(|(player_idx, action)| format!("P{} {}", player_idx, azul::action_notation(*action))).collect()
+}
+
+// Evaluations of the best `k` actions from MCTS statistics
+pub fn top_evaluations(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, stats: &[azul::MctsStats], k: usize) -> Vec<Evaluation> {
+    stats.iter().take(k).map(|s| Evaluation {
+        action: s.action,
+        notation: azul::action_notation(s.action),
+        n_games: s.n_games,
+        mean_score: s.mean_score,
+        win_rate: s.win_rate,
+        n_repeats: s.n_repeats,
+        pv: format_variation(&azul::principal_variation(rules, state, player_idx, s.action, PV_DEPTH)),
+    }).collect()
+}
+
+// Parse durations like `2s`, `1.5s`, or `500ms`
+pub fn parse_budget(budget: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Invalid budget '{}', use something like 2s or 500ms", budget);
+
+    if let Some(ms) = budget.strip_suffix("ms") {
+        Ok(Duration::from_millis(ms.parse().map_err(|_| invalid())?))
+    } else if let Some(secs) = budget.strip_suffix('s') {
+        // Negative, infinite, and NaN seconds are no duration
+        Duration::try_from_secs_f64(secs.parse().map_err(|_| invalid())?).map_err(|_| invalid())
+    } else {
+        Err(invalid())
+    }
+}
+
+pub fn read_state(state_file: &PathBuf) -> Result<azul::State> {
+    let mut text = String::new();
+    if state_file.as_os_str() == "-" {
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        File::open(state_file)?.read_to_string(&mut text)?;
+    }
+
+    Ok(serde_json::from_str(&text)?)
+}
+
+// State and player to move from a state file, or from an observed position
+// which can name the player itself
+pub fn read_position(state_file: &PathBuf, player_idx: usize, observed: bool) -> Result<(azul::State, usize)> {
+    if observed {
+        observed::load(state_file, player_idx)
+    } else {
+        Ok((read_state(state_file)?, player_idx))
+    }
+}
+
+fn check_position(rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> Result<()> {
+    if player_idx >= state.players.len() {
+        return Err(anyhow!("Player {} is not in a {} player game", player_idx, state.players.len()));
+    }
+    if rules.list_valid_actions(state, player_idx).is_empty() {
+        return Err(anyhow!("No valid actions for player {} in this state", player_idx));
+    }
+    Ok(())
+}
+
+// Search settings with rollouts limited by the budget only
+fn search_config(config: &azul::MctsConfig, budget: Duration) -> azul::MctsConfig {
+    azul::MctsConfig { n_games: MAX_ROLLOUTS, time_budget: Some(budget), ..*config }
+}
+
+fn best_move(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, stats: &[azul::MctsStats], top_k: Option<usize>) -> BestMove {
+    let best_action = stats[0].action;
+    BestMove {
+        action: best_action,
+        notation: azul::action_notation(best_action),
+        pv: format_variation(&azul::principal_variation(rules, state, player_idx, best_action, PV_DEPTH)),
+        top: top_k.map(|k| top_evaluations(rules, state, player_idx, stats, k)),
+        cached: false,
+    }
+}
+
+// Search the given state for the player and print the chosen action as JSON,
+// along with the best `top_k` evaluations if asked. With an `info` interval,
+// the current favorite is printed every interval as `{"info": ...}` lines
+// before that. The top of the search tree can also be dumped to a file.
+pub fn run(state: &azul::State, player_idx: usize, budget: Duration, top_k: Option<usize>, widening: usize, info: Option<Duration>, dump_tree: Option<&Path>) -> Result<()> {
+    if let Some(file) = dump_tree {
+        treedump::tree_format(file)?;
+    }
+    let rules = azul::AzulRules::current();
+    check_position(&rules, state, player_idx)?;
+
+    let config = search_config(&azul::MctsConfig { widening, ..azul::MctsConfig::default() }, budget);
+    let stats = match info {
+        Some(interval) => {
+            let started = Instant::now();
+            let mut print_info = |rollouts: usize, stats: &[azul::MctsStats]| {
+                if let Some(best) = stats.first() {
+                    let line = InfoLine { info: SearchInfo::new(started.elapsed(), rollouts, best) };
+                    println!("{}", serde_json::to_string(&line).unwrap());
+                }
+            };
+            azul::mcts_evaluate_with_info(&rules, state, player_idx, &config, (interval, &mut print_info))
+        },
+        None => azul::mcts_evaluate(&rules, state, player_idx, &config),
+    };
+
+    println!("{}", serde_json::to_string(&best_move(&rules, state, player_idx, &stats, top_k))?);
+
+    if let Some(file) = dump_tree {
+        SearchTree::new(state, player_idx, stats, treedump::TREE_WIDTH).write(file)?;
+    }
+    Ok(())
+}
+
+// A position to search sent to a long running engine, with the same options
+// as the bestmove command
+#[derive(serde::Deserialize)]
+struct Query {
+    state: azul::State,
+    #[serde(default)]
+    player: usize,
+    #[serde(default = "default_budget")]
+    budget: String,
+    #[serde(default)]
+    top_k: Option<usize>,
+}
+
+fn default_budget() -> String {
+    "1s".to_string()
+}
+
+// Searches of positions asked about before, by exact state and player, with
+// the budget they were searched with. Display order is part of the key since
+// actions refer to displays by position.
+#[derive(Default)]
+pub struct SearchCache {
+    entries: HashMap<u64, (Duration, Vec<azul::MctsStats>)>,
+    order: VecDeque<u64>,
+}
+
+impl SearchCache {
+    fn key(state: &azul::State, player_idx: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(state).unwrap().hash(&mut hasher);
+        player_idx.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Statistics of the position searched with at least `budget`, searching
+    // it now if there are none. Also tells if they were cached.
+    pub fn search(&mut self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, config: &azul::MctsConfig, budget: Duration) -> (Vec<azul::MctsStats>, bool) {
+        let key = Self::key(state, player_idx);
+        if let Some((searched, stats)) = self.entries.get(&key) {
+            if *searched >= budget {
+                return (stats.clone(), true);
+            }
+        }
+
+        let stats = azul::mcts_evaluate(rules, state, player_idx, &search_config(config, budget));
+        if self.entries.insert(key, (budget, stats.clone())).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > MAX_CACHED_SEARCHES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        (stats, false)
+    }
+
+    fn answer(&mut self, rules: &azul::AzulRules, config: &azul::MctsConfig, line: &str) -> Result<BestMove> {
+        let query: Query = serde_json::from_str(line)?;
+        check_position(rules, &query.state, query.player)?;
+        let budget = parse_budget(&query.budget)?;
+
+        let (stats, cached) = self.search(rules, &query.state, query.player, config, budget);
+        Ok(BestMove { cached, ..best_move(rules, &query.state, query.player, &stats, query.top_k) })
+    }
+}
+
+// Answer bestmove queries, one JSON object per line on stdin with the state
+// and optionally the player, budget, and top_k, with one line of the same
+// JSON the bestmove command prints, or `{"error": ...}`. Searches are kept
+// so asking about a position again is answered right away.
+pub fn serve(config: &azul::MctsConfig) -> Result<()> {
+    let rules = azul::AzulRules::current();
+    let mut cache = SearchCache::default();
+    let mut stdout = io::stdout().lock();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match cache.answer(&rules, config, &line) {
+            Ok(best) => serde_json::to_string(&best)?,
+            Err(err) => serde_json::json!({ "error": err.to_string() }).to_string(),
+        };
+        writeln!(stdout, "{}", reply)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_budget() {
+        assert_eq!(parse_budget("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_budget("500ms").unwrap(), Duration::from_millis(500));
+        assert!(parse_budget("2").is_err());
+        assert!(parse_budget("-1s").is_err());
+        assert!(parse_budget("infs").is_err());
+        assert!(parse_budget("NaNs").is_err());
+    }
+
+    #[test]
+    fn test_search_cache() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let config = azul::MctsConfig::default();
+        let mut cache = SearchCache::default();
+        let query = serde_json::json!({ "state": state, "budget": "20ms" }).to_string();
+
+        assert!(!cache.answer(&rules, &config, &query).unwrap().cached);
+        assert!(cache.answer(&rules, &config, &query).unwrap().cached);
+        // A longer budget searches again, and the other player is another
+        // position
+        assert!(!cache.search(&rules, &state, 0, &config, Duration::from_millis(40)).1);
+        assert!(!cache.search(&rules, &state, 1, &config, Duration::from_millis(20)).1);
+        assert!(cache.answer(&rules, &config, r#"{"state": null}"#).is_err());
+        let negative = serde_json::json!({ "state": state, "budget": "-1s" }).to_string();
+        assert!(cache.answer(&rules, &config, &negative).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::accuracy;
+use crate::games::azul;
+use crate::logreader;
+use crate::output::{self, Blunder, Format};
+use crate::par::prelude::*;
+use crate::text;
+
+// Evaluate every logged move (or those of `game_id`) and write the `top` with
+// the biggest loss against the engine's best move, worst first, as puzzles
+// with the position, the move played, and the move to find
+pub fn run(log_file: &PathBuf, top: usize, game_id: Option<usize>, format: Format) -> Result<()> {
+    let play_log = logreader::read(log_file)?;
+    let (moves, n_games) = accuracy::logged_moves(&play_log, game_id)?;
+    let rules = azul::AzulRules::current();
+    output::progress(format, &format!("Analyzing {} moves from {} games", moves.len(), n_games));
+
+    let mut evals = moves
+        .par_iter()
+        .map(|m| Ok((m, m.evaluate(&rules)?)))
+        .collect::<Result<Vec<_>>>()?;
+    evals.sort_by(|(_, a), (_, b)| b.loss().total_cmp(&a.loss()));
+    evals.truncate(top);
+
+    let rows = evals
+        .iter()
+        .map(|(m, eval)| {
+            Ok(Blunder {
+                game_id: m.ply.game_id,
+                ply_id: m.ply.ply_id,
+                player: m.ply.player_id,
+                strategy: m.strategy.to_string(),
+                played: azul::action_notation(eval.action),
+                best: azul::action_notation(eval.best_action),
+                expected_score: eval.expected_score,
+                best_expected_score: eval.best_expected_score,
+                loss: eval.loss(),
+                state: serde_json::to_string(&eval.state)?,
+            })
+        })
+        .collect::<Result<Vec<Blunder>>>()?;
+
+    if format != Format::Text {
+        return output::write_rows(&rows, format);
+    }
+    for (i, (row, (_, eval))) in rows.iter().zip(&evals).enumerate() {
+        println!(
+            "#{} game {}, ply {}, P{} ({}): played {} for {:.2}, best is {} for {:.2}, {:.2} lost",
+            i + 1, row.game_id, row.ply_id, row.player, row.strategy, row.played, row.expected_score, row.best,
+            row.best_expected_score, row.loss,
+        );
+        println!("{}", text::render_text(&eval.state, row.player));
+        println!("State: {}", row.state);
+        println!();
+    }
+    Ok(())
+}
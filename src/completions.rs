@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::Command;
+pub use clap_complete::Shell;
+
+// Completion script for the command line, offering subcommands and then
+// their flags and flag values. It is made in memory first since
+// clap_complete panics on write errors, like a closed pipe.
+pub fn generate(shell: Shell, root: &mut Command, out: &mut impl Write) -> io::Result<()> {
+    let name = root.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, root, name, &mut script);
+    out.write_all(&script)
+}
+
+// Manual page in roff for the command line, listing the subcommands by the
+// pages `man_pages` writes for them
+pub fn man_page(root: Command, out: &mut impl Write) -> io::Result<()> {
+    clap_mangen::Man::new(root).render(out)
+}
+
+// Manual pages for the command line and every subcommand, like
+// `luza-tournament.1`
+pub fn man_pages(root: Command, out_dir: &Path) -> io::Result<()> {
+    clap_mangen::generate_to(root, out_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_generate() {
+        let mut script = Vec::new();
+        generate(Shell::Bash, &mut crate::Args::command(), &mut script).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("luza,tournament)"));
+        assert!(script.contains("round-robin swiss"));
+
+        let mut page = Vec::new();
+        man_page(crate::Args::command(), &mut page).unwrap();
+        assert!(String::from_utf8(page).unwrap().contains("luza\\-tournament(1)"));
+
+        let dir = std::env::temp_dir().join(format!("luza-man-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        man_pages(crate::Args::command(), &dir).unwrap();
+        assert!(crate::Args::command().get_subcommands().all(|sub| dir.join(format!("luza-{}.1", sub.get_name())).exists()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
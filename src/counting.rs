@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::accuracy;
+use crate::games::azul::{self, Tile, COLORS};
+use crate::logreader;
+use crate::output::{self, CountingRound, Format};
+use crate::par::prelude::*;
+use crate::tournament;
+
+// Everything on the table can be seen at a glance, but which of the tiles
+// off the table are in the lid and which still in the bag takes counting.
+// The state a player sees when they remember `memory` (0 to 1) of each
+// color in the lid. They still know how many tiles are in the lid, so the
+// forgotten ones are spread over the colors in proportion to the tiles not
+// seen anywhere, as if bag and lid were mixed.
+pub fn belief(rules: &azul::AzulRules, state: &azul::State, memory: f64) -> azul::State {
+    let bag = rules.bag_tiles(state);
+    let remembered: BTreeMap<Tile, usize> = COLORS
+        .iter()
+        .map(|&color| (color, (state.lid[&color] as f64 * memory).floor() as usize))
+        .collect();
+    let unseen: BTreeMap<Tile, usize> = COLORS
+        .iter()
+        .map(|&color| (color, bag[&color] + state.lid[&color] - remembered[&color]))
+        .collect();
+
+    let n_forgotten = state.lid.values().sum::<usize>() - remembered.values().sum::<usize>();
+    let n_unseen = unseen.values().sum::<usize>();
+    let mut lid = remembered;
+    if n_forgotten > 0 {
+        // Largest remainders get the tiles left over from rounding down
+        let mut shares: Vec<(Tile, usize)> = unseen.iter().map(|(&color, &n)| (color, n_forgotten * n)).collect();
+        for &(color, share) in &shares {
+            *lid.get_mut(&color).unwrap() += share / n_unseen;
+        }
+        shares.sort_by_key(|&(color, share)| (std::cmp::Reverse(share % n_unseen), color));
+        let n_left = n_forgotten - shares.iter().map(|(_, share)| share / n_unseen).sum::<usize>();
+        for &(color, _) in shares.iter().take(n_left) {
+            *lid.get_mut(&color).unwrap() += 1;
+        }
+    }
+
+    // Forgotten tiles could be in the bag as well, so it is counted again
+    azul::State { lid: lid.into_iter().collect(), bag: None, ..state.clone() }
+}
+
+pub fn check_memory(memory: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&memory) {
+        return Err(anyhow!("Memory {} is outside [0, 1]", memory));
+    }
+    Ok(())
+}
+
+// Engine advice with full information against advice from what the player
+// remembers, with the loss of the latter in expected score
+struct Comparison {
+    round: usize,
+    agrees: bool,
+    loss: f32,
+}
+
+// Both searches start from the same seed so they differ only by what they
+// know, and agree whenever the lid is remembered
+fn compare(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, memory: f64, seed: u64) -> Comparison {
+    let full = azul::with_seed(seed, || azul::play_mcts(rules, state, player_idx));
+    let partial = azul::with_seed(seed, || azul::play_mcts(rules, &belief(rules, state, memory), player_idx));
+    let loss = if full == partial {
+        0.0
+    } else {
+        azul::with_seed(seed, || {
+            (azul::mcts_q_fn(rules, state, player_idx, full).0 - azul::mcts_q_fn(rules, state, player_idx, partial).0).max(0.0)
+        })
+    };
+    Comparison { round: state.rounds, agrees: full == partial, loss }
+}
+
+// Search every position of the logged games (or only `game_id`) with full
+// information and with what a player remembering `memory` of the lid knows,
+// and report by round how often the advice agrees and what not counting costs
+pub fn run(log_file: &PathBuf, memory: f64, game_id: Option<usize>, format: Format) -> Result<()> {
+    let rules = azul::AzulRules::current();
+    let play_log = logreader::read(log_file)?;
+    let (moves, n_games) = accuracy::logged_moves(&play_log, game_id)?;
+    output::progress(format, &format!("Comparing advice in {} positions from {} games", moves.len(), n_games));
+
+    let comparisons = moves
+        .par_iter()
+        .map(|m| {
+            let state: azul::State = serde_json::from_str(m.state)?;
+            let seed = tournament::match_seed(0, m.ply.game_id, m.ply.ply_id, m.ply.player_id);
+            Ok(compare(&rules, &state, m.ply.player_id, memory, seed))
+        })
+        .collect::<Result<Vec<Comparison>>>()?;
+
+    // Positions, agreements, and total loss by round
+    let mut rounds: BTreeMap<usize, (usize, usize, f64)> = BTreeMap::new();
+    for c in comparisons {
+        let totals = rounds.entry(c.round).or_default();
+        totals.0 += 1;
+        totals.1 += c.agrees as usize;
+        totals.2 += c.loss as f64;
+    }
+    let rows: Vec<CountingRound> = rounds
+        .into_iter()
+        .map(|(round, (positions, agreements, loss))| CountingRound {
+            round,
+            positions,
+            agreements,
+            agreement_rate: agreements as f64 / positions as f64,
+            mean_loss: loss / positions as f64,
+        })
+        .collect();
+
+    if format != Format::Text {
+        return output::write_rows(&rows, format);
+    }
+    for row in rows {
+        println!(
+            "Round {}: same advice in {} of {} positions ({:.1}%), {:.2} expected points lost per position",
+            row.round, row.agreements, row.positions, 100.0 * row.agreement_rate, row.mean_loss
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    #[test]
+    fn test_belief() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        state.lid = COLORS.iter().map(|&color| (color, 0)).collect();
+        state.lid.insert(Tile::Red, 6);
+        state.lid.insert(Tile::Blue, 3);
+        // The lid is changed by hand so the bag is counted from the table
+        state.bag = None;
+
+        let full = belief(&rules, &state, 1.0);
+        assert_eq!(full.lid, state.lid);
+
+        for memory in [0.0, 0.5] {
+            let seen = belief(&rules, &state, memory);
+            assert_eq!(seen.lid.values().sum::<usize>(), 9);
+            assert_eq!(seen.factory_displays, state.factory_displays);
+            let bag = rules.bag_tiles(&seen);
+            assert_eq!(bag.values().sum::<usize>(), rules.bag_tiles(&state).values().sum::<usize>());
+        }
+        // Forgetting the lid spreads its reds over all colors
+        assert!(belief(&rules, &state, 0.0).lid[&Tile::Red] < 6);
+        assert!(belief(&rules, &state, 0.5).lid[&Tile::Red] >= 3);
+
+        assert!(check_memory(1.5).is_err());
+    }
+}
@@ -0,0 +1,671 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use memmap2::{Mmap, MmapMut};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::games::azul;
+use crate::games::{FeatureDescriptor, RepresentOptions, Representable};
+use crate::logreader;
+use crate::playlog::LogRecord;
+
+// Bump when the layout of binary records changes
+const BINARY_VERSION: usize = 1;
+// Records a binary data file grows by at least when it's full
+const MIN_GROWTH: usize = 4096;
+
+// First line of a dataset, telling what the feature values are
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct Header {
+    schema_version: usize,
+    schema: Vec<FeatureDescriptor>,
+    normalized: bool,
+    // Player boards start from the player to move instead of seat 0
+    mover_first: bool,
+}
+
+// One move of a logged game with the features of the state it was played in
+#[derive(serde::Serialize)]
+struct Sample {
+    game_id: usize,
+    round_id: usize,
+    ply_id: usize,
+    player_id: usize,
+    features: Vec<f64>,
+    action: azul::Action,
+    score: i32,
+    // Display permutation for augmented samples
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permutation: Option<Vec<usize>>,
+}
+
+// Games seen so far, to keep repeated games out of training data. Low
+// temperature self-play on the same deal tends to play the same game again,
+// which would otherwise dominate the data. Games are identified by a hash of
+// their seed and every action played.
+#[derive(Default)]
+pub struct Dedup {
+    seen: HashSet<u64>,
+    pub games: usize,
+    pub duplicates: usize,
+}
+
+impl Dedup {
+    // Whether the game wasn't seen before, counting it either way
+    pub fn is_new(&mut self, seed: u64, actions: &[azul::Action]) -> bool {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        actions.hash(&mut hasher);
+
+        self.games += 1;
+        let new = self.seen.insert(hasher.finish());
+        self.duplicates += (!new) as usize;
+        new
+    }
+
+    pub fn rate(&self) -> f64 {
+        if self.games == 0 { 0.0 } else { self.duplicates as f64 / self.games as f64 }
+    }
+
+    pub fn report(&self) -> String {
+        format!("{} of {} games were duplicates ({:.1}%)", self.duplicates, self.games, self.rate() * 100.0)
+    }
+}
+
+// Index of a binary dataset, written next to the data file. Records are
+// fixed width and little endian: the features as f32, the position of the
+// action in the canonical action space as u32, and the outcome for the mover
+// as f32 (1 won, 0.5 drawn, 0 lost).
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Index {
+    binary_version: usize,
+    #[serde(flatten)]
+    header: Header,
+    n_features: usize,
+    record_size: usize,
+    n_records: usize,
+    // Records of each game, in the order they are stored
+    games: Vec<GameRecords>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GameRecords {
+    game_id: usize,
+    start: usize,
+    len: usize,
+}
+
+// Index of `data.bin` is `data.bin.idx`
+fn index_path(data_file: &Path) -> PathBuf {
+    let mut path = data_file.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+// Result of the game for the player
+fn outcome(winner: Option<usize>, player_id: usize) -> f32 {
+    match winner {
+        Some(winner) if winner == player_id => 1.0,
+        Some(_) => 0.0,
+        None => 0.5,
+    }
+}
+
+// Writes records straight into a memory map of the data file, growing the
+// file when it's full and cutting it to the records written at the end
+struct BinaryWriter {
+    file: File,
+    map: MmapMut,
+    capacity: usize,
+    index: Index,
+    index_file: PathBuf,
+}
+
+impl BinaryWriter {
+    fn create(out_file: &Path, header: Header) -> Result<Self> {
+        let n_features = header.schema.iter().map(|feature| feature.size).sum::<usize>();
+        let record_size = 4 * (n_features + 2);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(out_file)?;
+        file.set_len((MIN_GROWTH * record_size) as u64)?;
+        // The data file is only changed through this map while writing
+        let map = unsafe { MmapMut::map_mut(&file)? };
+
+        Ok(Self {
+            file,
+            map,
+            capacity: MIN_GROWTH,
+            index: Index { binary_version: BINARY_VERSION, header, n_features, record_size, n_records: 0, games: Vec::new() },
+            index_file: index_path(out_file),
+        })
+    }
+
+    fn grow(&mut self) -> Result<()> {
+        self.map.flush()?;
+        self.capacity += self.capacity.max(MIN_GROWTH);
+        self.file.set_len((self.capacity * self.index.record_size) as u64)?;
+        self.map = unsafe { MmapMut::map_mut(&self.file)? };
+        Ok(())
+    }
+
+    fn write(&mut self, game_id: usize, features: &[f64], action: azul::Action, outcome: f32) -> Result<()> {
+        let action_idx = azul::action_index(&action)
+            .ok_or_else(|| anyhow!("{} has no place in the canonical action space", azul::action_notation(action)))?;
+        if self.index.n_records == self.capacity {
+            self.grow()?;
+        }
+
+        let mut record = Vec::with_capacity(self.index.record_size);
+        for &value in features {
+            record.extend((value as f32).to_le_bytes());
+        }
+        record.extend((action_idx as u32).to_le_bytes());
+        record.extend(outcome.to_le_bytes());
+        let offset = self.index.n_records * self.index.record_size;
+        self.map[offset..offset + record.len()].copy_from_slice(&record);
+
+        match self.index.games.last_mut() {
+            Some(game) if game.game_id == game_id => game.len += 1,
+            _ => self.index.games.push(GameRecords { game_id, start: self.index.n_records, len: 1 }),
+        }
+        self.index.n_records += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        self.map.flush()?;
+        drop(self.map);
+        self.file.set_len((self.index.n_records * self.index.record_size) as u64)?;
+        serde_json::to_writer(BufWriter::new(File::create(&self.index_file)?), &self.index)?;
+        Ok(())
+    }
+}
+
+// Where samples go, set up once the first state tells the schema
+enum Sink {
+    Jsonl(BufWriter<File>),
+    Binary(BinaryWriter),
+}
+
+impl Sink {
+    fn create(out_file: &Path, header: Header, binary: bool) -> Result<Self> {
+        if binary {
+            return Ok(Sink::Binary(BinaryWriter::create(out_file, header)?));
+        }
+        let mut writer = BufWriter::new(File::create(out_file)?);
+        writeln!(writer, "{}", serde_json::to_string(&header)?)?;
+        Ok(Sink::Jsonl(writer))
+    }
+
+    fn write(&mut self, sample: &Sample, outcome: f32) -> Result<()> {
+        match self {
+            Sink::Jsonl(writer) => writeln!(writer, "{}", serde_json::to_string(sample)?)?,
+            Sink::Binary(writer) => writer.write(sample.game_id, &sample.features, sample.action, outcome)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Sink::Jsonl(mut writer) => writer.flush()?,
+            Sink::Binary(writer) => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+// Part of the games to write to their own file, like `train=0.8`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Split {
+    pub name: String,
+    pub fraction: f64,
+}
+
+// Splits like `train=0.8,val=0.1,test=0.1`, the fractions adding up to 1
+pub fn parse_splits(spec: &str) -> Result<Vec<Split>> {
+    let mut splits: Vec<Split> = Vec::new();
+    for part in spec.split(',') {
+        let (name, fraction) = part.split_once('=').ok_or_else(|| anyhow!("Split '{}' is not like name=fraction", part))?;
+        let fraction: f64 = fraction.parse().map_err(|_| anyhow!("Split {} has fraction '{}', which is not a number", name, fraction))?;
+        if name.is_empty() || name.contains(std::path::is_separator) {
+            return Err(anyhow!("Split name '{}' can't be part of a file name", name));
+        }
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(anyhow!("Split {} has fraction {} outside [0, 1]", name, fraction));
+        }
+        if splits.iter().any(|split| split.name == name) {
+            return Err(anyhow!("Split {} is given twice", name));
+        }
+        splits.push(Split { name: name.to_string(), fraction });
+    }
+
+    let total = splits.iter().map(|split| split.fraction).sum::<f64>();
+    if (total - 1.0).abs() > 1e-6 {
+        return Err(anyhow!("Split fractions add up to {}, not 1", total));
+    }
+    Ok(splits)
+}
+
+// What games are grouped by so every split gets its share of each group
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Stratify {
+    /// Rounds the game lasted, so every split gets its share of the late
+    /// rounds only longer games reach
+    GameLength,
+}
+
+// File of a split next to the output, `data.train.jsonl` for `data.jsonl`
+fn split_path(out_file: &Path, name: &str) -> PathBuf {
+    match out_file.extension() {
+        Some(ext) => out_file.with_extension(format!("{}.{}", name, ext.to_string_lossy())),
+        None => out_file.with_extension(name),
+    }
+}
+
+// Place of the game in [0, 1), fixed by its id. This is the splitmix64 mix
+// rather than `DefaultHasher`, whose output can change between Rust releases,
+// so a log splits the same way with every build.
+fn split_point(game_id: usize) -> f64 {
+    let mut z = (game_id as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// Split each game goes to. Games only ever go to one split so moves of a game
+// can't leak between them. Without strata, the split follows from the game's
+// place alone. With strata, the games of each stratum are ordered by their
+// place and cut in the split fractions.
+fn assign_splits(games: &[(usize, usize)], splits: &[Split], stratified: bool) -> BTreeMap<usize, usize> {
+    let bounds: Vec<f64> = splits
+        .iter()
+        .scan(0.0, |total, split| {
+            *total += split.fraction;
+            Some(*total)
+        })
+        .collect();
+    // Rounding can leave the last bound short of 1
+    let split_at = |point: f64| bounds.iter().position(|&bound| point < bound).unwrap_or(splits.len() - 1);
+
+    if !stratified {
+        return games.iter().map(|&(game_id, _)| (game_id, split_at(split_point(game_id)))).collect();
+    }
+
+    let mut strata: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for &(game_id, stratum) in games {
+        strata.entry(stratum).or_default().push(game_id);
+    }
+    let mut assigned = BTreeMap::new();
+    for mut game_ids in strata.into_values() {
+        game_ids.sort_by(|a, b| split_point(*a).total_cmp(&split_point(*b)).then(a.cmp(b)));
+        let n_games = game_ids.len() as f64;
+        for (i, game_id) in game_ids.into_iter().enumerate() {
+            // Middle of the game's slot so the cuts round to the nearest game
+            assigned.insert(game_id, split_at((i as f64 + 0.5) / n_games));
+        }
+    }
+    assigned
+}
+
+// How moves are turned into samples and where they go
+pub struct Options {
+    // Scale all features to [0, 1]
+    pub normalize: bool,
+    // Start the player boards from the player to move
+    pub mover_first: bool,
+    // Extra samples per move with the factory displays shuffled
+    pub augment: usize,
+    pub keep_duplicates: bool,
+    // Fixed width records with an index instead of JSONL
+    pub binary: bool,
+    // Files to split the games into, all in one file if empty
+    pub splits: Vec<Split>,
+    pub stratify_by: Option<Stratify>,
+}
+
+// Write every move in the simulation log as a JSONL sample after a header
+// with the feature schema. All games need to have the same number of players.
+// Features can be normalized to [0, 1] and have the boards start from the
+// player to move. With `augment`, every move is also written that many times
+// with the factory displays shuffled. Repeats of a game are left out unless
+// `keep_duplicates` is set. With `binary`, samples are fixed width records
+// with the outcome instead, and games that didn't finish are left out. With
+// splits, every game goes to the file of one split.
+pub fn run(log_file: &PathBuf, out_file: &Path, options: &Options) -> Result<()> {
+    let play_log = logreader::read(log_file)?;
+
+    // Games are simulated in parallel so their records are interleaved
+    let mut games: BTreeMap<usize, Vec<&LogRecord>> = BTreeMap::new();
+    for record in &play_log {
+        if let Some(game_id) = record.game_id() {
+            games.entry(game_id).or_default().push(record);
+        }
+    }
+
+    let (out_files, assigned) = if options.splits.is_empty() {
+        (vec![out_file.to_path_buf()], BTreeMap::new())
+    } else {
+        let strata: Vec<(usize, usize)> = games
+            .iter()
+            .map(|(&game_id, records)| {
+                let rounds = records.iter().filter_map(|record| match record {
+                    LogRecord::Ply(ply) => Some(ply.round_id + 1),
+                    _ => None,
+                });
+                (game_id, rounds.max().unwrap_or(0))
+            })
+            .collect();
+        let out_files = options.splits.iter().map(|split| split_path(out_file, &split.name)).collect();
+        (out_files, assign_splits(&strata, &options.splits, options.stratify_by.is_some()))
+    };
+
+    let mut sinks: Vec<Sink> = Vec::new();
+    let mut schema: Option<Vec<FeatureDescriptor>> = None;
+    // Samples and games written to each file
+    let mut written = vec![(0, 0); out_files.len()];
+    let mut n_unfinished = 0;
+    // Fixed seed so the same log gives the same dataset
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let mut dedup = Dedup::default();
+
+    for (game_id, records) in &games {
+        let seed = match records.first() {
+            Some(LogRecord::GameStart { seed, .. }) => *seed,
+            _ => 0,
+        };
+        let actions = records
+            .iter()
+            .filter_map(|record| match record {
+                LogRecord::Ply(ply) => Some(serde_json::from_str(&ply.action)),
+                _ => None,
+            })
+            .collect::<Result<Vec<azul::Action>, _>>()?;
+        if !dedup.is_new(seed, &actions) && !options.keep_duplicates {
+            continue;
+        }
+        let winner = records.iter().find_map(|record| match record {
+            LogRecord::GameEnd { winner, .. } => Some(*winner),
+            _ => None,
+        });
+        if options.binary && winner.is_none() {
+            n_unfinished += 1;
+            continue;
+        }
+        let split = assigned.get(game_id).copied().unwrap_or(0);
+        written[split].1 += 1;
+
+        // Logged states are after the move, so the one before a move is in
+        // the previous record
+        for (before, record) in records.iter().zip(records.iter().skip(1)) {
+            let (LogRecord::Ply(record), Some(before)) = (record, before.state()) else {
+                continue;
+            };
+
+            let state: azul::State = serde_json::from_str(before)?;
+            match &schema {
+                None => {
+                    let header = Header {
+                        schema_version: azul::SCHEMA_VERSION,
+                        schema: state.schema(),
+                        normalized: options.normalize,
+                        mover_first: options.mover_first,
+                    };
+                    schema = Some(header.schema.clone());
+                    sinks = out_files.iter().map(|file| Sink::create(file, header.clone(), options.binary)).collect::<Result<_>>()?;
+                },
+                Some(schema) if *schema != state.schema() => {
+                    return Err(anyhow!("Game {} has a different number of players than the ones before", record.game_id));
+                },
+                Some(_) => {},
+            }
+            let sink = &mut sinks[split];
+            let outcome = outcome(winner.flatten(), record.player_id);
+
+            let represent = RepresentOptions {
+                normalize: options.normalize,
+                perspective: if options.mover_first { record.player_id } else { 0 },
+            };
+            let action: azul::Action = serde_json::from_str(&record.action)?;
+            let mut sample = Sample {
+                game_id: record.game_id,
+                round_id: record.round_id,
+                ply_id: record.ply_id,
+                player_id: record.player_id,
+                features: state.represent_with(&represent),
+                action,
+                score: record.score,
+                permutation: None,
+            };
+            sink.write(&sample, outcome)?;
+            written[split].0 += 1;
+
+            for _ in 0..options.augment {
+                let mut permutation: Vec<usize> = (0..state.factory_displays.len()).collect();
+                permutation.shuffle(&mut rng);
+
+                let (permuted, permuted_action) = azul::permute_displays(&state, action, &permutation);
+                sample.features = permuted.represent_with(&represent);
+                sample.action = permuted_action;
+                sample.permutation = Some(permutation);
+                sink.write(&sample, outcome)?;
+                written[split].0 += 1;
+            }
+        }
+    }
+
+    if sinks.is_empty() {
+        // Nothing to tell the schema, but the files are still made
+        for file in &out_files {
+            File::create(file)?;
+        }
+    }
+    for sink in sinks {
+        sink.finish()?;
+    }
+
+    let n_samples = written.iter().map(|(samples, _)| samples).sum::<usize>();
+    let n_games = written.iter().map(|(_, games)| games).sum::<usize>();
+    println!("Wrote {} samples from {} games", n_samples, n_games);
+    if !options.splits.is_empty() {
+        for ((split, file), (samples, games)) in options.splits.iter().zip(&out_files).zip(&written) {
+            println!("  {}: {} samples from {} games to {}", split.name, samples, games, file.display());
+        }
+    }
+    println!("{}, {}", dedup.report(), if options.keep_duplicates { "kept with --keep-duplicates" } else { "left out" });
+    if n_unfinished > 0 {
+        println!("{} games without an end were left out", n_unfinished);
+    }
+    Ok(())
+}
+
+// Binary dataset with its index, after checking they match. The data file is
+// memory mapped so datasets of any size open at once.
+struct BinaryDataset {
+    index: Index,
+    map: Option<Mmap>,
+}
+
+impl BinaryDataset {
+    fn open(data_file: &Path) -> Result<Self> {
+        let index_file = index_path(data_file);
+        let reader = BufReader::new(File::open(&index_file).map_err(|err| anyhow!("Can't open index {}: {}", index_file.display(), err))?);
+        let index: Index = serde_json::from_reader(reader)?;
+        if index.binary_version != BINARY_VERSION {
+            return Err(anyhow!("Dataset has binary version {} but this luza reads {}", index.binary_version, BINARY_VERSION));
+        }
+
+        let file = File::open(data_file)?;
+        let size = file.metadata()?.len() as usize;
+        if size != index.n_records * index.record_size {
+            return Err(anyhow!("{} has {} bytes but its index lists {} records of {} bytes", data_file.display(), size, index.n_records, index.record_size));
+        }
+        // Empty files can't be mapped
+        let map = if size == 0 { None } else { Some(unsafe { Mmap::map(&file)? }) };
+        Ok(Self { index, map })
+    }
+
+    // Features, action index, and outcome of a record
+    fn record(&self, i: usize) -> (Vec<f32>, usize, f32) {
+        let offset = i * self.index.record_size;
+        let bytes = &self.map.as_ref().unwrap()[offset..offset + self.index.record_size];
+        let values: Vec<[u8; 4]> = bytes.chunks_exact(4).map(|chunk| chunk.try_into().unwrap()).collect();
+        let n_features = self.index.n_features;
+
+        let features = values[..n_features].iter().map(|&v| f32::from_le_bytes(v)).collect();
+        (features, u32::from_le_bytes(values[n_features]) as usize, f32::from_le_bytes(values[n_features + 1]))
+    }
+}
+
+// Summary of a binary dataset, and the record at `record` as JSON if asked
+pub fn inspect(data_file: &Path, record: Option<usize>) -> Result<()> {
+    let dataset = BinaryDataset::open(data_file)?;
+    let index = &dataset.index;
+
+    let mut outcomes = [0; 3];
+    let mut actions = HashSet::new();
+    for i in 0..index.n_records {
+        let (_, action, outcome) = dataset.record(i);
+        if action >= azul::N_ACTIONS {
+            return Err(anyhow!("Record {} has action {} outside the canonical action space", i, action));
+        }
+        actions.insert(action);
+        let slot = match outcome {
+            0.0 => 0,
+            0.5 => 1,
+            1.0 => 2,
+            _ => return Err(anyhow!("Record {} has outcome {}, expected 0, 0.5, or 1", i, outcome)),
+        };
+        outcomes[slot] += 1;
+    }
+
+    println!("{} records from {} games, {} bytes each", index.n_records, index.games.len(), index.record_size);
+    println!(
+        "{} features of schema version {} in {} slices{}{}",
+        index.n_features,
+        index.header.schema_version,
+        index.header.schema.len(),
+        if index.header.normalized { ", normalized" } else { "" },
+        if index.header.mover_first { ", mover first" } else { "" }
+    );
+    println!("Outcomes for the mover: {} won, {} drawn, {} lost", outcomes[2], outcomes[1], outcomes[0]);
+    println!("{} of {} actions played", actions.len(), azul::N_ACTIONS);
+
+    if let Some(i) = record {
+        if i >= index.n_records {
+            return Err(anyhow!("Dataset has {} records, there's no record {}", index.n_records, i));
+        }
+        let game_id = index.games.iter().find(|game| (game.start..game.start + game.len).contains(&i)).map(|game| game.game_id);
+        let (features, action, outcome) = dataset.record(i);
+        let record = serde_json::json!({ "record": i, "game_id": game_id, "features": features, "action": action, "outcome": outcome });
+        println!("{}", record);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    #[test]
+    fn test_dedup() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        let actions = rules.list_valid_actions(&state, 0);
+
+        let mut dedup = Dedup::default();
+        assert!(dedup.is_new(7, &actions[..2]));
+        assert!(!dedup.is_new(7, &actions[..2]));
+        assert!(dedup.is_new(8, &actions[..2]));
+        assert!(dedup.is_new(7, &actions[1..3]));
+        assert_eq!((dedup.games, dedup.duplicates), (4, 1));
+        assert_eq!(dedup.rate(), 0.25);
+    }
+
+    #[test]
+    fn test_parse_splits() {
+        let splits = parse_splits("train=0.8,val=0.1,test=0.1").unwrap();
+        assert_eq!(splits.iter().map(|split| split.name.as_str()).collect::<Vec<_>>(), ["train", "val", "test"]);
+        assert_eq!(splits[0].fraction, 0.8);
+
+        assert!(parse_splits("train=0.8,val=0.1").is_err());
+        assert!(parse_splits("train=0.5,train=0.5").is_err());
+        assert!(parse_splits("train").is_err());
+        assert!(parse_splits("train=1.5,val=-0.5").is_err());
+        assert_eq!(split_path(Path::new("out/data.jsonl"), "val"), Path::new("out/data.val.jsonl"));
+    }
+
+    #[test]
+    fn test_assign_splits() {
+        let splits = parse_splits("train=0.8,val=0.1,test=0.1").unwrap();
+        // Half the games last 5 rounds and half 6
+        let games: Vec<(usize, usize)> = (0..1000).map(|game_id| (game_id, 5 + game_id % 2)).collect();
+        // The first splitmix64 output, so splits don't change with the build
+        assert_eq!(split_point(0), (0xe220a8397b1dcdafu64 >> 11) as f64 / (1u64 << 53) as f64);
+
+        for stratified in [false, true] {
+            let assigned = assign_splits(&games, &splits, stratified);
+            assert_eq!(assigned, assign_splits(&games, &splits, stratified));
+            let share = |split: usize| assigned.values().filter(|&&s| s == split).count();
+            assert!((750..=850).contains(&share(0)));
+            assert!((60..=140).contains(&share(1)));
+        }
+
+        // Stratified splits get their exact share of every stratum
+        let assigned = assign_splits(&games, &splits, true);
+        for stratum in [5, 6] {
+            let n_val = games.iter().filter(|&&(game_id, s)| s == stratum && assigned[&game_id] == 1).count();
+            assert_eq!(n_val, 50);
+        }
+    }
+
+    #[test]
+    fn test_binary_records() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        let actions = rules.list_valid_actions(&state, 0);
+        let header_of = |state: &azul::State| Header { schema_version: azul::SCHEMA_VERSION, schema: state.schema(), normalized: false, mover_first: false };
+        let features = state.represent_with(&RepresentOptions { normalize: false, perspective: 0 });
+
+        let data_file = std::env::temp_dir().join(format!("luza-dataset-{}.bin", std::process::id()));
+        let mut writer = BinaryWriter::create(&data_file, header_of(&state)).unwrap();
+        // Enough records to grow the file once
+        for i in 0..MIN_GROWTH + 10 {
+            writer.write(i / 100, &features, actions[i % actions.len()], outcome(Some(0), i % 2)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let dataset = BinaryDataset::open(&data_file).unwrap();
+        assert_eq!(dataset.index.n_records, MIN_GROWTH + 10);
+        assert_eq!(dataset.index.games.len(), (MIN_GROWTH + 10).div_ceil(100));
+        assert_eq!((dataset.index.games[1].start, dataset.index.games[1].len), (100, 100));
+
+        let (read_features, action, outcome) = dataset.record(MIN_GROWTH + 1);
+        assert_eq!(read_features, features.iter().map(|&v| v as f32).collect::<Vec<f32>>());
+        assert_eq!(Some(action), azul::action_index(&actions[(MIN_GROWTH + 1) % actions.len()]));
+        assert_eq!(outcome, 0.0);
+        assert_eq!(dataset.record(0).2, 1.0);
+        assert!(inspect(&data_file, None).is_ok());
+
+        // Outcomes other than a win, draw, or loss are named by record
+        let mut writer = BinaryWriter::create(&data_file, header_of(&state)).unwrap();
+        writer.write(0, &features, actions[0], 1.0).unwrap();
+        writer.write(0, &features, actions[0], f32::NAN).unwrap();
+        writer.finish().unwrap();
+        let err = inspect(&data_file, None).unwrap_err().to_string();
+        assert!(err.starts_with("Record 1 has outcome NaN"), "{}", err);
+
+        std::fs::remove_file(index_path(&data_file)).unwrap();
+        std::fs::remove_file(&data_file).unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use rusqlite::{params, Connection};
+
+use crate::games::azul::{self, ActionDisplay};
+use crate::playlog::LogRecord;
+use crate::GameResult;
+
+// Schema for the normalized results database. Actions are split in columns
+// instead of JSON strings so that cross-game queries are plain SQL. Every
+// simulation writing to the database adds a run, so game ids only need to be
+// unique within their run.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    run_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- NULL for logs without a run header. Seeds are stored with the same
+    -- bits, so larger ones read back negative.
+    seed INTEGER,
+    players TEXT,
+    version TEXT,
+    timestamp INTEGER
+);
+CREATE TABLE IF NOT EXISTS games (
+    run_id INTEGER NOT NULL REFERENCES runs(run_id),
+    game_id INTEGER NOT NULL,
+    n_players INTEGER NOT NULL,
+    rounds INTEGER NOT NULL,
+    -- NULL for a draw
+    winner INTEGER,
+    PRIMARY KEY (run_id, game_id)
+);
+CREATE TABLE IF NOT EXISTS players (
+    run_id INTEGER NOT NULL,
+    game_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    final_score INTEGER NOT NULL,
+    is_winner INTEGER NOT NULL,
+    PRIMARY KEY (run_id, game_id, player_id),
+    FOREIGN KEY (run_id, game_id) REFERENCES games(run_id, game_id)
+);
+CREATE TABLE IF NOT EXISTS plies (
+    run_id INTEGER NOT NULL,
+    game_id INTEGER NOT NULL,
+    round_id INTEGER NOT NULL,
+    ply_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    display TEXT NOT NULL,
+    color TEXT NOT NULL,
+    pattern_line INTEGER,
+    score INTEGER NOT NULL,
+    applicable_partials TEXT NOT NULL,
+    matching_partials TEXT NOT NULL,
+    PRIMARY KEY (run_id, game_id, ply_id),
+    FOREIGN KEY (run_id, game_id) REFERENCES games(run_id, game_id)
+);
+";
+
+fn display_name(display: ActionDisplay) -> String {
+    match display {
+        ActionDisplay::FactoryDisplay(i) => format!("D{}", i),
+        ActionDisplay::Center => "Center".to_string(),
+    }
+}
+
+// Add the simulation as a new run of the database, all of it or nothing.
+// Only the ply records of the play log go in the plies table. Returns the id
+// of the run.
+pub fn insert_run(conn: &mut Connection, play_log: &[LogRecord], games: &[GameResult]) -> Result<i64> {
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+
+    let header = play_log.iter().find_map(|record| match record {
+        LogRecord::Run(header) => Some(header),
+        _ => None,
+    });
+    tx.execute(
+        "INSERT INTO runs (seed, players, version, timestamp) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            header.map(|h| h.seed as i64),
+            header.map(|h| h.players.join(",")),
+            header.map(|h| h.version.clone()),
+            header.map(|h| h.timestamp as i64),
+        ],
+    )?;
+    let run_id = tx.last_insert_rowid();
+
+    {
+        let mut insert_game = tx.prepare("INSERT INTO games VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut insert_player = tx.prepare("INSERT INTO players VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for game in games {
+            insert_game.execute(params![run_id, game.game_id, game.scores.len(), game.rounds, game.winner])?;
+            for (player_id, score) in game.scores.iter().enumerate() {
+                insert_player.execute(params![run_id, game.game_id, player_id, score, game.winner == Some(player_id)])?;
+            }
+        }
+
+        let mut insert_ply = tx.prepare("INSERT INTO plies VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")?;
+        for record in play_log {
+            let LogRecord::Ply(ply) = record else {
+                continue;
+            };
+            let action: azul::Action = serde_json::from_str(&ply.action)?;
+            insert_ply.execute(params![
+                run_id,
+                ply.game_id,
+                ply.round_id,
+                ply.ply_id,
+                ply.player_id,
+                display_name(action.action_display_choice),
+                format!("{:?}", action.color_choice),
+                action.pattern_line_choice,
+                ply.score,
+                ply.applicable_partials.join(","),
+                ply.matching_partials.join(","),
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(run_id)
+}
+
+// Add simulation results to the SQLite database at given path, creating it
+// if needed
+pub fn write_play_db(play_log: &[LogRecord], games: &[GameResult], file: &PathBuf) -> Result<()> {
+    let mut conn = Connection::open(file)?;
+    insert_run(&mut conn, play_log, games)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlog::Ply;
+
+    #[test]
+    fn test_insert_runs() {
+        let action = azul::Action { action_display_choice: ActionDisplay::Center, color_choice: azul::Tile::Red, pattern_line_choice: None };
+        let ply = Ply {
+            game_id: 0,
+            round_id: 0,
+            ply_id: 0,
+            player_id: 1,
+            action: serde_json::to_string(&action).unwrap(),
+            state: String::new(),
+            score: -1,
+            applicable_partials: Vec::new(),
+            matching_partials: Vec::new(),
+            analysis: String::new(),
+            events: Vec::new(),
+            incident: None,
+        };
+        let games = vec![GameResult { game_id: 0, winner: None, scores: vec![12, 12], rounds: 5 }];
+
+        // The same game ids from a second simulation go in their own run
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(insert_run(&mut conn, &[LogRecord::Ply(ply.clone())], &games).unwrap(), 1);
+        assert_eq!(insert_run(&mut conn, &[LogRecord::Ply(ply)], &games).unwrap(), 2);
+
+        let count = |table: &str| conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get::<_, i64>(0)).unwrap();
+        assert_eq!((count("runs"), count("games"), count("players"), count("plies")), (2, 2, 4, 2));
+        let (display, line): (String, Option<i64>) = conn.query_row("SELECT display, pattern_line FROM plies WHERE run_id = 2", [], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((display.as_str(), line), ("Center", None));
+    }
+}
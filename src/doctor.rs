@@ -0,0 +1,193 @@
+#[cfg(feature = "tui")]
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "tui")]
+use crossterm::terminal;
+
+use crate::autosave;
+use crate::games::azul::{self, COLORS};
+use crate::games::{Rules, Validate};
+use crate::par;
+use crate::profile;
+
+const SEED: u64 = 1;
+const ROLLOUTS: usize = 20;
+
+// A check gives a short description of what it found or why it failed
+type Check = fn() -> Result<String>;
+
+// Rule invariants that should hold in any state of a game
+pub fn check_invariants(state: &azul::State) -> Result<()> {
+    let config = azul::config();
+
+    for color in COLORS {
+        let mut seen = state.lid[&color] + state.center.tiles[&color];
+        seen += state.factory_displays.iter().map(|d| d[&color]).sum::<usize>();
+        for player in &state.players {
+            seen += player.pattern_lines.iter().filter(|(c, _)| *c == Some(color)).map(|(_, n)| n).sum::<usize>();
+            for (row, colors) in azul::WALL_COLORS.iter().enumerate() {
+                seen += colors.iter().zip(player.wall[row]).filter(|&(&c, filled)| c == color && filled).count();
+            }
+        }
+
+        let total = config.tiles_per_color.get(&color).copied().unwrap_or(0);
+        if seen > total {
+            return Err(anyhow!("{} {:?} tiles are out of the bag but the game only has {}", seen, color, total));
+        }
+    }
+
+    for (i, player) in state.players.iter().enumerate() {
+        for (row, &(color, count)) in player.pattern_lines.iter().enumerate() {
+            if count > row + 1 {
+                return Err(anyhow!("P{} has {} tiles on pattern line {}", i, count, row));
+            }
+            let col = color.and_then(|c| azul::WALL_COLORS[row].iter().position(|&w| w == c));
+            if count > 0 && col.is_some_and(|col| player.wall[row][col]) {
+                return Err(anyhow!("P{} is collecting {:?} on line {} with that color already on the wall", i, color.unwrap(), row));
+            }
+        }
+        if player.score < 0 {
+            return Err(anyhow!("P{} has a negative score", i));
+        }
+    }
+
+    if state.players.iter().filter(|p| p.starting_marker).count() > 1 {
+        return Err(anyhow!("More than one player has the starting marker"));
+    }
+
+    Ok(())
+}
+
+// Play a seeded greedy against random game, checking invariants before every
+// move, and return the final scores
+fn seeded_game(rules: &azul::AzulRules) -> Result<Vec<i32>> {
+    let violation: Mutex<Option<String>> = Mutex::new(None);
+
+    let checked = |play: fn(&azul::AzulRules, &azul::State, usize) -> azul::Action| {
+        let violation = &violation;
+        move |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, _| {
+            if let Err(err) = check_invariants(state) {
+                violation.lock().unwrap().get_or_insert(err.to_string());
+            }
+            play(rules, state, player_idx)
+        }
+    };
+    let greedy = checked(azul::play_greedy);
+    let random = checked(azul::play_random);
+
+    let (state, _) = azul::with_seed(SEED, || azul::play_game(rules, &[&greedy, &random], None));
+    check_invariants(&state)?;
+
+    match violation.into_inner().unwrap() {
+        Some(err) => Err(anyhow!(err)),
+        None => Ok(state.players.iter().map(|p| p.score).collect()),
+    }
+}
+
+fn check_game() -> Result<String> {
+    let rules = azul::AzulRules::current();
+    let scores = seeded_game(&rules)?;
+    if seeded_game(&rules)? != scores {
+        return Err(anyhow!("Replaying seed {} gave different scores", SEED));
+    }
+    Ok(format!("seeded game ended {:?} with all invariants holding", scores))
+}
+
+fn check_speed() -> Result<String> {
+    let rules = azul::AzulRules::current();
+    let mut state = rules.new_state(2);
+    rules.refill(&mut state, &mut azul::game_rng());
+    let config = azul::MctsConfig { n_games: ROLLOUTS, ..azul::MctsConfig::default() };
+
+    let start = Instant::now();
+    let stats = azul::mcts_evaluate(&rules, &state, 0, &config);
+    let n_rollouts: usize = stats.iter().map(|s| s.n_games).sum();
+
+    Ok(format!("{:.0} rollouts/s on {} threads", n_rollouts as f64 / start.elapsed().as_secs_f64(), par::current_num_threads()))
+}
+
+fn check_rules() -> Result<String> {
+    let config = azul::config();
+    config.validate()?;
+
+    if *config == azul::AzulConfig::default() {
+        Ok("standard rules".to_string())
+    } else {
+        Ok("house rules from --rules are valid".to_string())
+    }
+}
+
+fn check_data() -> Result<String> {
+    let dir = profile::data_dir()?;
+    let autosave = match autosave::load()? {
+        Some(_) => "an autosaved game",
+        None => "no autosave",
+    };
+    Ok(format!("{} with {}", dir.display(), autosave))
+}
+
+#[cfg(not(feature = "tui"))]
+fn check_terminal() -> Result<String> {
+    Ok("not needed, built without the TUI".to_string())
+}
+
+#[cfg(feature = "tui")]
+fn check_terminal() -> Result<String> {
+    if !io::stdout().is_terminal() {
+        return Err(anyhow!("stdout is not a terminal, the TUI needs one (--no-tui works without)"));
+    }
+
+    let (width, height) = terminal::size()?;
+    terminal::enable_raw_mode()?;
+    terminal::disable_raw_mode()?;
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    let truecolor = std::env::var("COLORTERM").is_ok_and(|c| c == "truecolor" || c == "24bit");
+    Ok(format!("{}x{}, TERM={}{}", width, height, term, if truecolor { ", truecolor" } else { "" }))
+}
+
+// Run every check and print its result. Failing checks don't stop the rest.
+pub fn run() -> Result<()> {
+    let checks: [(&str, Check); 5] = [
+        ("azul", check_game),
+        ("speed", check_speed),
+        ("rules", check_rules),
+        ("data", check_data),
+        ("terminal", check_terminal),
+    ];
+
+    let mut n_failed = 0;
+    for (name, check) in checks {
+        match check() {
+            Ok(detail) => println!("[ok] {}: {}", name, detail),
+            Err(err) => {
+                println!("[fail] {}: {}", name, err);
+                n_failed += 1;
+            },
+        }
+    }
+
+    if n_failed > 0 {
+        return Err(anyhow!("{} of {} checks failed", n_failed, checks.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_invariants() {
+        let rules = azul::AzulRules::current();
+        assert!(seeded_game(&rules).is_ok());
+
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        state.players[0].pattern_lines[1] = (Some(azul::Tile::Red), 3);
+        assert!(check_invariants(&state).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::{azul, Rules};
+use crate::strategy::Strategy;
+
+// Time an external engine gets to answer a genmove
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often a genmove checks whether it was stopped while waiting
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+// An external program playing over a line based text protocol on its stdin
+// and stdout. For every move the engine gets two lines:
+//
+//   state <state as json>
+//   genmove <player index>
+//
+// When the game is played with clocks, a `time <remaining milliseconds>` line
+// comes before the genmove.
+//
+// and has to answer with one line `play <action notation>`, like
+// `play D2 Blue 3` (see `azul::action_notation`). Lines from the engine that
+// don't start with `play` are ignored so engines can print diagnostics.
+// `luza engine` speaks the other side, see `serve`.
+pub struct Engine {
+    command: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+}
+
+impl Engine {
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| anyhow!("Empty engine command"))?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Unable to start engine '{}': {}", command, e))?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Reading happens on a separate thread so waiting for a reply can
+        // time out
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(|l| l.ok()) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { command: command.to_string(), child, stdin, lines, timeout })
+    }
+
+    // Ask for a move, giving up once the engine's timeout passes or `stop`
+    // is set. Either way the engine is still thinking and shouldn't be asked
+    // again.
+    pub fn genmove(&mut self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>, stop: &AtomicBool) -> Result<azul::Action> {
+        // Drop late replies to earlier requests that timed out
+        while self.lines.try_recv().is_ok() {}
+
+        writeln!(self.stdin, "state {}", serde_json::to_string(state)?)?;
+        if let Some(remaining) = remaining {
+            writeln!(self.stdin, "time {}", remaining.as_millis())?;
+        }
+        writeln!(self.stdin, "genmove {}", player_idx)?;
+        self.stdin.flush()?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                return Err(anyhow!("Engine '{}' was stopped", self.command));
+            }
+            let wait = deadline.saturating_duration_since(Instant::now()).min(STOP_POLL);
+            let line = match self.lines.recv_timeout(wait) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) if Instant::now() < deadline => continue,
+                Err(_) => return Err(anyhow!("Engine '{}' didn't reply within {:?}", self.command, self.timeout)),
+            };
+
+            if let Some(notation) = line.strip_prefix("play ") {
+                let action = azul::parse_action_notation(notation)?;
+                if !rules.list_valid_actions(state, player_idx).contains(&action) {
+                    return Err(anyhow!("Engine '{}' played invalid action '{}'", self.command, notation));
+                }
+                log::debug!("Engine '{}' played {}", self.command, azul::action_notation(action));
+                return Ok(action);
+            }
+        }
+    }
+}
+
+// Engine processes for one command, with a process of its own for every move
+// asked at the same time so games played in parallel don't queue up on one
+// engine. Processes are started as needed and kept for later moves, except
+// ones that failed to answer or were stopped, which are killed.
+pub struct EnginePool {
+    command: String,
+    timeout: Duration,
+    idle: Mutex<Vec<Engine>>,
+}
+
+impl EnginePool {
+    // Starts the first engine right away so a bad command fails early
+    pub fn spawn(command: &str, timeout: Duration) -> Result<Self> {
+        let engine = Engine::spawn(command, timeout)?;
+        Ok(Self { command: command.to_string(), timeout, idle: Mutex::new(vec![engine]) })
+    }
+
+    pub fn genmove(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>, stop: &AtomicBool) -> Result<azul::Action> {
+        let idle = self.idle.lock().unwrap().pop();
+        let mut engine = match idle {
+            Some(engine) => engine,
+            None => Engine::spawn(&self.command, self.timeout)?,
+        };
+
+        let action = engine.genmove(rules, state, player_idx, remaining, stop)?;
+        self.idle.lock().unwrap().push(engine);
+        Ok(action)
+    }
+}
+
+// Engine side of the protocol for one session, the state and clock last sent
+#[derive(Default)]
+struct Session {
+    state: Option<azul::State>,
+    remaining: Option<Duration>,
+}
+
+impl Session {
+    // Handle a line from the client, returning the reply if there is one.
+    // A `quit` line ends the session.
+    fn handle(&mut self, rules: &azul::AzulRules, strategy: &Strategy, line: &str) -> Result<Option<String>> {
+        let (command, arg) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match command {
+            "state" => self.state = Some(serde_json::from_str(arg)?),
+            "time" => self.remaining = Some(Duration::from_millis(arg.parse()?)),
+            "genmove" => {
+                let player_idx: usize = arg.parse()?;
+                let state = self.state.as_ref().ok_or_else(|| anyhow!("genmove before any state"))?;
+                if player_idx >= state.players.len() || rules.list_valid_actions(state, player_idx).is_empty() {
+                    return Err(anyhow!("No valid actions for player {} in the state", player_idx));
+                }
+                let action = strategy.play_timed(rules, state, player_idx, self.remaining.take());
+                return Ok(Some(format!("play {}", azul::action_notation(action))));
+            },
+            "" => {},
+            _ => return Err(anyhow!("Unknown command '{}'", command)),
+        }
+        Ok(None)
+    }
+}
+
+// Play as an external engine with the strategy, reading the protocol on
+// stdin and answering on stdout until stdin closes or a `quit` line. Bad
+// lines get an `error` line back, which clients ignore, and the session goes
+// on.
+pub fn serve(strategy: &Strategy) -> Result<()> {
+    let rules = azul::AzulRules::current();
+    let mut session = Session::default();
+    let mut stdout = io::stdout().lock();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+        match session.handle(&rules, strategy, &line) {
+            Ok(Some(reply)) => writeln!(stdout, "{}", reply)?,
+            Ok(None) => continue,
+            Err(err) => writeln!(stdout, "error {}", err)?,
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let greedy = Strategy::parse("greedy").unwrap();
+        let mut session = Session::default();
+
+        assert!(session.handle(&rules, &greedy, "genmove 0").is_err());
+        assert_eq!(session.handle(&rules, &greedy, &format!("state {}", serde_json::to_string(&state).unwrap())).unwrap(), None);
+        assert_eq!(session.handle(&rules, &greedy, "time 500").unwrap(), None);
+
+        let reply = session.handle(&rules, &greedy, "genmove 1").unwrap().unwrap();
+        let action = azul::parse_action_notation(reply.strip_prefix("play ").unwrap()).unwrap();
+        assert_eq!(action, azul::play_greedy(&rules, &state, 1));
+        assert_eq!(session.remaining, None);
+
+        assert!(session.handle(&rules, &greedy, "genmove 2").is_err());
+        assert!(session.handle(&rules, &greedy, "ponder").is_err());
+    }
+}
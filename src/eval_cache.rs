@@ -0,0 +1,138 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::games::azul;
+use crate::profile;
+
+// Bump when the cache layout or the way evaluations are made changes
+const FORMAT_VERSION: usize = 3;
+// Oldest entries are dropped beyond this many on save
+pub const MAX_ENTRIES: usize = 100_000;
+
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct CachedEval {
+    expected_score: f32,
+    win_probability: f32,
+    // Value of the use counter when this was last read or written
+    last_used: u64,
+}
+
+// On disk cache of action evaluations (expected score and win probability)
+// keyed by a hash of the state, player, and action. Positions that only
+// differ in the order of factory displays share entries. A cache made with
+// different engine parameters is discarded on load.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct EvalCache {
+    version: String,
+    counter: u64,
+    entries: HashMap<String, CachedEval>,
+}
+
+// Changes with anything that changes evaluations, including the rules
+fn engine_version() -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(&*azul::config()).unwrap().hash(&mut hasher);
+    format!("v{}-q{}-r{:016x}", FORMAT_VERSION, azul::Q_FN_GAMES, hasher.finish())
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("eval_cache.json"))
+}
+
+fn key(state: &azul::State, player_idx: usize, action: azul::Action) -> String {
+    let mut hasher = DefaultHasher::new();
+    azul::hash_state(state, &mut hasher);
+    player_idx.hash(&mut hasher);
+    azul::hash_action(state, action, &mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl EvalCache {
+    pub fn load() -> Result<Self> {
+        let path = cache_path()?;
+        let empty = Self { version: engine_version(), counter: 0, entries: HashMap::new() };
+
+        if !path.exists() {
+            return Ok(empty);
+        }
+
+        let cache: Self = serde_json::from_reader(File::open(path)?)?;
+        if cache.version != engine_version() {
+            log::info!("Discarding evaluation cache made with engine {}", cache.version);
+            return Ok(empty);
+        }
+
+        Ok(cache)
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        if self.entries.len() > MAX_ENTRIES {
+            let mut last_used: Vec<u64> = self.entries.values().map(|e| e.last_used).collect();
+            last_used.sort_unstable();
+            let cutoff = last_used[last_used.len() - MAX_ENTRIES];
+            self.entries.retain(|_, e| e.last_used >= cutoff);
+        }
+
+        let path = cache_path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn get(&mut self, state: &azul::State, player_idx: usize, action: azul::Action) -> Option<(f32, f32)> {
+        self.counter += 1;
+        let counter = self.counter;
+
+        self.entries.get_mut(&key(state, player_idx, action)).map(|entry| {
+            entry.last_used = counter;
+            (entry.expected_score, entry.win_probability)
+        })
+    }
+
+    pub fn insert(&mut self, state: &azul::State, player_idx: usize, action: azul::Action, evaluation: (f32, f32)) {
+        self.counter += 1;
+        self.entries.insert(key(state, player_idx, action), CachedEval {
+            expected_score: evaluation.0,
+            win_probability: evaluation.1,
+            last_used: self.counter,
+        });
+    }
+
+    // Evaluate with `mcts_q_fn` unless the evaluation is already cached
+    pub fn q_fn(&mut self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, action: azul::Action) -> (f32, f32) {
+        if let Some(evaluation) = self.get(state, player_idx, action) {
+            return evaluation;
+        }
+
+        let evaluation = azul::mcts_q_fn(rules, state, player_idx, action);
+        self.insert(state, player_idx, action, evaluation);
+        evaluation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    #[test]
+    fn test_key() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let actions = rules.list_valid_actions(&state, 0);
+
+        let copy: azul::State = serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+        assert_eq!(key(&state, 0, actions[0]), key(&copy, 0, actions[0]));
+        assert_ne!(key(&state, 0, actions[0]), key(&state, 0, actions[1]));
+        assert_ne!(key(&state, 0, actions[0]), key(&state, 1, actions[0]));
+    }
+}
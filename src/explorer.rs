@@ -0,0 +1,244 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::accuracy;
+use crate::batch;
+use crate::bestmove;
+use crate::games::{azul, Rules};
+use crate::logreader;
+use crate::output::{self, Format, OpeningMove};
+use crate::playlog::LogRecord;
+
+// Games a continuation was played in, how many of them the player making it
+// won, and their total final score
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Continuation {
+    pub games: usize,
+    pub wins: usize,
+    pub total_score: i64,
+}
+
+// Moves played from every position in a corpus of logs, like a chess opening
+// book. Positions are keyed by a hash of the state and the player to move and
+// moves by a hash of the action, so positions that only differ in the order of
+// factory displays are one position.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct OpeningIndex {
+    pub logs: Vec<String>,
+    pub games: usize,
+    pub positions: HashMap<String, BTreeMap<String, Continuation>>,
+}
+
+pub fn position_key(state: &azul::State, player_idx: usize) -> String {
+    let mut hasher = DefaultHasher::new();
+    azul::hash_state(state, &mut hasher);
+    player_idx.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn action_key(state: &azul::State, action: azul::Action) -> String {
+    let mut hasher = DefaultHasher::new();
+    azul::hash_action(state, action, &mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl OpeningIndex {
+    pub fn load(file: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(file)?))?)
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(file)?), self)?;
+        Ok(())
+    }
+
+    // Add the moves of the finished games in a log
+    pub fn add_log(&mut self, play_log: &[LogRecord]) -> Result<()> {
+        let mut results: HashMap<usize, (Option<usize>, &[i32])> = HashMap::new();
+        for record in play_log {
+            if let LogRecord::GameEnd { game_id, winner, final_scores, .. } = record {
+                results.insert(*game_id, (*winner, final_scores));
+            }
+        }
+        self.games += results.len();
+
+        let (moves, _) = accuracy::logged_moves(play_log, None)?;
+        for m in moves {
+            let Some(&(winner, final_scores)) = results.get(&m.ply.game_id) else {
+                continue;
+            };
+            let state: azul::State = serde_json::from_str(m.state)?;
+            let action: azul::Action = serde_json::from_str(&m.ply.action)?;
+            let player_idx = m.ply.player_id;
+
+            let continuation = self.positions
+                .entry(position_key(&state, player_idx))
+                .or_default()
+                .entry(action_key(&state, action))
+                .or_default();
+            continuation.games += 1;
+            continuation.wins += (winner == Some(player_idx)) as usize;
+            continuation.total_score += final_scores.get(player_idx).copied().unwrap_or(0) as i64;
+        }
+        Ok(())
+    }
+
+    // Valid moves of the position that were played in the corpus, most played
+    // first
+    pub fn continuations(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> Vec<(azul::Action, Continuation)> {
+        let Some(played) = self.positions.get(&position_key(state, player_idx)) else {
+            return Vec::new();
+        };
+
+        // Identical displays give the same move more than once
+        let mut seen = HashSet::new();
+        let mut continuations: Vec<(azul::Action, Continuation)> = Vec::new();
+        for action in azul::actions_canonical(rules, state, player_idx) {
+            let key = action_key(state, action);
+            if let Some(c) = played.get(&key) {
+                if seen.insert(key) {
+                    continuations.push((action, *c));
+                }
+            }
+        }
+        continuations.sort_by_key(|(action, c)| (std::cmp::Reverse(c.games), *action));
+        continuations
+    }
+}
+
+// Simulation logs given directly or as directories of logs
+fn log_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(batch::list_logs(path)?);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+// Index every move of the logs for the explorer
+pub fn index(paths: &[PathBuf], out: &Path) -> Result<()> {
+    let files = log_files(paths)?;
+    if files.is_empty() {
+        return Err(anyhow!("No logs to index"));
+    }
+
+    let mut index = OpeningIndex::default();
+    for file in &files {
+        index.add_log(&logreader::read(file)?).map_err(|err| anyhow!("{}: {}", file.display(), err))?;
+        index.logs.push(file.display().to_string());
+    }
+    index.save(out)?;
+    println!("Indexed {} positions from {} games in {} logs", index.positions.len(), index.games, files.len());
+    Ok(())
+}
+
+// Show the moves played from a position, reached from the state file by the
+// moves in notation, with how often they were played and how the games went
+// for the player making them
+pub fn explore(index_file: &Path, state_file: &PathBuf, player_idx: usize, moves: &[String], format: Format) -> Result<()> {
+    let rules = azul::AzulRules::current();
+    let index = OpeningIndex::load(index_file)?;
+    let mut state = bestmove::read_state(state_file)?;
+    let mut player_idx = player_idx;
+    for notation in moves {
+        let action = azul::parse_action_notation(notation)?;
+        if !rules.list_valid_actions(&state, player_idx).contains(&action) {
+            return Err(anyhow!("'{}' is not a valid move for P{} here", notation, player_idx));
+        }
+        rules.take_action(&mut state, player_idx, action);
+        player_idx = (player_idx + 1) % state.players.len();
+    }
+
+    let continuations = index.continuations(&rules, &state, player_idx);
+    let total: usize = continuations.iter().map(|(_, c)| c.games).sum();
+    let rows: Vec<OpeningMove> = continuations
+        .iter()
+        .map(|(action, c)| OpeningMove {
+            action: azul::action_notation(*action),
+            games: c.games,
+            share: c.games as f64 / total as f64,
+            win_rate: c.wins as f64 / c.games as f64,
+            mean_score: c.total_score as f64 / c.games as f64,
+        })
+        .collect();
+
+    if format == Format::Text {
+        println!("P{} to move, {} games in {} reached this position", player_idx, total, index_file.display());
+        for row in &rows {
+            println!(
+                "  {:<14} {:>6} games ({:>5.1}%), win rate {:>5.1}%, mean score {:.1}",
+                row.action,
+                row.games,
+                row.share * 100.0,
+                row.win_rate * 100.0,
+                row.mean_score
+            );
+        }
+    }
+    output::write_rows(&rows, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlog::{GameEnd, Ply};
+
+    #[test]
+    fn test_opening_index() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(3, || rules.refill(&mut state, &mut azul::game_rng()));
+        let actions = azul::actions_canonical(&rules, &state, 0);
+
+        // Three games from the same position, the second with the displays
+        // in another order
+        let mut play_log = Vec::new();
+        for (game_id, action, winner) in [(0, actions[0], 0), (1, actions[0], 1), (2, actions[1], 0)] {
+            let (start, action) = if game_id == 1 {
+                let n = state.factory_displays.len();
+                azul::permute_displays(&state, action, &(0..n).rev().collect::<Vec<_>>())
+            } else {
+                (state.clone(), action)
+            };
+            let mut after = start.clone();
+            rules.take_action(&mut after, 0, action);
+            play_log.push(LogRecord::GameStart { game_id, seed: 0, players: vec!["a".to_string(), "b".to_string()], state: serde_json::to_string(&start).unwrap() });
+            play_log.push(LogRecord::Ply(Ply {
+                game_id,
+                round_id: 0,
+                ply_id: 0,
+                player_id: 0,
+                action: serde_json::to_string(&action).unwrap(),
+                state: serde_json::to_string(&after).unwrap(),
+                score: 0,
+                applicable_partials: Vec::new(),
+                matching_partials: Vec::new(),
+                analysis: String::new(),
+                events: Vec::new(),
+                incident: None,
+            }));
+            play_log.push(LogRecord::GameEnd { game_id, winner: Some(winner), final_scores: vec![30, 20], end: GameEnd::Finished });
+        }
+
+        let mut index = OpeningIndex::default();
+        index.add_log(&play_log).unwrap();
+        assert_eq!(index.games, 3);
+        assert_eq!(index.positions.len(), 1);
+
+        let continuations = index.continuations(&rules, &state, 0);
+        assert_eq!(continuations.len(), 2);
+        assert_eq!(continuations[0], (actions[0], Continuation { games: 2, wins: 1, total_score: 60 }));
+        assert_eq!(continuations[1], (actions[1], Continuation { games: 1, wins: 1, total_score: 30 }));
+        assert!(index.continuations(&rules, &state, 1).is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::{azul, GameState, Rules};
+
+// Search settings fixtures run with unless they set their own, fixed so that
+// results don't change between runs
+const FIXTURE_SEED: u64 = 0;
+const FIXTURE_ROLLOUTS: usize = 200;
+
+// An engine regression case. The position is either a full state with the
+// player to move, or a setup played from a seeded deal. Expectations are
+// checked against a seeded MCTS search of the position.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Fixture {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub state: Option<azul::State>,
+    #[serde(default)]
+    pub player: Option<usize>,
+    #[serde(default)]
+    pub setup: Option<Setup>,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub rollouts: Option<usize>,
+    // Action the engine has to pick, in notation like `D2 Blue 3`
+    #[serde(default)]
+    pub best: Option<String>,
+    #[serde(default)]
+    pub expected_score: Option<ScoreRange>,
+}
+
+// Game dealt with `seed` and played on with `moves` in notation, round ends
+// and refills included. The player to move is whoever is next.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Setup {
+    pub players: usize,
+    pub seed: u64,
+    #[serde(default)]
+    pub moves: Vec<String>,
+}
+
+// Bounds on the mean rollout score of an action, the engine's pick if none
+// is given
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ScoreRange {
+    #[serde(default)]
+    pub action: Option<String>,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Setup {
+    pub fn position(&self) -> Result<(azul::State, usize)> {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(self.players);
+        azul::with_seed(self.seed, || {
+            rules.refill(&mut state, &mut azul::game_rng());
+            let mut player_idx = 0;
+
+            for notation in &self.moves {
+                if rules.is_game_over(&state) {
+                    return Err(anyhow!("Setup move '{}' comes after the game is over", notation));
+                }
+                let action = azul::parse_action_notation(notation)?;
+                if !rules.list_valid_actions(&state, player_idx).contains(&action) {
+                    return Err(anyhow!("Setup move '{}' is not valid for P{}", notation, player_idx));
+                }
+                rules.take_action(&mut state, player_idx, action);
+                player_idx = (player_idx + 1) % self.players;
+
+                if rules.is_round_over(&state) {
+                    rules.end_round(&mut state);
+                    if rules.is_game_over(&state) {
+                        continue;
+                    }
+                    if let Some(first) = azul::first_player(&state) {
+                        state.players[first].starting_marker = false;
+                        player_idx = first;
+                    }
+                    rules.refill(&mut state, &mut azul::game_rng());
+                }
+            }
+            Ok((state, player_idx))
+        })
+    }
+}
+
+impl Fixture {
+    pub fn load(file: &Path) -> Result<Self> {
+        let mut fixture: Fixture = serde_json::from_str(&fs::read_to_string(file)?)?;
+        if fixture.name.is_empty() {
+            fixture.name = file.display().to_string();
+        }
+        Ok(fixture)
+    }
+
+    pub fn position(&self) -> Result<(azul::State, usize)> {
+        match (&self.state, self.player, &self.setup) {
+            (Some(state), Some(player_idx), None) => Ok((state.clone(), player_idx)),
+            (None, None, Some(setup)) => setup.position(),
+            _ => Err(anyhow!("Fixture needs either a state with a player or a setup")),
+        }
+    }
+
+    // Search the position and tell what doesn't match the expectations
+    pub fn check(&self, rules: &azul::AzulRules) -> Result<()> {
+        let (state, player_idx) = self.position()?;
+        if state.is_round_over() {
+            return Err(anyhow!("No moves to make in the position"));
+        }
+
+        let config = azul::MctsConfig {
+            n_games: self.rollouts.unwrap_or(FIXTURE_ROLLOUTS),
+            ..azul::MctsConfig::default()
+        };
+        let stats = azul::with_seed(self.seed.unwrap_or(FIXTURE_SEED), || {
+            azul::mcts_evaluate(rules, &state, player_idx, &config)
+        });
+        let picked = stats.first().ok_or_else(|| anyhow!("Search explored no actions"))?.action;
+
+        if let Some(best) = &self.best {
+            let best = azul::parse_action_notation(best)?;
+            if picked != best {
+                return Err(anyhow!(
+                    "Engine picked {} instead of {}",
+                    azul::action_notation(picked),
+                    azul::action_notation(best)
+                ));
+            }
+        }
+
+        if let Some(range) = &self.expected_score {
+            let action = match &range.action {
+                Some(notation) => azul::parse_action_notation(notation)?,
+                None => picked,
+            };
+            let score = stats
+                .iter()
+                .find(|s| s.action == action)
+                .ok_or_else(|| anyhow!("Search never tried {}", azul::action_notation(action)))?
+                .mean_score;
+            if score < range.min || score > range.max {
+                return Err(anyhow!(
+                    "Expected score of {} is {:.2}, outside [{}, {}]",
+                    azul::action_notation(action),
+                    score,
+                    range.min,
+                    range.max
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Fixture files in the directory, in name order
+pub fn list(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|file| file.extension().is_some_and(|ext| ext == "json"));
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures() {
+        let rules = azul::AzulRules::current();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let files = list(&dir).unwrap();
+        assert!(!files.is_empty(), "No fixtures in {}", dir.display());
+
+        let failures: Vec<String> = files
+            .iter()
+            .filter_map(|file| match Fixture::load(file) {
+                Ok(fixture) => fixture.check(&rules).err().map(|err| format!("{}: {}", fixture.name, err)),
+                Err(err) => Some(format!("{}: {}", file.display(), err)),
+            })
+            .collect();
+        assert!(failures.is_empty(), "Failing fixtures:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_setup() {
+        let rules = azul::AzulRules::current();
+        let setup = Setup { players: 2, seed: 3, moves: Vec::new() };
+        let (state, player_idx) = setup.position().unwrap();
+        assert_eq!(player_idx, 0);
+        assert_eq!(setup.position().unwrap().0.factory_displays, state.factory_displays);
+
+        let action = rules.list_valid_actions(&state, 0)[0];
+        let setup = Setup { moves: vec![azul::action_notation(action)], ..setup };
+        assert_eq!(setup.position().unwrap().1, 1);
+
+        let setup = Setup { moves: vec!["D0 Blue 9".to_string()], ..setup };
+        assert!(setup.position().is_err());
+    }
+}
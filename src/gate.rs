@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+use crate::output::{self, Exit, Format, GateResult};
+use crate::rating;
+use crate::strategy::Strategy;
+use crate::tournament::{self, match_seed};
+
+// Sequential probability ratio test of whether a candidate is at least `elo1`
+// stronger than the baseline (H1) or at most `elo0` (H0). Log likelihood
+// ratios use the normal approximation of the mean game score, with draws as
+// half a point, so the test stops as soon as the results are significant at
+// the given error rates.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    // Chance of accepting H1 when H0 holds
+    pub alpha: f64,
+    // Chance of accepting H0 when H1 holds
+    pub beta: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    // H1, the candidate is stronger
+    Pass,
+    // H0, the candidate is not stronger
+    Fail,
+    Undecided,
+}
+
+const MIN_VARIANCE: f64 = 1e-3;
+
+// Expected score of a player that much Elo stronger
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+impl Sprt {
+    // Bounds of the log likelihood ratio for accepting H0 and H1
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    pub fn llr(&self, (wins, losses, draws): (usize, usize, usize)) -> f64 {
+        let n = (wins + losses + draws) as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+
+        let score = (wins as f64 + draws as f64 / 2.0) / n;
+        // Floored so a run of only wins or only draws still decides
+        let variance = (wins as f64 * (1.0 - score).powi(2) + draws as f64 * (0.5 - score).powi(2) + losses as f64 * score.powi(2)) / n;
+        let variance = variance.max(MIN_VARIANCE);
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        n * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn verdict(&self, results: (usize, usize, usize)) -> Verdict {
+        let (lower, upper) = self.bounds();
+        let llr = self.llr(results);
+        if llr >= upper {
+            Verdict::Pass
+        } else if llr <= lower {
+            Verdict::Fail
+        } else {
+            Verdict::Undecided
+        }
+    }
+}
+
+// Play the candidate against the baseline in batches of games with seats
+// alternating until the test decides or `max_games` are played
+pub fn run(candidate: &str, baseline: &str, sprt: Sprt, batch: usize, max_games: usize, seed: Option<u64>, format: Format) -> Result<()> {
+    if sprt.elo1 <= sprt.elo0 {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("elo1 has to be larger than elo0")));
+    }
+    if batch == 0 || !batch.is_multiple_of(2) {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Batches need an even number of games so both sides play both seats")));
+    }
+    let candidate_strategy = Strategy::parse(candidate)?;
+    let baseline_strategy = Strategy::parse(baseline)?;
+    let rules = azul::AzulRules::current();
+
+    let seed = seed.unwrap_or_else(rand::random);
+    output::progress(format, &format!("Seed: {}", seed));
+    if let Some(handicaps) = azul::config().handicap_summary() {
+        output::progress(format, &format!("Handicaps: {}", handicaps));
+    }
+    let (lower, upper) = sprt.bounds();
+    output::progress(format, &format!("SPRT elo0 {} elo1 {}, LLR bounds [{:.2}, {:.2}]", sprt.elo0, sprt.elo1, lower, upper));
+
+    let mut results = (0, 0, 0);
+    let mut verdict = Verdict::Undecided;
+    let mut batch_idx = 0;
+    while verdict == Verdict::Undecided && results.0 + results.1 + results.2 < max_games {
+        let games = tournament::play_match(&rules, &candidate_strategy, &baseline_strategy, batch, None, match_seed(seed, batch_idx, 0, 1));
+        let (wins, losses, draws) = tournament::tally(&games);
+        results = (results.0 + wins, results.1 + losses, results.2 + draws);
+        verdict = sprt.verdict(results);
+        batch_idx += 1;
+        output::progress(
+            format,
+            &format!("  {} games: {}-{} ({} drawn), LLR {:.2}", results.0 + results.1 + results.2, results.0, results.1, results.2, sprt.llr(results)),
+        );
+    }
+    rating::record_matches(&[(candidate.to_string(), baseline.to_string(), results)], "gate")?;
+
+    let result = GateResult {
+        candidate: candidate.to_string(),
+        baseline: baseline.to_string(),
+        games: results.0 + results.1 + results.2,
+        wins: results.0,
+        losses: results.1,
+        draws: results.2,
+        llr: sprt.llr(results),
+        verdict: match verdict {
+            Verdict::Pass => "pass",
+            Verdict::Fail => "fail",
+            Verdict::Undecided => "undecided",
+        }.to_string(),
+    };
+    if format == Format::Text {
+        match verdict {
+            Verdict::Pass => println!("Pass: {} is stronger than {} by at least {} Elo", candidate, baseline, sprt.elo1),
+            Verdict::Fail => println!("Fail: {} is not stronger than {} by more than {} Elo", candidate, baseline, sprt.elo0),
+            Verdict::Undecided => println!("Undecided after {} games", result.games),
+        }
+    }
+    output::write_rows(&[result], format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprt() {
+        let sprt = Sprt { elo0: 0.0, elo1: 20.0, alpha: 0.05, beta: 0.05 };
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+
+        // Even results lean to H0, a steady edge to H1
+        assert!(sprt.llr((50, 50, 0)) < 0.0);
+        assert_eq!(sprt.verdict((1000, 1000, 100)), Verdict::Fail);
+        assert_eq!(sprt.verdict((10, 8, 2)), Verdict::Undecided);
+        assert_eq!(sprt.verdict((700, 500, 100)), Verdict::Pass);
+        assert_eq!(sprt.verdict((20, 0, 0)), Verdict::Pass);
+        assert_eq!(sprt.verdict((0, 0, 20)), Verdict::Fail);
+        assert_eq!(sprt.llr((0, 0, 0)), 0.0);
+    }
+}
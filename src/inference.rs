@@ -0,0 +1,113 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+use crate::policy::{self, Policy, PolicyNet};
+
+// Features to evaluate and where to send their logits, or why the batch
+// couldn't be evaluated
+struct Request {
+    features: Vec<f64>,
+    reply: Sender<Result<Vec<f64>, String>>,
+}
+
+// Shared inference service for a policy used by many games in parallel.
+// Requests from all threads are collected until `batch_size` of them are
+// waiting or `timeout` passes since the first one, then evaluated together in
+// one forward pass.
+pub struct InferenceQueue {
+    model: Arc<dyn PolicyNet>,
+    sender: Sender<Request>,
+}
+
+// Serve requests until every sender is gone
+fn serve(model: Arc<dyn PolicyNet>, receiver: Receiver<Request>, batch_size: usize, timeout: Duration) {
+    while let Ok(first) = receiver.recv() {
+        let deadline = Instant::now() + timeout;
+        let mut batch = vec![first];
+
+        while batch.len() < batch_size {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(request) => batch.push(request),
+                Err(_) => break,
+            }
+        }
+
+        let features: Vec<Vec<f64>> = batch.iter_mut().map(|r| std::mem::take(&mut r.features)).collect();
+        log::debug!("Evaluating a batch of {}", features.len());
+
+        // The requesting threads may have gone away, nothing to do then
+        match model.forward(&features) {
+            Ok(logits) => {
+                for (request, logits) in batch.into_iter().zip(logits) {
+                    let _ = request.reply.send(Ok(logits));
+                }
+            },
+            Err(err) => {
+                for request in batch {
+                    let _ = request.reply.send(Err(err.to_string()));
+                }
+            },
+        }
+    }
+}
+
+impl InferenceQueue {
+    pub fn start(model: impl PolicyNet + 'static, batch_size: usize, timeout: Duration) -> Self {
+        let model: Arc<dyn PolicyNet> = Arc::new(model);
+        let (sender, receiver) = mpsc::channel();
+
+        let worker_model = model.clone();
+        thread::spawn(move || serve(worker_model, receiver, batch_size.max(1), timeout));
+
+        Self { model, sender }
+    }
+
+    // Logits of the whole action space, waiting for the batch to be run
+    pub fn logits(&self, features: Vec<f64>) -> Result<Vec<f64>> {
+        let (reply, receiver) = mpsc::channel();
+        self.sender.send(Request { features, reply }).map_err(|_| anyhow!("Inference queue has stopped"))?;
+        receiver.recv().map_err(|_| anyhow!("Inference queue has stopped"))?.map_err(|err| anyhow!(err))
+    }
+}
+
+impl Policy for InferenceQueue {
+    fn probabilities(&self, state: &azul::State, player_idx: usize, temperature: f64) -> Result<Vec<f64>> {
+        let features = self.model.features(state, player_idx);
+        self.model.check_features(&features)?;
+        let logits = self.logits(features)?;
+        Ok(policy::masked_softmax(&logits, &azul::actions_canonical(&azul::AzulRules::current(), state, player_idx), temperature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::{Representable, Rules};
+    use crate::policy::PolicyModel;
+
+    #[test]
+    fn test_batched_matches_direct() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+
+        let mut model = PolicyModel::zeros(state.represent().len(), false, true);
+        let n_features = model.weights[0].len();
+        for (i, row) in model.weights.iter_mut().enumerate() {
+            row[i % n_features] = 0.1 * i as f64;
+        }
+        let direct = model.probabilities(&state, 1, 1.0).unwrap();
+
+        let queue = InferenceQueue::start(model, 4, Duration::from_millis(50));
+        thread::scope(|s| {
+            for _ in 0..6 {
+                s.spawn(|| assert_eq!(queue.probabilities(&state, 1, 1.0).unwrap(), direct));
+            }
+        });
+    }
+}
@@ -0,0 +1,866 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::widgets::TableState;
+
+use crate::eval_cache::EvalCache;
+use crate::games::azul::{self, play_partial_greedy};
+use crate::games::clock::{self, Clock};
+use crate::games::Rules;
+use crate::sound::{self, Cue, Sound};
+use crate::tui::{self, ActionAnalysis, GameUi, Heuristic, InteractiveApp};
+use crate::{autosave, bestmove, ponder, profile, script, strategy, treedump};
+use crate::{InteractiveArgs, PartialPlayFn, PlayFn};
+
+// Animation lengths at normal speed and the redraw interval while animating
+const MOVE_ANIMATION: Duration = Duration::from_millis(800);
+const ROUND_ANIMATION: Duration = Duration::from_millis(2000);
+const ANIMATION_TICK: Duration = Duration::from_millis(50);
+// Actions listed for each engine when comparing
+const COMPARE_TOP_K: usize = 5;
+
+fn animation(events: Vec<azul::GameEvent>, duration: Duration, speed: f32) -> Option<tui::Animation<azul::GameEvent>> {
+    if speed > 0.0 {
+        Some(tui::Animation::new(events, duration.div_f32(speed)))
+    } else {
+        None
+    }
+}
+
+// Play a game in the TUI from the observed position if there is one, or else
+// resuming the autosaved game if the player wants
+pub fn run(args: &InteractiveArgs, mut n_players: usize, observed: Option<(azul::State, usize)>, sound: Sound) {
+    let teacher: PlayFn = azul::play_mcts;
+    let _action_heuristics: Vec<PartialPlayFn> = Vec::new();
+
+    let mut eval_cache = if args.eval_cache {
+        match EvalCache::load() {
+            Ok(cache) => Some(cache),
+            Err(err) => {
+                eprintln!("Failed to load evaluation cache: {}", err);
+                return;
+            },
+        }
+    } else {
+        None
+    };
+
+    // An observed position is played instead of the autosaved game
+    let resumed = match observed.as_ref().map_or_else(autosave::load, |_| Ok(None)) {
+        Ok(Some(save)) if offer_resume(&save) => Some(save),
+        Ok(_) => None,
+        Err(err) => {
+            log::error!("Failed to load autosaved game: {}", err);
+            None
+        },
+    };
+    if let Some(save) = &resumed {
+        n_players = save.state.players.len();
+        if args.seat >= n_players {
+            println!("Seat {} is not available in the resumed {} player game", args.seat, n_players);
+            return;
+        }
+        azul::set_config(save.rules.clone());
+    }
+    let rules = azul::AzulRules::current();
+
+    let mut scripts = match args.heuristics.as_deref().map(script::ScriptSet::load).transpose() {
+        Ok(scripts) => scripts,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+
+    let engines = match args.compare.iter().map(|spec| Ok((spec.clone(), strategy::parse_analysis_engine(spec)?))).collect::<Result<Vec<_>>>() {
+        Ok(engines) if engines.is_empty() || engines.len() == 2 => engines,
+        Ok(_) => {
+            println!("Compare needs two engines, like --compare mcts --compare mcts:n_games=5000");
+            return;
+        },
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+
+    color_eyre::install().unwrap();
+    let mut terminal = ratatui::init();
+
+    let mut app = InteractiveApp{
+        state: rules.new_state(n_players),
+        game_over: false,
+        current_player: 0,
+        ply: 0,
+        ply_round: 0,
+        last_move: None,
+        actions: Vec::new(),
+        actions_state: TableState::default(),
+        analyses: HashMap::new(),
+        show_action_details: false,
+        show_heuristic_details: false,
+        show_state_details: false,
+        round_preview: None,
+        tiling: None,
+        search_tree: None,
+        comparison: None,
+        continuation: Vec::new(),
+        teacher_info: None,
+        difficulty: format!("{:?}", args.difficulty),
+        gift_threshold: args.gift_threshold,
+        clock: args.time_control.map(|tc| Clock::new(tc, n_players)),
+        compact_players: false,
+        animation: None,
+        heuristics: heuristics(&rules, scripts.as_ref()),
+        notes: BTreeMap::new(),
+        note_input: None,
+        autoplay: args.autoplay_forced,
+        auto_played: BTreeSet::new(),
+    };
+    // Start with the score sheet when full boards don't fit
+    app.compact_players = terminal.size().is_ok_and(|size| !app.fits_full_boards(size.height));
+
+    let mut user_exit = false;
+    // Mistakes made by the profile owner, by kind
+    let mut mistakes: HashMap<String, usize> = HashMap::new();
+    let mut reviews: Vec<tui::PlyReview> = Vec::new();
+    let mut reviewer = ponder::Reviewer::start(&rules, teacher);
+    let mut difficulty = args.difficulty;
+    let mut ponder: Option<ponder::Ponder> = None;
+    let mut history: Vec<(usize, azul::Action)> = Vec::new();
+    let mut think_times: Vec<Option<u64>> = Vec::new();
+    let mut positions: Vec<azul::State> = Vec::new();
+    // When the player to move got the position, to time moves picked by hand
+    let mut turn_start;
+    // A resumed game continues the round it was saved in, and an observed
+    // position the round on the table
+    let mut resume_round = resumed.is_some() || observed.is_some();
+
+    if let Some((state, current_player)) = observed {
+        app.state = state;
+        app.current_player = current_player;
+    }
+
+    if let Some(save) = resumed {
+        app.state = save.state;
+        app.current_player = save.current_player;
+        app.ply = save.ply;
+        app.ply_round = save.ply_round;
+        history = save.history;
+        think_times = save.think_times;
+        think_times.resize(history.len(), None);
+        positions = save.positions;
+        mistakes = save.mistakes;
+        app.notes = save.notes;
+        app.auto_played = save.auto_played;
+    }
+
+    loop {
+        if !resume_round {
+            app.current_player = match azul::first_player(&app.state) {
+                Some(one) => {
+                    app.state.players[one].starting_marker = false;
+                    one
+                },
+                None => 0,
+            };
+
+            rules.refill(&mut app.state, &mut azul::game_rng());
+        }
+        resume_round = false;
+        turn_start = Instant::now();
+        terminal.draw(|frame| {
+            frame.render_widget(app.clone(), frame.area());
+        }).unwrap();
+
+        loop {
+            if scripts.as_mut().is_some_and(|set| set.reload_if_changed()) {
+                app.heuristics = heuristics(&rules, scripts.as_ref());
+            }
+
+            terminal.draw(|frame| {
+                frame.render_widget(app.clone(), frame.area());
+            }).unwrap();
+
+            if rules.is_round_over(&app.state) {
+                app.ply_round = 0;
+                break;
+            }
+
+            // Forced moves are played once the last move is shown
+            if app.autoplay && app.animation.is_none() {
+                if let Some(action) = azul::forced_action(&rules, &app.state, app.current_player) {
+                    if args.review {
+                        reviewer.submit(review_job(&app, action, Duration::ZERO, true, &mut eval_cache));
+                    }
+                    positions.push(app.state.clone());
+                    history.push((app.current_player, action));
+                    app.auto_played.insert(app.ply);
+                    play_move(&rules, &mut app, action, args.animation_speed);
+                    if let Some(last_move) = app.last_move.as_mut() {
+                        last_move.explanation.push_str(" (forced, auto-played)");
+                    }
+                    think_times.push(None);
+                    save_game(&app, &history, &think_times, &positions, &mistakes);
+                    turn_start = Instant::now();
+                    continue;
+                }
+            }
+
+            app.actions = rules.list_valid_actions(&app.state, app.current_player);
+            for action in &app.actions {
+                if app.analyses.contains_key(action) {
+                    // This is already cached (and maybe extended by other
+                    // analyses) so not doing anything
+                } else {
+                    app.analyses.insert(*action, ActionAnalysis {
+                        score_gain: rules.reward(&app.state, app.current_player, *action),
+                        gift: azul::best_reply_gain(&rules, &app.state, app.current_player, *action),
+                        expected_score: None,
+                        win_probability: None,
+                        floor_probability: None,
+                        expected_floor_penalty: None,
+                        marker_value: None,
+                    });
+                }
+            }
+
+            app.actions.sort_by_key(|a| (-app.analyses[a].score_gain, *a));
+
+            if let Some(cache) = eval_cache.as_mut() {
+                for action in &app.actions {
+                    let analysis = app.analyses.get_mut(action).unwrap();
+                    if analysis.expected_score.is_none() {
+                        if let Some((expected_score, win_probability)) = cache.get(&app.state, app.current_player, *action) {
+                            analysis.expected_score = Some(expected_score);
+                            analysis.win_probability = Some(win_probability);
+                        }
+                    }
+                }
+            }
+
+            if !args.no_ponder && !ponder.as_ref().is_some_and(|p| p.ply == app.ply && p.config == difficulty.config()) {
+                let unevaluated = app.actions.iter().filter(|a| app.analyses[a].expected_score.is_none()).copied().collect();
+                ponder = Some(ponder::Ponder::start(&rules, &app.state, app.current_player, app.ply, difficulty.config(), unevaluated));
+            }
+
+            if let Some(ponder) = ponder.as_mut() {
+                for (action, expected_score, win_probability) in ponder.poll() {
+                    if let Some(cache) = eval_cache.as_mut() {
+                        cache.insert(&app.state, app.current_player, action, (expected_score, win_probability));
+                    }
+                    if let Some(analysis) = app.analyses.get_mut(&action) {
+                        analysis.expected_score.get_or_insert(expected_score);
+                        analysis.win_probability.get_or_insert(win_probability);
+                    }
+                }
+            }
+            collect_reviews(&mut reviewer, &mut reviews, &mut eval_cache);
+
+            // The favorite is only shown while the teacher's search runs
+            app.teacher_info = ponder.as_ref().filter(|p| p.is_searching()).and_then(|p| p.favorite()).map(|favorite| {
+                format!(
+                    "leaning to {} (EXP Score {:.1}, Win P {:.0}%, {} rollouts)",
+                    azul::action_notation(favorite.action),
+                    favorite.mean_score,
+                    favorite.win_rate * 100.0,
+                    favorite.rollouts,
+                )
+            });
+
+            // Center actions taking the starting marker get it weighed as soon
+            // as they are highlighted
+            if let Some(action) = app.actions_state.selected().and_then(|i| app.actions.get(i)) {
+                if let Some(analysis) = app.analyses.get_mut(action) {
+                    if analysis.marker_value.is_none() {
+                        analysis.marker_value = azul::marker_value(&rules, &app.state, app.current_player, *action);
+                    }
+                }
+            }
+
+            terminal.draw(|frame| {
+                frame.render_widget(app.clone(), frame.area());
+            }).unwrap();
+
+            if app.animation.as_ref().is_some_and(|a| a.is_done()) {
+                app.animation = None;
+            }
+
+            // Animations, running clocks, and pondering results need redraws
+            // even without key presses
+            let tick = if app.animation.is_some() {
+                Some(ANIMATION_TICK)
+            } else if app.clock.is_some() || ponder.is_some() {
+                Some(Duration::from_millis(250))
+            } else {
+                None
+            };
+            if tick.is_some_and(|tick| !event::poll(tick).unwrap()) {
+                continue;
+            }
+
+            if let Event::Key(key_event) = event::read().unwrap() {
+                if let Some(note) = app.note_input.as_mut() {
+                    match key_event.code {
+                        KeyCode::Char(c) => note.push(c),
+                        KeyCode::Backspace => {
+                            note.pop();
+                        },
+                        KeyCode::Enter => {
+                            // Saving an empty note removes it
+                            let note = note.trim().to_string();
+                            if note.is_empty() {
+                                app.notes.remove(&app.ply);
+                            } else {
+                                app.notes.insert(app.ply, note);
+                            }
+                            app.note_input = None;
+                            save_game(&app, &history, &think_times, &positions, &mistakes);
+                        },
+                        KeyCode::Esc => app.note_input = None,
+                        _ => {},
+                    }
+                    continue;
+                }
+                if app.comparison.is_some() {
+                    match key_event.code {
+                        KeyCode::Char('v') | KeyCode::Char('q') => app.comparison = None,
+                        _ => {},
+                    }
+                } else if let Some(tree) = app.search_tree.as_mut() {
+                    match key_event.code {
+                        KeyCode::Char('t') | KeyCode::Char('q') => app.search_tree = None,
+                        KeyCode::Down => tree.select_next(),
+                        KeyCode::Up => tree.select_previous(),
+                        KeyCode::Enter | KeyCode::Right | KeyCode::Left => tree.toggle(),
+                        _ => {},
+                    }
+                } else if app.show_action_details || app.show_heuristic_details || app.show_state_details || app.round_preview.is_some() {
+                    // When any popup is open, only exiting is allowed
+                    if let KeyCode::Char('q') = key_event.code {
+                        app.show_action_details = false;
+                        app.show_heuristic_details = false;
+                        app.show_state_details = false;
+                        app.round_preview = None;
+                    }
+                } else {
+                    match key_event.code {
+                        KeyCode::Char('q') => {
+                            user_exit = true;
+                            break;
+                        },
+                        KeyCode::Char(' ') => {
+                            let pondered = ponder
+                                .as_mut()
+                                .filter(|p| p.ply == app.ply && p.config == difficulty.config())
+                                .and_then(|p| p.teacher_move());
+
+                            let action = match pondered {
+                                Some(action) => action,
+                                None => {
+                                    let mut config = difficulty.config();
+                                    if let Some(clock) = &app.clock {
+                                        config.time_budget = Some(clock::move_budget(clock.remaining(app.current_player, app.current_player)));
+                                    }
+                                    azul::play_mcts_with(&rules, &app.state, app.current_player, &config)
+                                },
+                            };
+                            positions.push(app.state.clone());
+                            history.push((app.current_player, action));
+                            play_move(&rules, &mut app, action, args.animation_speed);
+                            think_times.push(None);
+                            save_game(&app, &history, &think_times, &positions, &mistakes);
+                            turn_start = Instant::now();
+                        },
+                        // Quick plays for watching games: the action ranked
+                        // that high, a random one, or the greedy one
+                        KeyCode::Char(c @ ('1'..='5' | 'r' | 'g')) => {
+                            let action = match c {
+                                'r' => Some(azul::play_random(&rules, &app.state, app.current_player)),
+                                'g' => Some(azul::play_greedy(&rules, &app.state, app.current_player)),
+                                _ => ranked_actions(&app).get(c as usize - '1' as usize).copied(),
+                            };
+                            match action {
+                                Some(action) => {
+                                    positions.push(app.state.clone());
+                                    history.push((app.current_player, action));
+                                    play_move(&rules, &mut app, action, args.animation_speed);
+                                    think_times.push(None);
+                                    save_game(&app, &history, &think_times, &positions, &mistakes);
+                                    turn_start = Instant::now();
+                                },
+                                None => sound.play(Cue::Illegal),
+                            }
+                        },
+                        KeyCode::Enter => {
+                            if let Some(action_idx) = app.actions_state.selected() {
+                                let action = app.actions[action_idx];
+                                let think_time = turn_start.elapsed();
+                                if app.current_player == args.seat {
+                                    if let Some(kind) = profile::classify_mistake(&app.actions, &app.analyses, action) {
+                                        *mistakes.entry(kind).or_insert(0) += 1;
+                                    }
+                                    if sound::is_blunder(&app.analyses, action) {
+                                        sound.play(Cue::Blunder);
+                                    }
+                                }
+                                if args.review {
+                                    reviewer.submit(review_job(&app, action, think_time, false, &mut eval_cache));
+                                }
+                                positions.push(app.state.clone());
+                                history.push((app.current_player, action));
+                                play_move(&rules, &mut app, action, args.animation_speed);
+                                think_times.push(Some(think_time.as_millis() as u64));
+                                save_game(&app, &history, &think_times, &positions, &mistakes);
+                                turn_start = Instant::now();
+                            };
+                        },
+                        KeyCode::Down => {
+                            if let Some(action_idx) = app.actions_state.selected() {
+                                if action_idx < app.actions.len() - 1 {
+                                    app.actions_state.select_next();
+                                }
+                            } else {
+                                app.actions_state.select_first();
+                            }
+                        },
+                        KeyCode::Up => {
+                            if app.actions_state.selected().is_some() {
+                                app.actions_state.select_previous();
+                            } else {
+                                app.actions_state.select_first();
+                            }
+                        },
+                        KeyCode::Char('p') => {
+                            if let Some(action_idx) = app.actions_state.selected() {
+                                let action = app.actions[action_idx];
+
+                                // Expected score may already be there from pondering
+                                if let Some(analysis) = app.analyses.get_mut(&action) {
+                                    if analysis.expected_score.is_none() {
+                                        let (expected_score, win_probability) = evaluate(&rules, &mut eval_cache, &app.state, app.current_player, action);
+                                        analysis.expected_score = Some(expected_score);
+                                        analysis.win_probability = Some(win_probability);
+                                    }
+                                    if analysis.floor_probability.is_none() {
+                                        let (floor_probability, expected_floor_penalty) = azul::forecast_floor(&rules, &app.state, app.current_player, action);
+                                        analysis.floor_probability = Some(floor_probability);
+                                        analysis.expected_floor_penalty = Some(expected_floor_penalty);
+                                    }
+                                }
+                            }
+                        },
+                        KeyCode::Char('a') => {
+                            if let Some(action_idx) = app.actions_state.selected() {
+                                let action = app.actions[action_idx];
+
+                                if let Some(analysis) = app.analyses.get_mut(&action) {
+                                    if analysis.floor_probability.is_none() {
+                                        let (floor_probability, expected_floor_penalty) = azul::forecast_floor(&rules, &app.state, app.current_player, action);
+                                        analysis.floor_probability = Some(floor_probability);
+                                        analysis.expected_floor_penalty = Some(expected_floor_penalty);
+                                    }
+                                }
+
+                                let variation = azul::principal_variation(&rules, &app.state, app.current_player, action, bestmove::PV_DEPTH);
+                                app.continuation = bestmove::format_variation(&variation);
+                                app.show_action_details = true;
+                            }
+                        },
+                        KeyCode::Char('c') => {
+                            app.compact_players = !app.compact_players;
+                        },
+                        KeyCode::Char('d') => {
+                            difficulty = difficulty.next();
+                            app.difficulty = format!("{:?}", difficulty);
+                        },
+                        KeyCode::Char('f') => {
+                            app.round_preview = Some(azul::play_out_round(&rules, &app.state, app.current_player, args.preview_policy.play_fn()));
+                        },
+                        KeyCode::Char('t') => {
+                            let tree = azul::search_tree(&rules, &app.state, app.current_player, &difficulty.config(), treedump::TREE_WIDTH);
+                            app.search_tree = Some(tui::SearchTreeView::new(search_nodes(&app.state, app.current_player, tree)));
+                        },
+                        KeyCode::Char('o') => {
+                            app.autoplay = !app.autoplay;
+                        },
+                        KeyCode::Char('v') if !engines.is_empty() => {
+                            app.comparison = Some(compare_engines(&rules, &app.state, app.current_player, &engines));
+                        },
+                        KeyCode::Char(';') => {
+                            app.note_input = Some(app.notes.get(&app.ply).cloned().unwrap_or_default());
+                        },
+                        KeyCode::Char('h')
+                            if app.actions_state.selected().is_some() => {
+                                app.show_heuristic_details = true;
+                            },
+                        KeyCode::Char('s')
+                            if app.actions_state.selected().is_some() => {
+                                app.show_state_details = true;
+                            },
+                        _ => {},
+                    }
+                }
+            };
+
+            terminal.draw(|frame| {
+                frame.render_widget(app.clone(), frame.area());
+            }).unwrap();
+        }
+        let mut scored = app.state.clone();
+        let events = rules.end_round(&mut scored);
+        if !user_exit && !args.no_tiling_phase {
+            app.tiling = Some(tui::TilingPhase { scored: scored.clone(), events: events.clone(), player: 0 });
+            run_tiling_phase(&mut terminal, &mut app);
+            // Time spent looking at the round end isn't charged to anyone
+            if let Some(clock) = app.clock.as_mut() {
+                clock.restart_turn();
+            }
+        }
+        app.state = scored;
+        app.game_over = rules.is_game_over(&app.state);
+        app.animation = animation(events, ROUND_ANIMATION, args.animation_speed);
+        if !user_exit {
+            sound.play(Cue::RoundEnd);
+        }
+
+        if app.game_over || user_exit {
+            break;
+        }
+    }
+
+    if app.game_over {
+        // Annotated games are kept to be replayed with their notes
+        if app.notes.is_empty() {
+            if let Err(err) = autosave::clear() {
+                log::error!("Failed to remove autosaved game: {}", err);
+            }
+        } else {
+            save_game(&app, &history, &think_times, &positions, &mistakes);
+            match autosave::archive() {
+                Ok(file) => log::info!("Annotated game saved to {}", file.display()),
+                Err(err) => log::error!("Failed to keep annotated game: {}", err),
+            }
+        }
+    }
+
+    if let Some(name) = &args.name {
+        if app.game_over {
+            if let Err(err) = profile::record_game(name, &app.state, args.seat, app.ply, mistakes) {
+                log::error!("Failed to save profile: {}", err);
+            }
+        }
+    }
+
+    // Listen to Q unless user has showed intention to quit already
+    if !user_exit {
+        terminal.draw(|frame| {
+            frame.render_widget(app.clone(), frame.area());
+        }).unwrap();
+
+        loop {
+            if app.animation.as_ref().is_some_and(|a| a.is_done()) {
+                app.animation = None;
+            }
+
+            // Animations, running clocks, and pondering results need redraws
+            // even without key presses
+            let tick = if app.animation.is_some() {
+                Some(ANIMATION_TICK)
+            } else if app.clock.is_some() || ponder.is_some() {
+                Some(Duration::from_millis(250))
+            } else {
+                None
+            };
+            if tick.is_some_and(|tick| !event::poll(tick).unwrap()) {
+                continue;
+            }
+
+            if let Event::Key(key_event) = event::read().unwrap() {
+                if let KeyCode::Char('q') = key_event.code { break; }
+            };
+        }
+
+        if !reviews.is_empty() || reviewer.pending() > 0 {
+            run_review(&mut terminal, reviews, &mut reviewer, &mut eval_cache);
+        }
+    }
+
+    if let Some(cache) = eval_cache.as_mut() {
+        if let Err(err) = cache.save() {
+            log::error!("Failed to save evaluation cache: {}", err);
+        }
+    }
+
+    ratatui::restore();
+}
+
+// Evaluate the human's action against the teacher's choice for the position
+// Expected score and win probability of the action, going through the
+// evaluation cache if there is one
+fn evaluate(rules: &azul::AzulRules, eval_cache: &mut Option<EvalCache>, state: &azul::State, player_idx: usize, action: azul::Action) -> (f32, f32) {
+    match eval_cache {
+        Some(cache) => cache.q_fn(rules, state, player_idx, action),
+        None => azul::mcts_q_fn(rules, state, player_idx, action),
+    }
+}
+
+// Ask on stdin whether to continue the autosaved game instead of starting a
+// new one
+fn offer_resume(save: &autosave::Autosave) -> bool {
+    print!(
+        "Resume the unfinished {} player game from round {}, ply {}? [Y/n] ",
+        save.state.players.len(), save.state.rounds + 1, save.ply
+    );
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    !answer.trim().to_lowercase().starts_with('n')
+}
+
+// Search tree nodes for the popup, with replies made by the next player
+fn search_nodes(state: &azul::State, player_idx: usize, tree: Vec<(azul::MctsStats, Vec<azul::MctsStats>)>) -> Vec<tui::SearchNode<azul::Action>> {
+    let node = |player: usize, stats: &azul::MctsStats, replies| tui::SearchNode {
+        player,
+        action: stats.action,
+        n_games: stats.n_games,
+        mean_score: stats.mean_score,
+        win_rate: stats.win_rate,
+        replies,
+    };
+    let next_player = (player_idx + 1) % state.players.len();
+
+    tree.iter()
+        .map(|(stats, replies)| node(player_idx, stats, replies.iter().map(|reply| node(next_player, reply, Vec::new())).collect()))
+        .collect()
+}
+
+// Best actions of both engines for the position
+fn compare_engines(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, engines: &[(String, azul::MctsConfig)]) -> tui::EngineComparison<azul::Action> {
+    let best = |(spec, config): &(String, azul::MctsConfig)| {
+        let stats = azul::mcts_evaluate(rules, state, player_idx, config);
+        let nodes = stats.iter().take(COMPARE_TOP_K).map(|s| tui::SearchNode {
+            player: player_idx,
+            action: s.action,
+            n_games: s.n_games,
+            mean_score: s.mean_score,
+            win_rate: s.win_rate,
+            replies: Vec::new(),
+        }).collect();
+        (spec.clone(), nodes)
+    };
+    tui::EngineComparison { engines: [best(&engines[0]), best(&engines[1])] }
+}
+
+// Write the game in progress after every ply. Failing to save shouldn't stop
+// the game so errors are only logged.
+fn save_game(
+    app: &InteractiveApp,
+    history: &[(usize, azul::Action)],
+    think_times: &[Option<u64>],
+    positions: &[azul::State],
+    mistakes: &HashMap<String, usize>,
+) {
+    let save = autosave::Autosave {
+        rules: (*azul::config()).clone(),
+        state: app.state.clone(),
+        current_player: app.current_player,
+        ply: app.ply,
+        ply_round: app.ply_round,
+        history: history.to_vec(),
+        think_times: think_times.to_vec(),
+        positions: positions.to_vec(),
+        mistakes: mistakes.clone(),
+        notes: app.notes.clone(),
+        auto_played: app.auto_played.clone(),
+    };
+
+    if let Err(err) = save.save() {
+        log::error!("Failed to autosave game: {}", err);
+    }
+}
+
+// Review of the move to queue, with the evaluations the analyses and the
+// evaluation cache already have
+fn review_job(
+    app: &InteractiveApp,
+    action: azul::Action,
+    think_time: Duration,
+    auto_played: bool,
+    eval_cache: &mut Option<EvalCache>,
+) -> ponder::ReviewJob {
+    let mut known: HashMap<azul::Action, (f32, f32)> = app
+        .analyses
+        .iter()
+        .filter_map(|(action, analysis)| Some((*action, (analysis.expected_score?, analysis.win_probability?))))
+        .collect();
+    if let Some(cache) = eval_cache.as_mut() {
+        if let Some(evaluation) = cache.get(&app.state, app.current_player, action) {
+            known.entry(action).or_insert(evaluation);
+        }
+    }
+
+    ponder::ReviewJob {
+        state: app.state.clone(),
+        player_idx: app.current_player,
+        ply: app.ply,
+        action,
+        think_time,
+        auto_played,
+        known,
+    }
+}
+
+// Keep the finished reviews, adding what was searched for them to the
+// evaluation cache
+fn collect_reviews(reviewer: &mut ponder::Reviewer, reviews: &mut Vec<tui::PlyReview>, eval_cache: &mut Option<EvalCache>) {
+    for reviewed in reviewer.poll() {
+        if let Some(cache) = eval_cache.as_mut() {
+            for (action, expected_score, win_probability) in reviewed.evaluations {
+                cache.insert(&reviewed.review.state, reviewed.review.player, action, (expected_score, win_probability));
+            }
+        }
+        reviews.push(reviewed.review);
+    }
+}
+
+// Play the move for the player to move and pass the turn
+fn play_move(rules: &azul::AzulRules, app: &mut InteractiveApp, action: azul::Action, animation_speed: f32) {
+    let explanation = app.state.format_move(app.current_player, action);
+    let events = rules.take_action(&mut app.state, app.current_player, action);
+    app.animation = animation(events, MOVE_ANIMATION, animation_speed);
+
+    app.last_move = Some(tui::Move {
+        player: app.current_player,
+        action,
+        explanation,
+    });
+
+    // Reset analysis cache
+    app.analyses = HashMap::new();
+
+    app.actions_state.select_first();
+    if let Some(clock) = app.clock.as_mut() {
+        clock.switch(app.current_player);
+    }
+    app.current_player = (app.current_player + 1) % app.state.players.len();
+    app.ply += 1;
+    app.ply_round += 1;
+}
+
+// Show the post game review screen till the user quits
+// Actions by the teacher's expected score where searched, best first, and
+// then in the order of the table
+fn ranked_actions(app: &InteractiveApp) -> Vec<azul::Action> {
+    let mut actions = app.actions.clone();
+    actions.sort_by(|a, b| {
+        let score = |action| app.analyses.get(action).and_then(|analysis| analysis.expected_score);
+        match (score(a), score(b)) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        }
+    });
+    actions
+}
+
+// Built in heuristics followed by the scripted ones
+fn heuristics(rules: &azul::AzulRules, scripts: Option<&script::ScriptSet>) -> Vec<Heuristic> {
+    let greedy_rules = rules.clone();
+    let greedy = move |state: &azul::State, player_idx| play_partial_greedy(&greedy_rules, state, player_idx);
+    let mut heuristics = vec![Heuristic { name: "greedy".to_string(), function: Arc::new(greedy) }];
+    for script in scripts.map_or(&[][..], |set| &set.scripts) {
+        let script = script.clone();
+        let rules = rules.clone();
+        heuristics.push(Heuristic {
+            name: script.name.clone(),
+            function: Arc::new(move |state: &azul::State, player_idx| script.play(&rules, state, player_idx)),
+        });
+    }
+    heuristics
+}
+
+// Show the wall tiling of each player in turn until the last one is confirmed
+// or the rest are skipped
+fn run_tiling_phase(terminal: &mut ratatui::DefaultTerminal, app: &mut InteractiveApp) {
+    loop {
+        if app.animation.as_ref().is_some_and(|a| a.is_done()) {
+            app.animation = None;
+        }
+        terminal.draw(|frame| {
+            frame.render_widget(app.clone(), frame.area());
+        }).unwrap();
+
+        if app.animation.is_some() && !event::poll(ANIMATION_TICK).unwrap() {
+            continue;
+        }
+
+        if let Event::Key(key_event) = event::read().unwrap() {
+            match key_event.code {
+                KeyCode::Enter | KeyCode::Char(' ') if !app.tiling.as_mut().is_some_and(|tiling| tiling.advance()) => break,
+                KeyCode::Char('q') => break,
+                _ => {},
+            }
+        }
+    }
+    app.tiling = None;
+}
+
+fn run_review(
+    terminal: &mut ratatui::DefaultTerminal,
+    reviews: Vec<tui::PlyReview>,
+    reviewer: &mut ponder::Reviewer,
+    eval_cache: &mut Option<EvalCache>,
+) {
+    let mut review_app = tui::ReviewApp {
+        reviews,
+        reviews_state: TableState::default(),
+        pending: reviewer.pending(),
+    };
+    review_app.reviews.sort_by(|a, b| b.loss().total_cmp(&a.loss()));
+    review_app.reviews_state.select_first();
+
+    loop {
+        // Reviews still running are added as they finish, keeping the
+        // selected ply selected
+        if review_app.pending > 0 {
+            let selected = review_app.reviews_state.selected().and_then(|idx| review_app.reviews.get(idx)).map(|r| r.ply);
+            collect_reviews(reviewer, &mut review_app.reviews, eval_cache);
+            review_app.pending = reviewer.pending();
+            review_app.reviews.sort_by(|a, b| b.loss().total_cmp(&a.loss()));
+            match selected {
+                Some(ply) => review_app.reviews_state.select(review_app.reviews.iter().position(|r| r.ply == ply)),
+                None => review_app.reviews_state.select_first(),
+            }
+        }
+
+        terminal.draw(|frame| {
+            frame.render_widget(review_app.clone(), frame.area());
+        }).unwrap();
+
+        if review_app.pending > 0 && !event::poll(Duration::from_millis(250)).unwrap() {
+            continue;
+        }
+
+        if let Event::Key(key_event) = event::read().unwrap() {
+            match key_event.code {
+                KeyCode::Char('q') => break,
+                KeyCode::Down => {
+                    if let Some(idx) = review_app.reviews_state.selected() {
+                        if idx + 1 < review_app.reviews.len() {
+                            review_app.reviews_state.select_next();
+                        }
+                    }
+                },
+                KeyCode::Up => review_app.reviews_state.select_previous(),
+                _ => {}
+            }
+        }
+    }
+}
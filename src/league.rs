@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+use crate::output::{self, Exit, Format, LeagueRating};
+use crate::rating;
+use crate::strategy::Strategy;
+use crate::tournament;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Agent {
+    pub spec: String,
+    pub rating: f64,
+    pub games: usize,
+    // Rating after each match the agent played
+    pub history: Vec<f64>,
+}
+
+// Best agent of the league at some point in time
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    pub after_match: usize,
+    pub spec: String,
+    pub rating: f64,
+}
+
+// A population of agents playing scheduled matches against each other. This
+// is persisted as JSON so a league can continue over many runs.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct League {
+    pub agents: Vec<Agent>,
+    // Number of matches played between each pair of agents
+    pub match_counts: Vec<Vec<usize>>,
+    pub n_matches: usize,
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl League {
+    pub fn load_or_new(file: &PathBuf) -> Result<Self> {
+        if file.exists() {
+            Ok(serde_json::from_reader(File::open(file)?)?)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, file: &PathBuf) -> Result<()> {
+        let writer = BufWriter::new(File::create(file)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    // Add an agent unless one with the same spec is already present
+    pub fn add_agent(&mut self, spec: &str) {
+        if self.agents.iter().any(|a| a.spec == spec) {
+            return;
+        }
+
+        self.agents.push(Agent {
+            spec: spec.to_string(),
+            rating: rating::INITIAL_RATING,
+            games: 0,
+            history: Vec::new(),
+        });
+
+        for counts in self.match_counts.iter_mut() {
+            counts.push(0);
+        }
+        self.match_counts.push(vec![0; self.agents.len()]);
+    }
+
+    // Pick the pairing whose outcome is least certain. Pairings with expected
+    // scores close to 0.5 are preferred and the preference decays with the
+    // number of matches already played between the two agents.
+    pub fn next_pairing(&self) -> (usize, usize) {
+        let mut best = (0, 1);
+        let mut best_priority = f64::MIN;
+
+        for i in 0..self.agents.len() {
+            for j in (i + 1)..self.agents.len() {
+                let expected = rating::expected_score(self.agents[i].rating, self.agents[j].rating);
+                let uncertainty = 1.0 - 2.0 * (expected - 0.5).abs();
+                let priority = uncertainty / (1.0 + self.match_counts[i][j] as f64);
+
+                if priority > best_priority {
+                    best_priority = priority;
+                    best = (i, j);
+                }
+            }
+        }
+
+        best
+    }
+
+    pub fn best_agent(&self) -> &Agent {
+        self.agents.iter().max_by(|a, b| a.rating.total_cmp(&b.rating)).unwrap()
+    }
+
+    // Update ratings with the results of a match from the first agent's view
+    pub fn record_match(&mut self, i: usize, j: usize, results: (usize, usize, usize)) {
+        let (i_wins, j_wins, draws) = results;
+        let scores = [(i_wins, 1.0), (j_wins, 0.0), (draws, 0.5)];
+
+        for (count, score) in scores {
+            for _ in 0..count {
+                let (ri, rj) = rating::update(self.agents[i].rating, self.agents[j].rating, score);
+                self.agents[i].rating = ri;
+                self.agents[j].rating = rj;
+            }
+        }
+
+        let n_games = i_wins + j_wins + draws;
+        for k in [i, j] {
+            self.agents[k].games += n_games;
+            let rating = self.agents[k].rating;
+            self.agents[k].history.push(rating);
+        }
+
+        self.match_counts[i][j] += 1;
+        self.match_counts[j][i] += 1;
+        self.n_matches += 1;
+    }
+
+    pub fn snapshot(&mut self) {
+        let best = self.best_agent();
+        let snapshot = Snapshot {
+            after_match: self.n_matches,
+            spec: best.spec.clone(),
+            rating: best.rating,
+        };
+        self.snapshots.push(snapshot);
+    }
+}
+
+pub fn run(specs: &[String], file: &PathBuf, n_matches: usize, games_per_match: usize, snapshot_every: usize, format: Format) -> Result<()> {
+    let mut league = League::load_or_new(file)?;
+    for spec in specs {
+        league.add_agent(spec);
+    }
+
+    if league.agents.len() < 2 {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("League needs at least two agents")));
+    }
+
+    let strategies = league.agents.iter().map(|a| Strategy::parse(&a.spec)).collect::<Result<Vec<Strategy>>>()?;
+    let rules = azul::AzulRules::current();
+
+    for _ in 0..n_matches {
+        let (i, j) = league.next_pairing();
+        let results = tournament::tally(&tournament::play_match(&rules, &strategies[i], &strategies[j], games_per_match, None, rand::random()));
+        league.record_match(i, j, results);
+        rating::record_matches(&[(strategies[i].spec.clone(), strategies[j].spec.clone(), results)], "league")?;
+
+        output::progress(format, &format!(
+            "Match {}: {} vs {}: {}-{} ({} drawn)",
+            league.n_matches, strategies[i].spec, strategies[j].spec, results.0, results.1, results.2
+        ));
+
+        if snapshot_every > 0 && league.n_matches % snapshot_every == 0 {
+            league.snapshot();
+            let snapshot = league.snapshots.last().unwrap();
+            output::progress(format, &format!("Snapshot: best agent is {} ({:.1})", snapshot.spec, snapshot.rating));
+        }
+
+        // Saving after every match so an interrupted league loses nothing
+        league.save(file)?;
+    }
+
+    let mut agents = league.agents.clone();
+    agents.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+
+    if format != Format::Text {
+        let rows: Vec<LeagueRating> = agents
+            .iter()
+            .enumerate()
+            .map(|(rank, agent)| LeagueRating { rank: rank + 1, spec: agent.spec.clone(), rating: agent.rating, games: agent.games })
+            .collect();
+        return output::write_rows(&rows, format);
+    }
+
+    println!("Ratings after {} matches:", league.n_matches);
+    for (rank, agent) in agents.iter().enumerate() {
+        println!("{:>3}. {} rating: {:.1}, games: {}", rank + 1, agent.spec, agent.rating, agent.games);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_pairing() {
+        let mut league = League::default();
+        for spec in ["random", "greedy", "mcts"] {
+            league.add_agent(spec);
+        }
+
+        league.agents[0].rating = 1200.0;
+        assert_eq!(league.next_pairing(), (1, 2));
+
+        // Repeated matches make the evenly rated pair less attractive
+        league.record_match(1, 2, (0, 0, 1));
+        league.record_match(1, 2, (0, 0, 1));
+        league.record_match(1, 2, (0, 0, 1));
+        assert_ne!(league.next_pairing(), (1, 2));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::games::{azul, Rules};
+use crate::playlog::{self, GameEnd, LogRecord, Ply, LOG_VERSION};
+use crate::strategy::Forfeit;
+
+// Simulation logs went through these versions:
+//
+// 0. One untagged row per ply, with `init`, `reset-round`, `game-over`,
+//    `round-cap`, and `forfeit` rows marking the rest of the game
+// 1. Tagged `LogRecord` records
+// 2. Tagged records opened by a run header, which carries the version from here
+//    on
+// 3. Game ends with no winner for draws, which used to go to one of the tied
+//    players
+//
+// Old logs are migrated to the current records when read so every command
+// works on historical data.
+
+// Row of a version 0 log. Ids are -1 on the rows that are not plies.
+#[derive(Debug, Clone, serde::Deserialize)]
+struct LegacyPly {
+    game_id: usize,
+    round_id: i32,
+    ply_id: i32,
+    player_id: i32,
+    action: String,
+    state: String,
+    score: i32,
+    applicable_partials: Vec<String>,
+    matching_partials: Vec<String>,
+    #[serde(default)]
+    analysis: String,
+    #[serde(default)]
+    events: Vec<azul::GameEvent>,
+    #[serde(default)]
+    incident: Option<String>,
+}
+
+// Tell the version of a log from its first record
+pub fn detect_version(first_line: &str) -> Result<usize> {
+    let value: serde_json::Value = serde_json::from_str(first_line)?;
+    match value.get("record").and_then(|r| r.as_str()) {
+        Some("run") => Ok(value.get("log_version").and_then(|v| v.as_u64()).map_or(2, |v| v as usize)),
+        Some(_) => Ok(1),
+        None if value.get("ply_id").is_some() && value.get("action").is_some() => Ok(0),
+        None => Err(anyhow!("Unrecognized log record: {}", first_line)),
+    }
+}
+
+// Progress of one game while its version 0 rows are migrated
+#[derive(Default)]
+struct LegacyGame {
+    round_id: usize,
+    // State after the last ply of the round, to be scored at the round end
+    last_ply_state: Option<String>,
+}
+
+// Records for the round that ended after the last ply, scored the way the
+// simulation scored it. Events logged with the next row are kept if there
+// are any.
+fn legacy_round_end(rules: &azul::AzulRules, game_id: usize, game: &mut LegacyGame, events: &[azul::GameEvent]) -> Result<Option<LogRecord>> {
+    let Some(state) = game.last_ply_state.take() else {
+        return Ok(None);
+    };
+    let mut state: azul::State = serde_json::from_str(&state)?;
+    let scoring = rules.end_round(&mut state);
+    let record = LogRecord::RoundEnd {
+        game_id,
+        round_id: game.round_id,
+        scores: state.players.iter().map(|p| p.score).collect(),
+        state: serde_json::to_string(&state)?,
+        events: if events.is_empty() { scoring } else { events.to_vec() },
+    };
+    game.round_id += 1;
+    Ok(Some(record))
+}
+
+// Turn version 0 rows into records. Rows of games played in parallel are
+// interleaved so every game is followed on its own. Strategies and seeds were
+// not logged then, so seats are named by their index and seeds are 0.
+fn migrate_legacy(rows: Vec<LegacyPly>) -> Result<Vec<LogRecord>> {
+    let rules = azul::AzulRules::current();
+    let mut games: HashMap<usize, LegacyGame> = HashMap::new();
+    let mut records = Vec::new();
+
+    for row in rows {
+        let game_id = row.game_id;
+        let game = games.entry(game_id).or_default();
+
+        match row.action.as_str() {
+            "init" => {
+                let state: azul::State = serde_json::from_str(&row.state)?;
+                records.push(LogRecord::GameStart {
+                    game_id,
+                    seed: 0,
+                    players: (0..state.players.len()).map(|i| format!("P{}", i)).collect(),
+                    state: row.state,
+                });
+            },
+            "reset-round" => {
+                records.extend(legacy_round_end(&rules, game_id, game, &row.events)?);
+                records.push(LogRecord::RoundStart { game_id, round_id: game.round_id, state: row.state });
+            },
+            "game-over" | "round-cap" => {
+                // The row has the scored state of the last round
+                game.last_ply_state = None;
+                let state: azul::State = serde_json::from_str(&row.state)?;
+                let final_scores: Vec<i32> = state.players.iter().map(|p| p.score).collect();
+                records.push(LogRecord::RoundEnd {
+                    game_id,
+                    round_id: game.round_id,
+                    scores: final_scores.clone(),
+                    state: row.state,
+                    events: row.events,
+                });
+                records.push(LogRecord::GameEnd {
+                    game_id,
+                    winner: azul::winner(&state),
+                    final_scores,
+                    end: if row.action == "round-cap" { GameEnd::RoundCap } else { GameEnd::Finished },
+                });
+            },
+            "forfeit" => {
+                let state: azul::State = serde_json::from_str(&row.state)?;
+                let seat = row.player_id as usize;
+                let winner = (0..state.players.len())
+                    .filter(|&i| i != seat)
+                    .max_by_key(|&i| state.players[i].score);
+                records.push(LogRecord::GameEnd {
+                    game_id,
+                    winner,
+                    final_scores: state.players.iter().map(|p| p.score).collect(),
+                    end: GameEnd::Forfeit(Forfeit { seat, reason: row.incident.unwrap_or_default() }),
+                });
+            },
+            _ => {
+                if row.round_id < 0 || row.ply_id < 0 || row.player_id < 0 {
+                    return Err(anyhow!("Unknown row '{}' in game {}", row.action, game_id));
+                }
+                game.last_ply_state = Some(row.state.clone());
+                records.push(LogRecord::Ply(Ply {
+                    game_id,
+                    round_id: row.round_id as usize,
+                    ply_id: row.ply_id as usize,
+                    player_id: row.player_id as usize,
+                    action: row.action,
+                    state: row.state,
+                    score: row.score,
+                    applicable_partials: row.applicable_partials,
+                    matching_partials: row.matching_partials,
+                    analysis: row.analysis,
+                    events: row.events,
+                    incident: row.incident,
+                }));
+            },
+        }
+    }
+
+    Ok(records)
+}
+
+// Read a simulation log of any version as current records
+pub fn read(file: &PathBuf) -> Result<Vec<LogRecord>> {
+    let reader = BufReader::new(File::open(file)?);
+    let lines: Vec<String> = reader
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .collect::<Result<_, _>>()?;
+    let Some(first_line) = lines.first() else {
+        return Ok(Vec::new());
+    };
+
+    match detect_version(first_line)? {
+        0 => {
+            log::info!("Migrating version 0 log {}", file.display());
+            let rows = lines.iter().map(|line| serde_json::from_str(line)).collect::<Result<Vec<LegacyPly>, _>>()?;
+            migrate_legacy(rows)
+        },
+        version if version <= LOG_VERSION => playlog::parse(&lines),
+        version => Err(anyhow!(
+            "{} is a version {} log, newer than the version {} this luza reads",
+            file.display(),
+            version,
+            LOG_VERSION
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::GameState;
+
+    #[test]
+    fn test_detect_version() {
+        assert_eq!(detect_version(r#"{"game_id":0,"round_id":-1,"ply_id":-1,"action":"init"}"#).unwrap(), 0);
+        assert_eq!(detect_version(r#"{"record":"game-start","game_id":0}"#).unwrap(), 1);
+        assert_eq!(detect_version(r#"{"record":"run","version":"0.1.0"}"#).unwrap(), 2);
+        assert_eq!(detect_version(r#"{"record":"run","log_version":5}"#).unwrap(), 5);
+        assert!(detect_version(r#"{"name":"x"}"#).is_err());
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        let legacy_row = |action: &str, state: &azul::State, ids: (i32, i32, i32)| LegacyPly {
+            game_id: 4,
+            round_id: ids.0,
+            ply_id: ids.1,
+            player_id: ids.2,
+            action: action.to_string(),
+            state: serde_json::to_string(state).unwrap(),
+            score: 0,
+            applicable_partials: Vec::new(),
+            matching_partials: Vec::new(),
+            analysis: String::new(),
+            events: Vec::new(),
+            incident: None,
+        };
+
+        let mut rows = vec![legacy_row("init", &state, (-1, -1, -1))];
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        rows.push(legacy_row("reset-round", &state, (-1, -1, -1)));
+        let mut player_idx = 0;
+        let mut ply_id = 0;
+        while !state.is_round_over() {
+            let action = rules.list_valid_actions(&state, player_idx)[0];
+            rules.take_action(&mut state, player_idx, action);
+            rows.push(legacy_row(&serde_json::to_string(&action).unwrap(), &state, (0, ply_id, player_idx as i32)));
+            player_idx = 1 - player_idx;
+            ply_id += 1;
+        }
+        let n_plies = ply_id as usize;
+        rules.end_round(&mut state);
+        let scored = state.clone();
+        azul::with_seed(2, || rules.refill(&mut state, &mut azul::game_rng()));
+        rows.push(legacy_row("reset-round", &state, (-1, -1, -1)));
+        rows.push(legacy_row("forfeit", &state, (1, ply_id, 1)));
+
+        let records = migrate_legacy(rows).unwrap();
+        assert_eq!(records.len(), n_plies + 5);
+        assert!(matches!(&records[0], LogRecord::GameStart { game_id: 4, players, .. } if players.len() == 2));
+        assert!(matches!(&records[1], LogRecord::RoundStart { round_id: 0, .. }));
+        assert!(matches!(&records[n_plies + 2], LogRecord::RoundEnd { round_id: 0, state, .. } if *state == serde_json::to_string(&scored).unwrap()));
+        assert!(matches!(&records[n_plies + 3], LogRecord::RoundStart { round_id: 1, .. }));
+        assert!(matches!(&records[n_plies + 4], LogRecord::GameEnd { winner: Some(0), end: GameEnd::Forfeit(Forfeit { seat: 1, .. }), .. }));
+    }
+}
@@ -1,438 +1,1133 @@
-use games::azul::play_partial_greedy;
-use ratatui::widgets::TableState;
-use tui::{ActionAnalysis, Heuristic, InteractiveApp};
-use std::fs::File;
-use std::io::BufWriter;
-use std::{collections::HashMap, path::PathBuf};
+// Helpers only the TUI uses are left unused in builds without it
+#![cfg_attr(not(feature = "tui"), allow(dead_code))]
+
+use luza_core::games;
+#[cfg(feature = "tui")]
+use luza_tui as tui;
+use sound::Sound;
+use playlog::LogRecord;
+use strategy::Strategy;
+use output::{Exit, OrExit};
+use std::{collections::HashMap, io, path::PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use rayon::iter::ParallelIterator;
 
-use crossterm::event::{self, Event, KeyCode};
-use games::{azul, Validate, GameState};
-use rayon::iter::IntoParallelIterator;
-use clap::{Parser, Subcommand};
+use games::{azul, Validate, Rules};
+use games::clock::TimeControl;
+use par::prelude::*;
+use clap::{CommandFactory, Parser, Subcommand};
+use signal_hook::consts::SIGINT;
 
-mod games;
-mod tui;
+mod accuracy;
+mod autosave;
+mod batch;
+mod bestmove;
+mod blunders;
+mod completions;
+mod counting;
+#[cfg(feature = "dataset")]
+mod dataset;
+mod doctor;
+#[cfg(feature = "db")]
+mod db;
+mod engine;
+#[cfg(feature = "tui")]
+mod eval_cache;
+mod explorer;
+#[cfg(test)]
+mod fixture;
+mod gate;
+#[cfg(feature = "nn")]
+mod inference;
+#[cfg(feature = "tui")]
+mod interactive;
+mod league;
+mod logreader;
+mod observed;
+mod par;
+#[cfg(feature = "nn")]
+mod policy;
+#[cfg(feature = "tui")]
+mod ponder;
+mod output;
+mod playlog;
+mod plugin;
+mod profile;
+#[cfg(feature = "tui")]
+mod puzzle;
+mod rating;
+mod regress;
+mod script;
+mod sensitivity;
+mod smoke;
+mod sound;
+#[cfg(feature = "arrow")]
+mod stats;
+mod strategy;
+mod study;
+mod text;
+mod tournament;
+mod treedump;
+#[cfg(feature = "nn")]
+mod train;
+mod verify;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
     #[command(subcommand)]
     commands: Commands,
+    /// TOML file with house rules, standard rules are used otherwise
+    #[arg(long, global = true)]
+    rules: Option<PathBuf>,
+    /// Points added to each seat's final score like 0,3, overriding the
+    /// handicaps of the rules
+    #[arg(long, global = true, value_delimiter = ',', allow_negative_numbers = true)]
+    handicap: Vec<i32>,
+    /// Directory of strategy plugins, `plugins` in the data directory is used
+    /// if it exists
+    #[arg(long, global = true)]
+    plugins: Option<PathBuf>,
+    /// Output format for the results of simulate, tournament, league, ratings,
+    /// sensitivity, and stats
+    #[arg(long, global = true, value_enum, default_value_t = output::Format::Text)]
+    format: output::Format,
 }
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Simulate games between strategies and write every move to a log
     Simulate {
+        /// Simulation log to write, as JSONL
         #[arg(short)]
         log_file: PathBuf,
+        /// Optional SQLite database to write normalized results into
+        #[arg(long)]
+        log_db: Option<PathBuf>,
+        /// Strategy specs, one per seat, defaulting to greedy against mcts
+        #[arg(short, long)]
+        players: Vec<String>,
+        /// Log top MCTS evaluations with principal variations for every ply
+        #[arg(long)]
+        log_analysis: bool,
+        /// Seed for all games, random and printed if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Directory of heuristic scripts to match against moves along with
+        /// the built in ones
+        #[arg(long)]
+        heuristics: Option<PathBuf>,
+        /// Game to play, only azul for now
         game: String,
     },
-    Interactive {
+    /// Play a game interactively, `play` is kept as a shorter alias
+    #[command(alias = "play")]
+    Interactive(InteractiveArgs),
+    /// Answer canned questions over a simulation log
+    #[cfg(feature = "arrow")]
+    Stats {
+        /// Simulation log to read
+        #[arg(short, long)]
+        log_file: PathBuf,
+        /// Question to answer
+        #[arg(short, long, value_enum)]
+        query: stats::Query,
+    },
+    /// Score every logged move against the engine's best move and report the
+    /// accuracy of each strategy
+    Analyze {
+        /// Simulation log to read
+        #[arg(short, long)]
+        log_file: PathBuf,
+        /// Analyze only this game of the log
+        #[arg(long)]
+        game_id: Option<usize>,
+    },
+    /// Summarize every simulation log in a directory, with engine accuracy of
+    /// each strategy, into one CSV or JSON file. Logs summarized before are
+    /// kept in a manifest and skipped.
+    AnalyzeAll {
+        /// Directory of simulation logs
+        #[arg(long)]
+        dir: PathBuf,
+        /// Summary file, .parquet, .csv, or .json
+        #[arg(long)]
+        out: PathBuf,
+        /// Manifest of analyzed logs, .luza-analyze-all.json in the directory
+        /// by default
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+    },
+    /// Find the logged moves that lost the most against the engine's best move
+    Blunders {
+        /// Simulation log to read
+        #[arg(short, long)]
+        log_file: PathBuf,
+        /// Number of blunders to list
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+        /// Look only at this game of the log
+        #[arg(long)]
+        game_id: Option<usize>,
+    },
+    /// Compare the engine's advice in logged positions with advice from only
+    /// what a player remembers of the lid, to see how much counting tiles
+    /// matters
+    Counting {
+        /// Simulation log to read
+        #[arg(short, long)]
+        log_file: PathBuf,
+        /// Share of each color in the lid the player remembers, from 0 to 1
+        #[arg(long, default_value_t = 0.0)]
+        memory: f64,
+        /// Look only at this game of the log
+        #[arg(long)]
+        game_id: Option<usize>,
+    },
+    /// Write a Markdown study sheet of a saved interactive game with the
+    /// engine's evaluation of every move and the boards at the key moments
+    ExportMd {
+        /// Saved interactive game
+        #[arg(long)]
+        save: PathBuf,
+        /// Markdown file to write
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Index the moves of simulation logs (or directories of them) for the
+    /// opening explorer
+    Index {
+        /// Simulation logs or directories of them
+        #[arg(short, long, required = true)]
+        log_file: Vec<PathBuf>,
+        /// Index file to write
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Moves played from a position in an indexed corpus with their win rates
+    Explore {
+        /// Index written by `index`
+        #[arg(short, long)]
+        index: PathBuf,
+        /// State file, - to read from stdin
+        #[arg(long)]
+        state: PathBuf,
+        /// Player to move
+        #[arg(long, default_value_t = 0)]
+        player: usize,
+        /// Moves to play from the state first, like `-m 'D2 Blue 3' -m 'C Red F'`
+        #[arg(short, long = "move")]
+        moves: Vec<String>,
+    },
+    /// Drill finding the engine's best move in positions from a puzzle set,
+    /// like the one `blunders` writes with --format json
+    #[cfg(feature = "tui")]
+    Puzzle {
+        /// Puzzle set file
+        #[arg(long)]
+        set: PathBuf,
+        /// Guesses allowed per puzzle
+        #[arg(long, default_value_t = 3)]
+        tries: usize,
+    },
+    /// Export every move in a simulation log as state features for training
+    #[cfg(feature = "dataset")]
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Dataset {
+        #[command(subcommand)]
+        command: Option<DatasetCommands>,
+        /// Simulation log to read
+        #[arg(short, long, required = true)]
+        log_file: Option<PathBuf>,
+        /// Dataset file to write
+        #[arg(short, long, required = true)]
+        out: Option<PathBuf>,
+        /// Scale all features to [0, 1]
+        #[arg(long)]
+        normalize: bool,
+        /// Start the player boards from the player to move
+        #[arg(long)]
+        mover_first: bool,
+        /// Extra samples per move with the factory displays shuffled
+        #[arg(long, default_value_t = 0)]
+        augment: usize,
+        /// Write repeated games too instead of only their first copy
+        #[arg(long)]
+        keep_duplicates: bool,
+        /// Write fixed width binary records and an index instead of JSONL
+        #[arg(long)]
+        binary: bool,
+        /// Split the games into files by fraction like train=0.8,val=0.1,test=0.1
+        #[arg(long)]
+        split: Option<String>,
+        /// Give every split its share of games of each length
+        #[arg(long, value_enum, requires = "split")]
+        stratify_by: Option<dataset::Stratify>,
+    },
+    /// Improve a policy model by alternating self-play, training, and a gated
+    /// match against the best model so far
+    #[cfg(feature = "nn")]
+    Train {
+        /// TOML file with the training settings
+        #[arg(short, long)]
+        config: PathBuf,
+    },
+    /// Replay a simulation log and check that logged states and scores match
+    Verify {
+        /// Simulation log to check
+        #[arg(short, long)]
+        log_file: PathBuf,
+    },
+    /// Print the engine's move for a state given as JSON
+    Bestmove {
+        /// Game of the state, only azul for now
+        #[arg(long, default_value = "azul")]
         game: String,
+        /// State file, - to read from stdin
+        #[arg(long)]
+        state: PathBuf,
+        /// Read the state file as a position observed on a real table, with
+        /// tiles listed by color
+        #[arg(long)]
+        observed: bool,
+        /// Player to move, unless an observed position names one
+        #[arg(long, default_value_t = 0)]
+        player: usize,
+        /// Search only what a player remembering this share of each color in
+        /// the lid knows, from 0 to 1, instead of counting every tile
+        #[arg(long)]
+        memory: Option<f64>,
+        /// Search time like 2s or 500ms
+        #[arg(long, default_value = "1s")]
+        budget: String,
+        /// Also list the best k actions with their evaluations
+        #[arg(long)]
+        top_k: Option<usize>,
+        /// Start rollouts from only this many actions with the best immediate
+        /// reward, widening as rollouts are done. 0 searches all actions.
+        #[arg(long, default_value_t = 0)]
+        widening: usize,
+        /// Print the current best move every interval like 250ms while
+        /// searching
+        #[arg(long)]
+        info: Option<String>,
+        /// Write the top of the search tree to a .json or GraphViz .dot file
+        #[arg(long)]
+        dump_tree: Option<PathBuf>,
+    },
+    /// Show summary of a player profile's match history
+    Profile {
+        /// Name of the profile
+        name: String,
+    },
+    /// Run a two player tournament between strategies given as spec strings,
+    /// one per --players flag
+    Tournament {
+        /// Strategy spec of an entrant, one per flag
+        #[arg(short, long, required = true)]
+        players: Vec<String>,
+        /// How entrants are paired in each round
+        #[arg(long, value_enum, default_value_t = tournament::Pairing::RoundRobin)]
+        pairing: tournament::Pairing,
+        /// Number of rounds for Swiss pairing
+        #[arg(long, default_value_t = 5)]
+        rounds: usize,
+        /// Games played in each match, seats alternating
+        #[arg(long, default_value_t = 2)]
+        games_per_match: usize,
+        /// Clocks for each game as <base>+<increment> seconds, a player whose
+        /// flag falls loses the game
+        #[arg(long)]
+        time_control: Option<TimeControl>,
+        /// Seed all games derive their seeds from, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Write the seed, rules, and seating of every game to this JSON file
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+        /// Game to play, only azul for now
+        game: String,
+    },
+    /// Run tournaments declared per game in a JSON config file and report the
+    /// standings of each
+    MultiTournament {
+        /// Config file declaring the tournaments
+        #[arg(short, long)]
+        config: PathBuf,
+    },
+    /// Decide whether a candidate strategy beats a baseline with a sequential
+    /// probability ratio test, stopping as soon as the result is significant
+    Gate {
+        /// Strategy spec of the candidate
+        #[arg(long)]
+        candidate: String,
+        /// Strategy spec of the baseline
+        #[arg(long)]
+        baseline: String,
+        /// Elo difference of H0, that the candidate is not stronger
+        #[arg(long, default_value_t = 0.0)]
+        elo0: f64,
+        /// Elo difference of H1, that the candidate is stronger
+        #[arg(long, default_value_t = 20.0)]
+        elo1: f64,
+        /// Error rate of accepting H1 when H0 holds
+        #[arg(long, default_value_t = 0.05)]
+        alpha: f64,
+        /// Error rate of accepting H0 when H1 holds
+        #[arg(long, default_value_t = 0.05)]
+        beta: f64,
+        /// Games played between checks of the test
+        #[arg(long, default_value_t = 10)]
+        batch: usize,
+        /// Stop undecided after this many games
+        #[arg(long, default_value_t = 2000)]
+        max_games: usize,
+        /// Seed all games derive their seeds from, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Play the current build against an older luza binary with the same
+    /// strategy over the engine protocol and report the Elo difference, to
+    /// catch strength regressions before a release
+    Regress {
+        /// Older luza binary to play against
+        #[arg(long)]
+        baseline_bin: PathBuf,
+        /// Strategy spec played by both builds
+        #[arg(long, default_value = "mcts")]
+        strategy: String,
+        /// Number of games to play
+        #[arg(long, default_value_t = 100)]
+        games: usize,
+        /// Seed all games derive their seeds from, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Play as an external engine over stdin and stdout, answering `genmove`
+    /// requests with the strategy, for `exec:` players of other programs
+    Engine {
+        /// Strategy spec to play with
+        #[arg(long, default_value = "mcts")]
+        strategy: String,
+        /// Answer bestmove queries as JSON lines on stdin instead, searching
+        /// with the settings of an mcts strategy and keeping the searches
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Play one game of a tournament again from its manifest, printing the
+    /// moves, or print the moves and notes of a saved interactive game
+    Replay {
+        /// Tournament manifest written with --manifest
+        #[arg(short, long, required_unless_present = "saved_game", requires = "game_id")]
+        manifest: Option<PathBuf>,
+        /// Game id as listed in the manifest
+        #[arg(long)]
+        game_id: Option<usize>,
+        /// Interactive game kept in `games` in the data directory
+        #[arg(long, conflicts_with = "manifest")]
+        saved_game: Option<PathBuf>,
+    },
+    /// Leaderboard of the ratings kept across tournament, league, and train
+    /// runs
+    Ratings {
+        /// Print the rating of every strategy after each of its matches
+        #[arg(long)]
+        history: bool,
+    },
+    /// Sweep one rule parameter over a set of values and report how win rates
+    /// and scores of the strategies shift. Rules not swept come from --rules.
+    Sensitivity {
+        /// Rule parameter to vary, like column_bonus or floor_penalties
+        #[arg(long)]
+        parameter: String,
+        /// Values to try as JSON, one per flag, like 7 or "[1, 2, 3]"
+        #[arg(short, long, required = true)]
+        values: Vec<String>,
+        /// Strategy specs, one per seat, defaulting to greedy against mcts
+        #[arg(short, long)]
+        players: Vec<String>,
+        /// Games played for each value
+        #[arg(long, default_value_t = 100)]
+        games: usize,
+        /// Seed for the games, the same games are played for every value
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Game to play, only azul for now
+        game: String,
+    },
+    /// Keep playing matches between a population of agents, prioritizing the
+    /// most uncertain pairings, and track their ratings over time
+    League {
+        /// League state file, created if missing and resumed otherwise
+        #[arg(short, long)]
+        league_file: PathBuf,
+        /// Agents to add to the league as strategy specs
+        #[arg(short, long)]
+        players: Vec<String>,
+        /// Matches to play in this run
+        #[arg(long, default_value_t = 10)]
+        matches: usize,
+        /// Games played in each match, seats alternating
+        #[arg(long, default_value_t = 2)]
+        games_per_match: usize,
+        /// Record the best agent after every these many matches
+        #[arg(long, default_value_t = 5)]
+        snapshot_every: usize,
+        /// Game to play, only azul for now
+        game: String,
+    },
+    /// Print a shell completion script, like `luza completions bash >
+    /// /etc/bash_completion.d/luza`
+    Completions {
+        /// Shell to print the script for
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+    /// Print the man page in roff, for `luza man | man -l -`, or write the
+    /// pages of every subcommand too
+    Man {
+        /// Directory to write the man pages of every subcommand to
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Check that games play correctly, how fast rollouts are, and that the
+    /// rules, data directory, and terminal are usable
+    Doctor,
+    /// Play a few seeded games with every strategy, checking rule invariants
+    /// and that replays end the same, and exit with an error on any failure
+    Smoke,
+}
+
+#[cfg(feature = "dataset")]
+#[derive(Subcommand)]
+enum DatasetCommands {
+    /// Summarize a binary dataset after checking it against its index
+    Inspect {
+        /// Binary dataset written with --binary
+        file: PathBuf,
+        /// Also print this record as JSON
+        #[arg(long)]
+        record: Option<usize>,
     },
 }
 
-// One ply in the game log, the string representations here are serialized data
-// points and not only vectors
-#[derive(Debug, Clone, serde::Serialize)]
-struct PlayLogPly {
+#[derive(clap::Args)]
+struct InteractiveArgs {
+    /// Replies worth more than this many points are highlighted as gifts
+    #[arg(long, default_value_t = 5)]
+    gift_threshold: i32,
+    /// Policy used by all players when previewing rest of the round
+    #[arg(long, value_enum, default_value_t = Policy::Greedy)]
+    preview_policy: Policy,
+    /// Profile to record the game in, if any
+    #[arg(long)]
+    name: Option<String>,
+    /// Seat played by the profile owner
+    #[arg(long, default_value_t = 0)]
+    seat: usize,
+    /// Number of players at the table, from 2 to 4
+    #[arg(long, default_value_t = 3)]
+    n_players: usize,
+    /// Strength of the teacher's moves, can be changed during the game
+    #[arg(long, value_enum, default_value_t = Difficulty::Hard)]
+    difficulty: Difficulty,
+    /// Evaluate human moves against the teacher and review them after the game
+    #[arg(long)]
+    review: bool,
+    /// Clocks for all players as <base>+<increment> seconds
+    #[arg(long)]
+    time_control: Option<TimeControl>,
+    /// Directory of heuristic scripts for the heuristic panels, reloaded when
+    /// they change
+    #[arg(long)]
+    heuristics: Option<PathBuf>,
+    /// Don't search for the teacher's move and analyses in the background
+    /// while the human is thinking
+    #[arg(long)]
+    no_ponder: bool,
+    /// Line based play on stdin and stdout instead of the TUI
+    #[arg(long)]
+    no_tui: bool,
+    /// Reuse action evaluations from earlier sessions and keep new ones
+    #[arg(long)]
+    eval_cache: bool,
+    /// Ring the terminal bell on invalid keys, round ends, and blunders
+    #[arg(long)]
+    sound: bool,
+    /// Score rounds right away instead of going through the wall tiling of
+    /// every player first
+    #[arg(long)]
+    no_tiling_phase: bool,
+    /// Play moves without asking when there is no real choice, toggled with
+    /// <o> during the game
+    #[arg(long)]
+    autoplay_forced: bool,
+    /// Speed multiplier for move and scoring animations, 0 turns them off
+    #[arg(long, default_value_t = 1.0)]
+    animation_speed: f32,
+    /// Two analysis engines like `mcts` and `mcts:n_games=5000` whose best
+    /// actions for the position are compared side by side with <v>
+    #[arg(long)]
+    compare: Vec<String>,
+    /// Start from a position observed on a real table instead of a new game,
+    /// - to read from stdin
+    #[arg(long)]
+    observed: Option<PathBuf>,
+    /// Game to play, only azul for now
+    game: String,
+}
+
+// Final outcome of one simulated game, only read back to write the results
+// database
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "db"), allow(dead_code))]
+struct GameResult {
     game_id: usize,
-    round_id: i32,
-    ply_id: i32,
-    player_id: i32,
-    action: String,
-    state: String,
-    score: i32,
-    applicable_partials: Vec<String>,
-    matching_partials: Vec<String>
+    // None for a draw
+    winner: Option<usize>,
+    scores: Vec<i32>,
+    rounds: usize,
 }
 
-type PlayFn = fn(&azul::State, usize) -> azul::Action;
-type PartialPlayFn = fn(&azul::State, usize) -> Option<azul::Action>;
+type PlayFn = fn(&azul::AzulRules, &azul::State, usize) -> azul::Action;
 
-type PlayLog = Vec<PlayLogPly>;
+// Strength of the engine when playing against humans
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    fn config(&self) -> azul::MctsConfig {
+        match self {
+            Difficulty::Beginner => azul::MctsConfig { n_games: 20, epsilon: 0.3, blunder_rate: 0.4, max_blunder_loss: 10.0, ..azul::MctsConfig::default() },
+            Difficulty::Easy => azul::MctsConfig { n_games: 50, epsilon: 0.2, blunder_rate: 0.25, max_blunder_loss: 6.0, ..azul::MctsConfig::default() },
+            Difficulty::Medium => azul::MctsConfig { n_games: 100, epsilon: 0.1, blunder_rate: 0.1, max_blunder_loss: 3.0, ..azul::MctsConfig::default() },
+            Difficulty::Hard => azul::MctsConfig::default(),
+        }
+    }
 
-fn write_play_log(play_log: &PlayLog, file: &PathBuf) {
-    let file = File::create(file).unwrap();
-    let mut writer = BufWriter::new(file);
-    for item in play_log {
-        jsonl::write(&mut writer, item).unwrap();
+    // Cycle to the next level, wrapping around after the hardest
+    fn next(&self) -> Self {
+        match self {
+            Difficulty::Beginner => Difficulty::Easy,
+            Difficulty::Easy => Difficulty::Medium,
+            Difficulty::Medium => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Beginner,
+        }
     }
 }
 
-fn report(game_log: Vec<usize>, n_players: usize) {
+// Named policies that can be picked from the command line
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Policy {
+    Random,
+    Greedy,
+    Mcts,
+}
+
+impl Policy {
+    fn play_fn(&self) -> PlayFn {
+        match self {
+            Policy::Random => azul::play_random,
+            Policy::Greedy => azul::play_greedy,
+            Policy::Mcts => azul::play_mcts,
+        }
+    }
+}
+type PartialPlayFn = fn(&azul::AzulRules, &azul::State, usize) -> Option<azul::Action>;
+
+type PlayLog = Vec<LogRecord>;
+
+// Number of evaluations logged per ply with --log-analysis
+const LOG_ANALYSIS_TOP_K: usize = 3;
+
+// How a simulated game ended
+enum Outcome {
+    Winner(usize),
+    // Tied on score and completed rows
+    Draw,
+    // Stopped by the round cap without ending by the rules
+    RoundCap,
+    // Lost by the seat whose strategy panicked, with the winner among the rest
+    Forfeit { seat: usize, winner: usize },
+    // Abandoned after Ctrl-C, left out of the log
+    Interrupted,
+}
+
+// Win counts by seat over the games that were played. Games stopped by the
+// round cap are counted on their own and interrupted ones are left out.
+// Forfeited games count as a win for the best of the other seats and draws
+// for nobody.
+fn report(game_log: &[Outcome], n_players: usize, format: output::Format) {
     let mut win_counts: HashMap<usize, usize> = HashMap::from_iter((0..n_players).map(|i| (i, 0)));
-    let total_games = game_log.len();
+    let total_games = game_log.iter().filter(|o| !matches!(o, Outcome::Interrupted)).count();
+    let mut n_capped = 0;
+    let mut n_draws = 0;
+    let mut forfeits = vec![0; n_players];
+
+    if total_games < game_log.len() {
+        output::progress(format, &format!("Interrupted, partial results ({} of {} games)", total_games, game_log.len()));
+    }
 
-    for i in game_log {
-        let new_score = win_counts[&i] + 1;
-        if let Some(current) = win_counts.get_mut(&i) {
-            *current = new_score;
+    for outcome in game_log {
+        match outcome {
+            Outcome::Winner(i) => *win_counts.get_mut(i).unwrap() += 1,
+            Outcome::Draw => n_draws += 1,
+            Outcome::RoundCap => n_capped += 1,
+            Outcome::Forfeit { seat, winner } => {
+                *win_counts.get_mut(winner).unwrap() += 1;
+                forfeits[*seat] += 1;
+            },
+            Outcome::Interrupted => {},
         }
     }
 
+    if format != output::Format::Text {
+        let rows: Vec<output::SeatWins> = (0..n_players)
+            .map(|i| output::SeatWins {
+                seat: i,
+                wins: win_counts[&i],
+                games: total_games,
+                draws: n_draws,
+                round_capped: n_capped,
+                forfeits: forfeits[i],
+            })
+            .collect();
+        if let Err(err) = output::write_rows(&rows, format) {
+            eprintln!("{}", err);
+        }
+        return;
+    }
+
     for i in 0..n_players {
         println!("Win Count for P{}: {}/{}, ratio: {}", i, win_counts[&i], total_games, (win_counts[&i] as f64 / total_games as f64));
     }
+    if n_draws > 0 {
+        println!("Draws: {}/{}", n_draws, total_games);
+    }
+    if n_capped > 0 {
+        println!("Stopped at the round cap: {}/{}", n_capped, total_games);
+    }
+    for (i, &n_forfeits) in forfeits.iter().enumerate().filter(|(_, &n)| n > 0) {
+        println!("Forfeited by P{} after a panic: {}/{}", i, n_forfeits, total_games);
+    }
+}
+
+// Flag set by the first Ctrl-C. A second one exits right away.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    let registered = signal_hook::flag::register_conditional_shutdown(SIGINT, 1, flag.clone())
+        .and_then(|_| signal_hook::flag::register(SIGINT, flag.clone()));
+    if let Err(err) = registered {
+        log::warn!("Unable to handle Ctrl-C: {}", err);
+    }
+    flag
 }
 
-fn simulate(_game: &str, log_file: &PathBuf, n_sims: usize) {
-    let players: Vec<PlayFn> = [
-        azul::play_greedy,
-        azul::play_mcts,
-    ].to_vec();
+#[allow(clippy::too_many_arguments)]
+fn simulate(_game: &str, log_file: &PathBuf, log_db: Option<&PathBuf>, player_specs: &[String], log_analysis: bool, seed: Option<u64>, scripts: &[Arc<script::Script>], n_sims: usize, format: output::Format) -> anyhow::Result<()> {
+    #[cfg(not(feature = "db"))]
+    if log_db.is_some() {
+        return Err(Exit::InvalidConfig.wrap(anyhow::anyhow!("--log-db needs luza built with the db feature")));
+    }
+
+    let player_specs = if player_specs.is_empty() {
+        vec!["greedy".to_string(), "mcts".to_string()]
+    } else {
+        player_specs.to_vec()
+    };
+
+    let players = player_specs.iter().map(|s| Strategy::parse(s)).collect::<anyhow::Result<Vec<Strategy>>>()?;
     let n_players = players.len();
-    // MCTS has been consistently doing better than greedy in our trials
-    let best_player_idx = 1;
+    // First of the strongest seats, which partials are matched against
+    let best_player_idx = (0..n_players).rev().max_by_key(|&i| strategy::strength(&player_specs[i])).unwrap_or(0);
 
     // Partial functions that need to be put against the best player
-    let partials: Vec<(String, PartialPlayFn)> = [
-        ("greedy".to_string(), azul::play_partial_greedy as PartialPlayFn),
-        ("random".to_string(), azul::play_partial_random as PartialPlayFn),
-    ].to_vec();
+    let partials: Vec<(String, PartialPlayFn)> = azul::PARTIALS.iter().map(|&(name, f)| (name.to_string(), f)).collect();
+
+    let rules = azul::AzulRules::current();
+    rules.new_state(n_players).validate().or_exit(Exit::InvalidConfig)?;
 
     log::info!("Running {} simulations for {} players,", n_sims, n_players);
+    // Games in progress on Ctrl-C are dropped and the rest are written out
+    let interrupted = interrupt_flag();
+
+    let seed = seed.unwrap_or_else(rand::random);
+    output::progress(format, &format!("Seed: {}", seed));
+    if let Some(handicaps) = azul::config().handicap_summary() {
+        output::progress(format, &format!("Handicaps: {}", handicaps));
+    }
+    let header = playlog::RunHeader::new(&player_specs, seed)?;
+
+    let play_log: Arc<Mutex<PlayLog>> = Arc::new(Mutex::new(vec![LogRecord::Run(header)]));
+    let game_results: Arc<Mutex<Vec<GameResult>>> = Arc::new(Mutex::new(Vec::new()));
 
-    let play_log: Arc<Mutex<PlayLog>> = Arc::new(Mutex::new(Vec::new()));
-
-    let game_log: Vec<usize> = (0..n_sims).into_par_iter().map(|game_idx| {
-        let mut state = azul::State::new(n_players);
-
-        play_log.lock().unwrap().push(PlayLogPly {
-            game_id: game_idx,
-            round_id: -1,
-            ply_id: -1,
-            player_id: -1,
-            action: "init".to_string(),
-            state: serde_json::to_string(&state).unwrap(),
-            score: 0,
-            applicable_partials: Vec::new(),
-            matching_partials: Vec::new(),
-        });
-
-        if let Err(err) = state.validate() {
-            println!("{}", err);
-            return usize::MAX;
+    let game_log: Vec<Outcome> = (0..n_sims).into_par_iter().map(|game_idx| {
+        if interrupted.load(Ordering::Relaxed) {
+            return Outcome::Interrupted;
         }
+        // Every game is seeded so it can be played again from its log
+        let seed = tournament::match_seed(seed, game_idx, 0, 0);
+        azul::with_seed(seed, || {
+            let mut state = rules.new_state(n_players);
 
-        let mut ply_id: i32 = 0;
-        let mut round_id: i32 = 0;
-        loop {
-            log::debug!("Round: {}", state.rounds);
-            let mut current_player = match azul::first_player(&state) {
-                Some(one) => {
-                    state.players[one].starting_marker = false;
-                    one
-                },
-                None => 0,
-            };
-
-            log::debug!("Starting player: {}", current_player);
-
-            azul::refill_tiles(&mut state);
-            play_log.lock().unwrap().push(PlayLogPly {
+            play_log.lock().unwrap().push(LogRecord::GameStart {
                 game_id: game_idx,
-                round_id: -1,
-                ply_id: -1,
-                player_id: -1,
-                action: "reset-round".to_string(),
+                seed,
+                players: player_specs.clone(),
                 state: serde_json::to_string(&state).unwrap(),
-                score: 0,
-                applicable_partials: Vec::new(),
-                matching_partials: Vec::new(),
             });
 
+            let mut ply_id = 0;
+            let mut round_id = 0;
             loop {
-                // If tiles are over, round stops
-                if state.is_round_over() {
-                    state.rounds += 1;
-                    break;
-                }
-                let action = players[current_player](&state, current_player);
-
-                // Partial fn matching
-                let mut applicable_partials: Vec<String> = Vec::new();
-                let mut matching_partials: Vec<String> = Vec::new();
-                if current_player == best_player_idx {
-                    for (p_name, p_fn) in partials.clone() {
-                        if let Some(p_action) = p_fn(&state, current_player) {
-                            applicable_partials.push(p_name.clone());
-                            if p_action == action {
-                                matching_partials.push(p_name);
+                log::debug!("Round: {}", state.rounds);
+                let mut current_player = match azul::first_player(&state) {
+                    Some(one) => {
+                        state.players[one].starting_marker = false;
+                        one
+                    },
+                    None => 0,
+                };
+
+                log::debug!("Starting player: {}", current_player);
+
+                rules.refill(&mut state, &mut azul::game_rng());
+                play_log.lock().unwrap().push(LogRecord::RoundStart {
+                    game_id: game_idx,
+                    round_id,
+                    state: serde_json::to_string(&state).unwrap(),
+                });
+
+                loop {
+                    // If tiles are over, round stops
+                    if rules.is_round_over(&state) {
+                        break;
+                    }
+                    if interrupted.load(Ordering::Relaxed) {
+                        return Outcome::Interrupted;
+                    }
+                    let analysis = if log_analysis {
+                        let stats = azul::mcts_evaluate(&rules, &state, current_player, &azul::MctsConfig::default());
+                        serde_json::to_string(&bestmove::top_evaluations(&rules, &state, current_player, &stats, LOG_ANALYSIS_TOP_K)).unwrap()
+                    } else {
+                        String::new()
+                    };
+
+                    let (action, incident) = match players[current_player].play_guarded(&rules, &state, current_player, None) {
+                        Ok(played) => played,
+                        Err(forfeit) => {
+                            log::warn!("{} forfeited game {}: {}", players[forfeit.seat].spec, game_idx, forfeit.reason);
+                            let winner = (0..n_players)
+                                .filter(|&i| i != forfeit.seat)
+                                .max_by_key(|&i| state.players[i].score)
+                                .unwrap();
+                            let scores: Vec<i32> = state.players.iter().map(|p| p.score).collect();
+
+                            game_results.lock().unwrap().push(GameResult { game_id: game_idx, winner: Some(winner), scores: scores.clone(), rounds: state.rounds });
+                            play_log.lock().unwrap().push(LogRecord::GameEnd {
+                                game_id: game_idx,
+                                winner: Some(winner),
+                                final_scores: scores,
+                                end: playlog::GameEnd::Forfeit(forfeit.clone()),
+                            });
+                            return Outcome::Forfeit { seat: forfeit.seat, winner };
+                        },
+                    };
+                    if let Some(incident) = &incident {
+                        log::warn!("{}: {}", players[current_player].spec, incident);
+                    }
+
+                    // Partial fn matching
+                    let mut applicable_partials: Vec<String> = Vec::new();
+                    let mut matching_partials: Vec<String> = Vec::new();
+                    if current_player == best_player_idx {
+                        for (p_name, p_fn) in partials.clone() {
+                            if let Some(p_action) = p_fn(&rules, &state, current_player) {
+                                applicable_partials.push(p_name.clone());
+                                if p_action == action {
+                                    matching_partials.push(p_name);
+                                }
+                            }
+                        }
+                        for script in scripts {
+                            if let Some(p_action) = script.play(&rules, &state, current_player) {
+                                applicable_partials.push(script.name.clone());
+                                if p_action == action {
+                                    matching_partials.push(script.name.clone());
+                                }
                             }
                         }
                     }
-                }
-                azul::take_action(&mut state, current_player, action);
+                    let events = rules.take_action(&mut state, current_player, action);
+
+                    let mut state_clone = state.clone();
+                    rules.score_round(&mut state_clone, current_player);
 
-                let mut state_clone = state.clone();
-                azul::score_round(&mut state_clone, current_player);
+                    play_log.lock().unwrap().push(LogRecord::Ply(playlog::Ply {
+                        game_id: game_idx,
+                        round_id,
+                        ply_id,
+                        player_id: current_player,
+                        action: serde_json::to_string(&action).unwrap(),
+                        state: serde_json::to_string(&state).unwrap(),
+                        score: state_clone.players[current_player].score,
+                        applicable_partials,
+                        matching_partials,
+                        analysis,
+                        events,
+                        incident,
+                    }));
+
+                    current_player += 1;
+                    current_player %= n_players;
+                    ply_id += 1;
+                }
 
-                play_log.lock().unwrap().push(PlayLogPly {
+                let events = rules.end_round(&mut state);
+                for event in &events {
+                    log::debug!("{}", event);
+                }
+                play_log.lock().unwrap().push(LogRecord::RoundEnd {
                     game_id: game_idx,
                     round_id,
-                    ply_id,
-                    player_id: current_player as i32,
-                    action: serde_json::to_string(&action).unwrap(),
+                    scores: state.players.iter().map(|p| p.score).collect(),
                     state: serde_json::to_string(&state).unwrap(),
-                    score: state_clone.players[current_player].score,
-                    applicable_partials,
-                    matching_partials,
+                    events,
                 });
+                round_id += 1;
 
-                current_player += 1;
-                current_player %= n_players;
-                ply_id += 1;
+                if rules.is_game_over(&state) {
+                    break;
+                }
             }
 
             for i in 0..n_players {
-                azul::score_round(&mut state, i);
-                log::debug!("Score P{}: {}", i, state.players[i].score);
+                log::info!("Final score P{}: {}", i, state.players[i].score);
             }
-            round_id += 1;
+            log::info!("Winner is {}", azul::winner_name(azul::winner(&state)));
 
-            if state.is_game_over() {
-                break;
+            // Games stopped by the round cap are told apart in the log and
+            // the report
+            let capped = rules.hit_round_cap(&state);
+            if capped {
+                log::warn!("Game {} stopped at the round cap after {} rounds", game_idx, state.rounds);
             }
-        }
 
-        for i in 0..n_players {
-            log::info!("Final score P{}: {}", i, state.players[i].score);
-        }
-        log::info!("Winner is P{}", azul::winner(&state));
+            let winner = azul::winner(&state);
+            let scores: Vec<i32> = state.players.iter().map(|p| p.score).collect();
+            game_results.lock().unwrap().push(GameResult { game_id: game_idx, winner, scores: scores.clone(), rounds: state.rounds });
+            play_log.lock().unwrap().push(LogRecord::GameEnd {
+                game_id: game_idx,
+                winner,
+                final_scores: scores,
+                end: if capped { playlog::GameEnd::RoundCap } else { playlog::GameEnd::Finished },
+            });
 
-        azul::winner(&state)
+            match winner {
+                _ if capped => Outcome::RoundCap,
+                Some(i) => Outcome::Winner(i),
+                None => Outcome::Draw,
+            }
+        })
     }).collect();
 
-    report(game_log, n_players);
-    write_play_log(&play_log.lock().unwrap().to_vec(), log_file);
+    report(&game_log, n_players, format);
+    // Only complete games go in the log
+    play_log.lock().unwrap().retain(|record| record.game_id().is_none_or(|id| !matches!(game_log[id], Outcome::Interrupted)));
+    if let Err(err) = playlog::write(&play_log.lock().unwrap(), log_file) {
+        eprintln!("Failed to write the log: {}", err);
+    }
+
+    #[cfg(feature = "db")]
+    if let Some(db_file) = log_db {
+        if let Err(err) = db::write_play_db(&play_log.lock().unwrap(), &game_results.lock().unwrap(), db_file) {
+            eprintln!("Failed to write results database: {}", err);
+        }
+    }
+
+    let n_interrupted = game_log.iter().filter(|o| matches!(o, Outcome::Interrupted)).count();
+    let n_forfeits = game_log.iter().filter(|o| matches!(o, Outcome::Forfeit { .. })).count();
+    if n_interrupted > 0 {
+        return Err(Exit::Interrupted.wrap(anyhow::anyhow!("Interrupted with {} of {} games played", n_sims - n_interrupted, n_sims)));
+    }
+    if n_forfeits > 0 {
+        return Err(Exit::StrategyFailure.wrap(anyhow::anyhow!("{} of {} games were forfeited by a failing strategy", n_forfeits, n_sims)));
+    }
+    Ok(())
 }
 
-fn run_interactive(_game: &str) {
-    let teacher: PlayFn = azul::play_mcts;
-    let _action_heuristics: Vec<PartialPlayFn> = Vec::new();
-
-    color_eyre::install().unwrap();
-    let mut terminal = ratatui::init();
-    let n_players = 3;
-
-    let mut app = InteractiveApp{
-        state: azul::State::new(n_players),
-        current_player: 0,
-        ply: 0,
-        ply_round: 0,
-        last_move: None,
-        actions: Vec::new(),
-        actions_state: TableState::default(),
-        analyses: HashMap::new(),
-        show_action_details: false,
-        show_heuristic_details: false,
-        show_state_details: false,
-        heuristics: vec![
-            Heuristic {
-                name: "greedy".to_string(),
-                function: play_partial_greedy,
-            }
-        ]
+fn run_interactive(args: &InteractiveArgs) {
+    let observed = match args.observed.as_deref().map(|file| observed::load(file, 0)).transpose() {
+        Ok(position) => position,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        },
     };
+    let n_players = observed.as_ref().map_or(args.n_players, |(state, _)| state.players.len());
+    let sound = Sound::new(args.sound);
+    if !(2..=4).contains(&n_players) {
+        eprintln!("Azul is played by 2 to 4 players, not {}", n_players);
+        return;
+    }
+    if args.seat >= n_players {
+        eprintln!("Seat {} is not available in a {} player game", args.seat, n_players);
+        return;
+    }
 
-    let mut user_exit = false;
-
-    loop {
-        app.current_player = match azul::first_player(&app.state) {
-            Some(one) => {
-                app.state.players[one].starting_marker = false;
-                one
-            },
-            None => 0,
-        };
-
-        azul::refill_tiles(&mut app.state);
-        terminal.draw(|frame| {
-            frame.render_widget(app.clone(), frame.area());
-        }).unwrap();
-
-        loop {
-            terminal.draw(|frame| {
-                frame.render_widget(app.clone(), frame.area());
-            }).unwrap();
-
-            if app.state.is_round_over() {
-                app.state.rounds += 1;
-                app.ply_round = 0;
-                break;
-            }
+    // Builds without the TUI only have the line based game
+    #[cfg(feature = "tui")]
+    if !args.no_tui {
+        interactive::run(args, n_players, observed, sound);
+        return;
+    }
 
-            app.actions = azul::list_valid_actions(&app.state, app.current_player);
-            for action in &app.actions {
-                if app.analyses.contains_key(action) && app.analyses[action].expected_score.is_some() {
-                    // This is already cached so not doing anything
-                } else {
-                    app.analyses.insert(*action, ActionAnalysis {
-                        score_gain: azul::calculate_reward(&app.state, app.current_player, action.clone()),
-                        expected_score: None,
-                        win_probability: None,
-                    });
+    match text::run(n_players, observed, args.difficulty.config(), args.autoplay_forced, &sound) {
+        Ok(Some(game)) => {
+            if let Some(name) = &args.name {
+                if let Err(err) = profile::record_game(name, &game.state, args.seat, game.ply, HashMap::new()) {
+                    log::error!("Failed to save profile: {}", err);
                 }
             }
+        },
+        Ok(None) => {},
+        Err(err) => eprintln!("{}", err),
+    }
+}
 
-            app.actions.sort_by_key(|a| -app.analyses[&a].score_gain);
-
-            terminal.draw(|frame| {
-                frame.render_widget(app.clone(), frame.area());
-            }).unwrap();
-
-            match event::read().unwrap() {
-                Event::Key(key_event) => {
-                    if app.show_action_details || app.show_heuristic_details || app.show_state_details {
-                        // When any popup is open, only exiting is allowed
-                        match key_event.code {
-                            KeyCode::Char('q') => {
-                                app.show_action_details = false;
-                                app.show_heuristic_details = false;
-                                app.show_state_details = false;
-                            },
-                            _ => {}
-                        }
-                    } else {
-                        match key_event.code {
-                            KeyCode::Char('q') => {
-                                user_exit = true;
-                                break;
-                            },
-                            KeyCode::Char(' ') => {
-                                let action = teacher(&app.state, app.current_player);
-                                azul::take_action(&mut app.state, app.current_player, action);
-
-                                app.last_move = Some(tui::Move {
-                                    player: app.current_player,
-                                    action: action.clone()
-                                });
-
-                                // Reset analysis cache
-                                app.analyses = HashMap::new();
-
-                                app.actions_state.select_first();
-                                app.current_player += 1;
-                                app.current_player %= n_players;
-                                app.ply += 1;
-                                app.ply_round += 1;
-                            },
-                            KeyCode::Enter => {
-                                match app.actions_state.selected() {
-                                    Some(action_idx) => {
-                                        let action = app.actions[action_idx];
-                                        azul::take_action(&mut app.state, app.current_player, action);
-
-                                        app.last_move = Some(tui::Move {
-                                            player: app.current_player,
-                                            action: action.clone()
-                                        });
-
-                                        // Reset analysis cache
-                                        app.analyses = HashMap::new();
-
-                                        app.actions_state.select_first();
-                                        app.current_player += 1;
-                                        app.current_player %= n_players;
-                                        app.ply += 1;
-                                        app.ply_round += 1;
-                                    },
-                                    None => {}
-                                };
-                            },
-                            KeyCode::Down => {
-                                if let Some(action_idx) = app.actions_state.selected() {
-                                    if action_idx < app.actions.len() - 1 {
-                                        app.actions_state.select_next();
-                                    }
-                                } else {
-                                    app.actions_state.select_first();
-                                }
-                            },
-                            KeyCode::Up => {
-                                if let Some(_) = app.actions_state.selected() {
-                                    app.actions_state.select_previous();
-                                } else {
-                                    app.actions_state.select_first();
-                                }
-                            },
-                            KeyCode::Char('p') => {
-                                if let Some(action_idx) = app.actions_state.selected() {
-                                    let action = app.actions[action_idx];
-
-                                    if !app.analyses.contains_key(&action) || app.analyses[&action].expected_score == None {
-                                        let score_gain = azul::calculate_reward(&app.state, app.current_player, action);
-                                        let (expected_score, win_probability) = azul::mcts_q_fn(&app.state, app.current_player, action);
-
-                                        app.analyses.insert(action, ActionAnalysis {
-                                            score_gain,
-                                            expected_score: Some(expected_score),
-                                            win_probability: Some(win_probability),
-                                        });
-                                    }
-                                }
-                            },
-                            KeyCode::Char('h') => {
-                                if let Some(_) = app.actions_state.selected() {
-                                    app.show_heuristic_details = true;
-                                }
-                            },
-                            KeyCode::Char('s') => {
-                                if let Some(_) = app.actions_state.selected() {
-                                    app.show_state_details = true;
-                                }
-                            },
-                            _ => {}
-                        }
-                    }
-                },
-                _ => {}
-            };
-
-            terminal.draw(|frame| {
-                frame.render_widget(app.clone(), frame.area());
-            }).unwrap();
-        }
-        for i in 0..n_players {
-            azul::score_round(&mut app.state, i);
-        }
+fn main() {
+    env_logger::init();
+    let args = Args::parse();
 
-        if app.state.is_game_over() || user_exit {
-            break;
-        }
+    if let Err(err) = run(args) {
+        eprintln!("{}", err);
+        std::process::exit(output::exit_code(&err));
     }
+}
 
-    // Listen to Q unless user has showed intention to quit already
-    if !user_exit {
-        terminal.draw(|frame| {
-            frame.render_widget(app.clone(), frame.area());
-        }).unwrap();
-
-        loop {
-            match event::read().unwrap() {
-                Event::Key(key_event) => {
-                    match key_event.code {
-                        KeyCode::Char('q') => { break; },
-                        _ => {}
-                    }
-                },
-                _ => {}
-            };
-        }
+fn run(args: Args) -> anyhow::Result<()> {
+    if let Some(rules) = &args.rules {
+        azul::AzulConfig::load(rules)
+            .map(azul::set_config)
+            .map_err(|err| anyhow::anyhow!("Failed to load rules from {}: {}", rules.display(), err))
+            .or_exit(Exit::InvalidConfig)?;
+    }
+    if !args.handicap.is_empty() {
+        let mut config = (*azul::config()).clone();
+        config.handicaps = args.handicap.clone();
+        azul::set_config(config);
     }
 
-    ratatui::restore();
-}
+    output::check_format(args.format).or_exit(Exit::InvalidConfig)?;
 
-fn main() {
-    env_logger::init();
-    let args = Args::parse();
+    let plugins = args.plugins.clone().or_else(|| profile::data_dir().ok().map(|dir| dir.join("plugins")).filter(|dir| dir.is_dir()));
+    if let Some(plugins) = &plugins {
+        plugin::load_dir(plugins, &strategy::BUILTIN_STRATEGIES)
+            .map_err(|err| anyhow::anyhow!("Failed to load plugins from {}: {}", plugins.display(), err))
+            .or_exit(Exit::StrategyFailure)?;
+    }
 
     match args.commands {
-        Commands::Simulate { log_file, game } => simulate(&game, &log_file, 100),
-        Commands::Interactive { game } => run_interactive(&game),
+        Commands::Simulate { log_file, log_db, players, log_analysis, seed, heuristics, game } => {
+            let scripts = heuristics.as_deref().map(script::ScriptSet::load).transpose().or_exit(Exit::InvalidConfig)?;
+            let scripts = scripts.map_or(Vec::new(), |set| set.scripts);
+            simulate(&game, &log_file, log_db.as_ref(), &players, log_analysis, seed, &scripts, 100, args.format)
+        },
+        Commands::Interactive(interactive_args) => {
+            run_interactive(&interactive_args);
+            Ok(())
+        },
+        #[cfg(feature = "dataset")]
+        Commands::Dataset { command: Some(DatasetCommands::Inspect { file, record }), .. } => dataset::inspect(&file, record),
+        #[cfg(feature = "dataset")]
+        Commands::Dataset {
+            command: None,
+            log_file: Some(log_file),
+            out: Some(out),
+            normalize,
+            mover_first,
+            augment,
+            keep_duplicates,
+            binary,
+            split,
+            stratify_by,
+        } => {
+            let splits = split.map_or(Ok(Vec::new()), |spec| dataset::parse_splits(&spec)).or_exit(Exit::InvalidConfig)?;
+            let options = dataset::Options { normalize, mover_first, augment, keep_duplicates, binary, splits, stratify_by };
+            dataset::run(&log_file, &out, &options)
+        },
+        // clap requires both without a subcommand
+        #[cfg(feature = "dataset")]
+        Commands::Dataset { .. } => unreachable!(),
+        #[cfg(feature = "arrow")]
+        Commands::Stats { log_file, query } => stats::run(&log_file, query, args.format),
+        Commands::Analyze { log_file, game_id } => accuracy::run(&log_file, game_id, args.format),
+        Commands::AnalyzeAll { dir, out, manifest } => batch::run(&dir, &out, manifest.as_deref()),
+        Commands::Blunders { log_file, top, game_id } => blunders::run(&log_file, top, game_id, args.format),
+        Commands::Counting { log_file, memory, game_id } => {
+            counting::check_memory(memory).or_exit(Exit::InvalidConfig)?;
+            counting::run(&log_file, memory, game_id, args.format)
+        },
+        Commands::ExportMd { save, out } => study::run(&save, &out),
+        Commands::Index { log_file, out } => explorer::index(&log_file, &out),
+        Commands::Explore { index, state, player, moves } => explorer::explore(&index, &state, player, &moves, args.format),
+        #[cfg(feature = "tui")]
+        Commands::Puzzle { set, tries } => puzzle::run(&set, tries),
+        Commands::MultiTournament { config } => tournament::run_config(&config, args.format),
+        Commands::Gate { candidate, baseline, elo0, elo1, alpha, beta, batch, max_games, seed } => {
+            let sprt = gate::Sprt { elo0, elo1, alpha, beta };
+            gate::run(&candidate, &baseline, sprt, batch, max_games, seed, args.format)
+        },
+        Commands::Tournament { players, pairing, rounds, games_per_match, time_control, seed, manifest, game } => {
+            games::check_game(&game).or_exit(Exit::InvalidConfig)?;
+            tournament::run(&players, pairing, rounds, games_per_match, time_control, seed, manifest.as_ref(), args.format)
+        },
+        Commands::Replay { manifest, game_id, saved_game } => match (manifest, game_id, saved_game) {
+            (Some(manifest), Some(game_id), _) => tournament::replay(&manifest, game_id),
+            (_, _, Some(saved_game)) => autosave::replay(&saved_game),
+            _ => unreachable!("clap requires a manifest and game id or a saved game"),
+        },
+        Commands::League { league_file, players, matches, games_per_match, snapshot_every, game: _ } => {
+            league::run(&players, &league_file, matches, games_per_match, snapshot_every, args.format)
+        },
+        Commands::Ratings { history } => rating::run(history, args.format),
+        Commands::Sensitivity { parameter, values, players, games, seed, game } => {
+            games::check_game(&game).or_exit(Exit::InvalidConfig)?;
+            let players = if players.is_empty() { vec!["greedy".to_string(), "mcts".to_string()] } else { players };
+            sensitivity::run(&players, &parameter, &values, games, seed, args.format)
+        },
+        #[cfg(feature = "nn")]
+        Commands::Train { config } => train::run(&config),
+        Commands::Verify { log_file } => verify::run(&log_file),
+        Commands::Bestmove { game, state, observed, player, memory, budget, top_k, widening, info, dump_tree } => {
+            games::check_game(&game).or_exit(Exit::InvalidConfig)?;
+            let (mut state, player) = bestmove::read_position(&state, player, observed).or_exit(Exit::InvalidConfig)?;
+            if let Some(memory) = memory {
+                counting::check_memory(memory).or_exit(Exit::InvalidConfig)?;
+                state = counting::belief(&azul::AzulRules::current(), &state, memory);
+            }
+            let budget = bestmove::parse_budget(&budget).or_exit(Exit::InvalidConfig)?;
+            let info = info.as_deref().map(bestmove::parse_budget).transpose().or_exit(Exit::InvalidConfig)?;
+            bestmove::run(&state, player, budget, top_k, widening, info, dump_tree.as_deref())
+        },
+        Commands::Completions { shell } => Ok(completions::generate(shell, &mut Args::command(), &mut io::stdout())?),
+        Commands::Man { out_dir: None } => Ok(completions::man_page(Args::command(), &mut io::stdout())?),
+        Commands::Man { out_dir: Some(out_dir) } => Ok(completions::man_pages(Args::command(), &out_dir)?),
+        Commands::Regress { baseline_bin, strategy, games, seed } => {
+            regress::run(&baseline_bin, &strategy, games, seed, args.rules.as_deref(), args.format)
+        },
+        Commands::Engine { strategy, stdin: true } => bestmove::serve(&strategy::parse_analysis_engine(&strategy).or_exit(Exit::InvalidConfig)?),
+        Commands::Engine { strategy, stdin: false } => engine::serve(&Strategy::parse(&strategy)?),
+        Commands::Doctor => doctor::run(),
+        Commands::Smoke => smoke::run(),
+        Commands::Profile { name } => {
+            profile::load(&name)?.print_summary();
+            Ok(())
+        },
     }
 }
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul::{self, Tile, WALL_COLORS};
+
+// A position as seen on a real table, like from a computer vision tool that
+// annotates a photo. Tiles are listed by color wherever they lie, so the
+// importer does the counting and checks the position can happen in a game.
+// The first player marker is either in the center or with one player.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Observed {
+    // Tiles on each factory display, empty for taken ones
+    pub factories: Vec<Vec<Tile>>,
+    pub center: Vec<Tile>,
+    pub marker_in_center: bool,
+    pub players: Vec<ObservedPlayer>,
+    // Discarded tiles, if the tool can see the lid
+    #[serde(default)]
+    pub lid: Vec<Tile>,
+    // Rounds played before this one
+    #[serde(default)]
+    pub rounds: usize,
+    #[serde(default)]
+    pub to_move: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObservedPlayer {
+    pub score: i32,
+    // Colors placed on each wall row, their columns follow from the wall
+    // pattern
+    pub wall: [Vec<Tile>; 5],
+    // Tiles on each pattern line, top line first
+    pub pattern_lines: [Vec<Tile>; 5],
+    pub floor: Vec<Tile>,
+    #[serde(default)]
+    pub marker: bool,
+}
+
+fn count_tiles(tiles: &[Tile]) -> HashMap<Tile, usize> {
+    azul::COLORS.iter().map(|&color| (color, tiles.iter().filter(|&&t| t == color).count())).collect()
+}
+
+impl ObservedPlayer {
+    fn board(&self, seat: usize, floor_size: usize) -> Result<azul::PlayerState> {
+        if self.score < 0 {
+            return Err(anyhow!("P{} has a score of {}, scores can't go below 0", seat, self.score));
+        }
+
+        let mut wall = [[false; 5]; 5];
+        for (row, colors) in self.wall.iter().enumerate() {
+            for &color in colors {
+                let col = WALL_COLORS[row].iter().position(|&c| c == color).unwrap();
+                if wall[row][col] {
+                    return Err(anyhow!("P{} wall row {} has {:?} twice", seat, row, color));
+                }
+                wall[row][col] = true;
+            }
+        }
+
+        let mut pattern_lines = [(None, 0); 5];
+        for (row, tiles) in self.pattern_lines.iter().enumerate() {
+            let Some(&color) = tiles.first() else { continue };
+            if let Some(&other) = tiles.iter().find(|&&t| t != color) {
+                return Err(anyhow!("P{} pattern line {} mixes {:?} and {:?}", seat, row, color, other));
+            }
+            if tiles.len() > row + 1 {
+                return Err(anyhow!("P{} pattern line {} has {} tiles but room for {}", seat, row, tiles.len(), row + 1));
+            }
+            if self.wall[row].contains(&color) {
+                return Err(anyhow!("P{} pattern line {} has {:?}, which is already on wall row {}", seat, row, color, row));
+            }
+            // Full lines are tiled at the end of the round, not during it
+            pattern_lines[row] = (Some(color), tiles.len());
+        }
+
+        if self.floor.len() > floor_size {
+            return Err(anyhow!("P{} floor has {} tiles but room for {}", seat, self.floor.len(), floor_size));
+        }
+
+        Ok(azul::PlayerState {
+            score: self.score,
+            wall,
+            pattern_lines,
+            floor_line: self.floor.len(),
+            starting_marker: self.marker,
+        })
+    }
+
+    // Tiles of the player's board, each wall tile once
+    fn tiles(&self) -> Vec<Tile> {
+        self.wall.iter().chain(&self.pattern_lines).flatten().chain(&self.floor).copied().collect()
+    }
+}
+
+impl Observed {
+    // State under the current rules with the player to move, or what makes
+    // the position impossible. Floor tiles go to the lid right away like in
+    // games played by the engine.
+    pub fn position(&self, default_player: usize) -> Result<(azul::State, usize)> {
+        let config = azul::config();
+        let n_players = self.players.len();
+        if !(2..=4).contains(&n_players) {
+            return Err(anyhow!("Position has {} players, Azul is played by 2 to 4", n_players));
+        }
+
+        let n_factories = config.n_factories(n_players);
+        if self.factories.len() != n_factories {
+            return Err(anyhow!("Position has {} factories but a {} player game has {}", self.factories.len(), n_players, n_factories));
+        }
+        for (i, tiles) in self.factories.iter().enumerate() {
+            if tiles.len() > config.tiles_per_factory {
+                return Err(anyhow!("Factory {} has {} tiles but holds {}", i, tiles.len(), config.tiles_per_factory));
+            }
+        }
+
+        let holders: Vec<String> = self.players.iter().enumerate().filter(|(_, p)| p.marker).map(|(i, _)| format!("P{}", i)).collect();
+        match (self.marker_in_center, holders.len()) {
+            (true, 0) | (false, 1) => {},
+            (true, _) => return Err(anyhow!("First player marker is in the center and with {}", holders.join(", "))),
+            (false, 0) => return Err(anyhow!("First player marker is neither in the center nor with a player")),
+            (false, _) => return Err(anyhow!("First player marker is with more than one player: {}", holders.join(", "))),
+        }
+
+        let floor_size = config.floor_penalties.len();
+        let players = self.players.iter().enumerate().map(|(seat, p)| p.board(seat, floor_size)).collect::<Result<Vec<_>>>()?;
+
+        // A color can't show up more often than the game has tiles of it
+        let mut seen: Vec<Tile> = self.factories.iter().flatten().chain(&self.center).chain(&self.lid).copied().collect();
+        seen.extend(self.players.iter().flat_map(|p| p.tiles()));
+        let seen = count_tiles(&seen);
+        for color in azul::COLORS {
+            let available = config.tiles_per_color.get(&color).copied().unwrap_or(0);
+            if seen[&color] > available {
+                return Err(anyhow!("Position has {} {:?} tiles but the game has {}", seen[&color], color, available));
+            }
+        }
+
+        if self.factories.iter().all(|f| f.is_empty()) && self.center.is_empty() {
+            return Err(anyhow!("No tiles left to take, the round is over"));
+        }
+
+        let player_idx = self.to_move.unwrap_or(default_player);
+        if player_idx >= n_players {
+            return Err(anyhow!("Player {} is not in a {} player game", player_idx, n_players));
+        }
+
+        let floor: Vec<Tile> = self.players.iter().flat_map(|p| p.floor.iter().copied()).collect();
+        let state = azul::State {
+            factory_displays: self.factories.iter().map(|tiles| count_tiles(tiles)).collect(),
+            center: azul::CenterState { tiles: count_tiles(&self.center), starting_marker: self.marker_in_center },
+            players,
+            rounds: self.rounds,
+            lid: count_tiles(&[self.lid.as_slice(), &floor].concat()),
+            bag: None,
+        };
+        Ok((state, player_idx))
+    }
+}
+
+// Read an observed position from a file, - for stdin, and import it. The
+// player to move is `default_player` unless the file says.
+pub fn load(file: &Path, default_player: usize) -> Result<(azul::State, usize)> {
+    let mut text = String::new();
+    if file.as_os_str() == "-" {
+        io::stdin().read_to_string(&mut text)?;
+    } else {
+        File::open(file)?.read_to_string(&mut text)?;
+    }
+
+    let observed: Observed = serde_json::from_str(&text).map_err(|err| anyhow!("Invalid observed position: {}", err))?;
+    observed.position(default_player).map_err(|err| anyhow!("Impossible observed position: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    fn observed() -> Observed {
+        let player = ObservedPlayer {
+            score: 4,
+            wall: [vec![Tile::Blue], vec![], vec![], vec![], vec![]],
+            pattern_lines: [vec![], vec![Tile::Red], vec![], vec![], vec![]],
+            floor: vec![Tile::Black],
+            marker: false,
+        };
+        Observed {
+            factories: vec![vec![Tile::Blue, Tile::Blue, Tile::Red, Tile::White], vec![], vec![], vec![], vec![]],
+            center: vec![Tile::Yellow],
+            marker_in_center: true,
+            players: vec![player.clone(), player],
+            lid: Vec::new(),
+            rounds: 1,
+            to_move: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_position() {
+        let rules = azul::AzulRules::current();
+        let (state, player_idx) = observed().position(0).unwrap();
+        assert_eq!(player_idx, 1);
+        assert_eq!(state.factory_displays[0][&Tile::Blue], 2);
+        assert_eq!(state.players[0].pattern_lines[1], (Some(Tile::Red), 1));
+        assert!(state.players[1].wall[0][0]);
+        assert_eq!(state.players[1].floor_line, 1);
+        assert_eq!(state.lid[&Tile::Black], 2);
+        assert!(!rules.list_valid_actions(&state, player_idx).is_empty());
+    }
+
+    #[test]
+    fn test_impossible_positions() {
+        let mut pos = observed();
+        pos.players[0].pattern_lines[0] = vec![Tile::Red, Tile::Red];
+        assert!(pos.position(0).unwrap_err().to_string().contains("room for 1"));
+
+        let mut pos = observed();
+        pos.players[1].pattern_lines[0] = vec![Tile::Blue];
+        assert!(pos.position(0).is_err());
+
+        let mut pos = observed();
+        pos.players[1].marker = true;
+        assert!(pos.position(0).is_err());
+
+        let mut pos = observed();
+        pos.lid = vec![Tile::Blue; 20];
+        assert!(pos.position(0).unwrap_err().to_string().contains("Blue"));
+
+        let mut pos = observed();
+        pos.factories.pop();
+        assert!(pos.position(0).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, Result};
+
+// Output format of reporting commands
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+// Wins of one seat in a simulation
+#[derive(serde::Serialize)]
+pub struct SeatWins {
+    pub seat: usize,
+    pub wins: usize,
+    // Games finished, including those stopped by the round cap
+    pub games: usize,
+    // Games tied after the tie-break, the same for every seat
+    pub draws: usize,
+    pub round_capped: usize,
+    // Games this seat lost because its strategy panicked
+    pub forfeits: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct TournamentStanding {
+    pub rank: usize,
+    pub spec: String,
+    pub points: f64,
+    pub buchholz: f64,
+    pub game_wins: usize,
+    pub games: usize,
+}
+
+// Standing in one tournament of a multi-game config
+#[derive(serde::Serialize)]
+pub struct GameStanding {
+    pub tournament: usize,
+    pub game: String,
+    pub rank: usize,
+    pub spec: String,
+    pub points: f64,
+    pub buchholz: f64,
+    pub game_wins: usize,
+    pub games: usize,
+}
+
+#[derive(serde::Serialize)]
+pub struct LeagueRating {
+    pub rank: usize,
+    pub spec: String,
+    pub rating: f64,
+    pub games: usize,
+}
+
+// Rating of a strategy after one of its matches
+#[derive(serde::Serialize)]
+pub struct RatingHistory {
+    pub spec: String,
+    pub timestamp: u64,
+    pub source: String,
+    pub rating: f64,
+}
+
+// Results of one strategy for one value of the swept parameter
+#[derive(serde::Serialize)]
+pub struct SensitivityResult {
+    pub value: String,
+    pub spec: String,
+    pub win_rate: f64,
+    pub mean_score: f64,
+    pub score_std_dev: f64,
+    pub min_score: i32,
+    pub max_score: i32,
+    pub draws: usize,
+    pub mean_rounds: f64,
+}
+
+#[cfg(feature = "arrow")]
+#[derive(serde::Serialize)]
+pub struct FirstPlayerWins {
+    pub first_player: usize,
+    // Rounds started with the starting marker
+    pub rounds: usize,
+    pub wins: usize,
+}
+
+#[cfg(feature = "arrow")]
+#[derive(serde::Serialize)]
+pub struct RoundFloorPenalty {
+    pub round: usize,
+    pub mean_penalty: f64,
+}
+
+#[cfg(feature = "arrow")]
+#[derive(serde::Serialize)]
+pub struct HeuristicMatch {
+    pub ply: usize,
+    pub heuristic: String,
+    pub applicable: usize,
+    pub matching: usize,
+}
+
+// How often a heuristic picks the action a strategy played, in one column of
+// a breakdown by round or by number of valid actions
+#[cfg(feature = "arrow")]
+#[derive(serde::Serialize)]
+pub struct HeuristicAgreement {
+    pub heuristic: String,
+    pub strategy: String,
+    pub breakdown: String,
+    pub column: String,
+    pub applicable: usize,
+    pub matching: usize,
+    pub rate: f64,
+}
+
+// Expected score a strategy or player lost against the engine's best moves
+#[derive(serde::Serialize)]
+pub struct PlayerAccuracy {
+    pub player: String,
+    pub moves: usize,
+    pub centipoints_lost: i64,
+    pub mean_loss: f64,
+    pub accuracy: f64,
+}
+
+// A logged move that lost expected score against the engine's best move,
+// with the state it was played in as JSON
+#[derive(serde::Serialize)]
+pub struct Blunder {
+    pub game_id: usize,
+    pub ply_id: usize,
+    pub player: usize,
+    pub strategy: String,
+    pub played: String,
+    pub best: String,
+    pub expected_score: f32,
+    pub best_expected_score: f32,
+    pub loss: f32,
+    pub state: String,
+}
+
+// How often advice from what a player remembers of the lid agrees with the
+// full information engine in the positions of one round, and the expected
+// score lost when it doesn't
+#[derive(serde::Serialize)]
+pub struct CountingRound {
+    pub round: usize,
+    pub positions: usize,
+    pub agreements: usize,
+    pub agreement_rate: f64,
+    pub mean_loss: f64,
+}
+
+// A move played from a position in the opening explorer, with the share of
+// games it was played in and how they went for the player making it
+#[derive(serde::Serialize)]
+pub struct OpeningMove {
+    pub action: String,
+    pub games: usize,
+    pub share: f64,
+    pub win_rate: f64,
+    pub mean_score: f64,
+}
+
+// Outcome of a gating match decided by a sequential probability ratio test
+#[derive(serde::Serialize)]
+pub struct GateResult {
+    pub candidate: String,
+    pub baseline: String,
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    pub llr: f64,
+    // pass, fail, or undecided
+    pub verdict: String,
+}
+
+// Match of this build against an older binary playing the same strategy,
+// with the Elo difference from this build's view and its 95% margin
+#[derive(serde::Serialize)]
+pub struct RegressResult {
+    pub current: String,
+    pub baseline: String,
+    pub strategy: String,
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    pub elo_delta: f64,
+    pub elo_margin: f64,
+}
+
+// Results of one strategy in one log of a batch analysis
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LogSummary {
+    pub file: String,
+    // Seconds since the Unix epoch when the log was made
+    pub timestamp: u64,
+    pub strategy: String,
+    pub games: usize,
+    pub wins: usize,
+    pub mean_score: f64,
+    pub moves: usize,
+    pub centipoints_lost: i64,
+    pub accuracy: f64,
+}
+
+// Write the rows of a report as a JSON array or as CSV with a header named
+// after the fields. Nothing is written for text since commands print that
+// themselves, written for reading.
+pub fn write_rows<R: serde::Serialize>(rows: &[R], format: Format) -> Result<()> {
+    write_rows_to(&mut io::stdout().lock(), rows, format)
+}
+
+// Fail before any work for formats this build can't write
+pub fn check_format(format: Format) -> Result<()> {
+    match format {
+        Format::Csv if !cfg!(feature = "csv") => Err(anyhow!("CSV output needs luza built with the csv feature")),
+        _ => Ok(()),
+    }
+}
+
+pub fn write_rows_to<R: serde::Serialize>(writer: &mut impl Write, rows: &[R], format: Format) -> Result<()> {
+    match format {
+        Format::Text => {},
+        Format::Json => writeln!(writer, "{}", serde_json::to_string_pretty(rows)?)?,
+        #[cfg(not(feature = "csv"))]
+        Format::Csv => check_format(format)?,
+        #[cfg(feature = "csv")]
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for row in rows {
+                csv_writer.serialize(row)?;
+            }
+            csv_writer.flush()?;
+        },
+    }
+
+    Ok(())
+}
+
+// Whether stdout is a terminal rather than a pipe, file, or container log
+static STDOUT_TERMINAL: LazyLock<bool> = LazyLock::new(|| io::stdout().is_terminal());
+
+// Print a progress line, kept off stdout when it carries JSON or CSV. When
+// stdout isn't a terminal progress goes to stderr as `{"progress": ...}` JSON
+// lines for tools following the run.
+pub fn progress(format: Format, line: &str) {
+    if !*STDOUT_TERMINAL {
+        eprintln!("{}", serde_json::json!({ "progress": line }));
+    } else if format == Format::Text {
+        println!("{}", line);
+    } else {
+        eprintln!("{}", line);
+    }
+}
+
+// Exit codes for failures scripts and orchestration tools can act on. Other
+// errors exit with 1, and clap exits with 2 on bad arguments like an invalid
+// config.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    // Rules, strategy specs, settings, or config files that can't be used
+    InvalidConfig = 2,
+    // A strategy failed to load or forfeited games by panicking
+    StrategyFailure = 3,
+    // Stopped by Ctrl-C after writing the results so far
+    Interrupted = 4,
+}
+
+// Error ending luza with an exit code, shown as the error it wraps
+#[derive(Debug)]
+struct ExitError {
+    exit: Exit,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for ExitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl Exit {
+    // Mark the error as ending luza with this exit code, unless it already
+    // has a more specific one
+    pub fn wrap(self, error: impl Into<anyhow::Error>) -> anyhow::Error {
+        let error = error.into();
+        if exit_of(&error).is_some() {
+            return error;
+        }
+        ExitError { exit: self, error }.into()
+    }
+}
+
+pub trait OrExit<T> {
+    fn or_exit(self, exit: Exit) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> OrExit<T> for std::result::Result<T, E> {
+    fn or_exit(self, exit: Exit) -> Result<T> {
+        self.map_err(|error| exit.wrap(error))
+    }
+}
+
+fn exit_of(error: &anyhow::Error) -> Option<Exit> {
+    error.chain().find_map(|e| e.downcast_ref::<ExitError>()).map(|e| e.exit)
+}
+
+// Exit code of a failed command
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    exit_of(error).map_or(1, |exit| exit as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn test_csv_rows() {
+        let row = TournamentStanding { rank: 1, spec: "mcts:n_games=5,epsilon=0.1".to_string(), points: 1.5, buchholz: 0.0, game_wins: 3, games: 4 };
+        let mut out = Vec::new();
+        write_rows_to(&mut out, &[row], Format::Csv).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "rank,spec,points,buchholz,game_wins,games\n1,\"mcts:n_games=5,epsilon=0.1\",1.5,0.0,3,4\n");
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let invalid = Exit::InvalidConfig.wrap(anyhow::anyhow!("Unknown strategy 'foo'"));
+        assert_eq!(exit_code(&invalid), 2);
+        assert_eq!(invalid.to_string(), "Unknown strategy 'foo'");
+
+        // The first code given sticks through later wrapping and context
+        let failed = Exit::InvalidConfig.wrap(Exit::StrategyFailure.wrap(anyhow::anyhow!("engine died")));
+        assert_eq!(exit_code(&failed.context("Tournament stopped")), 3);
+        assert_eq!(exit_code(&anyhow::anyhow!("No games in the log")), 1);
+    }
+}
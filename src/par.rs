@@ -0,0 +1,52 @@
+// Parallel iteration over games with rayon, or the same calls running one
+// after another in builds without the `parallel` feature. Modules import
+// `par::prelude::*` instead of rayon's prelude.
+
+#[cfg(feature = "parallel")]
+pub use rayon::prelude;
+
+#[cfg(feature = "parallel")]
+pub fn current_num_threads() -> usize {
+    rayon::current_num_threads()
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn current_num_threads() -> usize {
+    1
+}
+
+#[cfg(not(feature = "parallel"))]
+pub mod prelude {
+    // `into_par_iter` and `par_iter` giving plain iterators, which have the
+    // `map` and `collect` the callers use
+    pub trait IntoParallelIterator {
+        type Iter: Iterator;
+
+        fn into_par_iter(self) -> Self::Iter;
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {
+        type Iter = I::IntoIter;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait IntoParallelRefIterator<'data> {
+        type Iter: Iterator;
+
+        fn par_iter(&'data self) -> Self::Iter;
+    }
+
+    impl<'data, I: 'data + ?Sized> IntoParallelRefIterator<'data> for I
+    where
+        &'data I: IntoIterator,
+    {
+        type Iter = <&'data I as IntoIterator>::IntoIter;
+
+        fn par_iter(&'data self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+}
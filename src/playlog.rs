@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use crate::games::azul::{self, AzulConfig};
+use crate::strategy::Forfeit;
+
+// One line of a simulation log, tagged by the `record` field. States and
+// actions are kept as the game's own JSON so other games can log the same
+// records.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "record", rename_all = "kebab-case")]
+pub enum LogRecord {
+    Run(RunHeader),
+    GameStart {
+        game_id: usize,
+        seed: u64,
+        // Strategy specs by seat
+        players: Vec<String>,
+        state: String,
+    },
+    // State after the displays are filled
+    RoundStart {
+        game_id: usize,
+        round_id: usize,
+        state: String,
+    },
+    Ply(Ply),
+    // State after the walls are tiled and scored, with what the scoring did
+    RoundEnd {
+        game_id: usize,
+        round_id: usize,
+        scores: Vec<i32>,
+        state: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        events: Vec<azul::GameEvent>,
+    },
+    GameEnd {
+        game_id: usize,
+        // Null for a draw the tie-break didn't settle
+        winner: Option<usize>,
+        final_scores: Vec<i32>,
+        end: GameEnd,
+    },
+}
+
+// Version of the log format written now, see `logreader` for the history
+pub const LOG_VERSION: usize = 3;
+
+fn first_header_version() -> usize {
+    2
+}
+
+// First record of a log, telling what made it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RunHeader {
+    // Headers from before this field are all version 2
+    #[serde(default = "first_header_version")]
+    pub log_version: usize,
+    pub version: String,
+    // Output of `git describe` when the binary was built
+    pub git_commit: String,
+    pub args: Vec<String>,
+    // Strategy specs by seat
+    pub players: Vec<String>,
+    // Game seeds are derived from this
+    pub seed: u64,
+    pub rules: AzulConfig,
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl RunHeader {
+    pub fn new(players: &[String], seed: u64) -> Result<Self> {
+        Ok(Self {
+            log_version: LOG_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("LUZA_GIT_COMMIT").to_string(),
+            args: std::env::args().collect(),
+            players: players.to_vec(),
+            seed,
+            rules: (*azul::config()).clone(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        })
+    }
+}
+
+// One move. The state is the one after the move and the score is the mover's
+// score if the round ended right then.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Ply {
+    pub game_id: usize,
+    pub round_id: usize,
+    pub ply_id: usize,
+    pub player_id: usize,
+    pub action: String,
+    pub state: String,
+    pub score: i32,
+    pub applicable_partials: Vec<String>,
+    pub matching_partials: Vec<String>,
+    // Top MCTS evaluations of the position before the move, only logged with
+    // --log-analysis
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub analysis: String,
+    // What the move changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<azul::GameEvent>,
+    // Why the player's watchdog played this move in place of its strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incident: Option<String>,
+}
+
+// How a game ended
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GameEnd {
+    Finished,
+    // Stopped by the round cap without ending by the rules
+    RoundCap,
+    // Given up by a seat whose strategy panicked
+    Forfeit(Forfeit),
+}
+
+impl LogRecord {
+    // Game the record belongs to, none for the run header
+    pub fn game_id(&self) -> Option<usize> {
+        match self {
+            LogRecord::Run(_) => None,
+            LogRecord::GameStart { game_id, .. }
+            | LogRecord::RoundStart { game_id, .. }
+            | LogRecord::RoundEnd { game_id, .. }
+            | LogRecord::GameEnd { game_id, .. } => Some(*game_id),
+            LogRecord::Ply(ply) => Some(ply.game_id),
+        }
+    }
+
+    // Game state as of this record, if it has one
+    pub fn state(&self) -> Option<&str> {
+        match self {
+            LogRecord::GameStart { state, .. } | LogRecord::RoundStart { state, .. } | LogRecord::RoundEnd { state, .. } => Some(state),
+            LogRecord::Ply(ply) => Some(&ply.state),
+            LogRecord::Run(_) | LogRecord::GameEnd { .. } => None,
+        }
+    }
+}
+
+// Records of a log in the current format, logs of any version are read with
+// `logreader::read`
+pub fn parse(lines: &[String]) -> Result<Vec<LogRecord>> {
+    lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+pub fn write(play_log: &[LogRecord], file: &PathBuf) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    for record in play_log {
+        jsonl::write(&mut writer, record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_tags() {
+        let record = LogRecord::GameEnd { game_id: 3, winner: Some(1), final_scores: vec![20, 31], end: GameEnd::RoundCap };
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(line, r#"{"record":"game-end","game_id":3,"winner":1,"final_scores":[20,31],"end":"round-cap"}"#);
+
+        let forfeit = LogRecord::GameEnd {
+            game_id: 3,
+            winner: Some(1),
+            final_scores: vec![0, 0],
+            end: GameEnd::Forfeit(Forfeit { seat: 0, reason: "strategy panicked".to_string() }),
+        };
+        let parsed: LogRecord = serde_json::from_str(&serde_json::to_string(&forfeit).unwrap()).unwrap();
+        assert!(matches!(parsed, LogRecord::GameEnd { end: GameEnd::Forfeit(Forfeit { seat: 0, .. }), .. }));
+
+        let draw = LogRecord::GameEnd { game_id: 4, winner: None, final_scores: vec![30, 30], end: GameEnd::Finished };
+        assert!(serde_json::to_string(&draw).unwrap().contains(r#""winner":null"#));
+    }
+}
@@ -0,0 +1,218 @@
+// Builds without the plugins feature only make plugins from functions in tests
+#![cfg_attr(not(feature = "plugins"), allow(dead_code))]
+
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+#[cfg(all(feature = "plugins", any(unix, windows)))]
+use libloading::{Library, Symbol};
+
+use crate::games::{azul, Rules};
+
+// Strategies compiled as shared libraries (`.so`, `.dylib`, or `.dll`) and
+// loaded at runtime. A plugin exports three C functions:
+//
+//   uint32_t luza_plugin_abi_version(void);
+//   const char *luza_plugin_name(void);
+//   int32_t luza_plugin_genmove(const char *state_json, uint32_t player_idx,
+//                               int64_t remaining_ms, char *out, size_t out_len);
+//
+// The version has to be `PLUGIN_ABI_VERSION`. The name is how the strategy is
+// given in specs. `genmove` gets the state as JSON, the player to move, and
+// their remaining time in milliseconds (-1 without clocks), and writes the
+// action notation (see `azul::action_notation`) into `out` as a NUL terminated
+// string, returning 0 on success.
+//
+// Games are played on several threads, but luza never calls `genmove` of one
+// plugin from two threads at once, so plugins don't have to be thread safe.
+// Calls to different plugins can run at the same time.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+// Room for the action notation written by plugins
+const NOTATION_LEN: usize = 64;
+
+type VersionFn = unsafe extern "C" fn() -> u32;
+type NameFn = unsafe extern "C" fn() -> *const c_char;
+type GenmoveFn = unsafe extern "C" fn(*const c_char, u32, i64, *mut c_char, usize) -> i32;
+
+// A loaded plugin. Its library is kept for as long as the plugin so the
+// functions taken from it stay valid.
+pub struct Plugin {
+    pub name: String,
+    pub file: PathBuf,
+    genmove: GenmoveFn,
+    // Held so genmove is never called on two threads at once
+    genmove_lock: Mutex<()>,
+    #[cfg(all(feature = "plugins", any(unix, windows)))]
+    _library: Option<Library>,
+}
+
+impl Plugin {
+    // Plugin from its exported functions, checking the ABI version
+    fn from_fns(file: &Path, version: VersionFn, name: NameFn, genmove: GenmoveFn) -> Result<Self> {
+        let abi_version = unsafe { version() };
+        if abi_version != PLUGIN_ABI_VERSION {
+            return Err(anyhow!(
+                "Plugin {} is built for ABI version {}, this luza loads version {}",
+                file.display(),
+                abi_version,
+                PLUGIN_ABI_VERSION
+            ));
+        }
+
+        let name = unsafe { name() };
+        if name.is_null() {
+            return Err(anyhow!("Plugin {} has no name", file.display()));
+        }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().to_string();
+        if name.is_empty() || name.contains([':', '@', ',']) {
+            return Err(anyhow!("Plugin {} has an invalid name '{}'", file.display(), name));
+        }
+
+        Ok(Self {
+            name,
+            file: file.to_path_buf(),
+            genmove,
+            genmove_lock: Mutex::new(()),
+            #[cfg(all(feature = "plugins", any(unix, windows)))]
+            _library: None,
+        })
+    }
+
+    #[cfg(all(feature = "plugins", any(unix, windows)))]
+    pub fn load(file: &Path) -> Result<Self> {
+        let at_file = |err: libloading::Error| anyhow!("Unable to load plugin {}: {}", file.display(), err);
+        let library = unsafe { Library::new(file) }.map_err(at_file)?;
+
+        let plugin = unsafe {
+            let version: Symbol<VersionFn> = library.get(b"luza_plugin_abi_version").map_err(at_file)?;
+            let name: Symbol<NameFn> = library.get(b"luza_plugin_name").map_err(at_file)?;
+            let genmove: Symbol<GenmoveFn> = library.get(b"luza_plugin_genmove").map_err(at_file)?;
+            Self::from_fns(file, *version, *name, *genmove)?
+        };
+        Ok(Self { _library: Some(library), ..plugin })
+    }
+
+    #[cfg(all(feature = "plugins", not(any(unix, windows))))]
+    pub fn load(file: &Path) -> Result<Self> {
+        Err(anyhow!("Unable to load plugin {}: plugins are not supported on this platform", file.display()))
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    pub fn load(file: &Path) -> Result<Self> {
+        Err(anyhow!("Unable to load plugin {}: plugins need luza built with the plugins feature", file.display()))
+    }
+
+    pub fn genmove(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>) -> Result<azul::Action> {
+        let state_json = CString::new(serde_json::to_string(state)?)?;
+        let remaining_ms = remaining.map_or(-1, |r| r.as_millis() as i64);
+        let mut out = [0 as c_char; NOTATION_LEN];
+
+        let status = {
+            let _lock = self.genmove_lock.lock().unwrap();
+            unsafe { (self.genmove)(state_json.as_ptr(), player_idx as u32, remaining_ms, out.as_mut_ptr(), out.len()) }
+        };
+        if status != 0 {
+            return Err(anyhow!("Plugin '{}' failed with status {}", self.name, status));
+        }
+        // A missing terminator is cut off at the end of the buffer
+        out[NOTATION_LEN - 1] = 0;
+        let notation = unsafe { CStr::from_ptr(out.as_ptr()) }.to_string_lossy().to_string();
+
+        let action = azul::parse_action_notation(&notation)?;
+        if !rules.list_valid_actions(state, player_idx).contains(&action) {
+            return Err(anyhow!("Plugin '{}' played invalid action '{}'", self.name, notation));
+        }
+        Ok(action)
+    }
+}
+
+static PLUGINS: LazyLock<RwLock<BTreeMap<String, Arc<Plugin>>>> = LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+fn is_library(file: &Path) -> bool {
+    file.extension().is_some_and(|ext| ext == "so" || ext == "dylib" || ext == "dll")
+}
+
+// Load every plugin in the directory into the strategy registry, returning
+// their names. Plugins can't take the name of a built in strategy or of
+// another plugin.
+pub fn load_dir(dir: &Path, reserved: &[&str]) -> Result<Vec<String>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|file| is_library(file));
+    files.sort();
+
+    let mut plugins = PLUGINS.write().unwrap();
+    let mut names = Vec::new();
+    for file in files {
+        let plugin = Plugin::load(&file)?;
+        if reserved.contains(&plugin.name.as_str()) {
+            return Err(anyhow!("Plugin {} can't be named after the built in strategy '{}'", file.display(), plugin.name));
+        }
+        if let Some(other) = plugins.get(&plugin.name) {
+            return Err(anyhow!("Plugins {} and {} are both named '{}'", other.file.display(), file.display(), plugin.name));
+        }
+        log::info!("Loaded plugin '{}' from {}", plugin.name, file.display());
+        names.push(plugin.name.clone());
+        plugins.insert(plugin.name.clone(), Arc::new(plugin));
+    }
+    Ok(names)
+}
+
+pub fn get(name: &str) -> Option<Arc<Plugin>> {
+    PLUGINS.read().unwrap().get(name).cloned()
+}
+
+pub fn names() -> Vec<String> {
+    PLUGINS.read().unwrap().keys().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn current_version() -> u32 {
+        PLUGIN_ABI_VERSION
+    }
+
+    unsafe extern "C" fn old_version() -> u32 {
+        0
+    }
+
+    unsafe extern "C" fn first_name() -> *const c_char {
+        c"first".as_ptr()
+    }
+
+    // Plays the first valid action
+    unsafe extern "C" fn first_genmove(state_json: *const c_char, player_idx: u32, _remaining_ms: i64, out: *mut c_char, out_len: usize) -> i32 {
+        let state: azul::State = serde_json::from_str(&CStr::from_ptr(state_json).to_string_lossy()).unwrap();
+        let action = azul::AzulRules::current().list_valid_actions(&state, player_idx as usize)[0];
+        let notation = CString::new(azul::action_notation(action)).unwrap();
+        let bytes = notation.as_bytes_with_nul();
+        if bytes.len() > out_len {
+            return 1;
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr() as *const c_char, out, bytes.len());
+        0
+    }
+
+    #[test]
+    fn test_plugin() {
+        let rules = azul::AzulRules::current();
+        let file = Path::new("first.so");
+        assert!(Plugin::from_fns(file, old_version, first_name, first_genmove).is_err());
+
+        let plugin = Plugin::from_fns(file, current_version, first_name, first_genmove).unwrap();
+        assert_eq!(plugin.name, "first");
+
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        assert_eq!(plugin.genmove(&rules, &state, 0, None).unwrap(), rules.list_valid_actions(&state, 0)[0]);
+    }
+}
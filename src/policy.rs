@@ -0,0 +1,347 @@
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use rand::distr::{weighted::WeightedIndex, Distribution};
+use rand::seq::IndexedRandom;
+use tract_onnx::prelude::*;
+use tract_onnx::tract_core::internal::DimLike;
+use tract_onnx::tract_hir::infer::Factoid;
+
+use crate::games::azul::{self, action_index, N_ACTIONS};
+use crate::games::{RepresentOptions, Representable};
+
+// Linear softmax policy over the canonical action space with one row of
+// feature weights per action. Features are the state representation with the
+// model's options, and only valid actions are considered.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PolicyModel {
+    pub schema_version: usize,
+    pub normalize: bool,
+    // Features start from the board of the player to move
+    pub mover_first: bool,
+    pub weights: Vec<Vec<f64>>,
+    pub bias: Vec<f64>,
+}
+
+impl PolicyModel {
+    // Model with all weights zero, which plays uniformly at random
+    pub fn zeros(n_features: usize, normalize: bool, mover_first: bool) -> Self {
+        Self {
+            schema_version: azul::SCHEMA_VERSION,
+            normalize,
+            mover_first,
+            weights: vec![vec![0.0; n_features]; N_ACTIONS],
+            bias: vec![0.0; N_ACTIONS],
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let model: Self = serde_json::from_reader(File::open(path)?)?;
+
+        if model.schema_version != azul::SCHEMA_VERSION {
+            return Err(anyhow!(
+                "Policy in {} uses feature schema {} but this version has {}",
+                path.display(), model.schema_version, azul::SCHEMA_VERSION
+            ));
+        }
+        if model.weights.len() != N_ACTIONS || model.bias.len() != N_ACTIONS {
+            return Err(anyhow!("Policy in {} needs {} rows of weights and biases", path.display(), N_ACTIONS));
+        }
+        if model.weights.iter().any(|row| row.len() != model.weights[0].len()) {
+            return Err(anyhow!("Policy in {} has rows of different sizes", path.display()));
+        }
+
+        Ok(model)
+    }
+
+    // One step of gradient descent on the cross entropy of the target action
+    // against the other valid actions, all given by their action index
+    pub fn sgd_step(&mut self, features: &[f64], valid_actions: &[usize], target: usize, learning_rate: f64) {
+        let logits: Vec<f64> = valid_actions
+            .iter()
+            .map(|&i| self.bias[i] + self.weights[i].iter().zip(features).map(|(w, f)| w * f).sum::<f64>())
+            .collect();
+        let max_logit = logits.iter().copied().fold(f64::MIN, f64::max);
+        let exps: Vec<f64> = logits.iter().map(|l| (l - max_logit).exp()).collect();
+        let total: f64 = exps.iter().sum();
+
+        for (&i, e) in valid_actions.iter().zip(exps) {
+            let gradient = e / total - if i == target { 1.0 } else { 0.0 };
+            self.bias[i] -= learning_rate * gradient;
+            for (w, f) in self.weights[i].iter_mut().zip(features) {
+                *w -= learning_rate * gradient * f;
+            }
+        }
+    }
+}
+
+// Probabilities of the valid actions, in `actions_canonical` order, given the
+// logits of the whole action space. Lower temperatures sharpen the
+// distribution and 0 puts all weight on the best action.
+pub fn masked_softmax(logits: &[f64], actions: &[azul::Action], temperature: f64) -> Vec<f64> {
+    let logits: Vec<f64> = actions.iter().map(|a| action_index(a).map_or(f64::MIN, |i| logits[i])).collect();
+    let max_logit = logits.iter().copied().fold(f64::MIN, f64::max);
+
+    if temperature <= 0.0 {
+        let best = logits.iter().position(|&l| l == max_logit).unwrap();
+        return (0..logits.len()).map(|i| if i == best { 1.0 } else { 0.0 }).collect();
+    }
+
+    let exps: Vec<f64> = logits.iter().map(|l| ((l - max_logit) / temperature).exp()).collect();
+    let total: f64 = exps.iter().sum();
+    exps.into_iter().map(|e| e / total).collect()
+}
+
+// State features with the options a model was trained with
+fn features(state: &azul::State, player_idx: usize, normalize: bool, mover_first: bool) -> Vec<f64> {
+    let options = RepresentOptions {
+        normalize,
+        perspective: if mover_first { player_idx } else { 0 },
+    };
+    state.represent_with(&options)
+}
+
+// Error for features that don't fit a model taking `n_features`
+fn check_features(n_features: usize, features: &[f64]) -> Result<()> {
+    if features.len() != n_features {
+        return Err(anyhow!(
+            "Policy takes {} features but the state has {}, it may be for a different number of players",
+            n_features, features.len()
+        ));
+    }
+    Ok(())
+}
+
+// Model giving logits of the whole action space from state features. These
+// are policies by themselves and can be shared through an `InferenceQueue`.
+pub trait PolicyNet: Send + Sync {
+    fn features(&self, state: &azul::State, player_idx: usize) -> Vec<f64>;
+
+    // Error for features that don't fit the model
+    fn check_features(&self, features: &[f64]) -> Result<()>;
+
+    // Logits for many feature vectors at once
+    fn forward(&self, batch: &[Vec<f64>]) -> Result<Vec<Vec<f64>>>;
+}
+
+impl PolicyNet for PolicyModel {
+    fn features(&self, state: &azul::State, player_idx: usize) -> Vec<f64> {
+        features(state, player_idx, self.normalize, self.mover_first)
+    }
+
+    fn check_features(&self, features: &[f64]) -> Result<()> {
+        check_features(self.weights[0].len(), features)
+    }
+
+    // Goes over the weights once for the whole batch
+    fn forward(&self, batch: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        let mut logits = vec![self.bias.clone(); batch.len()];
+        for (i, row) in self.weights.iter().enumerate() {
+            for (features, out) in batch.iter().zip(logits.iter_mut()) {
+                out[i] += row.iter().zip(features).map(|(w, f)| w * f).sum::<f64>();
+            }
+        }
+        Ok(logits)
+    }
+}
+
+// Network exported to ONNX taking a `[1, n_features]` float input and giving
+// `[1, N_ACTIONS]` logits. The file doesn't say how features were made so
+// that comes from the player spec.
+pub struct OnnxPolicy {
+    normalize: bool,
+    mover_first: bool,
+    n_features: usize,
+    plan: TypedRunnableModel<TypedModel>,
+}
+
+impl OnnxPolicy {
+    pub fn load(path: &Path, normalize: bool, mover_first: bool) -> Result<Self> {
+        let model = tract_onnx::onnx().model_for_path(path).map_err(|err| anyhow!("Can't read policy in {}: {}", path.display(), err))?;
+        Self::from_model(model, normalize, mover_first)
+    }
+
+    pub fn from_model(model: InferenceModel, normalize: bool, mover_first: bool) -> Result<Self> {
+        let n_features = model
+            .input_fact(0)?
+            .shape
+            .dims()
+            .last()
+            .and_then(|dim| dim.concretize())
+            .and_then(|dim| dim.to_usize().ok())
+            .ok_or_else(|| anyhow!("ONNX policies need an input with a fixed number of features"))?;
+
+        let plan = model
+            .with_input_fact(0, f32::fact([1, n_features]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+
+        Ok(Self { normalize, mover_first, n_features, plan })
+    }
+}
+
+impl PolicyNet for OnnxPolicy {
+    fn features(&self, state: &azul::State, player_idx: usize) -> Vec<f64> {
+        features(state, player_idx, self.normalize, self.mover_first)
+    }
+
+    fn check_features(&self, features: &[f64]) -> Result<()> {
+        check_features(self.n_features, features)
+    }
+
+    // The plan is made for one state at a time so batches are run row by row
+    fn forward(&self, batch: &[Vec<f64>]) -> Result<Vec<Vec<f64>>> {
+        batch
+            .iter()
+            .map(|features| {
+                let input = Tensor::from_shape(&[1, features.len()], &features.iter().map(|&f| f as f32).collect::<Vec<f32>>())?;
+                let outputs = self.plan.run(tvec!(input.into()))?;
+                let logits: Vec<f64> = outputs[0].as_slice::<f32>()?.iter().map(|&l| l as f64).collect();
+                if logits.len() != N_ACTIONS {
+                    return Err(anyhow!("Policy gave {} logits instead of {}", logits.len(), N_ACTIONS));
+                }
+                Ok(logits)
+            })
+            .collect()
+    }
+}
+
+// Anything giving probabilities over the valid actions of a state, either a
+// model evaluated directly or through a batching queue
+pub trait Policy: Send + Sync {
+    fn probabilities(&self, state: &azul::State, player_idx: usize, temperature: f64) -> Result<Vec<f64>>;
+
+    // Sample an action from the policy. A model that doesn't fit the state
+    // plays randomly instead of stopping the game, and one giving no weight
+    // to any valid action picks uniformly.
+    fn play(&self, state: &azul::State, player_idx: usize, temperature: f64) -> azul::Action {
+        let actions = azul::actions_canonical(&azul::AzulRules::current(), state, player_idx);
+        let mut rng = azul::game_rng();
+
+        match self.probabilities(state, player_idx, temperature) {
+            Ok(probabilities) => match WeightedIndex::new(&probabilities) {
+                Ok(dist) => actions[dist.sample(&mut rng)],
+                Err(_) => *actions.choose(&mut rng).unwrap(),
+            },
+            Err(err) => {
+                log::warn!("{}, playing a random move", err);
+                *actions.choose(&mut rng).unwrap()
+            },
+        }
+    }
+}
+
+impl<N: PolicyNet> Policy for N {
+    fn probabilities(&self, state: &azul::State, player_idx: usize, temperature: f64) -> Result<Vec<f64>> {
+        let features = self.features(state, player_idx);
+        self.check_features(&features)?;
+        let logits = self.forward(&[features])?.remove(0);
+        Ok(masked_softmax(&logits, &azul::actions_canonical(&azul::AzulRules::current(), state, player_idx), temperature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+    use tract_onnx::pb;
+
+    #[test]
+    fn test_probabilities() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let actions = azul::actions_canonical(&rules, &state, 0);
+
+        let mut indices: Vec<usize> = actions.iter().map(|a| action_index(a).unwrap()).collect();
+        indices.sort();
+        indices.dedup();
+        assert_eq!(indices.len(), actions.len());
+
+        let mut model = PolicyModel::zeros(state.represent().len(), false, true);
+        let probabilities = model.probabilities(&state, 0, 1.0).unwrap();
+        // All weight on invalid actions still plays a valid one
+        let action = model.play(&state, 0, 0.0);
+        assert!(actions.contains(&action));
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((probabilities[0] - 1.0 / actions.len() as f64).abs() < 1e-9);
+
+        model.bias[action_index(&actions[3]).unwrap()] = 1.0;
+        assert_eq!(model.probabilities(&state, 0, 0.0).unwrap()[3], 1.0);
+
+        let features = model.features(&state, 0);
+        let valid: Vec<usize> = actions.iter().map(|a| action_index(a).unwrap()).collect();
+        for _ in 0..20 {
+            model.sgd_step(&features, &valid, valid[5], 0.1);
+        }
+        assert_eq!(model.probabilities(&state, 0, 0.0).unwrap()[5], 1.0);
+    }
+
+    // ONNX graph computing `features * weights + bias` with zero weights and
+    // the given bias
+    fn linear_onnx(n_features: usize, bias: Vec<f32>) -> pb::ModelProto {
+        let tensor = |name: &str, dims: Vec<i64>, float_data: Vec<f32>| pb::TensorProto {
+            name: name.to_string(),
+            dims,
+            data_type: pb::tensor_proto::DataType::Float as i32,
+            float_data,
+            ..Default::default()
+        };
+        let value = |name: &str, dims: [i64; 2]| pb::ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(pb::TypeProto {
+                value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                    elem_type: pb::tensor_proto::DataType::Float as i32,
+                    shape: Some(pb::TensorShapeProto {
+                        dim: dims
+                            .into_iter()
+                            .map(|d| pb::tensor_shape_proto::Dimension { value: Some(pb::tensor_shape_proto::dimension::Value::DimValue(d)), ..Default::default() })
+                            .collect(),
+                    }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let node = |op: &str, input: [&str; 2], output: &str| pb::NodeProto {
+            op_type: op.to_string(),
+            input: input.map(String::from).to_vec(),
+            output: vec![output.to_string()],
+            ..Default::default()
+        };
+
+        pb::ModelProto {
+            ir_version: 7,
+            opset_import: vec![pb::OperatorSetIdProto { domain: String::new(), version: 13 }],
+            graph: Some(pb::GraphProto {
+                node: vec![node("MatMul", ["features", "weights"], "product"), node("Add", ["product", "bias"], "logits")],
+                initializer: vec![
+                    tensor("weights", vec![n_features as i64, N_ACTIONS as i64], vec![0.0; n_features * N_ACTIONS]),
+                    tensor("bias", vec![N_ACTIONS as i64], bias),
+                ],
+                input: vec![value("features", [1, n_features as i64])],
+                output: vec![value("logits", [1, N_ACTIONS as i64])],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_onnx() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let actions = azul::actions_canonical(&rules, &state, 0);
+        let n_features = state.represent().len();
+
+        let mut bias = vec![0.0; N_ACTIONS];
+        bias[action_index(&actions[3]).unwrap()] = 1.0;
+        let model = tract_onnx::onnx().model_for_proto_model(&linear_onnx(n_features, bias)).unwrap();
+        let policy = OnnxPolicy::from_model(model, false, true).unwrap();
+
+        assert_eq!(policy.probabilities(&state, 0, 0.0).unwrap()[3], 1.0);
+        assert!(policy.check_features(&[0.0; 3]).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::games::azul;
+use crate::tui;
+use crate::PlayFn;
+
+// How often the teacher's search reports its current favorite, matching the
+// redraws of the TUI
+const INFO_INTERVAL: Duration = Duration::from_millis(250);
+
+// Teacher's favorite so far with its mean score, win rate, and the rollouts
+// done
+#[derive(Debug, Clone, Copy)]
+pub struct Favorite {
+    pub action: azul::Action,
+    pub mean_score: f32,
+    pub win_rate: f32,
+    pub rollouts: usize,
+}
+
+enum PonderResult {
+    Info(Favorite),
+    TeacherMove(azul::Action),
+    // Expected score and win probability of an action
+    Analysis(azul::Action, f32, f32),
+}
+
+// Background search on a snapshot of the position while the human is thinking.
+// The teacher's move is searched first and then actions are analyzed in the
+// given order. The search is cancelled when this is dropped.
+pub struct Ponder {
+    // Ply and teacher configuration this search is valid for
+    pub ply: usize,
+    pub config: azul::MctsConfig,
+    cancel: Arc<AtomicBool>,
+    results: Receiver<PonderResult>,
+    teacher_move: Option<azul::Action>,
+    favorite: Option<Favorite>,
+    analyses: Vec<(azul::Action, f32, f32)>,
+}
+
+impl Ponder {
+    pub fn start(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, ply: usize, config: azul::MctsConfig, actions: Vec<azul::Action>) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, results) = mpsc::channel();
+
+        let rules = rules.clone();
+        let state = state.clone();
+        let thread_cancel = cancel.clone();
+
+        thread::spawn(move || {
+            let stop = || thread_cancel.load(Ordering::Relaxed);
+
+            let info_sender = sender.clone();
+            let mut send_info = |rollouts: usize, stats: &[azul::MctsStats]| {
+                if let Some(best) = stats.first() {
+                    let favorite = Favorite { action: best.action, mean_score: best.mean_score, win_rate: best.win_rate, rollouts };
+                    let _ = info_sender.send(PonderResult::Info(favorite));
+                }
+            };
+            let action = azul::play_mcts_until(&rules, &state, player_idx, &config, &stop, Some((INFO_INTERVAL, &mut send_info)));
+            if stop() || sender.send(PonderResult::TeacherMove(action)).is_err() {
+                return;
+            }
+
+            for action in actions {
+                let (expected_score, win_probability) = azul::mcts_q_fn(&rules, &state, player_idx, action);
+                if stop() || sender.send(PonderResult::Analysis(action, expected_score, win_probability)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { ply, config, cancel, results, teacher_move: None, favorite: None, analyses: Vec::new() }
+    }
+
+    fn handle(&mut self, result: PonderResult) {
+        match result {
+            PonderResult::Info(favorite) => self.favorite = Some(favorite),
+            PonderResult::TeacherMove(action) => self.teacher_move = Some(action),
+            PonderResult::Analysis(action, expected_score, win_probability) => {
+                self.analyses.push((action, expected_score, win_probability))
+            },
+        }
+    }
+
+    // Action analyses finished since the last call
+    pub fn poll(&mut self) -> Vec<(azul::Action, f32, f32)> {
+        let results: Vec<PonderResult> = self.results.try_iter().collect();
+        for result in results {
+            self.handle(result);
+        }
+
+        std::mem::take(&mut self.analyses)
+    }
+
+    // Teacher's favorite as of the last poll, which is only reported by
+    // searches running long enough
+    pub fn favorite(&self) -> Option<Favorite> {
+        self.favorite
+    }
+
+    // Whether the teacher's move is still being searched, as of the last poll
+    pub fn is_searching(&self) -> bool {
+        self.teacher_move.is_none()
+    }
+
+    // Teacher's move, waiting for the search to finish if it's still running
+    pub fn teacher_move(&mut self) -> Option<azul::Action> {
+        while self.teacher_move.is_none() {
+            match self.results.recv() {
+                Ok(result) => self.handle(result),
+                Err(_) => break,
+            }
+        }
+
+        self.teacher_move
+    }
+}
+
+impl Drop for Ponder {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// A played move to review against the teacher, with the expected scores and
+// win probabilities already known in its position
+pub struct ReviewJob {
+    pub state: azul::State,
+    pub player_idx: usize,
+    pub ply: usize,
+    pub action: azul::Action,
+    pub think_time: Duration,
+    pub auto_played: bool,
+    pub known: HashMap<azul::Action, (f32, f32)>,
+}
+
+// A finished review with the evaluations that had to be searched for it
+pub struct Reviewed {
+    pub review: tui::PlyReview,
+    pub evaluations: Vec<(azul::Action, f32, f32)>,
+}
+
+// Background reviews of played moves, run one after the other so the TUI
+// never waits on the teacher. Reviews still queued are dropped with this.
+pub struct Reviewer {
+    jobs: Sender<ReviewJob>,
+    results: Receiver<Reviewed>,
+    pending: usize,
+}
+
+impl Reviewer {
+    pub fn start(rules: &azul::AzulRules, teacher: PlayFn) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<ReviewJob>();
+        let (sender, results) = mpsc::channel();
+        let rules = rules.clone();
+
+        thread::spawn(move || {
+            for job in job_receiver {
+                if sender.send(review(&rules, job, teacher)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { jobs, results, pending: 0 }
+    }
+
+    pub fn submit(&mut self, job: ReviewJob) {
+        if self.jobs.send(job).is_ok() {
+            self.pending += 1;
+        }
+    }
+
+    // Reviews finished since the last call
+    pub fn poll(&mut self) -> Vec<Reviewed> {
+        let reviewed: Vec<Reviewed> = self.results.try_iter().collect();
+        self.pending -= reviewed.len();
+        reviewed
+    }
+
+    // Reviews submitted and not yet returned by a poll
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+fn review(rules: &azul::AzulRules, job: ReviewJob, teacher: PlayFn) -> Reviewed {
+    let mut evaluations = Vec::new();
+    let mut evaluate = |action: azul::Action| match job.known.get(&action) {
+        Some(&(expected_score, _)) => expected_score,
+        None => {
+            let (expected_score, win_probability) = azul::mcts_q_fn(rules, &job.state, job.player_idx, action);
+            evaluations.push((action, expected_score, win_probability));
+            expected_score
+        },
+    };
+
+    let best_action = teacher(rules, &job.state, job.player_idx);
+    let expected_score = evaluate(job.action);
+    let best_expected_score = if best_action == job.action { expected_score } else { evaluate(best_action) };
+
+    let review = tui::PlyReview {
+        ply: job.ply,
+        player: job.player_idx,
+        state: job.state,
+        action: job.action,
+        best_action,
+        expected_score,
+        best_expected_score,
+        think_time: job.think_time,
+        auto_played: job.auto_played,
+    };
+    Reviewed { review, evaluations }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+#[cfg(feature = "tui")]
+use crate::tui::ActionAnalysis;
+
+// One finished interactive game as seen from the profile owner's seat
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GameRecord {
+    pub timestamp: u64,
+    pub n_players: usize,
+    pub seat: usize,
+    pub score: i32,
+    pub won: bool,
+    // Shared the win with players tied on score and completed rows
+    #[serde(default)]
+    pub drawn: bool,
+    pub rounds: usize,
+    pub plies: usize,
+    pub mistakes: HashMap<String, usize>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub games: Vec<GameRecord>,
+}
+
+// Directory for all locally persisted data. Follows XDG and falls back to
+// ~/.local/share/luza.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Ok(dir) = std::env::var("XDG_DATA_HOME") {
+        return Ok(PathBuf::from(dir).join("luza"));
+    }
+
+    match std::env::var("HOME") {
+        Ok(home) => Ok(PathBuf::from(home).join(".local/share/luza")),
+        Err(_) => Err(anyhow!("Unable to locate data directory, neither XDG_DATA_HOME nor HOME is set")),
+    }
+}
+
+fn profile_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(anyhow!("Invalid profile name '{}', use letters, digits, '-' and '_' only", name));
+    }
+
+    Ok(data_dir()?.join("profiles").join(format!("{}.json", name)))
+}
+
+// Load the profile with given name, a missing profile is returned empty
+pub fn load(name: &str) -> Result<Profile> {
+    let path = profile_path(name)?;
+
+    if !path.exists() {
+        return Ok(Profile { name: name.to_string(), games: Vec::new() });
+    }
+
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+pub fn save(profile: &Profile) -> Result<()> {
+    let path = profile_path(&profile.name)?;
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, profile)?;
+    Ok(())
+}
+
+// Expected score a move has to lose against the best analyzed move to count
+// as a mistake
+#[cfg(feature = "tui")]
+const MISTAKE_LOSS: f32 = 1.0;
+
+// Tell what kind of mistake, if any, the chosen action is compared to the
+// other available actions. Like the accuracy report this judges by the
+// teacher's expected score, so a move with no immediate loss can still be a
+// positional mistake. Without teacher evaluations this falls back to the
+// immediate score gains.
+#[cfg(feature = "tui")]
+pub fn classify_mistake(actions: &[azul::Action], analyses: &HashMap<azul::Action, ActionAnalysis>, action: azul::Action) -> Option<String> {
+    let analysis = analyses.get(&action)?;
+    let best_gain = actions.iter().filter_map(|a| analyses.get(a)).map(|a| a.score_gain).max()?;
+    let floor_dump = action.pattern_line_choice.is_none() && actions.iter().any(|a| a.pattern_line_choice.is_some());
+
+    let best_expected = actions
+        .iter()
+        .filter_map(|a| analyses.get(a).and_then(|a| a.expected_score))
+        .max_by(|a, b| a.total_cmp(b));
+
+    if let (Some(expected), Some(best_expected)) = (analysis.expected_score, best_expected) {
+        if best_expected - expected < MISTAKE_LOSS {
+            return None;
+        }
+    }
+
+    if floor_dump {
+        Some("floor-dump".to_string())
+    } else if analysis.score_gain < best_gain {
+        Some("missed-gain".to_string())
+    } else if analysis.expected_score.is_some() && best_expected.is_some() {
+        Some("positional".to_string())
+    } else {
+        None
+    }
+}
+
+// Add a finished game to the named profile and persist it
+pub fn record_game(name: &str, state: &azul::State, seat: usize, plies: usize, mistakes: HashMap<String, usize>) -> Result<()> {
+    let mut profile = load(name)?;
+
+    profile.games.push(GameRecord {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        n_players: state.players.len(),
+        seat,
+        score: state.players[seat].score,
+        won: azul::winner(state) == Some(seat),
+        drawn: azul::winner(state).is_none() && azul::winners(state).contains(&seat),
+        rounds: state.rounds,
+        plies,
+        mistakes,
+    });
+
+    save(&profile)
+}
+
+impl Profile {
+    pub fn print_summary(&self) {
+        let n_games = self.games.len();
+        println!("Profile: {}", self.name);
+        println!("Games played: {}", n_games);
+
+        if n_games == 0 {
+            return;
+        }
+
+        let n_wins = self.games.iter().filter(|g| g.won).count();
+        let total_score: i32 = self.games.iter().map(|g| g.score).sum();
+        println!("Wins: {}/{}, ratio: {}", n_wins, n_games, n_wins as f64 / n_games as f64);
+        let n_draws = self.games.iter().filter(|g| g.drawn).count();
+        if n_draws > 0 {
+            println!("Draws: {}/{}", n_draws, n_games);
+        }
+        println!("Average score: {:.2}", total_score as f64 / n_games as f64);
+
+        let mut mistakes: HashMap<&str, usize> = HashMap::new();
+        for game in &self.games {
+            for (kind, count) in &game.mistakes {
+                *mistakes.entry(kind).or_insert(0) += count;
+            }
+        }
+
+        let mut mistakes: Vec<(&str, usize)> = mistakes.into_iter().collect();
+        mistakes.sort_by_key(|(kind, count)| (std::cmp::Reverse(*count), *kind));
+
+        println!("Common mistakes:");
+        for (kind, count) in mistakes {
+            println!("  {}: {} ({:.2} per game)", kind, count, count as f64 / n_games as f64);
+        }
+
+        println!("Recent games:");
+        for game in self.games.iter().rev().take(5) {
+            println!(
+                "  {} players, seat P{}, score {}, {}",
+                game.n_players, game.seat, game.score, if game.won { "won" } else if game.drawn { "drawn" } else { "lost" }
+            );
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::widgets::TableState;
+
+use crate::games::{azul, Rules};
+use crate::profile;
+use crate::tui::PuzzleApp;
+
+// A position with a move to find, read from the rows `luza blunders` writes.
+// Only the position, the mover and the engine's best move are needed so sets
+// can be curated by hand too.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Puzzle {
+    pub state: String,
+    pub player: usize,
+    pub best: String,
+    // Move that was played in the game the position came from
+    #[serde(default)]
+    pub played: Option<String>,
+}
+
+// Puzzle results kept across sessions
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct PuzzleRecord {
+    pub attempted: usize,
+    pub solved: usize,
+    pub best_streak: usize,
+}
+
+fn record_path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("puzzles.json"))
+}
+
+pub fn load_record() -> Result<PuzzleRecord> {
+    let path = record_path()?;
+    if !path.exists() {
+        return Ok(PuzzleRecord::default());
+    }
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+pub fn save_record(record: &PuzzleRecord) -> Result<()> {
+    let path = record_path()?;
+    fs::create_dir_all(path.parent().unwrap())?;
+    serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), record)?;
+    Ok(())
+}
+
+// Read a puzzle set, either one puzzle per line or the JSON array written by
+// `luza --format json blunders`
+pub fn load(file: &PathBuf) -> Result<Vec<Puzzle>> {
+    let text = fs::read_to_string(file)?;
+    let puzzles: Vec<Puzzle> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(&text)?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?
+    };
+
+    if puzzles.is_empty() {
+        return Err(anyhow!("No puzzles in {}", file.display()));
+    }
+    Ok(puzzles)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guess {
+    Solved,
+    // Wrong with tries left
+    Wrong,
+    // Wrong and out of tries
+    Failed,
+}
+
+// Tries and streaks over a session of puzzles
+#[derive(Debug, Clone)]
+pub struct Drill {
+    pub tries: usize,
+    pub tries_left: usize,
+    pub streak: usize,
+    pub record: PuzzleRecord,
+}
+
+impl Drill {
+    pub fn new(tries: usize, record: PuzzleRecord) -> Self {
+        Drill { tries, tries_left: tries, streak: 0, record }
+    }
+
+    pub fn guess(&mut self, best: azul::Action, action: azul::Action) -> Guess {
+        if action == best {
+            self.streak += 1;
+            self.record.attempted += 1;
+            self.record.solved += 1;
+            self.record.best_streak = self.record.best_streak.max(self.streak);
+            return Guess::Solved;
+        }
+
+        self.tries_left = self.tries_left.saturating_sub(1);
+        if self.tries_left > 0 {
+            Guess::Wrong
+        } else {
+            self.give_up();
+            Guess::Failed
+        }
+    }
+
+    // Leave the puzzle unsolved, which ends the streak
+    pub fn give_up(&mut self) {
+        self.tries_left = 0;
+        self.streak = 0;
+        self.record.attempted += 1;
+    }
+
+    pub fn next(&mut self) {
+        self.tries_left = self.tries;
+    }
+}
+
+fn puzzle_app(puzzles: &[Puzzle], idx: usize, drill: &Drill) -> Result<(PuzzleApp, azul::Action)> {
+    let puzzle = &puzzles[idx];
+    let state: azul::State = serde_json::from_str(&puzzle.state)?;
+    let best = azul::parse_action_notation(&puzzle.best)?;
+    let actions = azul::AzulRules::current().list_valid_actions(&state, puzzle.player);
+    if !actions.contains(&best) {
+        return Err(anyhow!("Puzzle {} has best move {} which is not valid in its position", idx + 1, puzzle.best));
+    }
+
+    let mut actions_state = TableState::default();
+    actions_state.select_first();
+    let app = PuzzleApp {
+        state,
+        player: puzzle.player,
+        actions,
+        actions_state,
+        puzzle: idx,
+        n_puzzles: puzzles.len(),
+        tries_left: drill.tries_left,
+        streak: drill.streak,
+        best_streak: drill.record.best_streak,
+        tried: Vec::new(),
+        solution: None,
+        played: puzzle.played.clone(),
+        feedback: "Find the best move".to_string(),
+    };
+    Ok((app, best))
+}
+
+// Loop over the puzzles in the TUI until they run out or the user quits
+fn drill(terminal: &mut ratatui::DefaultTerminal, puzzles: &[Puzzle], drill: &mut Drill) -> Result<()> {
+    let mut idx = 0;
+    let (mut app, mut best) = puzzle_app(puzzles, idx, drill)?;
+
+    loop {
+        terminal.draw(|frame| {
+            frame.render_widget(app.clone(), frame.area());
+        })?;
+
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        match key_event.code {
+            KeyCode::Char('q') => {
+                if app.solution.is_none() && !app.tried.is_empty() {
+                    drill.give_up();
+                }
+                return Ok(());
+            },
+            KeyCode::Down if app.actions_state.selected().is_some_and(|i| i < app.actions.len() - 1) => {
+                app.actions_state.select_next();
+            },
+            KeyCode::Up => app.actions_state.select_previous(),
+            KeyCode::Enter if app.solution.is_none() => {
+                let Some(action) = app.actions_state.selected().map(|i| app.actions[i]) else {
+                    continue;
+                };
+                if app.tried.contains(&action) {
+                    continue;
+                }
+                app.feedback = match drill.guess(best, action) {
+                    Guess::Solved => {
+                        app.solution = Some(best);
+                        "Correct! Press n for the next puzzle".to_string()
+                    },
+                    Guess::Wrong => {
+                        app.tried.push(action);
+                        format!("{} is not it, try again", azul::action_notation(action))
+                    },
+                    Guess::Failed => {
+                        app.tried.push(action);
+                        app.solution = Some(best);
+                        format!("Out of tries, the best move is {}", azul::action_notation(best))
+                    },
+                };
+                app.tries_left = drill.tries_left;
+                app.streak = drill.streak;
+                app.best_streak = drill.record.best_streak;
+            },
+            KeyCode::Char('n') => {
+                if app.solution.is_none() {
+                    drill.give_up();
+                }
+                idx += 1;
+                if idx == puzzles.len() {
+                    return Ok(());
+                }
+                drill.next();
+                (app, best) = puzzle_app(puzzles, idx, drill)?;
+            },
+            _ => {},
+        }
+    }
+}
+
+// Present the puzzles of the set one by one, asking for the engine's best
+// move within `tries` guesses, and keep the best streak of solved puzzles
+pub fn run(set: &PathBuf, tries: usize) -> Result<()> {
+    if tries == 0 {
+        return Err(anyhow!("Puzzles need at least one try"));
+    }
+    let puzzles = load(set)?;
+    let mut session = Drill::new(tries, load_record()?);
+    let start = session.record.clone();
+    // Catch broken puzzles before the terminal is taken over
+    for idx in 0..puzzles.len() {
+        puzzle_app(&puzzles, idx, &session)?;
+    }
+
+    let mut terminal = ratatui::init();
+    let result = drill(&mut terminal, &puzzles, &mut session);
+    ratatui::restore();
+    result?;
+
+    save_record(&session.record)?;
+    println!(
+        "Solved {} of {} puzzles, best streak {}",
+        session.record.solved - start.solved,
+        session.record.attempted - start.attempted,
+        session.record.best_streak
+    );
+    println!(
+        "All time: solved {} of {} puzzles",
+        session.record.solved, session.record.attempted
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drill() {
+        let best = azul::parse_action_notation("D1 Blue 2").unwrap();
+        let other = azul::parse_action_notation("C Red F").unwrap();
+        let mut drill = Drill::new(2, PuzzleRecord { attempted: 4, solved: 3, best_streak: 1 });
+
+        assert_eq!(drill.guess(best, other), Guess::Wrong);
+        assert_eq!(drill.guess(best, best), Guess::Solved);
+        drill.next();
+        assert_eq!(drill.guess(best, best), Guess::Solved);
+        assert_eq!((drill.streak, drill.record.best_streak), (2, 2));
+
+        drill.next();
+        assert_eq!(drill.guess(best, other), Guess::Wrong);
+        assert_eq!(drill.guess(best, other), Guess::Failed);
+        assert_eq!(drill.streak, 0);
+        assert_eq!((drill.record.attempted, drill.record.solved, drill.record.best_streak), (7, 5, 2));
+    }
+}
@@ -0,0 +1,224 @@
+// Elo rating helpers shared by the league and evaluation commands, and the
+// ratings kept across runs
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+
+use crate::output::{self, Format, LeagueRating, RatingHistory};
+use crate::profile;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+pub const K_FACTOR: f64 = 16.0;
+
+// Expected score of a player rated `a` against one rated `b`
+pub fn expected_score(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+// New ratings for both players after `a` scored `score` (1 win, 0.5 draw, 0
+// loss) against `b`
+pub fn update(a: f64, b: f64, score: f64) -> (f64, f64) {
+    let delta = K_FACTOR * (score - expected_score(a, b));
+    (a + delta, b - delta)
+}
+
+// Match scores of 0 and 1 are taken as this close to them, which caps the
+// Elo difference of a shutout at about 1200
+const SCORE_CLAMP: f64 = 1e-3;
+
+// Elo difference implied by the results of a match from the first player's
+// view, as (wins, losses, draws), with the margin of its 95% confidence
+// interval
+pub fn elo_difference(results: (usize, usize, usize)) -> (f64, f64) {
+    let (wins, losses, draws) = (results.0 as f64, results.1 as f64, results.2 as f64);
+    let n = wins + losses + draws;
+    if n == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let score = (wins + draws / 2.0) / n;
+    let variance = (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2)) / n;
+    let score_margin = 1.96 * (variance / n).sqrt();
+    let elo = |s: f64| -400.0 * (1.0 / s.clamp(SCORE_CLAMP, 1.0 - SCORE_CLAMP) - 1.0).log10();
+    (elo(score), (elo(score + score_margin) - elo(score - score_margin)) / 2.0)
+}
+
+// Rating of a strategy after one match and what played the match
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RatingChange {
+    // Seconds since the Unix epoch
+    pub timestamp: u64,
+    // Command that played the match, like `tournament`
+    pub source: String,
+    pub rating: f64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RatedPlayer {
+    pub rating: f64,
+    pub games: usize,
+    pub history: Vec<RatingChange>,
+}
+
+impl Default for RatedPlayer {
+    fn default() -> Self {
+        RatedPlayer { rating: INITIAL_RATING, games: 0, history: Vec::new() }
+    }
+}
+
+// Ratings of every strategy spec (policy models included) that played in a
+// tournament, league, or training match, kept in the data directory so
+// results stay comparable across runs
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Ratings {
+    pub players: BTreeMap<String, RatedPlayer>,
+}
+
+fn ratings_path() -> Result<PathBuf> {
+    Ok(profile::data_dir()?.join("ratings.json"))
+}
+
+impl Ratings {
+    pub fn load() -> Result<Self> {
+        let path = ratings_path()?;
+        if !path.exists() {
+            return Ok(Ratings::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = ratings_path()?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    // Update both ratings game by game with the results of a match from the
+    // first player's view, as (wins, losses, draws)
+    pub fn record_match(&mut self, a: &str, b: &str, results: (usize, usize, usize), source: &str) {
+        let (a_wins, b_wins, draws) = results;
+        let mut ra = self.players.get(a).map_or(INITIAL_RATING, |p| p.rating);
+        let mut rb = self.players.get(b).map_or(INITIAL_RATING, |p| p.rating);
+        for (count, score) in [(a_wins, 1.0), (b_wins, 0.0), (draws, 0.5)] {
+            for _ in 0..count {
+                (ra, rb) = update(ra, rb, score);
+            }
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        for (spec, rating) in [(a, ra), (b, rb)] {
+            let player = self.players.entry(spec.to_string()).or_default();
+            player.rating = rating;
+            player.games += a_wins + b_wins + draws;
+            player.history.push(RatingChange { timestamp, source: source.to_string(), rating });
+        }
+    }
+
+    // Specs by rating, best first
+    pub fn leaderboard(&self) -> Vec<(&String, &RatedPlayer)> {
+        let mut players: Vec<_> = self.players.iter().collect();
+        players.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        players
+    }
+}
+
+// Add the results of a run's matches to the persisted ratings
+pub fn record_matches(matches: &[(String, String, (usize, usize, usize))], source: &str) -> Result<()> {
+    let mut ratings = Ratings::load()?;
+    for (a, b, results) in matches {
+        ratings.record_match(a, b, *results, source);
+    }
+    ratings.save()
+}
+
+// Print the leaderboard of persisted ratings, or with `history` the rating of
+// every spec after each of its matches
+pub fn run(history: bool, format: Format) -> Result<()> {
+    let ratings = Ratings::load()?;
+    if ratings.players.is_empty() {
+        return Err(anyhow!("No ratings yet, they are recorded by tournament, league, and train runs"));
+    }
+    let leaderboard = ratings.leaderboard();
+
+    if history {
+        if format != Format::Text {
+            let rows: Vec<RatingHistory> = leaderboard
+                .iter()
+                .flat_map(|(spec, player)| player.history.iter().map(|change| RatingHistory {
+                    spec: spec.to_string(),
+                    timestamp: change.timestamp,
+                    source: change.source.clone(),
+                    rating: change.rating,
+                }))
+                .collect();
+            return output::write_rows(&rows, format);
+        }
+        for (spec, player) in leaderboard {
+            let changes: Vec<String> = player.history.iter().map(|c| format!("{:.1} ({})", c.rating, c.source)).collect();
+            println!("{}: {:.1} → {}", spec, INITIAL_RATING, changes.join(" → "));
+        }
+        return Ok(());
+    }
+
+    if format != Format::Text {
+        let rows: Vec<LeagueRating> = leaderboard
+            .iter()
+            .enumerate()
+            .map(|(rank, (spec, player))| LeagueRating { rank: rank + 1, spec: spec.to_string(), rating: player.rating, games: player.games })
+            .collect();
+        return output::write_rows(&rows, format);
+    }
+    for (rank, (spec, player)) in leaderboard.iter().enumerate() {
+        println!("{:>3}. {} rating: {:.1}, games: {}", rank + 1, spec, player.rating, player.games);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let (a, b) = update(INITIAL_RATING, INITIAL_RATING, 1.0);
+        assert_eq!(a, INITIAL_RATING + K_FACTOR / 2.0);
+        assert_eq!(b, INITIAL_RATING - K_FACTOR / 2.0);
+        assert!((expected_score(1600.0, 1400.0) - 0.76).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_elo_difference() {
+        assert_eq!(elo_difference((0, 0, 0)), (0.0, 0.0));
+        let (even, margin) = elo_difference((10, 10, 0));
+        assert!(even.abs() < 1e-9 && margin > 0.0);
+
+        // 76% is about 200 Elo, and more games narrow the margin
+        let (elo, margin) = elo_difference((76, 24, 0));
+        assert!((elo - 200.0).abs() < 2.0);
+        assert!(elo_difference((760, 240, 0)).1 < margin);
+        assert!(elo_difference((20, 0, 0)).0.is_finite());
+    }
+
+    #[test]
+    fn test_record_match() {
+        let mut ratings = Ratings::default();
+        ratings.record_match("greedy", "random", (2, 0, 1), "tournament");
+        ratings.record_match("mcts", "greedy", (1, 1, 0), "league");
+
+        let greedy = &ratings.players["greedy"];
+        assert_eq!(greedy.games, 5);
+        assert_eq!(greedy.history.len(), 2);
+        assert_eq!(greedy.history[0].source, "tournament");
+        assert!(greedy.rating > INITIAL_RATING);
+
+        let leaderboard = ratings.leaderboard();
+        assert_eq!(leaderboard[0].0, "greedy");
+        assert_eq!(leaderboard[2].0, "random");
+    }
+}
@@ -0,0 +1,78 @@
+use std::env;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul;
+use crate::output::{self, Exit, Format, OrExit, RegressResult};
+use crate::rating;
+use crate::strategy::Strategy;
+use crate::tournament::{self, match_seed};
+
+// Command running another luza binary as an engine with the same strategy
+// and rules, over the stdio protocol of `engine.rs`
+fn engine_command(binary: &Path, spec: &str, rules: Option<&Path>) -> Result<String> {
+    let mut parts = vec![binary.display().to_string(), "engine".to_string(), "--strategy".to_string(), spec.to_string()];
+    if let Some(rules) = rules {
+        parts.extend(["--rules".to_string(), rules.display().to_string()]);
+    }
+    if parts.iter().any(|part| part.contains(char::is_whitespace)) {
+        return Err(anyhow!("Engine commands are split on spaces, so paths and specs can't have any"));
+    }
+    Ok(parts.join(" "))
+}
+
+// Play the strategy of this build against the same strategy of the baseline
+// binary, seats alternating, and report the Elo difference. Both sides play
+// under the rules in use, passed on to the baseline with `rules`.
+pub fn run(baseline_bin: &Path, spec: &str, n_games: usize, seed: Option<u64>, rules: Option<&Path>, format: Format) -> Result<()> {
+    if !baseline_bin.is_file() {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("No baseline binary at {}", baseline_bin.display())));
+    }
+    if n_games == 0 || !n_games.is_multiple_of(2) {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Regression matches need an even number of games so both sides play both seats")));
+    }
+
+    let current = Strategy::parse(spec)?;
+    let command = engine_command(baseline_bin, spec, rules).or_exit(Exit::InvalidConfig)?;
+    let baseline = Strategy::parse(&format!("exec:{}", command))?;
+
+    let seed = seed.unwrap_or_else(rand::random);
+    output::progress(format, &format!("Seed: {}", seed));
+    output::progress(format, &format!("Playing {} games of {} against {}", n_games, spec, baseline_bin.display()));
+
+    let games = tournament::play_match(&azul::AzulRules::current(), &current, &baseline, n_games, None, match_seed(seed, 0, 0, 1));
+    let (wins, losses, draws) = tournament::tally(&games);
+    let (elo_delta, elo_margin) = rating::elo_difference((wins, losses, draws));
+
+    let result = RegressResult {
+        current: env::current_exe().map_or_else(|_| "luza".to_string(), |exe| exe.display().to_string()),
+        baseline: baseline_bin.display().to_string(),
+        strategy: spec.to_string(),
+        games: n_games,
+        wins,
+        losses,
+        draws,
+        elo_delta,
+        elo_margin,
+    };
+    if format == Format::Text {
+        println!(
+            "{} against {}: {}-{} ({} drawn), {:+.0} ± {:.0} Elo",
+            spec, result.baseline, wins, losses, draws, elo_delta, elo_margin
+        );
+    }
+    output::write_rows(&[result], format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_command() {
+        let command = engine_command(Path::new("./luza-old"), "mcts:n_games=50", Some(Path::new("rules.toml"))).unwrap();
+        assert_eq!(command, "./luza-old engine --strategy mcts:n_games=50 --rules rules.toml");
+        assert!(engine_command(Path::new("./old luza"), "mcts", None).is_err());
+    }
+}
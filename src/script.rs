@@ -0,0 +1,343 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "scripts")]
+use std::sync::LazyLock;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "scripts")]
+use rhai::{Engine, Scope, AST, INT};
+
+use crate::games::azul;
+#[cfg(feature = "scripts")]
+use crate::games::Rules;
+
+// Heuristics written as scripts, so new ones can be tried without
+// recompiling. A script file holds one heuristic named after the file, with
+// a Rhai expression on each line:
+//
+//     # Grab the starting marker early when it costs nothing
+//     when round < 3 && marker_in_center
+//     require from_center && overflow == 0
+//     score if takes_marker { gain + 2 } else { gain }
+//
+// `when` tells if the heuristic applies to the state, each `require` line
+// keeps only the actions it holds for, and the action with the highest `score`
+// (`gain` if not given) is suggested, the first in action order on ties. The
+// heuristic doesn't apply when no action is left. `when` and `require` are
+// true or false and `score` is a number. Builds without the `scripts` feature
+// have no Rhai and fail to load any script.
+
+pub const SCRIPT_EXTENSION: &str = "heuristic";
+
+// Variables about the state and the player to move, usable everywhere.
+// `has_marker` and `marker_in_center` are booleans, the rest integers.
+#[cfg(feature = "scripts")]
+const STATE_VARS: [&str; 9] = [
+    "round", "players", "score", "lead", "floor_tiles", "wall_tiles", "has_marker", "marker_in_center", "tiles_left",
+];
+// Variables about the action being considered, usable in `require` and
+// `score`. `to_floor`, `completes`, `from_center`, and `takes_marker` are
+// booleans. `line` is -1 for the floor and `gift` is the best immediate gain
+// of the next player after the action.
+#[cfg(feature = "scripts")]
+const ACTION_VARS: [&str; 9] = [
+    "gain", "count", "line", "to_floor", "overflow", "completes", "from_center", "takes_marker", "gift",
+];
+
+// Shared by all scripts. Strict variables make unknown names an error when a
+// line is compiled instead of when it is first run.
+#[cfg(feature = "scripts")]
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut engine = Engine::new();
+    engine.set_strict_variables(true);
+    engine
+});
+
+// Scope with the variables a line can use, for compiling. Values don't
+// matter since nothing is run.
+#[cfg(feature = "scripts")]
+fn names_scope(with_action: bool, with_gift: bool) -> Scope<'static> {
+    let mut scope = Scope::new();
+    let action_vars = ACTION_VARS.iter().filter(|&&var| with_action && (with_gift || var != "gift"));
+    for var in STATE_VARS.iter().chain(action_vars) {
+        scope.push(*var, ());
+    }
+    scope
+}
+
+// Heuristic loaded from a script
+#[derive(Debug)]
+pub struct Script {
+    pub name: String,
+    #[cfg(feature = "scripts")]
+    when: Option<AST>,
+    #[cfg(feature = "scripts")]
+    require: Vec<AST>,
+    #[cfg(feature = "scripts")]
+    score: AST,
+    // The reply search for `gift` is only done for scripts using it
+    #[cfg(feature = "scripts")]
+    uses_gift: bool,
+    #[cfg(not(feature = "scripts"))]
+    never: std::convert::Infallible,
+}
+
+impl Script {
+    #[cfg(feature = "scripts")]
+    pub fn parse(name: &str, text: &str) -> Result<Self> {
+        let mut when = None;
+        let mut require = Vec::new();
+        let mut score = None;
+        let mut uses_gift = false;
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at_line = |err: &dyn std::fmt::Display| anyhow!("{} line {}: {}", name, i + 1, err);
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let with_action = keyword != "when";
+            let ast = ENGINE.compile_expression_with_scope(&names_scope(with_action, true), rest).map_err(|err| at_line(&err))?;
+            // Lines that don't compile without `gift` use it
+            uses_gift |= with_action && ENGINE.compile_expression_with_scope(&names_scope(true, false), rest).is_err();
+
+            match keyword {
+                "when" if when.is_none() => when = Some(ast),
+                "require" => require.push(ast),
+                "score" if score.is_none() => score = Some(ast),
+                "when" | "score" => return Err(at_line(&format!("Only one {} line is allowed", keyword))),
+                _ => return Err(at_line(&format!("Lines start with when, require, or score, not '{}'", keyword))),
+            }
+        }
+
+        let score = match score {
+            Some(score) => score,
+            None => ENGINE.compile_expression_with_scope(&names_scope(true, false), "gain")?,
+        };
+        Ok(Self { name: name.to_string(), when, require, score, uses_gift })
+    }
+
+    #[cfg(not(feature = "scripts"))]
+    pub fn parse(name: &str, _text: &str) -> Result<Self> {
+        Err(anyhow!("Heuristic {} needs luza built with the scripts feature", name))
+    }
+
+    pub fn load(file: &Path) -> Result<Self> {
+        let name = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        Self::parse(&name, &fs::read_to_string(file)?)
+    }
+
+    // Suggested action, or None if the heuristic doesn't apply. A script that
+    // fails in the state, like adding a boolean to a number, doesn't apply
+    // either and the error is logged.
+    pub fn play(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> Option<azul::Action> {
+        self.try_play(rules, state, player_idx).unwrap_or_else(|err| {
+            log::error!("Heuristic {} failed: {}", self.name, err);
+            None
+        })
+    }
+
+    #[cfg(feature = "scripts")]
+    fn try_play(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> Result<Option<azul::Action>> {
+        let mut scope = state_vars(state, player_idx);
+        if let Some(when) = &self.when {
+            if !ENGINE.eval_ast_with_scope::<bool>(&mut scope, when).map_err(|err| anyhow!("when: {}", err))? {
+                return Ok(None);
+            }
+        }
+
+        let mut best: Option<(f64, azul::Action)> = None;
+        'actions: for action in rules.list_valid_actions(state, player_idx) {
+            action_vars(rules, state, player_idx, action, self.uses_gift, &mut scope);
+            for require in &self.require {
+                if !ENGINE.eval_ast_with_scope::<bool>(&mut scope, require).map_err(|err| anyhow!("require: {}", err))? {
+                    continue 'actions;
+                }
+            }
+            let value = ENGINE.eval_ast_with_scope::<rhai::Dynamic>(&mut scope, &self.score).map_err(|err| anyhow!("score: {}", err))?;
+            let score = match value.as_int() {
+                Ok(n) => n as f64,
+                Err(_) => value.as_float().map_err(|kind| anyhow!("score is a {}, not a number", kind))?,
+            };
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, action));
+            }
+        }
+
+        Ok(best.map(|(_, action)| action))
+    }
+
+    #[cfg(not(feature = "scripts"))]
+    fn try_play(&self, _rules: &azul::AzulRules, _state: &azul::State, _player_idx: usize) -> Result<Option<azul::Action>> {
+        match self.never {}
+    }
+}
+
+#[cfg(feature = "scripts")]
+fn state_vars(state: &azul::State, player_idx: usize) -> Scope<'static> {
+    let player = &state.players[player_idx];
+    let best_other = (0..state.players.len()).filter(|&i| i != player_idx).map(|i| state.players[i].score).max().unwrap_or(0);
+
+    let mut scope = Scope::new();
+    scope.push_constant("round", state.rounds as INT);
+    scope.push_constant("players", state.players.len() as INT);
+    scope.push_constant("score", player.score as INT);
+    scope.push_constant("lead", (player.score - best_other) as INT);
+    scope.push_constant("floor_tiles", player.floor_line as INT);
+    scope.push_constant("wall_tiles", player.wall.iter().flatten().filter(|&&tiled| tiled).count() as INT);
+    scope.push_constant("has_marker", player.starting_marker);
+    scope.push_constant("marker_in_center", state.center.starting_marker);
+    scope.push_constant("tiles_left", azul::colors_on_table(state).iter().map(|(_, n)| n).sum::<usize>() as INT);
+    scope
+}
+
+#[cfg(feature = "scripts")]
+fn action_vars(rules: &azul::AzulRules, state: &azul::State, player_idx: usize, action: azul::Action, with_gift: bool, scope: &mut Scope) {
+    let from_center = action.action_display_choice == azul::ActionDisplay::Center;
+    let count = match action.action_display_choice {
+        azul::ActionDisplay::FactoryDisplay(i) => state.factory_displays[i][&action.color_choice],
+        azul::ActionDisplay::Center => state.center.tiles[&action.color_choice],
+    };
+    let (space, line) = match action.pattern_line_choice {
+        Some(i) => (i + 1 - state.players[player_idx].pattern_lines[i].1, i as INT),
+        None => (0, -1),
+    };
+
+    scope.set_or_push("gain", rules.reward(state, player_idx, action) as INT);
+    scope.set_or_push("count", count as INT);
+    scope.set_or_push("line", line);
+    scope.set_or_push("to_floor", action.pattern_line_choice.is_none());
+    scope.set_or_push("overflow", count.saturating_sub(space) as INT);
+    scope.set_or_push("completes", action.pattern_line_choice.is_some() && count >= space);
+    scope.set_or_push("from_center", from_center);
+    scope.set_or_push("takes_marker", from_center && state.center.starting_marker);
+    if with_gift {
+        scope.set_or_push("gift", azul::best_reply_gain(rules, state, player_idx, action) as INT);
+    }
+}
+
+// Modification times of the scripts in a directory
+fn script_stamps(dir: &Path) -> Result<BTreeMap<PathBuf, SystemTime>> {
+    let mut stamps = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == SCRIPT_EXTENSION) {
+            stamps.insert(path.clone(), fs::metadata(&path)?.modified()?);
+        }
+    }
+    Ok(stamps)
+}
+
+// Scripts of a directory, in file name order, loaded again when files are
+// added, removed, or changed
+pub struct ScriptSet {
+    dir: PathBuf,
+    stamps: BTreeMap<PathBuf, SystemTime>,
+    pub scripts: Vec<Arc<Script>>,
+}
+
+impl ScriptSet {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let stamps = script_stamps(dir).map_err(|err| anyhow!("Unable to read heuristics from {}: {}", dir.display(), err))?;
+        let scripts = stamps.keys().map(|file| Script::load(file).map(Arc::new)).collect::<Result<_>>()?;
+        Ok(Self { dir: dir.to_path_buf(), stamps, scripts })
+    }
+
+    // Reload if anything changed, telling if it did. Scripts that no longer
+    // parse are left out with the error logged so an edit in progress doesn't
+    // stop a game.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let stamps = match script_stamps(&self.dir) {
+            Ok(stamps) if stamps != self.stamps => stamps,
+            Ok(_) => return false,
+            Err(err) => {
+                log::error!("Unable to read heuristics from {}: {}", self.dir.display(), err);
+                return false;
+            },
+        };
+
+        self.scripts = stamps
+            .keys()
+            .filter_map(|file| match Script::load(file) {
+                Ok(script) => Some(Arc::new(script)),
+                Err(err) => {
+                    log::error!("{}", err);
+                    None
+                },
+            })
+            .collect();
+        self.stamps = stamps;
+        log::info!("Reloaded {} heuristic scripts", self.scripts.len());
+        true
+    }
+}
+
+#[cfg(all(test, feature = "scripts"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vars() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+        let action = rules.list_valid_actions(&state, 0)[0];
+
+        let mut scope = state_vars(&state, 0);
+        assert!(STATE_VARS.iter().all(|var| scope.contains(var)));
+        action_vars(&rules, &state, 0, action, true, &mut scope);
+        assert!(ACTION_VARS.iter().all(|var| scope.contains(var)));
+        assert_eq!(scope.len(), STATE_VARS.len() + ACTION_VARS.len());
+
+        // Integers mix with floats and the usual math functions are there
+        let eval = |text: &str| ENGINE.eval_expression_with_scope::<bool>(&mut scope.clone(), text).unwrap();
+        assert!(eval("round == 0 && !has_marker && line >= -1"));
+        assert!(eval("max(count, 0) > 0.5 && abs(-gain) == gain.abs() && min(lead, 0) == 0"));
+    }
+
+    #[test]
+    fn test_script() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(1, || rules.refill(&mut state, &mut azul::game_rng()));
+
+        // Same as greedy when only scoring by gain
+        let greedy = Script::parse("greedy", "# best gain\nscore gain").unwrap();
+        assert!(!greedy.uses_gift);
+        let best_gain = rules.list_valid_actions(&state, 0).iter().map(|&a| rules.reward(&state, 0, a)).max().unwrap();
+        assert_eq!(rules.reward(&state, 0, greedy.play(&rules, &state, 0).unwrap()), best_gain);
+
+        // Nothing is in the center before the first move
+        let center = Script::parse("center", "require from_center").unwrap();
+        assert_eq!(center.play(&rules, &state, 0), None);
+        let late = Script::parse("late", "when round > 2").unwrap();
+        assert_eq!(late.play(&rules, &state, 0), None);
+        let marker = "when round < 3 && marker_in_center\nrequire from_center && overflow == 0\nscore if takes_marker { gain + 2 } else { gain }";
+        assert_eq!(Script::parse("marker", marker).unwrap().try_play(&rules, &state, 0).unwrap(), None);
+
+        let floor = Script::parse("floor", "require to_floor\nscore -count").unwrap();
+        let action = floor.play(&rules, &state, 0).unwrap();
+        assert_eq!(action.pattern_line_choice, None);
+
+        let safe = Script::parse("safe", "require gift < 3\nscore gain - gift / 2.0").unwrap();
+        assert!(safe.uses_gift);
+        assert!(safe.play(&rules, &state, 0).is_some());
+
+        // Type errors only show when the line runs, and the script doesn't
+        // apply
+        let mixed = Script::parse("mixed", "score gain + takes_marker").unwrap();
+        assert_eq!(mixed.play(&rules, &state, 0), None);
+        assert!(mixed.try_play(&rules, &state, 0).unwrap_err().to_string().contains("score"));
+
+        assert!(Script::parse("bad", "when gain > 1").is_err());
+        assert!(Script::parse("bad", "require count >").is_err());
+        assert!(Script::parse("bad", "prefer gain").is_err());
+        assert!(Script::parse("bad", "score gain\nscore count").is_err());
+        assert!(Script::parse("bad", "score colour").unwrap_err().to_string().contains("line 1"));
+    }
+}
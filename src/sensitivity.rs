@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Result};
+
+use crate::games::azul::{self, AzulConfig};
+use crate::games::Validate;
+use crate::output::{self, Exit, Format, OrExit, SensitivityResult};
+use crate::par::prelude::*;
+use crate::strategy::Strategy;
+use crate::tournament;
+
+// Outcome of one game with scores indexed by strategy, not seat
+struct GameOutcome {
+    scores: Vec<i32>,
+    // None when the top score is shared
+    winner: Option<usize>,
+    rounds: usize,
+}
+
+// Results of all games played under one rule value
+struct Summary {
+    wins: Vec<usize>,
+    draws: usize,
+    scores: Vec<Vec<i32>>,
+    rounds: usize,
+}
+
+impl Summary {
+    fn new(outcomes: &[GameOutcome], n_strategies: usize) -> Self {
+        let mut summary = Self { wins: vec![0; n_strategies], draws: 0, scores: vec![Vec::new(); n_strategies], rounds: 0 };
+
+        for outcome in outcomes {
+            match outcome.winner {
+                Some(i) => summary.wins[i] += 1,
+                None => summary.draws += 1,
+            }
+            for (i, &score) in outcome.scores.iter().enumerate() {
+                summary.scores[i].push(score);
+            }
+            summary.rounds += outcome.rounds;
+        }
+
+        summary
+    }
+
+    fn n_games(&self) -> usize {
+        self.wins.iter().sum::<usize>() + self.draws
+    }
+
+    fn win_rate(&self, i: usize) -> f64 {
+        self.wins[i] as f64 / self.n_games() as f64
+    }
+
+    fn mean_score(&self, i: usize) -> f64 {
+        self.scores[i].iter().sum::<i32>() as f64 / self.scores[i].len() as f64
+    }
+
+    fn score_std_dev(&self, i: usize) -> f64 {
+        let mean = self.mean_score(i);
+        let variance = self.scores[i].iter().map(|&s| (s as f64 - mean).powi(2)).sum::<f64>() / self.scores[i].len() as f64;
+        variance.sqrt()
+    }
+}
+
+// Base rules with one parameter replaced. The value is JSON so lists like
+// floor penalties can be given too.
+pub fn with_parameter(base: &AzulConfig, parameter: &str, value: &str) -> Result<AzulConfig> {
+    let mut rules = serde_json::to_value(base)?;
+    let field = rules.get_mut(parameter).ok_or_else(|| anyhow!("Unknown rule parameter '{}'", parameter))?;
+    *field = serde_json::from_str(value).map_err(|_| anyhow!("Value '{}' for {} is not valid JSON", value, parameter))?;
+
+    let config: AzulConfig = serde_json::from_value(rules).map_err(|err| anyhow!("Invalid value '{}' for {}: {}", value, parameter, err))?;
+    config.validate()?;
+    Ok(config)
+}
+
+// Play games with all strategies at the table, rotating seats every game so
+// each value of the parameter is tested with the same seatings. Game seeds
+// come from `seed` alone so every value also gets the same tile draws.
+fn play_games(rules: &azul::AzulRules, strategies: &[Strategy], n_games: usize, seed: u64) -> Vec<GameOutcome> {
+    let n = strategies.len();
+
+    (0..n_games).into_par_iter().map(|game_idx| {
+        // Strategy sitting at each seat
+        let seating: Vec<usize> = (0..n).map(|seat| (seat + game_idx) % n).collect();
+        let play_fns: Vec<_> = seating
+            .iter()
+            .map(|&i| move |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining| strategies[i].play_timed(rules, state, player_idx, remaining))
+            .collect();
+        let players: Vec<azul::PlayerFn> = play_fns.iter().map(|f| f as azul::PlayerFn).collect();
+
+        let (state, _) = azul::with_seed(tournament::match_seed(seed, game_idx, 0, 0), || azul::play_game(rules, &players, None));
+
+        let mut scores = vec![0; n];
+        for (seat, &i) in seating.iter().enumerate() {
+            scores[i] = state.players[seat].score;
+        }
+
+        let winner = azul::winner(&state).map(|seat| seating[seat]);
+
+        GameOutcome { scores, winner, rounds: state.rounds }
+    }).collect()
+}
+
+pub fn run(specs: &[String], parameter: &str, values: &[String], n_games: usize, seed: Option<u64>, format: Format) -> Result<()> {
+    let strategies = specs.iter().map(|s| Strategy::parse(s)).collect::<Result<Vec<Strategy>>>()?;
+    if !(2..=4).contains(&strategies.len()) {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Sensitivity analysis needs two to four strategies")));
+    }
+    if n_games == 0 {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Sensitivity analysis needs at least one game per value")));
+    }
+
+    let seed = seed.unwrap_or_else(rand::random);
+    output::progress(format, &format!("Seed: {}", seed));
+
+    let base = azul::config();
+    let configs = values.iter().map(|v| with_parameter(&base, parameter, v)).collect::<Result<Vec<AzulConfig>>>().or_exit(Exit::InvalidConfig)?;
+
+    // Shifts are reported against the first value
+    let mut reference: Option<Summary> = None;
+    let mut rows: Vec<SensitivityResult> = Vec::new();
+
+    for (value, config) in values.iter().zip(configs) {
+        azul::set_config(config);
+        let summary = Summary::new(&play_games(&azul::AzulRules::current(), &strategies, n_games, seed), strategies.len());
+        let mean_rounds = summary.rounds as f64 / summary.n_games() as f64;
+
+        for (i, strategy) in strategies.iter().enumerate() {
+            rows.push(SensitivityResult {
+                value: value.clone(),
+                spec: strategy.spec.clone(),
+                win_rate: summary.win_rate(i),
+                mean_score: summary.mean_score(i),
+                score_std_dev: summary.score_std_dev(i),
+                min_score: *summary.scores[i].iter().min().unwrap(),
+                max_score: *summary.scores[i].iter().max().unwrap(),
+                draws: summary.draws,
+                mean_rounds,
+            });
+        }
+        if format != Format::Text {
+            continue;
+        }
+
+        println!("{} = {}", parameter, value);
+        for (i, strategy) in strategies.iter().enumerate() {
+            let shift = match &reference {
+                Some(r) => format!(
+                    " (win rate {:+.2}, mean score {:+.1})",
+                    summary.win_rate(i) - r.win_rate(i),
+                    summary.mean_score(i) - r.mean_score(i)
+                ),
+                None => String::new(),
+            };
+
+            println!(
+                "  {}: win rate {:.2}, score {:.1} ± {:.1}, range {}-{}{}",
+                strategy.spec,
+                summary.win_rate(i),
+                summary.mean_score(i),
+                summary.score_std_dev(i),
+                summary.scores[i].iter().min().unwrap(),
+                summary.scores[i].iter().max().unwrap(),
+                shift,
+            );
+        }
+        println!("  draws: {}, mean rounds: {:.1}", summary.draws, mean_rounds);
+
+        reference.get_or_insert(summary);
+    }
+
+    azul::set_config((*base).clone());
+    output::write_rows(&rows, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_parameter() {
+        let base = AzulConfig::default();
+
+        let config = with_parameter(&base, "column_bonus", "3").unwrap();
+        assert_eq!(config.column_bonus, 3);
+        assert_eq!(config.row_bonus, base.row_bonus);
+
+        let config = with_parameter(&base, "floor_penalties", "[1, 2]").unwrap();
+        assert_eq!(config.floor_penalties, vec![1, 2]);
+
+        assert!(with_parameter(&base, "wall_size", "6").is_err());
+        assert!(with_parameter(&base, "column_bonus", "[1]").is_err());
+        assert!(with_parameter(&base, "tiles_per_factory", "0").is_err());
+    }
+}
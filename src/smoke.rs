@@ -0,0 +1,107 @@
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+
+use crate::doctor;
+use crate::games::{self, azul};
+use crate::plugin;
+use crate::strategy::Strategy;
+use crate::tournament::match_seed;
+
+// Seeded games played by every strategy, with both seats
+const GAMES: usize = 4;
+
+// Built in strategies with options that keep the games quick. Policies and
+// external programs need files of their own so they are left out.
+const STRATEGIES: [&str; 4] = ["random", "greedy", "max-n", "mcts:n_games=20"];
+
+// Final scores and winner of a game
+type Outcome = (Vec<i32>, Option<usize>);
+
+// Play a seeded game against the opponent, checking the rule invariants before
+// every move
+fn checked_game(rules: &azul::AzulRules, strategy: &Strategy, opponent: &Strategy, seat: usize, seed: u64) -> Result<Outcome> {
+    let violation: Mutex<Option<String>> = Mutex::new(None);
+    let seats = if seat == 0 { [strategy, opponent] } else { [opponent, strategy] };
+    let play_fns: Vec<_> = seats
+        .iter()
+        .map(|s| {
+            let violation = &violation;
+            move |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, _| {
+                if let Err(err) = doctor::check_invariants(state) {
+                    violation.lock().unwrap().get_or_insert(err.to_string());
+                }
+                s.play(rules, state, player_idx)
+            }
+        })
+        .collect();
+    let players: Vec<azul::PlayerFn> = play_fns.iter().map(|f| f as azul::PlayerFn).collect();
+
+    let (state, _) = azul::with_seed(seed, || azul::play_game(rules, &players, None));
+    doctor::check_invariants(&state)?;
+    if let Some(err) = violation.into_inner().unwrap() {
+        return Err(anyhow!(err));
+    }
+    Ok((state.players.iter().map(|p| p.score).collect(), azul::winner(&state)))
+}
+
+// Play every game twice and check that the replay ends the same
+fn check_strategy(spec: &str) -> Result<String> {
+    let strategy = Strategy::parse(spec)?;
+    let opponent = Strategy::parse("greedy")?;
+    let rules = azul::AzulRules::current();
+
+    let mut n_wins = 0;
+    for game_idx in 0..GAMES {
+        let seat = game_idx % 2;
+        let seed = match_seed(0, game_idx, 0, 0);
+        let (scores, winner) = checked_game(&rules, &strategy, &opponent, seat, seed).map_err(|err| anyhow!("game {}: {}", game_idx, err))?;
+        let (replay_scores, replay_winner) = checked_game(&rules, &strategy, &opponent, seat, seed)?;
+        if (&scores, winner) != (&replay_scores, replay_winner) {
+            return Err(anyhow!(
+                "game {} with seed {} ended {:?} ({}) and then {:?} ({})",
+                game_idx, seed, scores, azul::winner_name(winner), replay_scores, azul::winner_name(replay_winner),
+            ));
+        }
+        n_wins += (winner == Some(seat)) as usize;
+    }
+    Ok(format!("{} games against greedy, {} won, replays match", GAMES, n_wins))
+}
+
+// Play seeded games with every strategy and game, printing a line per check
+// and failing if any invariant breaks or a replay diverges
+pub fn run() -> Result<()> {
+    let mut specs: Vec<String> = STRATEGIES.iter().map(|s| s.to_string()).collect();
+    specs.extend(plugin::names());
+
+    let mut n_failed = 0;
+    let mut n_checks = 0;
+    for game in games::GAMES {
+        for spec in &specs {
+            n_checks += 1;
+            match check_strategy(spec) {
+                Ok(detail) => println!("[ok] {} {}: {}", game, spec, detail),
+                Err(err) => {
+                    println!("[fail] {} {}: {}", game, spec, err);
+                    n_failed += 1;
+                },
+            }
+        }
+    }
+
+    if n_failed > 0 {
+        return Err(anyhow!("{} of {} smoke checks failed", n_failed, n_checks));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_strategy() {
+        assert!(check_strategy("random").is_ok());
+        assert!(check_strategy("unknown").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(feature = "tui")]
+use std::collections::HashMap;
+use std::io::{self, Write};
+#[cfg(feature = "audio")]
+use std::time::Duration;
+
+#[cfg(feature = "audio")]
+use rodio::Source;
+
+#[cfg(feature = "tui")]
+use crate::games::azul;
+#[cfg(feature = "tui")]
+use crate::tui::ActionAnalysis;
+
+// Expected score lost against the best analyzed action for a move to count as
+// a blunder
+pub const BLUNDER_LOSS: f32 = 5.0;
+
+// Length of one tone and the gap between tones of a cue
+#[cfg(feature = "audio")]
+const TONE: Duration = Duration::from_millis(120);
+#[cfg(feature = "audio")]
+const TONE_GAP: Duration = Duration::from_millis(80);
+
+#[derive(Clone, Copy, Debug)]
+pub enum Cue {
+    Illegal,
+    RoundEnd,
+    Blunder,
+}
+
+impl Cue {
+    // Cues are told apart by the number of tones and their pitch
+    #[cfg(feature = "audio")]
+    fn tones(&self) -> (usize, f32) {
+        match self {
+            Cue::Illegal => (1, 220.0),
+            Cue::RoundEnd => (2, 660.0),
+            Cue::Blunder => (3, 330.0),
+        }
+    }
+}
+
+// Output stream kept open for the whole game with a sink queueing the tones,
+// so playing a cue never blocks the UI
+#[cfg(feature = "audio")]
+struct Audio {
+    _stream: rodio::OutputStream,
+    sink: rodio::Sink,
+}
+
+#[cfg(feature = "audio")]
+impl Audio {
+    fn open() -> Option<Self> {
+        let (stream, handle) = rodio::OutputStream::try_default()
+            .map_err(|err| log::warn!("No audio output, using the terminal bell: {}", err))
+            .ok()?;
+        let sink = rodio::Sink::try_new(&handle)
+            .map_err(|err| log::warn!("No audio output, using the terminal bell: {}", err))
+            .ok()?;
+        Some(Self { _stream: stream, sink })
+    }
+
+    fn play(&self, cue: Cue) {
+        let (n_tones, frequency) = cue.tones();
+        for i in 0..n_tones {
+            let gap = if i > 0 { TONE_GAP } else { Duration::ZERO };
+            self.sink.append(rodio::source::SineWave::new(frequency).take_duration(TONE).amplify(0.2).delay(gap));
+        }
+    }
+}
+
+// Audible feedback, silent unless enabled. Builds with the `audio` feature
+// play tones on the sound card, others ring the terminal bell once for any
+// cue.
+pub struct Sound {
+    enabled: bool,
+    #[cfg(feature = "audio")]
+    audio: Option<Audio>,
+}
+
+impl Sound {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            #[cfg(feature = "audio")]
+            audio: if enabled { Audio::open() } else { None },
+        }
+    }
+
+    #[cfg_attr(not(feature = "audio"), allow(unused_variables))]
+    pub fn play(&self, cue: Cue) {
+        if !self.enabled {
+            return;
+        }
+
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            audio.play(cue);
+            return;
+        }
+
+        let mut out = io::stdout();
+        let _ = out.write_all(b"\x07");
+        let _ = out.flush();
+    }
+}
+
+// Tell if the action loses a lot of expected score compared to the best action
+// analyzed so far. Actions without an expected score are not judged.
+#[cfg(feature = "tui")]
+pub fn is_blunder(analyses: &HashMap<azul::Action, ActionAnalysis>, action: azul::Action) -> bool {
+    let Some(expected_score) = analyses.get(&action).and_then(|a| a.expected_score) else {
+        return false;
+    };
+
+    analyses
+        .values()
+        .filter_map(|a| a.expected_score)
+        .any(|best| best - expected_score >= BLUNDER_LOSS)
+}
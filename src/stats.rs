@@ -0,0 +1,424 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use arrow::array::{Array, ArrayRef, AsArray, BooleanArray, BooleanBuilder, ListArray, ListBuilder, RecordBatch, StringArray, StringBuilder, UInt64Array, UInt64Builder};
+use arrow::datatypes::UInt64Type;
+
+use crate::games::{azul, Rules};
+use crate::logreader;
+use crate::output::{self, FirstPlayerWins, Format, HeuristicAgreement, HeuristicMatch, RoundFloorPenalty};
+use crate::playlog::{GameEnd, LogRecord};
+
+// Canned questions that can be answered from a simulation log
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Query {
+    WinRateByFirstPlayer,
+    FloorPenaltiesByRound,
+    HeuristicMatchByPly,
+    HeuristicAgreement,
+}
+
+// Upper ends of the ranges of valid action counts that agreement is broken
+// down by
+const BRANCHING_BUCKETS: [usize; 5] = [10, 25, 50, 100, usize::MAX];
+
+// Shades of a heatmap cell from no agreement to full agreement
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+// Counts of (applicable, matching) plies keyed by heuristic, strategy, and
+// column of the breakdown
+type Agreement = BTreeMap<(&'static str, String, usize), (usize, usize)>;
+
+// A simulation log loaded as Arrow record batches that the queries run over.
+// Plies have a row each with the logged fields, the state before the move,
+// and the strategy of the seat that made it. Game ends have a row each with
+// the winner, null for a draw, and whether the game finished by the rules.
+struct Tables {
+    plies: RecordBatch,
+    game_ends: RecordBatch,
+}
+
+impl Tables {
+    fn new(play_log: &[LogRecord]) -> Result<Self> {
+        let (mut game_ids, mut round_ids, mut ply_ids, mut player_ids) = (UInt64Builder::new(), UInt64Builder::new(), UInt64Builder::new(), UInt64Builder::new());
+        let (mut actions, mut states, mut befores, mut strategies) = (StringBuilder::new(), StringBuilder::new(), StringBuilder::new(), StringBuilder::new());
+        let mut applicable = ListBuilder::new(StringBuilder::new());
+        let mut matching = ListBuilder::new(StringBuilder::new());
+        let (mut end_ids, mut winners, mut finished) = (UInt64Builder::new(), UInt64Builder::new(), BooleanBuilder::new());
+
+        // Players and latest state of every game seen so far
+        let mut seats: HashMap<usize, &[String]> = HashMap::new();
+        let mut latest: HashMap<usize, &str> = HashMap::new();
+        for record in play_log {
+            match record {
+                LogRecord::GameStart { game_id, players, .. } => {
+                    seats.insert(*game_id, players);
+                },
+                LogRecord::Ply(ply) => {
+                    game_ids.append_value(ply.game_id as u64);
+                    round_ids.append_value(ply.round_id as u64);
+                    ply_ids.append_value(ply.ply_id as u64);
+                    player_ids.append_value(ply.player_id as u64);
+                    actions.append_value(&ply.action);
+                    states.append_value(&ply.state);
+                    befores.append_option(latest.get(&ply.game_id));
+                    strategies.append_option(seats.get(&ply.game_id).and_then(|players| players.get(ply.player_id)));
+                    applicable.append_value(ply.applicable_partials.iter().map(Some));
+                    matching.append_value(ply.matching_partials.iter().map(Some));
+                },
+                LogRecord::GameEnd { game_id, winner, end, .. } => {
+                    end_ids.append_value(*game_id as u64);
+                    winners.append_option(winner.map(|seat| seat as u64));
+                    finished.append_value(matches!(end, GameEnd::Finished));
+                },
+                _ => {},
+            }
+            if let (Some(game_id), Some(state)) = (record.game_id(), record.state()) {
+                latest.insert(game_id, state);
+            }
+        }
+
+        let plies = RecordBatch::try_from_iter([
+            ("game_id", Arc::new(game_ids.finish()) as ArrayRef),
+            ("round_id", Arc::new(round_ids.finish())),
+            ("ply_id", Arc::new(ply_ids.finish())),
+            ("player_id", Arc::new(player_ids.finish())),
+            ("action", Arc::new(actions.finish())),
+            ("state", Arc::new(states.finish())),
+            ("before", Arc::new(befores.finish())),
+            ("strategy", Arc::new(strategies.finish())),
+            ("applicable_partials", Arc::new(applicable.finish())),
+            ("matching_partials", Arc::new(matching.finish())),
+        ])?;
+        let game_ends = RecordBatch::try_from_iter([
+            ("game_id", Arc::new(end_ids.finish()) as ArrayRef),
+            ("winner", Arc::new(winners.finish())),
+            ("finished", Arc::new(finished.finish())),
+        ])?;
+        Ok(Tables { plies, game_ends })
+    }
+}
+
+// Columns of the batches by name, which Tables::new always makes
+fn uints<'a>(batch: &'a RecordBatch, name: &str) -> &'a UInt64Array {
+    batch.column_by_name(name).unwrap().as_primitive::<UInt64Type>()
+}
+
+fn strings<'a>(batch: &'a RecordBatch, name: &str) -> &'a StringArray {
+    batch.column_by_name(name).unwrap().as_string::<i32>()
+}
+
+fn lists<'a>(batch: &'a RecordBatch, name: &str) -> &'a ListArray {
+    batch.column_by_name(name).unwrap().as_list::<i32>()
+}
+
+fn booleans<'a>(batch: &'a RecordBatch, name: &str) -> &'a BooleanArray {
+    batch.column_by_name(name).unwrap().as_boolean()
+}
+
+// Row of the earliest (or latest) ply of every round of every game
+fn round_plies(tables: &Tables, latest: bool) -> HashMap<(u64, u64), usize> {
+    let (game_ids, round_ids, ply_ids) = (uints(&tables.plies, "game_id"), uints(&tables.plies, "round_id"), uints(&tables.plies, "ply_id"));
+    let mut rows: HashMap<(u64, u64), usize> = HashMap::new();
+    for row in 0..tables.plies.num_rows() {
+        let key = (game_ids.value(row), round_ids.value(row));
+        if rows.get(&key).is_none_or(|&kept| (ply_ids.value(row) > ply_ids.value(kept)) == latest) {
+            rows.insert(key, row);
+        }
+    }
+    rows
+}
+
+// For each seat, count the rounds it started holding the starting marker and
+// the games it went on to win. The marker holder plays the first move of a
+// round, so the first ply of every round tells who had it. Only games that
+// finished by the rules are considered.
+fn win_rate_by_first_player(tables: &Tables) -> BTreeMap<usize, (usize, usize)> {
+    let (end_ids, winners, finished) = (uints(&tables.game_ends, "game_id"), uints(&tables.game_ends, "winner"), booleans(&tables.game_ends, "finished"));
+    let winners: HashMap<u64, Option<u64>> = (0..tables.game_ends.num_rows())
+        .filter(|&row| finished.value(row))
+        .map(|row| (end_ids.value(row), winners.is_valid(row).then(|| winners.value(row))))
+        .collect();
+
+    let player_ids = uints(&tables.plies, "player_id");
+    let mut counts: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for ((game_id, _), row) in round_plies(tables, false) {
+        if let Some(&winner) = winners.get(&game_id) {
+            let first_player = player_ids.value(row);
+            let entry = counts.entry(first_player as usize).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += (winner == Some(first_player)) as usize;
+        }
+    }
+
+    counts
+}
+
+// Average floor penalty per player at the end of each round. The last ply of
+// a round has the board just before the wall is tiled.
+fn floor_penalties_by_round(tables: &Tables) -> Result<BTreeMap<usize, f64>> {
+    let rules = azul::AzulRules::current();
+    let states = strings(&tables.plies, "state");
+
+    let mut totals: BTreeMap<usize, (i32, usize)> = BTreeMap::new();
+    for ((_game_id, round_id), row) in round_plies(tables, true) {
+        let state: azul::State = serde_json::from_str(states.value(row))?;
+        let entry = totals.entry(round_id as usize).or_insert((0, 0));
+        for player in &state.players {
+            entry.0 += rules.floor_penalty(player.floor_line);
+            entry.1 += 1;
+        }
+    }
+
+    Ok(totals.into_iter().map(|(round_id, (total, n))| (round_id, total as f64 / n as f64)).collect())
+}
+
+// Count of (applicable, matching) plies for each heuristic at each ply index
+fn heuristic_match_by_ply(tables: &Tables) -> BTreeMap<usize, BTreeMap<String, (usize, usize)>> {
+    let ply_ids = uints(&tables.plies, "ply_id");
+    let (applicable, matching) = (lists(&tables.plies, "applicable_partials"), lists(&tables.plies, "matching_partials"));
+    let mut counts: BTreeMap<usize, BTreeMap<String, (usize, usize)>> = BTreeMap::new();
+
+    for row in (0..tables.plies.num_rows()).filter(|&row| applicable.value_length(row) > 0) {
+        let ply_counts = counts.entry(ply_ids.value(row) as usize).or_default();
+        for name in applicable.value(row).as_string::<i32>().iter().flatten() {
+            ply_counts.entry(name.to_string()).or_insert((0, 0)).0 += 1;
+        }
+        for name in matching.value(row).as_string::<i32>().iter().flatten() {
+            ply_counts.entry(name.to_string()).or_insert((0, 0)).1 += 1;
+        }
+    }
+
+    counts
+}
+
+fn branching_label(bucket: usize) -> String {
+    let low = if bucket == 0 { 1 } else { BRANCHING_BUCKETS[bucket - 1] + 1 };
+    match BRANCHING_BUCKETS[bucket] {
+        usize::MAX => format!("{}+", low),
+        high => format!("{}-{}", low, high),
+    }
+}
+
+// How often each heuristic picks the action each strategy played, by round
+// and by number of valid actions. Heuristics are run again on the state
+// before every logged move so all seats are covered, not only the ones with
+// logged partial matches.
+fn heuristic_agreement(tables: &Tables) -> Result<(Agreement, Agreement)> {
+    let rules = azul::AzulRules::current();
+    let plies = &tables.plies;
+    let (round_ids, player_ids) = (uints(plies, "round_id"), uints(plies, "player_id"));
+    let (actions, befores, strategies) = (strings(plies, "action"), strings(plies, "before"), strings(plies, "strategy"));
+
+    let mut by_round = Agreement::new();
+    let mut by_branching = Agreement::new();
+    for row in (0..plies.num_rows()).filter(|&row| befores.is_valid(row) && strategies.is_valid(row)) {
+        let state: azul::State = serde_json::from_str(befores.value(row))?;
+        let action: azul::Action = serde_json::from_str(actions.value(row))?;
+        let player_idx = player_ids.value(row) as usize;
+        let n_actions = rules.list_valid_actions(&state, player_idx).len();
+        let bucket = BRANCHING_BUCKETS.iter().position(|&high| n_actions <= high).unwrap();
+
+        for (name, partial) in azul::PARTIALS {
+            let Some(partial_action) = partial(&rules, &state, player_idx) else {
+                continue;
+            };
+            let matched = (partial_action == action) as usize;
+            let strategy = strategies.value(row);
+            for (counts, column) in [(&mut by_round, round_ids.value(row) as usize), (&mut by_branching, bucket)] {
+                let entry = counts.entry((name, strategy.to_string(), column)).or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += matched;
+            }
+        }
+    }
+
+    Ok((by_round, by_branching))
+}
+
+fn agreement_rows(breakdown: &str, agreement: Agreement, label: impl Fn(usize) -> String) -> Vec<HeuristicAgreement> {
+    agreement
+        .into_iter()
+        .map(|((heuristic, strategy, column), (applicable, matching))| HeuristicAgreement {
+            heuristic: heuristic.to_string(),
+            strategy,
+            breakdown: breakdown.to_string(),
+            column: label(column),
+            applicable,
+            matching,
+            rate: matching as f64 / applicable as f64,
+        })
+        .collect()
+}
+
+// One table per heuristic with a row per strategy and a shaded cell with the
+// agreement rate per column
+fn print_heatmap(breakdown: &str, agreement: &Agreement, label: impl Fn(usize) -> String) {
+    let columns: Vec<usize> = agreement.keys().map(|(_, _, column)| *column).collect::<BTreeSet<_>>().into_iter().collect();
+    let labels: Vec<String> = columns.iter().map(|&c| label(c)).collect();
+    let width = labels.iter().map(|l| l.len()).max().unwrap_or(0).max(5);
+
+    for (name, _) in azul::PARTIALS {
+        let strategies: Vec<&String> = agreement.keys().filter(|(n, _, _)| *n == name).map(|(_, s, _)| s).collect::<BTreeSet<_>>().into_iter().collect();
+        let strategy_width = strategies.iter().map(|s| s.len()).max().unwrap_or(0);
+
+        println!("{} agreement by {}", name, breakdown);
+        let header: Vec<String> = labels.iter().map(|l| format!("{:>width$}", l)).collect();
+        println!("  {:strategy_width$}  {}", "", header.join(" "));
+        for strategy in strategies {
+            let cells: Vec<String> = columns
+                .iter()
+                .map(|&column| match agreement.get(&(name, strategy.clone(), column)) {
+                    Some(&(applicable, matching)) => {
+                        let rate = matching as f64 / applicable as f64;
+                        let shade = SHADES[(rate * (SHADES.len() - 1) as f64).round() as usize];
+                        format!("{}{:>w$.0}%", shade, rate * 100.0, w = width - 2)
+                    },
+                    None => format!("{:>width$}", "-"),
+                })
+                .collect();
+            println!("  {:strategy_width$}  {}", strategy, cells.join(" "));
+        }
+        println!();
+    }
+}
+
+pub fn run(log_file: &PathBuf, query: Query, format: Format) -> Result<()> {
+    let tables = Tables::new(&logreader::read(log_file)?)?;
+
+    if format != Format::Text {
+        return match query {
+            Query::WinRateByFirstPlayer => {
+                let rows: Vec<FirstPlayerWins> = win_rate_by_first_player(&tables)
+                    .into_iter()
+                    .map(|(first_player, (rounds, wins))| FirstPlayerWins { first_player, rounds, wins })
+                    .collect();
+                output::write_rows(&rows, format)
+            },
+            Query::FloorPenaltiesByRound => {
+                let rows: Vec<RoundFloorPenalty> = floor_penalties_by_round(&tables)?
+                    .into_iter()
+                    .map(|(round, mean_penalty)| RoundFloorPenalty { round, mean_penalty })
+                    .collect();
+                output::write_rows(&rows, format)
+            },
+            Query::HeuristicMatchByPly => {
+                let rows: Vec<HeuristicMatch> = heuristic_match_by_ply(&tables)
+                    .into_iter()
+                    .flat_map(|(ply, counts)| counts.into_iter().map(move |(heuristic, (applicable, matching))| {
+                        HeuristicMatch { ply, heuristic, applicable, matching }
+                    }))
+                    .collect();
+                output::write_rows(&rows, format)
+            },
+            Query::HeuristicAgreement => {
+                let (by_round, by_branching) = heuristic_agreement(&tables)?;
+                let mut rows = agreement_rows("round", by_round, |round| round.to_string());
+                rows.extend(agreement_rows("branching", by_branching, branching_label));
+                output::write_rows(&rows, format)
+            },
+        };
+    }
+
+    match query {
+        Query::WinRateByFirstPlayer => {
+            for (player, (n_rounds, n_wins)) in win_rate_by_first_player(&tables) {
+                println!("P{} started {} rounds and won the game after {}, ratio: {}", player, n_rounds, n_wins, n_wins as f64 / n_rounds as f64);
+            }
+        },
+        Query::FloorPenaltiesByRound => {
+            for (round_id, penalty) in floor_penalties_by_round(&tables)? {
+                println!("Round {}: average floor penalty {:.2}", round_id, penalty);
+            }
+        },
+        Query::HeuristicMatchByPly => {
+            for (ply_id, ply_counts) in heuristic_match_by_ply(&tables) {
+                let rates: Vec<String> = ply_counts
+                    .iter()
+                    .map(|(name, (n_applicable, n_matching))| {
+                        format!("{}: {}/{} ({:.2})", name, n_matching, n_applicable, *n_matching as f64 / *n_applicable as f64)
+                    })
+                    .collect();
+                println!("Ply {}: {}", ply_id, rates.join(", "));
+            }
+        },
+        Query::HeuristicAgreement => {
+            let (by_round, by_branching) = heuristic_agreement(&tables)?;
+            print_heatmap("round", &by_round, |round| format!("R{}", round));
+            print_heatmap("valid actions", &by_branching, branching_label);
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlog::Ply;
+
+    fn ply(game_id: usize, round_id: usize, ply_id: usize, player_id: usize) -> LogRecord {
+        LogRecord::Ply(Ply {
+            game_id,
+            round_id,
+            ply_id,
+            player_id,
+            action: String::new(),
+            state: String::new(),
+            score: 0,
+            applicable_partials: Vec::new(),
+            matching_partials: Vec::new(),
+            analysis: String::new(),
+            events: Vec::new(),
+            incident: None,
+        })
+    }
+
+    #[test]
+    fn test_win_rate_by_first_player() {
+        let game_end = |game_id, winner| LogRecord::GameEnd { game_id, winner, final_scores: vec![0, 0], end: GameEnd::Finished };
+        // P1 takes the marker in the first round of game 0 and starts the
+        // second, game 1 is a draw started by P0 both times
+        let play_log = vec![
+            ply(0, 0, 0, 0),
+            ply(0, 0, 1, 1),
+            ply(0, 1, 2, 1),
+            ply(0, 1, 3, 0),
+            game_end(0, Some(1)),
+            ply(1, 0, 0, 0),
+            ply(1, 1, 1, 0),
+            game_end(1, None),
+        ];
+
+        let counts = win_rate_by_first_player(&Tables::new(&play_log).unwrap());
+        assert_eq!(counts[&0], (3, 0));
+        assert_eq!(counts[&1], (1, 1));
+    }
+
+    #[test]
+    fn test_tables() {
+        let start = LogRecord::GameStart { game_id: 0, seed: 0, players: vec!["greedy".to_string(), "random".to_string()], state: "start".to_string() };
+        let mut first = ply(0, 0, 0, 1);
+        if let LogRecord::Ply(first) = &mut first {
+            first.state = "after".to_string();
+            first.applicable_partials = vec!["no_floor".to_string(), "fill_row".to_string()];
+            first.matching_partials = vec!["fill_row".to_string()];
+        }
+        // Plies of a game without a start have no strategy or state before
+        let play_log = vec![start, first, ply(0, 0, 1, 0), ply(1, 0, 0, 0)];
+
+        let tables = Tables::new(&play_log).unwrap();
+        assert_eq!(tables.plies.num_rows(), 3);
+        assert_eq!(tables.game_ends.num_rows(), 0);
+        let (befores, strategies) = (strings(&tables.plies, "before"), strings(&tables.plies, "strategy"));
+        assert_eq!(befores.iter().collect::<Vec<_>>(), [Some("start"), Some("after"), None]);
+        assert_eq!(strategies.iter().collect::<Vec<_>>(), [Some("random"), Some("greedy"), None]);
+
+        let counts = heuristic_match_by_ply(&tables);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&0]["no_floor"], (1, 0));
+        assert_eq!(counts[&0]["fill_row"], (1, 1));
+    }
+}
@@ -0,0 +1,560 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "nn")]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::bestmove;
+use crate::engine::{self, EnginePool};
+use crate::games::azul;
+use crate::games::clock::{self, TimeControl};
+use crate::output::{Exit, OrExit};
+#[cfg(feature = "nn")]
+use crate::inference::InferenceQueue;
+use crate::plugin;
+#[cfg(feature = "nn")]
+use crate::policy::{OnnxPolicy, Policy, PolicyModel, PolicyNet};
+
+// Names of the strategies built into luza, which plugins can't take
+pub const BUILTIN_STRATEGIES: [&str; 6] = ["random", "greedy", "max-n", "mcts", "policy", "exec"];
+
+// Strategies get the rules of the game, the player's remaining time, if the game is played with
+// clocks, to budget their thinking, and a flag set when the watchdog has
+// given up on the move. Searches and engines stop on it, quick strategies
+// and plugins just finish their move.
+type StrategyFn = dyn Fn(&azul::AzulRules, &azul::State, usize, Option<Duration>, &AtomicBool) -> azul::Action + Send + Sync;
+
+// A playing strategy built from a spec string. Specs are a strategy name
+// optionally followed by `:` and comma separated `key=value` options, like
+// `mcts:n_games=50,epsilon=0.1`. External engines are given as
+// `exec:<command>` and strategy plugins by the name they register (see
+// `plugin`). Learned policies are given as `policy:model=<path>` and
+// can bias MCTS with `mcts:policy=<path>`. Both take `batch_size` and
+// `batch_timeout` (milliseconds) to batch policy evaluations across parallel
+// games, and `normalize` and `mover_first` for the features of `.onnx` models.
+//
+// Any strategy can be put under a watchdog by appending `@` and its options,
+// like `mcts:n_games=500@timeout=2s,fallback=greedy`. A move that takes longer
+// than the timeout is replaced by the fallback's move.
+pub struct Strategy {
+    pub spec: String,
+    play_fn: Arc<StrategyFn>,
+    watchdog: Option<Watchdog>,
+}
+
+// Per move time limit and the strategy that plays when it runs out
+struct Watchdog {
+    timeout: Duration,
+    fallback: Fallback,
+}
+
+// Quick strategy playing the move a watched strategy didn't make in time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Fallback {
+    Random,
+    Greedy,
+}
+
+impl Fallback {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "random" => Ok(Fallback::Random),
+            "greedy" => Ok(Fallback::Greedy),
+            _ => Err(anyhow!("Watchdog fallback '{}' is not random or greedy", name)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Fallback::Random => "random",
+            Fallback::Greedy => "greedy",
+        }
+    }
+
+    fn play(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> azul::Action {
+        match self {
+            Fallback::Random => azul::play_random(rules, state, player_idx),
+            Fallback::Greedy => azul::play_greedy(rules, state, player_idx),
+        }
+    }
+}
+
+impl Watchdog {
+    fn parse(options: &str) -> Result<Self> {
+        let mut timeout = None;
+        let mut fallback = Fallback::Random;
+
+        for (key, value) in parse_options(options)? {
+            match key {
+                "timeout" => timeout = Some(bestmove::parse_budget(value)?),
+                "fallback" => fallback = Fallback::parse(value)?,
+                _ => return Err(anyhow!("Unknown watchdog option '{}'", key)),
+            }
+        }
+
+        let timeout = timeout.ok_or_else(|| anyhow!("Watchdog needs a timeout=<budget> option"))?;
+        Ok(Self { timeout, fallback })
+    }
+}
+
+// A game given up by the seat whose strategy panicked while choosing a move
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Forfeit {
+    pub seat: usize,
+    pub reason: String,
+}
+
+impl Forfeit {
+    fn from_panic(seat: usize, payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => payload.downcast_ref::<&str>().map_or("unknown error".to_string(), |m| m.to_string()),
+        };
+        Self { seat, reason: format!("strategy panicked: {}", message) }
+    }
+}
+
+// Play a full game with one strategy per seat. A panic in any strategy ends
+// the game as a forfeit of the seat that was choosing a move instead of
+// taking down the other games running in parallel.
+pub fn play_game(rules: &azul::AzulRules, seats: &[&Strategy], time_control: Option<TimeControl>) -> Result<(azul::State, Option<usize>), Forfeit> {
+    let mover = AtomicUsize::new(0);
+    let play_fns: Vec<_> = seats
+        .iter()
+        .map(|strategy| {
+            let mover = &mover;
+            move |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining| {
+                mover.store(player_idx, Ordering::Relaxed);
+                strategy.play_timed(rules, state, player_idx, remaining)
+            }
+        })
+        .collect();
+    let players: Vec<azul::PlayerFn> = play_fns.iter().map(|f| f as azul::PlayerFn).collect();
+
+    panic::catch_unwind(AssertUnwindSafe(|| azul::play_game(rules, &players, time_control)))
+        .map_err(|payload| Forfeit::from_panic(mover.load(Ordering::Relaxed), payload))
+}
+
+// Rough playing strength of the strategy of a spec, for picking the seat to
+// compare heuristics against. External engines and plugins are unknown and
+// ranked with learned policies, below search.
+pub fn strength(spec: &str) -> usize {
+    let name = spec.split(['@', ':']).next().unwrap_or(spec);
+    match name {
+        "random" => 0,
+        "greedy" => 1,
+        "max-n" => 2,
+        "mcts" => 4,
+        _ => 3,
+    }
+}
+
+fn parse_options(options: &str) -> Result<Vec<(&str, &str)>> {
+    options
+        .split(',')
+        .filter(|o| !o.is_empty())
+        .map(|o| o.split_once('=').ok_or_else(|| anyhow!("Option '{}' is not in key=value form", o)))
+        .collect()
+}
+
+// Learned policy MCTS takes its priors from. Builds without the `nn` feature
+// have none.
+#[cfg(feature = "nn")]
+type Priors = Box<dyn Policy>;
+#[cfg(not(feature = "nn"))]
+type Priors = std::convert::Infallible;
+
+// Settings for loading and evaluating a policy model. JSON models carry
+// their feature options, ONNX ones take them from here.
+#[cfg(feature = "nn")]
+struct Batching {
+    batch_size: usize,
+    timeout: Duration,
+    normalize: bool,
+    mover_first: bool,
+}
+
+#[cfg(feature = "nn")]
+impl Default for Batching {
+    fn default() -> Self {
+        Self { batch_size: 1, timeout: Duration::from_millis(2), normalize: true, mover_first: true }
+    }
+}
+
+#[cfg(feature = "nn")]
+impl Batching {
+    // Set a batching or feature option, returning false for other keys
+    fn set(&mut self, key: &str, value: &str) -> Result<bool> {
+        match key {
+            "batch_size" => self.batch_size = value.parse()?,
+            "batch_timeout" => self.timeout = Duration::from_millis(value.parse()?),
+            "normalize" => self.normalize = value.parse()?,
+            "mover_first" => self.mover_first = value.parse()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Load an ONNX network or a linear model from JSON
+    fn load(&self, path: &Path) -> Result<Box<dyn Policy>> {
+        if path.extension().is_some_and(|ext| ext == "onnx") {
+            Ok(self.policy(OnnxPolicy::load(path, self.normalize, self.mover_first)?))
+        } else {
+            Ok(self.policy(PolicyModel::load(path)?))
+        }
+    }
+
+    // Evaluate the model directly, or through a shared queue when batching
+    fn policy(&self, model: impl PolicyNet + 'static) -> Box<dyn Policy> {
+        if self.batch_size > 1 {
+            Box::new(InferenceQueue::start(model, self.batch_size, self.timeout))
+        } else {
+            Box::new(model)
+        }
+    }
+}
+
+// Set a search option, returning false for other keys
+fn set_mcts_option(config: &mut azul::MctsConfig, key: &str, value: &str) -> Result<bool> {
+    match key {
+        "n_games" => config.n_games = value.parse()?,
+        "epsilon" => config.epsilon = value.parse()?,
+        "blunder_rate" => config.blunder_rate = value.parse()?,
+        "max_blunder_loss" => config.max_blunder_loss = value.parse()?,
+        "widening" => config.widening = value.parse()?,
+        "determinizations" => config.determinizations = value.parse()?,
+        "c_puct" => config.c_puct = value.parse()?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+// MCTS config and the policy to take priors from, if any
+#[cfg(feature = "nn")]
+pub fn parse_mcts_config(options: &str) -> Result<(azul::MctsConfig, Option<Priors>)> {
+    mcts_config_with_policy(options, None)
+}
+
+// Same as `parse_mcts_config` with the policy path given apart from the
+// options, where it can't be given again
+#[cfg(feature = "nn")]
+fn mcts_config_with_policy(options: &str, policy: Option<&Path>) -> Result<(azul::MctsConfig, Option<Priors>)> {
+    let mut config = azul::MctsConfig::default();
+    let mut model = policy;
+    let mut batching = Batching::default();
+
+    for (key, value) in parse_options(options)? {
+        match key {
+            "policy" if policy.is_some() => return Err(anyhow!("The policy is given apart from the mcts options")),
+            "policy" => model = Some(Path::new(value)),
+            _ if set_mcts_option(&mut config, key, value)? => {},
+            _ if batching.set(key, value)? => {},
+            _ => return Err(anyhow!("Unknown mcts option '{}'", key)),
+        }
+    }
+
+    Ok((config, model.map(|path| batching.load(path)).transpose()?))
+}
+
+// Name of MCTS with the given options and priors from the model at `model`,
+// for logs and ratings. It isn't parsed back since paths can have any of the
+// characters specs are split on, see `Strategy::mcts_with_policy`.
+#[cfg(feature = "nn")]
+pub fn mcts_policy_spec(options: &str, model: &Path) -> String {
+    if options.is_empty() {
+        format!("mcts:policy={}", model.display())
+    } else {
+        format!("mcts:{},policy={}", options, model.display())
+    }
+}
+
+// Play function of MCTS with the given settings, searching with priors from
+// the policy if there is one
+fn mcts_fn(config: azul::MctsConfig, policy: Option<Priors>) -> Arc<StrategyFn> {
+    Arc::new(move |rules, state, player_idx, remaining: Option<Duration>, stop: &AtomicBool| {
+        let config = azul::MctsConfig { time_budget: remaining.map(clock::move_budget), ..config };
+        let stop = || stop.load(Ordering::Relaxed);
+        match priors(policy.as_ref(), state, player_idx) {
+            Some(Ok(priors)) => azul::play_mcts_with_priors(rules, state, player_idx, &config, &priors, &stop),
+            Some(Err(err)) => {
+                log::warn!("{}, searching without priors", err);
+                azul::play_mcts_until(rules, state, player_idx, &config, &stop, None)
+            },
+            None => azul::play_mcts_until(rules, state, player_idx, &config, &stop, None),
+        }
+    })
+}
+
+#[cfg(not(feature = "nn"))]
+pub fn parse_mcts_config(options: &str) -> Result<(azul::MctsConfig, Option<Priors>)> {
+    let mut config = azul::MctsConfig::default();
+
+    for (key, value) in parse_options(options)? {
+        match key {
+            "policy" => return Err(anyhow!("MCTS priors from a policy need luza built with the nn feature")),
+            _ if set_mcts_option(&mut config, key, value)? => {},
+            _ => return Err(anyhow!("Unknown mcts option '{}'", key)),
+        }
+    }
+
+    Ok((config, None))
+}
+
+// Search settings of an analysis engine spec like `mcts:n_games=500`, for
+// evaluating positions and comparing evaluations. Only plain MCTS reports them.
+pub fn parse_analysis_engine(spec: &str) -> Result<azul::MctsConfig> {
+    let (name, options) = spec.split_once(':').unwrap_or((spec, ""));
+    if name != "mcts" {
+        return Err(anyhow!("Analysis engines are mcts specs, not '{}'", name));
+    }
+    match parse_mcts_config(options)? {
+        (config, None) => Ok(config),
+        (_, Some(_)) => Err(anyhow!("Analysis engines search without policy priors")),
+    }
+}
+
+// Priors for the position from the policy, if there is one
+#[cfg(feature = "nn")]
+fn priors(policy: Option<&Priors>, state: &azul::State, player_idx: usize) -> Option<Result<Vec<f64>>> {
+    policy.map(|p| p.probabilities(state, player_idx, 1.0))
+}
+
+#[cfg(not(feature = "nn"))]
+fn priors(policy: Option<&Priors>, _state: &azul::State, _player_idx: usize) -> Option<Result<Vec<f64>>> {
+    policy.map(|&never| match never {})
+}
+
+#[cfg(feature = "nn")]
+fn parse_policy(options: &str) -> Result<(Box<dyn Policy>, f64)> {
+    let mut model = None;
+    let mut temperature = 1.0;
+    let mut batching = Batching::default();
+
+    for (key, value) in parse_options(options)? {
+        match key {
+            "model" => model = Some(value),
+            "temperature" => temperature = value.parse()?,
+            _ if batching.set(key, value)? => {},
+            _ => return Err(anyhow!("Unknown policy option '{}'", key)),
+        }
+    }
+
+    let model = model.ok_or_else(|| anyhow!("Policy strategy needs a model=<path> option"))?;
+    Ok((batching.load(Path::new(model))?, temperature))
+}
+
+impl Strategy {
+    // Specs that can't be read are config errors, engines that fail to start
+    // strategy failures
+    pub fn parse(spec: &str) -> Result<Self> {
+        Self::build(spec).or_exit(Exit::InvalidConfig)
+    }
+
+    fn build(spec: &str) -> Result<Self> {
+        let (strategy, watchdog) = match spec.rsplit_once('@') {
+            Some((strategy, options)) => (strategy, Some(Watchdog::parse(options)?)),
+            None => (spec, None),
+        };
+        let (name, options) = strategy.split_once(':').unwrap_or((strategy, ""));
+
+        let play_fn: Arc<StrategyFn> = match name {
+            "random" => Arc::new(|rules, state, player_idx, _, _| azul::play_random(rules, state, player_idx)),
+            "greedy" => Arc::new(|rules, state, player_idx, _, _| azul::play_greedy(rules, state, player_idx)),
+            "max-n" => Arc::new(|rules, state, player_idx, _, _| azul::play_max_n(rules, state, player_idx)),
+            "mcts" => {
+                let (config, policy) = parse_mcts_config(options)?;
+                mcts_fn(config, policy)
+            },
+            #[cfg(feature = "nn")]
+            "policy" => {
+                let (policy, temperature) = parse_policy(options)?;
+                Arc::new(move |_, state, player_idx, _, _| policy.play(state, player_idx, temperature))
+            },
+            #[cfg(not(feature = "nn"))]
+            "policy" => return Err(anyhow!("The policy strategy needs luza built with the nn feature")),
+            "exec" => {
+                let engines = EnginePool::spawn(options, engine::DEFAULT_TIMEOUT).or_exit(Exit::StrategyFailure)?;
+                let spec = spec.to_string();
+                // A failing engine forfeits its move to a random one instead of
+                // stopping the whole run
+                Arc::new(move |rules, state, player_idx, remaining, stop| match engines.genmove(rules, state, player_idx, remaining, stop) {
+                    Ok(action) => action,
+                    Err(err) => {
+                        log::warn!("{}: {}, playing a random move", spec, err);
+                        azul::play_random(rules, state, player_idx)
+                    },
+                })
+            },
+            _ => {
+                let plugin = plugin::get(name).ok_or_else(|| anyhow!("Unknown strategy '{}'", name))?;
+                let spec = spec.to_string();
+                Arc::new(move |rules, state, player_idx, remaining, _| match plugin.genmove(rules, state, player_idx, remaining) {
+                    Ok(action) => action,
+                    Err(err) => {
+                        log::warn!("{}: {}, playing a random move", spec, err);
+                        azul::play_random(rules, state, player_idx)
+                    },
+                })
+            },
+        };
+
+        if !options.is_empty() && !["mcts", "policy", "exec"].contains(&name) {
+            return Err(anyhow!("Strategy '{}' doesn't take options", name));
+        }
+
+        Ok(Self { spec: spec.to_string(), play_fn, watchdog })
+    }
+
+    // MCTS with the given options and priors from the model at `model`,
+    // which doesn't go through the spec string so it can be any path
+    #[cfg(feature = "nn")]
+    pub fn mcts_with_policy(options: &str, model: &Path) -> Result<Self> {
+        let (config, policy) = mcts_config_with_policy(options, Some(model)).or_exit(Exit::InvalidConfig)?;
+        Ok(Self { spec: mcts_policy_spec(options, model), play_fn: mcts_fn(config, policy), watchdog: None })
+    }
+
+    pub fn play(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize) -> azul::Action {
+        self.play_timed(rules, state, player_idx, None)
+    }
+
+    pub fn play_timed(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>) -> azul::Action {
+        let (action, incident) = self.play_watched(rules, state, player_idx, remaining);
+        if let Some(incident) = incident {
+            log::warn!("{}: {}", self.spec, incident);
+        }
+        action
+    }
+
+    // Play a move along with what went wrong if the watchdog had to step in.
+    // Watched moves are searched on their own thread, so seeded games with
+    // watched strategies aren't reproducible.
+    pub fn play_watched(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>) -> (azul::Action, Option<String>) {
+        let Some(watchdog) = &self.watchdog else {
+            return ((self.play_fn)(rules, state, player_idx, remaining, &AtomicBool::new(false)), None);
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let play_fn = self.play_fn.clone();
+        let thread_rules = rules.clone();
+        let thread_state = state.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(|| play_fn(&thread_rules, &thread_state, player_idx, remaining, &thread_stop))));
+        });
+
+        let incident = match receiver.recv_timeout(watchdog.timeout) {
+            Ok(Ok(action)) => return (action, None),
+            // Panics are passed on to the caller as if the strategy ran here
+            Ok(Err(payload)) => panic::resume_unwind(payload),
+            Err(mpsc::RecvTimeoutError::Timeout) => format!("no move in {:?}", watchdog.timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected) => "stopped without a move".to_string(),
+        };
+        // The late move is of no use, so searches wind down and engines are
+        // killed instead of thinking on in the background
+        stop.store(true, Ordering::Relaxed);
+        let incident = format!("{}, played a {} move", incident, watchdog.fallback.name());
+        (watchdog.fallback.play(rules, state, player_idx), Some(incident))
+    }
+
+    // Same as `play_watched` but a panic in the strategy comes back as the
+    // forfeit of its seat
+    pub fn play_guarded(&self, rules: &azul::AzulRules, state: &azul::State, player_idx: usize, remaining: Option<Duration>) -> Result<(azul::Action, Option<String>), Forfeit> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.play_watched(rules, state, player_idx, remaining)))
+            .map_err(|payload| Forfeit::from_panic(player_idx, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    #[test]
+    fn test_watchdog() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+
+        let slow = Strategy::parse("mcts:n_games=100000@timeout=10ms,fallback=greedy").unwrap();
+        let (action, incident) = slow.play_watched(&rules, &state, 0, None);
+        assert!(incident.is_some_and(|i| i.ends_with("played a greedy move")));
+        assert!(azul::actions_canonical(&rules, &state, 0).contains(&action));
+
+        let fast = Strategy::parse("greedy@timeout=10s").unwrap();
+        assert!(fast.play_watched(&rules, &state, 0, None).1.is_none());
+        assert!(Strategy::parse("greedy@fallback=greedy").is_err());
+        assert!(Strategy::parse("greedy@timeout=1s,fallback=max-n").is_err());
+
+        // A strategy given up on is told to stop instead of running on
+        let (stopped, was_stopped) = mpsc::channel();
+        let stopped = std::sync::Mutex::new(stopped);
+        let stuck = Strategy {
+            spec: "stuck".to_string(),
+            play_fn: Arc::new(move |rules, state, player_idx, _, stop| {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                stopped.lock().unwrap().send(()).unwrap();
+                azul::play_random(rules, state, player_idx)
+            }),
+            watchdog: Some(Watchdog::parse("timeout=10ms").unwrap()),
+        };
+        assert!(stuck.play_watched(&rules, &state, 0, None).1.is_some_and(|i| i.ends_with("played a random move")));
+        assert!(was_stopped.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let greedy = Strategy::parse("greedy").unwrap();
+        let broken = Strategy { spec: "broken".to_string(), play_fn: Arc::new(|_, _, _, _, _| panic!("no moves left")), watchdog: None };
+
+        let forfeit = play_game(&azul::AzulRules::current(), &[&greedy, &broken], None).unwrap_err();
+        assert_eq!(forfeit.seat, 1);
+        assert_eq!(forfeit.reason, "strategy panicked: no moves left");
+    }
+
+    #[test]
+    fn test_strength() {
+        assert!(strength("mcts:n_games=50@timeout=1s") > strength("greedy"));
+        assert!(strength("exec:luza engine mcts") > strength("max-n"));
+        assert_eq!(strength("random"), 0);
+    }
+
+    #[test]
+    fn test_parse_analysis_engine() {
+        assert_eq!(parse_analysis_engine("mcts").unwrap().n_games, azul::MctsConfig::default().n_games);
+        assert_eq!(parse_analysis_engine("mcts:n_games=50,widening=4").unwrap().widening, 4);
+        assert!(parse_analysis_engine("greedy").is_err());
+        assert!(parse_analysis_engine("mcts:depth=3").is_err());
+    }
+
+    #[cfg(feature = "nn")]
+    #[test]
+    fn test_mcts_with_policy() {
+        let rules = azul::AzulRules::current();
+        use crate::games::Representable;
+
+        // Paths with the characters specs are split on
+        let dir = std::env::temp_dir().join(format!("luza-policy,{}@x", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model_file = dir.join("best.json");
+        let n_features = rules.new_state(2).represent().len();
+        serde_json::to_writer(std::fs::File::create(&model_file).unwrap(), &PolicyModel::zeros(n_features, false, false)).unwrap();
+
+        let mut state = rules.new_state(2);
+        rules.refill(&mut state, &mut azul::game_rng());
+        let strategy = Strategy::mcts_with_policy("n_games=20", &model_file).unwrap();
+        assert!(azul::actions_canonical(&rules, &state, 0).contains(&strategy.play(&rules, &state, 0)));
+        assert_eq!(strategy.spec, format!("mcts:n_games=20,policy={}", model_file.display()));
+        assert!(Strategy::mcts_with_policy("policy=other.json", &model_file).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::accuracy::{self, MoveEval};
+use crate::autosave::Autosave;
+use crate::games::azul;
+use crate::par::prelude::*;
+use crate::sound::BLUNDER_LOSS;
+use crate::text;
+use crate::tournament;
+
+// Markdown study sheet of a saved interactive game: the move list with the
+// engine's evaluation of every move, and the board at the key moments, which
+// are the moves with notes and the blunders
+pub fn study_sheet(name: &str, save: &Autosave, evals: &[MoveEval]) -> String {
+    let mut md = String::new();
+    writeln!(md, "# Study sheet: {}\n", name).unwrap();
+    let scores: Vec<String> = save.state.players.iter().enumerate().map(|(i, p)| format!("P{} {}", i, p.score)).collect();
+    writeln!(md, "{} moves. Final scores: {}.\n", save.history.len(), scores.join(", ")).unwrap();
+
+    writeln!(md, "## Moves\n").unwrap();
+    writeln!(md, "| Ply | Player | Move | Engine | EXP Score | Engine EXP | Loss | Think | Note |").unwrap();
+    writeln!(md, "|----:|--------|------|--------|----------:|-----------:|-----:|------:|------|").unwrap();
+    for (ply, ((player, _), eval)) in save.history.iter().zip(evals).enumerate() {
+        let think_time = match save.think_times.get(ply).copied().flatten() {
+            Some(ms) => format!("{:.1}s", ms as f64 / 1000.0),
+            None if save.auto_played.contains(&ply) => "auto".to_string(),
+            None => String::new(),
+        };
+        let note = save.notes.get(&ply).map_or(String::new(), |note| note.replace('|', "\\|"));
+        writeln!(
+            md,
+            "| {} | P{} | {} | {} | {:.2} | {:.2} | {:.2} | {} | {} |",
+            ply,
+            player,
+            azul::action_notation(eval.action),
+            azul::action_notation(eval.best_action),
+            eval.expected_score,
+            eval.best_expected_score,
+            eval.loss(),
+            think_time,
+            note,
+        ).unwrap();
+    }
+
+    writeln!(md, "\n## Key moments").unwrap();
+    for (ply, ((player, _), eval)) in save.history.iter().zip(evals).enumerate() {
+        let note = save.notes.get(&ply);
+        if note.is_none() && eval.loss() < BLUNDER_LOSS {
+            continue;
+        }
+
+        writeln!(md, "\n### Ply {}: P{} played {}\n", ply, player, azul::action_notation(eval.action)).unwrap();
+        writeln!(md, "```text\n{}\n```\n", text::render_text(&eval.state, *player).trim_end()).unwrap();
+        if eval.best_action == eval.action {
+            writeln!(md, "The engine agrees (EXP Score {:.2}).", eval.expected_score).unwrap();
+        } else {
+            writeln!(
+                md,
+                "The engine prefers {} (EXP Score {:.2} against {:.2}, a loss of {:.2}).",
+                azul::action_notation(eval.best_action),
+                eval.best_expected_score,
+                eval.expected_score,
+                eval.loss(),
+            ).unwrap();
+        }
+        if let Some(note) = note {
+            writeln!(md, "\n> {}", note).unwrap();
+        }
+    }
+
+    writeln!(md, "\n## Final position\n").unwrap();
+    writeln!(md, "```text\n{}\n```", text::render_text(&save.state, save.current_player).trim_end()).unwrap();
+    md
+}
+
+// Evaluate every move of a saved game and write its study sheet. Moves are
+// seeded by their ply so the sheet comes out the same again.
+pub fn run(save_file: &Path, out: &Path) -> Result<()> {
+    let save: Autosave = serde_json::from_reader(File::open(save_file)?)?;
+    if save.positions.len() != save.history.len() {
+        return Err(anyhow!("{} doesn't have the positions of its moves, it was saved by an older luza", save_file.display()));
+    }
+    azul::set_config(save.rules.clone());
+    let rules = azul::AzulRules::current();
+
+    println!("Analyzing {} moves", save.history.len());
+    let evals: Vec<MoveEval> = (0..save.history.len())
+        .into_par_iter()
+        .map(|ply| {
+            let (player, action) = save.history[ply];
+            accuracy::evaluate_move(&rules, save.positions[ply].clone(), player, action, tournament::match_seed(0, 0, ply, player))
+        })
+        .collect();
+
+    let name = save_file.file_stem().map_or(String::new(), |n| n.to_string_lossy().to_string());
+    fs::write(out, study_sheet(&name, &save, &evals))?;
+    println!("Wrote {}", out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    #[test]
+    fn test_study_sheet() {
+        let rules = azul::AzulRules::current();
+        let mut state = rules.new_state(2);
+        azul::with_seed(2, || rules.refill(&mut state, &mut azul::game_rng()));
+        let actions = rules.list_valid_actions(&state, 0);
+        let eval = |action: azul::Action, expected_score: f32| MoveEval {
+            state: state.clone(),
+            action,
+            best_action: actions[0],
+            expected_score,
+            best_expected_score: 12.0,
+        };
+        let evals = vec![eval(actions[0], 12.0), eval(actions[1], 11.0), eval(actions[2], 4.0)];
+
+        let save = Autosave {
+            rules: azul::AzulConfig::default(),
+            state: state.clone(),
+            current_player: 1,
+            ply: 3,
+            ply_round: 3,
+            history: vec![(0, actions[0]), (1, actions[1]), (0, actions[2])],
+            think_times: vec![Some(2500), None, Some(900)],
+            positions: Vec::new(),
+            mistakes: HashMap::new(),
+            notes: BTreeMap::from([(1, "feared the floor".to_string())]),
+            auto_played: BTreeSet::new(),
+        };
+        let md = study_sheet("game", &save, &evals);
+
+        assert!(md.starts_with("# Study sheet: game\n"));
+        assert_eq!(md.matches("\n| ").count(), 1 + 3);
+        assert!(md.contains(&format!("| 0 | P0 | {} | {} | 12.00 | 12.00 | 0.00 | 2.5s |  |", azul::action_notation(actions[0]), azul::action_notation(actions[0]))));
+        // The noted move and the blunder are key moments, the best move isn't
+        assert!(!md.contains("### Ply 0"));
+        assert!(md.contains("### Ply 1") && md.contains("\n> feared the floor\n"));
+        assert!(md.contains("### Ply 2") && md.contains("a loss of 8.00"));
+        assert_eq!(md.matches("```text").count(), 3);
+    }
+}
@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::azul::{self, ActionDisplay, Tile, WALL_COLORS};
+use crate::games::Rules;
+use crate::sound::{Cue, Sound};
+
+// Single letter for a tile color, upper case for placed tiles
+fn tile_char(tile: Tile) -> char {
+    match tile {
+        Tile::Black => 'k',
+        Tile::Blue => 'b',
+        Tile::Red => 'r',
+        Tile::White => 'w',
+        Tile::Yellow => 'y',
+    }
+}
+
+fn tiles_text(tiles: &azul::FactoryDisplayState) -> String {
+    let mut colors: Vec<(&Tile, &usize)> = tiles.iter().filter(|(_, &count)| count > 0).collect();
+    colors.sort_by_key(|(&tile, _)| tile_char(tile));
+
+    let text: Vec<String> = colors.into_iter().map(|(&tile, &count)| format!("{:?}x{}", tile, count)).collect();
+    if text.is_empty() { "-".to_string() } else { text.join(" ") }
+}
+
+// Plain text board for terminals without TUI support. Pattern lines are shown
+// right aligned next to the wall, with `.` for empty slots. Wall tiles are in
+// upper case when placed and lower case otherwise.
+pub fn render_text(state: &azul::State, current_player: usize) -> String {
+    let mut lines = Vec::new();
+
+    lines.push(format!("Round {}, P{} to move", state.rounds, current_player));
+    for (i, display) in state.factory_displays.iter().enumerate() {
+        lines.push(format!("  D{}: {}", i, tiles_text(display)));
+    }
+    lines.push(format!(
+        "  Center: {}{}",
+        if state.center.starting_marker { "1 " } else { "" },
+        tiles_text(&state.center.tiles)
+    ));
+
+    for (i, player) in state.players.iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!(
+            "{} P{}: score {}, floor {}{}",
+            if i == current_player { ">" } else { " " },
+            i,
+            player.score,
+            player.floor_line,
+            if player.starting_marker { ", has starting marker" } else { "" },
+        ));
+
+        for (row, &(tile, count)) in player.pattern_lines.iter().enumerate() {
+            let mut pattern: String = " ".repeat(4 - row);
+            for slot in 0..=row {
+                pattern.push(match tile {
+                    Some(t) if slot < count => tile_char(t).to_ascii_uppercase(),
+                    _ => '.',
+                });
+            }
+
+            let wall: String = WALL_COLORS[row]
+                .iter()
+                .zip(&player.wall[row])
+                .map(|(&color, &placed)| {
+                    let c = tile_char(color);
+                    if placed { c.to_ascii_uppercase() } else { c }
+                })
+                .collect();
+
+            lines.push(format!("    {} {} | {}", row, pattern, wall));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// Game in progress with everything needed to undo moves
+#[derive(Clone, serde::Serialize)]
+pub struct TextGame {
+    pub state: azul::State,
+    pub current_player: usize,
+    pub ply: usize,
+}
+
+impl TextGame {
+    fn new(rules: &azul::AzulRules, n_players: usize) -> Self {
+        let mut game = Self { state: rules.new_state(n_players), current_player: 0, ply: 0 };
+        game.start_round(rules);
+        game
+    }
+
+    fn start_round(&mut self, rules: &azul::AzulRules) {
+        self.current_player = match azul::first_player(&self.state) {
+            Some(one) => {
+                self.state.players[one].starting_marker = false;
+                one
+            },
+            None => 0,
+        };
+
+        rules.refill(&mut self.state, &mut azul::game_rng());
+    }
+
+    // Apply the move, scoring and starting the next round when needed. Returns
+    // the scoring events when the move ended a round.
+    fn play(&mut self, rules: &azul::AzulRules, action: azul::Action) -> Vec<azul::GameEvent> {
+        rules.take_action(&mut self.state, self.current_player, action);
+        self.current_player = (self.current_player + 1) % self.state.players.len();
+        self.ply += 1;
+
+        let mut events = Vec::new();
+        if rules.is_round_over(&self.state) {
+            events = rules.end_round(&mut self.state);
+            if !rules.is_game_over(&self.state) {
+                self.start_round(rules);
+            }
+        }
+
+        events
+    }
+}
+
+fn action_text(action: &azul::Action) -> String {
+    let source = match action.action_display_choice {
+        ActionDisplay::FactoryDisplay(i) => format!("D{}", i),
+        ActionDisplay::Center => "Center".to_string(),
+    };
+
+    match action.pattern_line_choice {
+        Some(i) => format!("{} {:?} to row {}", source, action.color_choice, i),
+        None => format!("{} {:?} to floor", source, action.color_choice),
+    }
+}
+
+fn print_help() {
+    println!("Enter an action number or notation like 'D2 Blue 3' (F for floor) to move.");
+    println!("Other commands: hint, undo, save <file>, help, quit");
+}
+
+// Line based game loop reading moves from stdin, from a new game or the given
+// position with the player to move. Returns the finished game, or
+// None if the player quit or input ended before the game was over.
+pub fn run(n_players: usize, position: Option<(azul::State, usize)>, teacher: azul::MctsConfig, autoplay: bool, sound: &Sound) -> Result<Option<TextGame>> {
+    let rules = azul::AzulRules::current();
+    let mut game = match position {
+        Some((state, current_player)) => TextGame { state, current_player, ply: 0 },
+        None => TextGame::new(&rules, n_players),
+    };
+    let mut history: Vec<TextGame> = Vec::new();
+    let stdin = io::stdin();
+    let mut input = stdin.lock().lines();
+
+    print_help();
+
+    // Board is only printed again when the position changes
+    let mut show_board = true;
+
+    loop {
+        let actions = rules.list_valid_actions(&game.state, game.current_player);
+
+        // Forced moves are played without asking
+        let forced = if autoplay { azul::forced_action(&rules, &game.state, game.current_player) } else { None };
+        let action = if let Some(action) = forced {
+            println!("P{} has no real choice", game.current_player);
+            action
+        } else {
+            if show_board {
+                println!();
+                println!("{}", render_text(&game.state, game.current_player));
+                println!();
+                for (i, action) in actions.iter().enumerate() {
+                    println!("  {:>3}. {}", i, action_text(action));
+                }
+                show_board = false;
+            }
+            print!("P{}> ", game.current_player);
+            io::stdout().flush()?;
+
+            let line = match input.next() {
+                Some(line) => line?,
+                None => return Ok(None),
+            };
+            let line = line.trim();
+
+            match line.split_once(' ').unwrap_or((line, "")) {
+                ("", _) => continue,
+                ("quit" | "q", _) => return Ok(None),
+                ("help", _) => {
+                    print_help();
+                    continue;
+                },
+                ("hint", _) => {
+                    let action = azul::play_mcts_with(&rules, &game.state, game.current_player, &teacher);
+                    println!("Hint: {} ({})", azul::action_notation(action), azul::explain_action(&rules, &game.state, game.current_player, action));
+                    continue;
+                },
+                ("undo", _) => {
+                    match history.pop() {
+                        Some(previous) => {
+                            game = previous;
+                            show_board = true;
+                        },
+                        None => println!("Nothing to undo"),
+                    }
+                    continue;
+                },
+                ("save", file) => {
+                    if file.is_empty() {
+                        println!("Usage: save <file>");
+                    } else if let Err(err) = save(&game, file) {
+                        println!("Failed to save: {}", err);
+                    } else {
+                        println!("Saved to {}", file);
+                    }
+                    continue;
+                },
+                _ => match parse_choice(line, &actions) {
+                    Ok(action) => action,
+                    Err(err) => {
+                        sound.play(Cue::Illegal);
+                        println!("{}", err);
+                        continue;
+                    },
+                },
+            }
+        };
+
+        println!("P{}: {}", game.current_player, azul::explain_action(&rules, &game.state, game.current_player, action));
+        // Undo goes back past forced moves, which would be played again
+        if forced.is_none() {
+            history.push(game.clone());
+        }
+        show_board = true;
+
+        let (game_end, round_end): (Vec<_>, Vec<_>) = game.play(&rules, action)
+            .into_iter()
+            .partition(|event| matches!(event, azul::GameEvent::GameEnded { .. }));
+        if !round_end.is_empty() && game_end.is_empty() {
+            sound.play(Cue::RoundEnd);
+        }
+        for event in round_end {
+            println!("{}", event);
+        }
+
+        if let Some(event) = game_end.first() {
+            println!();
+            println!("{}", render_text(&game.state, game.current_player));
+            println!();
+            println!("{}", event);
+            return Ok(Some(game));
+        }
+    }
+}
+
+// Action picked by its number in the list or by notation
+fn parse_choice(line: &str, actions: &[azul::Action]) -> Result<azul::Action> {
+    let action = match line.parse::<usize>() {
+        Ok(idx) => *actions.get(idx).ok_or_else(|| anyhow!("No action numbered {}", idx))?,
+        Err(_) => azul::parse_action_notation(line)?,
+    };
+
+    if !actions.contains(&action) {
+        return Err(anyhow!("Action '{}' is not valid now", line));
+    }
+
+    Ok(action)
+}
+
+fn save(game: &TextGame, file: &str) -> Result<()> {
+    let writer = BufWriter::new(File::create(file)?);
+    serde_json::to_writer_pretty(writer, game)?;
+    Ok(())
+}
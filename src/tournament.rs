@@ -0,0 +1,537 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::games::{self, azul::{self, AzulConfig}};
+use crate::games::clock::TimeControl;
+use crate::output::{self, Exit, Format, GameStanding, OrExit, TournamentStanding};
+use crate::par::prelude::*;
+use crate::rating;
+use crate::strategy::{self, Forfeit, Strategy};
+
+// How entrants are paired against each other
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pairing {
+    /// Everyone plays everyone once
+    #[default]
+    RoundRobin,
+    /// Fixed number of rounds pairing entrants with similar scores
+    Swiss,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Standing {
+    // Match points, 1 for a match win, 0.5 for a drawn match or a bye
+    pub points: f64,
+    pub game_wins: usize,
+    pub games: usize,
+    pub opponents: Vec<usize>,
+    pub had_bye: bool,
+}
+
+// Sum of match points of all opponents, used as the first tie-break
+fn buchholz(standings: &[Standing], idx: usize) -> f64 {
+    standings[idx].opponents.iter().map(|&o| standings[o].points).sum()
+}
+
+// Entrant indices ordered by points, then Buchholz, then entry order
+pub fn ranking(standings: &[Standing]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    order.sort_by(|&a, &b| {
+        standings[b].points.total_cmp(&standings[a].points)
+            .then(buchholz(standings, b).total_cmp(&buchholz(standings, a)))
+            .then(a.cmp(&b))
+    });
+    order
+}
+
+pub fn round_robin_pairings(n_entrants: usize) -> Vec<(usize, usize)> {
+    let mut pairings = Vec::new();
+    for i in 0..n_entrants {
+        for j in (i + 1)..n_entrants {
+            pairings.push((i, j));
+        }
+    }
+    pairings
+}
+
+// Pair entrants for the next Swiss round. With an odd number of entrants the
+// lowest ranked entrant who hasn't had a bye yet sits out. Pairing walks down
+// the ranking matching each entrant with the next one they haven't played,
+// falling back to a rematch when that's not possible.
+pub fn swiss_pairings(standings: &[Standing]) -> (Vec<(usize, usize)>, Option<usize>) {
+    let mut order = ranking(standings);
+
+    let mut bye = None;
+    if order.len() % 2 == 1 {
+        let bye_pos = order
+            .iter()
+            .rposition(|&i| !standings[i].had_bye)
+            .unwrap_or(order.len() - 1);
+        bye = Some(order.remove(bye_pos));
+    }
+
+    let mut pairings = Vec::new();
+    while !order.is_empty() {
+        let a = order.remove(0);
+        let opponent_pos = order
+            .iter()
+            .position(|b| !standings[a].opponents.contains(b))
+            .unwrap_or(0);
+        let b = order.remove(opponent_pos);
+        pairings.push((a, b));
+    }
+
+    (pairings, bye)
+}
+
+// One game of a match between strategies a and b
+#[derive(Debug, Clone)]
+pub struct MatchGame {
+    pub a_seat: usize,
+    pub seed: u64,
+    // Scores by seat
+    pub scores: Vec<i32>,
+    // Winning seat after the tie-break, None for a draw
+    pub winner: Option<usize>,
+    // Seat whose flag fell, if any
+    pub flagged: Option<usize>,
+    // Seat whose strategy panicked, which loses the game
+    pub forfeit: Option<Forfeit>,
+}
+
+// One game in the manifest with everything needed to play it again
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GameEntry {
+    pub id: usize,
+    pub round: usize,
+    // Strategy specs by seat
+    pub seats: Vec<String>,
+    pub seed: u64,
+    pub scores: Vec<i32>,
+    pub flagged: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forfeit: Option<Forfeit>,
+}
+
+// Record of a tournament that lets any of its games be regenerated
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub seed: u64,
+    pub rules: AzulConfig,
+    pub games: Vec<GameEntry>,
+}
+
+// Seed for the games of a match, fixed by the tournament seed, the round, and
+// the pairing
+pub fn match_seed(seed: u64, round: usize, a: usize, b: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (seed, round, a, b).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Seed for one game of a match given its index and seat order
+fn game_seed(match_seed: u64, game_idx: usize, a_seat: usize) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (match_seed, game_idx, a_seat).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Play a match of `n_games` between two strategies in parallel, alternating
+// seats. Every game is seeded from `match_seed` so the match can be replayed.
+pub fn play_match(rules: &azul::AzulRules, a: &Strategy, b: &Strategy, n_games: usize, time_control: Option<TimeControl>, match_seed: u64) -> Vec<MatchGame> {
+    (0..n_games).into_par_iter().map(|game_idx| {
+        let a_seat = game_idx % 2;
+        let seed = game_seed(match_seed, game_idx, a_seat);
+        let seats = if a_seat == 0 { [a, b] } else { [b, a] };
+
+        match azul::with_seed(seed, || strategy::play_game(rules, &seats, time_control)) {
+            Ok((state, flagged)) => {
+                if let Some(seat) = flagged {
+                    log::info!("Flag fell for seat {} in game {}", seat, game_idx);
+                }
+                MatchGame { a_seat, seed, scores: state.players.iter().map(|p| p.score).collect(), winner: azul::winner(&state), flagged, forfeit: None }
+            },
+            Err(forfeit) => {
+                log::warn!("{} forfeited game {}: {}", seats[forfeit.seat].spec, game_idx, forfeit.reason);
+                MatchGame { a_seat, seed, scores: Vec::new(), winner: None, flagged: None, forfeit: Some(forfeit) }
+            },
+        }
+    }).collect()
+}
+
+// Wins of the first strategy, wins of the second, and draws. Tied scores go
+// to the player with more completed rows. With a time control, a player whose
+// flag falls loses the game, as does a player whose strategy panicked.
+pub fn tally(games: &[MatchGame]) -> (usize, usize, usize) {
+    let mut results = (0, 0, 0);
+
+    for game in games {
+        if let Some(seat) = game.flagged.or(game.forfeit.as_ref().map(|f| f.seat)) {
+            if seat == game.a_seat {
+                results.1 += 1;
+            } else {
+                results.0 += 1;
+            }
+            continue;
+        }
+
+        match game.winner {
+            Some(seat) if seat == game.a_seat => results.0 += 1,
+            Some(_) => results.1 += 1,
+            None => results.2 += 1,
+        }
+    }
+
+    results
+}
+
+fn record_match(standings: &mut [Standing], a: usize, b: usize, results: (usize, usize, usize)) {
+    let (a_wins, b_wins, draws) = results;
+    let n_games = a_wins + b_wins + draws;
+
+    let (a_points, b_points) = if a_wins > b_wins {
+        (1.0, 0.0)
+    } else if b_wins > a_wins {
+        (0.0, 1.0)
+    } else {
+        (0.5, 0.5)
+    };
+
+    standings[a].points += a_points;
+    standings[a].game_wins += a_wins;
+    standings[a].games += n_games;
+    standings[a].opponents.push(b);
+
+    standings[b].points += b_points;
+    standings[b].game_wins += b_wins;
+    standings[b].games += n_games;
+    standings[b].opponents.push(a);
+}
+
+// Specs and results of a match for the persisted ratings
+type RatedMatch = (String, String, (usize, usize, usize));
+
+// Settings shared by all rounds of a tournament
+struct Schedule {
+    rules: azul::AzulRules,
+    games_per_match: usize,
+    time_control: Option<TimeControl>,
+    seed: u64,
+    format: Format,
+}
+
+// Play all matches of a round in parallel, then record them in pairing order.
+// Returns the specs and results of the matches for the persisted ratings.
+fn run_round(strategies: &[Strategy], standings: &mut [Standing], pairings: &[(usize, usize)], round: usize, schedule: &Schedule, manifest: &mut Manifest) -> Vec<RatedMatch> {
+    let matches: Vec<Vec<MatchGame>> = pairings.par_iter().map(|&(a, b)| {
+        play_match(&schedule.rules, &strategies[a], &strategies[b], schedule.games_per_match, schedule.time_control, match_seed(schedule.seed, round, a, b))
+    }).collect();
+
+    let mut rated = Vec::new();
+    for (&(a, b), games) in pairings.iter().zip(matches) {
+        let results = tally(&games);
+        output::progress(
+            schedule.format,
+            &format!("  {} vs {}: {}-{} ({} drawn)", strategies[a].spec, strategies[b].spec, results.0, results.1, results.2),
+        );
+        record_match(standings, a, b, results);
+        rated.push((strategies[a].spec.clone(), strategies[b].spec.clone(), results));
+
+        for game in games {
+            let mut seats = vec![strategies[b].spec.clone(); 2];
+            seats[game.a_seat] = strategies[a].spec.clone();
+            manifest.games.push(GameEntry {
+                id: manifest.games.len(),
+                round,
+                seats,
+                seed: game.seed,
+                scores: game.scores,
+                flagged: game.flagged,
+                forfeit: game.forfeit,
+            });
+        }
+    }
+
+    rated
+}
+
+// Play all rounds of a tournament, returning the final standings, the
+// manifest, and the matches for the persisted ratings
+fn play(strategies: &[Strategy], pairing: Pairing, n_rounds: usize, schedule: &Schedule) -> (Vec<TournamentStanding>, Manifest, Vec<RatedMatch>) {
+    let format = schedule.format;
+    let mut standings = vec![Standing::default(); strategies.len()];
+    let mut manifest = Manifest { seed: schedule.seed, rules: (*schedule.rules.config).clone(), games: Vec::new() };
+    let mut rated = Vec::new();
+
+    match pairing {
+        Pairing::RoundRobin => {
+            output::progress(format, "Round robin");
+            rated.extend(run_round(strategies, &mut standings, &round_robin_pairings(strategies.len()), 0, schedule, &mut manifest));
+        },
+        Pairing::Swiss => {
+            for round in 0..n_rounds {
+                let (pairings, bye) = swiss_pairings(&standings);
+                output::progress(format, &format!("Round {}", round));
+
+                if let Some(i) = bye {
+                    output::progress(format, &format!("  {} gets a bye", strategies[i].spec));
+                    standings[i].points += 1.0;
+                    standings[i].had_bye = true;
+                }
+
+                rated.extend(run_round(strategies, &mut standings, &pairings, round, schedule, &mut manifest));
+            }
+        },
+    }
+
+    let rows: Vec<TournamentStanding> = ranking(&standings)
+        .into_iter()
+        .enumerate()
+        .map(|(rank, i)| TournamentStanding {
+            rank: rank + 1,
+            spec: strategies[i].spec.clone(),
+            points: standings[i].points,
+            buchholz: buchholz(&standings, i),
+            game_wins: standings[i].game_wins,
+            games: standings[i].games,
+        })
+        .collect();
+
+    (rows, manifest, rated)
+}
+
+fn print_standings(rows: &[TournamentStanding]) {
+    for row in rows {
+        println!(
+            "{:>3}. {} points: {}, buchholz: {}, game wins: {}/{}",
+            row.rank, row.spec, row.points, row.buchholz, row.game_wins, row.games
+        );
+    }
+}
+
+fn write_manifest(manifest: &Manifest, file: &Path) -> Result<()> {
+    let writer = BufWriter::new(File::create(file)?);
+    serde_json::to_writer_pretty(writer, manifest)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(specs: &[String], pairing: Pairing, n_rounds: usize, games_per_match: usize, time_control: Option<TimeControl>, seed: Option<u64>, manifest_file: Option<&PathBuf>, format: Format) -> Result<()> {
+    let strategies = specs.iter().map(|s| Strategy::parse(s)).collect::<Result<Vec<Strategy>>>()?;
+    if strategies.len() < 2 {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Tournament needs at least two strategies")));
+    }
+
+    let schedule = Schedule { rules: azul::AzulRules::current(), games_per_match, time_control, seed: seed.unwrap_or_else(rand::random), format };
+    output::progress(format, &format!("Seed: {}", schedule.seed));
+    if let Some(handicaps) = azul::config().handicap_summary() {
+        output::progress(format, &format!("Handicaps: {}", handicaps));
+    }
+    let (rows, manifest, rated) = play(&strategies, pairing, n_rounds, &schedule);
+
+    if format == Format::Text {
+        println!("Final standings:");
+        print_standings(&rows);
+    } else {
+        output::write_rows(&rows, format)?;
+    }
+
+    if let Some(file) = manifest_file {
+        write_manifest(&manifest, file)?;
+    }
+
+    rating::record_matches(&rated, "tournament")?;
+    check_forfeits(&manifest)
+}
+
+// Forfeited games count in the standings, but fail the run so tools notice
+// the broken strategy
+fn check_forfeits(manifest: &Manifest) -> Result<()> {
+    let n_forfeits = manifest.games.iter().filter(|g| g.forfeit.is_some()).count();
+    if n_forfeits > 0 {
+        return Err(Exit::StrategyFailure.wrap(anyhow!("{} of {} games were forfeited by a failing strategy", n_forfeits, manifest.games.len())));
+    }
+    Ok(())
+}
+
+fn default_rounds() -> usize {
+    5
+}
+
+fn default_games_per_match() -> usize {
+    2
+}
+
+// One tournament of a multi-game config file, with the same settings as the
+// tournament command
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct TournamentConfig {
+    pub game: String,
+    pub players: Vec<String>,
+    #[serde(default)]
+    pub pairing: Pairing,
+    #[serde(default = "default_rounds")]
+    pub rounds: usize,
+    #[serde(default = "default_games_per_match")]
+    pub games_per_match: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub manifest: Option<PathBuf>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct MultiConfig {
+    tournaments: Vec<TournamentConfig>,
+}
+
+// Tournaments of a config file, checked before any is played so a typo in the
+// last one doesn't waste the others
+pub fn parse_config(text: &str) -> Result<Vec<TournamentConfig>> {
+    let config: MultiConfig = serde_json::from_str(text)?;
+    if config.tournaments.is_empty() {
+        return Err(anyhow!("No tournaments in the config"));
+    }
+    for (i, tournament) in config.tournaments.iter().enumerate() {
+        games::check_game(&tournament.game).map_err(|err| anyhow!("Tournament {}: {}", i, err))?;
+        if tournament.players.len() < 2 {
+            return Err(anyhow!("Tournament {} needs at least two strategies", i));
+        }
+        for spec in &tournament.players {
+            Strategy::parse(spec).map_err(|err| anyhow!("Tournament {}: {}", i, err))?;
+        }
+    }
+    Ok(config.tournaments)
+}
+
+// Run the tournaments declared per game in a config file one after the
+// other, reporting the standings of each game
+pub fn run_config(config_file: &Path, format: Format) -> Result<()> {
+    let tournaments = parse_config(&std::fs::read_to_string(config_file)?).or_exit(Exit::InvalidConfig)?;
+    if let Some(handicaps) = azul::config().handicap_summary() {
+        output::progress(format, &format!("Handicaps: {}", handicaps));
+    }
+
+    let mut rows = Vec::new();
+    let mut rated = Vec::new();
+    for (i, config) in tournaments.iter().enumerate() {
+        let strategies = config.players.iter().map(|s| Strategy::parse(s)).collect::<Result<Vec<Strategy>>>()?;
+        let schedule = Schedule { rules: azul::AzulRules::current(), games_per_match: config.games_per_match, time_control: None, seed: config.seed.unwrap_or_else(rand::random), format };
+        output::progress(format, &format!("Tournament {} ({}), seed: {}", i, config.game, schedule.seed));
+        let (standings, manifest, matches) = play(&strategies, config.pairing, config.rounds, &schedule);
+
+        if format == Format::Text {
+            println!("Final standings of tournament {} ({}):", i, config.game);
+            print_standings(&standings);
+        }
+        if let Some(file) = &config.manifest {
+            write_manifest(&manifest, file)?;
+        }
+        rows.extend(standings.into_iter().map(|s| GameStanding {
+            tournament: i,
+            game: config.game.clone(),
+            rank: s.rank,
+            spec: s.spec,
+            points: s.points,
+            buchholz: s.buchholz,
+            game_wins: s.game_wins,
+            games: s.games,
+        }));
+        rated.extend(matches);
+    }
+
+    if format != Format::Text {
+        output::write_rows(&rows, format)?;
+    }
+    rating::record_matches(&rated, "tournament")
+}
+
+// Play one game from a tournament manifest again with its seed and rules,
+// printing every move. Games played with time budgets or external engines
+// may not come out the same.
+pub fn replay(manifest_file: &PathBuf, game_id: usize) -> Result<()> {
+    let manifest: Manifest = serde_json::from_reader(File::open(manifest_file)?)?;
+    let entry = manifest.games.iter().find(|g| g.id == game_id).ok_or_else(|| anyhow!("No game {} in the manifest", game_id))?;
+
+    azul::set_config(manifest.rules.clone());
+    let rules = &azul::AzulRules::current();
+    let strategies = entry.seats.iter().map(|s| Strategy::parse(s)).collect::<Result<Vec<Strategy>>>()?;
+    let play_fns: Vec<_> = strategies
+        .iter()
+        .map(|strategy| move |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, _| {
+            let action = strategy.play(rules, state, player_idx);
+            println!("P{} ({}): {}", player_idx, strategy.spec, azul::explain_action(rules, state, player_idx, action));
+            action
+        })
+        .collect();
+    let players: Vec<azul::PlayerFn> = play_fns.iter().map(|f| f as azul::PlayerFn).collect();
+
+    if let Some(forfeit) = &entry.forfeit {
+        println!("P{} forfeited this game, {}", forfeit.seat, forfeit.reason);
+    }
+
+    let (state, _) = azul::with_seed(entry.seed, || azul::play_game(rules, &players, None));
+
+    let scores: Vec<i32> = state.players.iter().map(|p| p.score).collect();
+    println!("Scores: {:?}", scores);
+    if entry.flagged.is_none() && entry.forfeit.is_none() && scores != entry.scores {
+        println!("Recorded scores were {:?}, the replay doesn't match", entry.scores);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swiss_pairings() {
+        let mut standings = vec![Standing::default(); 5];
+        standings[0].points = 1.0;
+        standings[1].points = 1.0;
+        standings[0].opponents = vec![1];
+        standings[1].opponents = vec![0];
+        standings[4].had_bye = true;
+
+        let (pairings, bye) = swiss_pairings(&standings);
+
+        // Lowest ranked entrant without a bye sits out and the leaders don't
+        // get a rematch
+        assert_eq!(bye, Some(3));
+        assert_eq!(pairings, vec![(0, 2), (1, 4)]);
+    }
+
+    #[test]
+    fn test_seeded_match() {
+        let a = Strategy::parse("greedy").unwrap();
+        let b = Strategy::parse("random").unwrap();
+        let rules = azul::AzulRules::current();
+
+        let scores = |seed| play_match(&rules, &a, &b, 4, None, seed).into_iter().map(|g| g.scores).collect::<Vec<_>>();
+        assert_eq!(scores(3), scores(3));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let tournaments = parse_config(r#"{"tournaments": [
+            {"game": "azul", "players": ["greedy", "random"]},
+            {"game": "azul", "players": ["greedy", "mcts"], "pairing": "swiss", "rounds": 3, "seed": 7}
+        ]}"#).unwrap();
+        assert_eq!(tournaments.len(), 2);
+        assert!(matches!(tournaments[0].pairing, Pairing::RoundRobin));
+        assert_eq!((tournaments[0].rounds, tournaments[0].games_per_match), (5, 2));
+        assert!(matches!(tournaments[1].pairing, Pairing::Swiss));
+        assert_eq!(tournaments[1].seed, Some(7));
+
+        assert!(parse_config(r#"{"tournaments": [{"game": "chess", "players": ["greedy", "random"]}]}"#).is_err());
+        assert!(parse_config(r#"{"tournaments": [{"game": "azul", "players": ["greedy"]}]}"#).is_err());
+        assert!(parse_config(r#"{"tournaments": []}"#).is_err());
+    }
+}
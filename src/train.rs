@@ -0,0 +1,242 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::dataset::Dedup;
+use crate::games::{azul, GameState, RepresentOptions, Representable};
+use crate::output::{Exit, OrExit};
+use crate::par::prelude::*;
+use crate::policy::{Policy, PolicyModel, PolicyNet};
+use crate::rating;
+use crate::strategy::{self, Strategy};
+use crate::tournament;
+
+// Settings of a training run, read from TOML. Everything but the work
+// directory has a default.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct TrainConfig {
+    // Directory for the models and self-play data, the run continues from
+    // the best model in it
+    pub work_dir: PathBuf,
+    pub iterations: usize,
+    pub self_play_games: usize,
+    // Options for the MCTS player used in self-play and evaluation, the
+    // model being trained is added as its policy
+    pub mcts: String,
+    // External command to train with instead of the built in SGD. It is
+    // called with the self-play data, the best model, and the path to write
+    // the candidate model to.
+    pub trainer: Option<String>,
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub normalize: bool,
+    pub mover_first: bool,
+    pub eval_games: usize,
+    // Fraction of evaluation points the candidate needs to replace the best
+    // model, draws counting half
+    pub promote_threshold: f64,
+    pub seed: Option<u64>,
+    // Self-play samples the first `temperature_plies` moves of a game by their
+    // rollout counts raised to 1 / temperature instead of playing the best
+    pub temperature: f64,
+    pub temperature_plies: usize,
+    // Dirichlet noise mixed into the root priors of every self-play search,
+    // an epsilon of 0 leaves the priors as they are
+    pub dirichlet_alpha: f64,
+    pub dirichlet_epsilon: f64,
+}
+
+impl Default for TrainConfig {
+    fn default() -> Self {
+        Self {
+            work_dir: PathBuf::new(),
+            iterations: 5,
+            self_play_games: 50,
+            mcts: "n_games=50".to_string(),
+            trainer: None,
+            epochs: 3,
+            learning_rate: 0.01,
+            normalize: true,
+            mover_first: true,
+            eval_games: 20,
+            promote_threshold: 0.55,
+            seed: None,
+            temperature: 1.0,
+            temperature_plies: 10,
+            dirichlet_alpha: 0.3,
+            dirichlet_epsilon: 0.25,
+        }
+    }
+}
+
+// One self-play move with the legal actions and the one played given by their
+// index in the policy action space
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sample {
+    features: Vec<f64>,
+    valid_actions: Vec<usize>,
+    action: usize,
+}
+
+// Play two player self-play games with MCTS biased by the model, writing
+// every move of the games not played before as a sample. Moves with actions
+// outside the policy action space are skipped.
+fn self_play(rules: &azul::AzulRules, mcts: &azul::MctsConfig, model: &PolicyModel, config: &TrainConfig, seed: u64, out_file: &Path) -> Result<(usize, Dedup)> {
+    let games: Vec<(u64, Vec<azul::Action>, Vec<Sample>)> = (0..config.self_play_games)
+        .into_par_iter()
+        .map(|game_idx| {
+            let actions = Mutex::new(Vec::new());
+            let samples = Mutex::new(Vec::new());
+            let play = |rules: &azul::AzulRules, state: &azul::State, player_idx: usize, _| {
+                let ply = actions.lock().unwrap().len();
+                let temperature = if ply < config.temperature_plies { config.temperature } else { 0.0 };
+                let priors = match model.probabilities(state, player_idx, 1.0) {
+                    Ok(priors) if config.dirichlet_epsilon > 0.0 => Some(azul::dirichlet_noise(&priors, config.dirichlet_alpha, config.dirichlet_epsilon)),
+                    Ok(priors) => Some(priors),
+                    Err(err) => {
+                        log::warn!("{}, searching without priors", err);
+                        None
+                    },
+                };
+                let action = azul::play_mcts_sampled(rules, state, player_idx, mcts, priors.as_deref(), temperature);
+
+                let valid_actions: Option<Vec<usize>> = azul::actions_canonical(rules, state, player_idx).iter().map(azul::action_index).collect();
+                if let (Some(valid_actions), Some(index)) = (valid_actions, azul::action_index(&action)) {
+                    let sample = Sample { features: model.features(state, player_idx), valid_actions, action: index };
+                    samples.lock().unwrap().push(sample);
+                }
+                actions.lock().unwrap().push(action);
+                action
+            };
+
+            let game_seed = tournament::match_seed(seed, game_idx, 0, 1);
+            let players: [azul::PlayerFn; 2] = [&play, &play];
+            azul::with_seed(game_seed, || azul::play_game(rules, &players, None));
+            (game_seed, actions.into_inner().unwrap(), samples.into_inner().unwrap())
+        })
+        .collect();
+
+    let mut dedup = Dedup::default();
+    let mut writer = BufWriter::new(File::create(out_file)?);
+    let mut n_samples = 0;
+    for (game_seed, actions, samples) in &games {
+        if !dedup.is_new(*game_seed, actions) {
+            continue;
+        }
+        for sample in samples {
+            writeln!(writer, "{}", serde_json::to_string(sample)?)?;
+        }
+        n_samples += samples.len();
+    }
+    writer.flush()?;
+
+    Ok((n_samples, dedup))
+}
+
+// Fit a copy of the model to the self-play moves with plain SGD
+fn fit(model: &PolicyModel, data_file: &Path, config: &TrainConfig, seed: u64) -> Result<PolicyModel> {
+    let mut samples = Vec::new();
+    for line in BufReader::new(File::open(data_file)?).lines() {
+        samples.push(serde_json::from_str::<Sample>(&line?)?);
+    }
+
+    let mut candidate = model.clone();
+    let mut rng = StdRng::seed_from_u64(seed);
+    for _ in 0..config.epochs {
+        samples.shuffle(&mut rng);
+        for sample in &samples {
+            candidate.sgd_step(&sample.features, &sample.valid_actions, sample.action, config.learning_rate);
+        }
+    }
+
+    Ok(candidate)
+}
+
+fn run_trainer(command: &str, data_file: &Path, best_file: &Path, candidate_file: &Path) -> Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or_else(|| anyhow!("Empty trainer command"))?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .args([data_file, best_file, candidate_file])
+        .status()
+        .map_err(|e| anyhow!("Unable to start trainer '{}': {}", command, e))?;
+
+    if !status.success() {
+        return Err(anyhow!("Trainer '{}' failed with {}", command, status));
+    }
+    Ok(())
+}
+
+fn save_model(model: &PolicyModel, file: &Path) -> Result<()> {
+    let writer = BufWriter::new(File::create(file)?);
+    serde_json::to_writer(writer, model)?;
+    Ok(())
+}
+
+// Alternate self-play with the best model, training a candidate on the
+// games, and a gated match between the candidate and the best model. The
+// candidate replaces the best model if it scores at least the promotion
+// threshold.
+pub fn run(config_file: &PathBuf) -> Result<()> {
+    let config: TrainConfig = toml::from_str(&fs::read_to_string(config_file)?).or_exit(Exit::InvalidConfig)?;
+    if config.work_dir.as_os_str().is_empty() {
+        return Err(Exit::InvalidConfig.wrap(anyhow!("Training config needs a work_dir")));
+    }
+    fs::create_dir_all(&config.work_dir)?;
+
+    let seed = config.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+
+    let best_file = config.work_dir.join("best.json");
+    if !best_file.exists() {
+        let options = RepresentOptions { normalize: config.normalize, perspective: 0 };
+        let n_features = azul::State::new(2).represent_with(&options).len();
+        save_model(&PolicyModel::zeros(n_features, config.normalize, config.mover_first), &best_file)?;
+        println!("Starting from an untrained model in {}", best_file.display());
+    }
+
+    // The best model file is overwritten on promotion so it is rated under
+    // the spec of the candidate it came from
+    let mut best_spec = strategy::mcts_policy_spec(&config.mcts, &best_file);
+    let (mcts, _) = strategy::parse_mcts_config(&config.mcts)?;
+    let rules = azul::AzulRules::current();
+    for iteration in 0..config.iterations {
+        let iteration_seed = tournament::match_seed(seed, iteration, 0, 0);
+        let best = PolicyModel::load(&best_file)?;
+        let best_strategy = Strategy::mcts_with_policy(&config.mcts, &best_file)?;
+
+        let data_file = config.work_dir.join(format!("self-play-{}.jsonl", iteration));
+        let (n_samples, dedup) = self_play(&rules, &mcts, &best, &config, iteration_seed, &data_file)?;
+        println!("Iteration {}: {} samples from {} self-play games, {}", iteration, n_samples, config.self_play_games, dedup.report());
+
+        let candidate_file = config.work_dir.join(format!("candidate-{}.json", iteration));
+        match &config.trainer {
+            Some(command) => run_trainer(command, &data_file, &best_file, &candidate_file)?,
+            None => save_model(&fit(&best, &data_file, &config, iteration_seed)?, &candidate_file)?,
+        }
+
+        let candidate_strategy = Strategy::mcts_with_policy(&config.mcts, &candidate_file)?;
+        let games = tournament::play_match(&rules, &candidate_strategy, &best_strategy, config.eval_games, None, iteration_seed);
+        let (wins, losses, draws) = tournament::tally(&games);
+        let score = (wins as f64 + draws as f64 / 2.0) / games.len().max(1) as f64;
+        println!("  Candidate vs best: {}-{} ({} drawn), score {:.2}", wins, losses, draws, score);
+        rating::record_matches(&[(candidate_strategy.spec.clone(), best_spec.clone(), (wins, losses, draws))], "train")?;
+
+        if score >= config.promote_threshold {
+            fs::copy(&candidate_file, &best_file)?;
+            best_spec = candidate_strategy.spec.clone();
+            println!("  Promoted candidate {}", iteration);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::bestmove::{format_variation, PV_DEPTH};
+use crate::games::azul;
+
+// Actions, and replies under each, in dumped trees
+pub const TREE_WIDTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeFormat {
+    Json,
+    Dot,
+}
+
+// Format of a tree dump, from the extension of the file
+pub fn tree_format(file: &Path) -> Result<TreeFormat> {
+    match file.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(TreeFormat::Json),
+        Some("dot") | Some("gv") => Ok(TreeFormat::Dot),
+        _ => Err(anyhow!("Tree dump {} should end with .json or .dot", file.display())),
+    }
+}
+
+// Searched action with its rollout statistics. Nodes on the principal
+// variation are marked.
+#[derive(Debug, serde::Serialize)]
+pub struct TreeNode {
+    player: usize,
+    action: azul::Action,
+    notation: String,
+    n_games: usize,
+    mean_score: f32,
+    max_score: usize,
+    win_rate: f32,
+    n_repeats: usize,
+    pv: bool,
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    fn new(player: usize, stats: &azul::MctsStats, pv: bool, children: Vec<TreeNode>) -> Self {
+        Self {
+            player,
+            action: stats.action,
+            notation: azul::action_notation(stats.action),
+            n_games: stats.n_games,
+            mean_score: stats.mean_score,
+            max_score: stats.max_score,
+            win_rate: stats.win_rate,
+            n_repeats: stats.n_repeats,
+            pv,
+            children,
+        }
+    }
+}
+
+// Snapshot of a search: the best actions of the player to move with the
+// replies of the next player under each
+#[derive(Debug, serde::Serialize)]
+pub struct SearchTree {
+    player: usize,
+    // Rollouts of the whole search, including actions not in the tree
+    rollouts: usize,
+    // Principal variation as `P<i> <notation>`, which can go deeper than the
+    // tree
+    pv: Vec<String>,
+    children: Vec<TreeNode>,
+}
+
+impl SearchTree {
+    // Tree of the best `width` actions from the statistics of a search, best
+    // first, searching the replies under each
+    pub fn new(state: &azul::State, player_idx: usize, stats: Vec<azul::MctsStats>, width: usize) -> Self {
+        let rules = azul::AzulRules::current();
+        let rollouts = stats.iter().map(|s| s.n_games).sum();
+        let variation = stats.first().map_or(Vec::new(), |best| azul::principal_variation(&rules, state, player_idx, best.action, PV_DEPTH));
+        let next_player = (player_idx + 1) % state.players.len();
+        let on_pv = |ply: usize, player: usize, action: azul::Action| variation.get(ply) == Some(&(player, action));
+
+        let children = azul::search_replies(&rules, state, player_idx, stats, width)
+            .iter()
+            .map(|(stats, replies)| {
+                let pv = on_pv(0, player_idx, stats.action);
+                let replies = replies
+                    .iter()
+                    .map(|reply| TreeNode::new(next_player, reply, pv && on_pv(1, next_player, reply.action), Vec::new()))
+                    .collect();
+                TreeNode::new(player_idx, stats, pv, replies)
+            })
+            .collect();
+
+        Self { player: player_idx, rollouts, pv: format_variation(&variation), children }
+    }
+
+    // GraphViz graph of the tree with the principal variation in bold red
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [shape=box, fontname=\"monospace\"];\n");
+        writeln!(dot, "    root [label=\"P{} to move\\n{} rollouts\"];", self.player, self.rollouts).unwrap();
+
+        fn add_node(dot: &mut String, parent: &str, id: String, node: &TreeNode) {
+            let style = if node.pv { ", color=red, penwidth=2" } else { "" };
+            writeln!(
+                dot,
+                "    {} [label=\"P{} {}\\nrollouts {}  mean {:.1}  win {:.0}%\"{}];",
+                id, node.player, node.notation, node.n_games, node.mean_score, node.win_rate * 100.0, style
+            ).unwrap();
+            writeln!(dot, "    {} -> {}{};", parent, id, if node.pv { " [color=red, penwidth=2]" } else { "" }).unwrap();
+            for (i, child) in node.children.iter().enumerate() {
+                add_node(dot, &id, format!("{}_{}", id, i), child);
+            }
+        }
+
+        for (i, node) in self.children.iter().enumerate() {
+            add_node(&mut dot, "root", format!("n{}", i), node);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn write(&self, file: &Path) -> Result<()> {
+        let text = match tree_format(file)? {
+            TreeFormat::Json => serde_json::to_string_pretty(self)?,
+            TreeFormat::Dot => self.to_dot(),
+        };
+        fs::write(file, text)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::Rules;
+
+    #[test]
+    fn test_search_tree_dump() {
+        let rules = azul::AzulRules::current();
+        assert_eq!(tree_format(Path::new("tree.json")).unwrap(), TreeFormat::Json);
+        assert_eq!(tree_format(Path::new("tree.dot")).unwrap(), TreeFormat::Dot);
+        assert!(tree_format(Path::new("tree.svg")).is_err());
+
+        let mut state = rules.new_state(2);
+        azul::with_seed(4, || rules.refill(&mut state, &mut azul::game_rng()));
+        let config = azul::MctsConfig { n_games: 40, ..azul::MctsConfig::default() };
+        let tree = azul::with_seed(6, || SearchTree::new(&state, 0, azul::mcts_evaluate(&rules, &state, 0, &config), 3));
+
+        assert_eq!(tree.rollouts, 40);
+        assert_eq!(tree.children.len(), 3);
+        assert!(tree.children[0].pv && tree.children.iter().filter(|c| c.pv).count() == 1);
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.contains("root -> n0 [color=red, penwidth=2];"));
+        assert_eq!(dot.matches(" -> ").count(), 3 + tree.children.iter().map(|c| c.children.len()).sum::<usize>());
+    }
+}